@@ -1,11 +1,19 @@
+#![feature(generic_const_exprs)]
 #![feature(option_result_contains)]
 #![feature(slice_flatten)]
 
 mod slice_box;
 mod util;
+#[cfg(not(feature = "pure-rust"))]
+mod py_sfa;
+mod py_sdr;
+mod py_sdr_dataset;
+mod py_conv_shape_nd;
+mod py_ecc_layer;
+mod py_stdp;
+mod py_histogram;
 use rand_distr::Distribution;
 use std::ops::Range;
-use std::str::FromStr;
 use numpy::{IntoPyArray, PyArray1, PyArray2, PyArray3, PyArray4, PyArray6, PyArrayDyn};
 use pyo3::prelude::*;
 use pyo3::{wrap_pyfunction, wrap_pymodule, PyObjectProtocol, PyNativeType};
@@ -15,35 +23,69 @@ use pyo3::types::PyList;
 use rand::Rng;
 use render::failure::err_msg;
 use vf::soft_wta::*;
-use vf::{ArrayCast, conv, VecCast, VectorField, VectorFieldDivAssign, VectorFieldMul, VectorFieldMulAssign, VectorFieldOne, VectorFieldZero};
+use vf::{ArrayCast, conv, VecCast, VectorField, VectorFieldAdd, VectorFieldSubOwned, VectorFieldDivAssign, VectorFieldMul, VectorFieldMulAssign, VectorFieldOne, VectorFieldZero};
 use vf::{arr2, arr3, slice_as_arr, tup2, tup3, tup4, tup6};
 use vf::dynamic_layout::shape;
 use vf::init::InitEmptyWithCapacity;
 use vf::top_k::argsort;
-use crate::util::{arrX, py_any_as_numpy};
+use crate::py_histogram::PyInit_histogram;
+use crate::util::{arrX, padding_nd, padding3, impl_save_load, pickle, py_any_as_numpy, unpickle};
 
 
-#[pyfunction]
-#[text_signature = "(input_size,stride,kernel)"]
-pub fn conv_out_size(input_size: PyObject, stride: PyObject, kernel: PyObject) -> PyResult<Vec<u32>> {
+/// Returns padding at the beginning and at the end of every axis. padding may be an int, a tuple, "same" or "valid"
+fn conv_padding(py: Python, padding: &Option<PyObject>, stride: &[u32; 3], kernel: &[u32; 3], dilation: &[u32; 3]) -> PyResult<([u32; 3], [u32; 3])> {
+    let padding = match padding {
+        None => conv::Padding::default(),
+        Some(padding) => padding3(py, padding)?
+    };
+    if padding == conv::Padding::Same && stride != &[1; 3] {
+        return Err(PyValueError::new_err(format!("Padding 'same' requires stride 1 but got {:?}", stride)));
+    }
+    Ok(padding.begin_end(kernel, dilation))
+}
+
+fn conv_dilation(py: Python, dilation: &Option<PyObject>) -> PyResult<[u32; 3]> {
+    let dilation = match dilation {
+        None => [1; 3],
+        Some(dilation) => arrX(py, dilation, 1, 1, 1)?
+    };
+    if dilation.contains(&0) {
+        return Err(PyValueError::new_err(format!("Dilation must be positive but got {:?}", dilation)));
+    }
+    Ok(dilation)
+}
+
+#[pyfunction(padding = "None", dilation = "None")]
+#[text_signature = "(input_size,stride,kernel,padding,dilation)"]
+/// Without padding and dilation the stride must evenly divide the input. Otherwise the trailing inputs
+/// are ignored, just like in PyTorch.
+pub fn conv_out_size(input_size: PyObject, stride: PyObject, kernel: PyObject, padding: Option<PyObject>, dilation: Option<PyObject>) -> PyResult<Vec<u32>> {
     let gil = Python::acquire_gil();
     let py = gil.python();
     let input_size = arrX(py, &input_size, 1, 1, 1)?;
     let stride = arrX(py, &stride, 1, 1, 1)?;
     let kernel = arrX(py, &kernel, 1, 1, 1)?;
-    let out_size = conv::out_size(&input_size, &stride, &kernel);
+    let out_size = if padding.is_none() && dilation.is_none() {
+        conv::out_size(&input_size, &stride, &kernel)
+    } else {
+        let dilation = conv_dilation(py, &dilation)?;
+        let (padding_begin, padding_end) = conv_padding(py, &padding, &stride, &kernel, &dilation)?;
+        conv::out_size_padded(&input_size, &stride, &kernel, &padding_begin, &padding_end, &dilation)
+    };
     Ok(out_size.to_vec())
 }
 
-#[pyfunction]
-#[text_signature = "(output_size,stride,kernel)"]
-pub fn conv_in_size(output_size: PyObject, stride: PyObject, kernel: PyObject) -> PyResult<Vec<u32>> {
+#[pyfunction(padding = "None", dilation = "None")]
+#[text_signature = "(output_size,stride,kernel,padding,dilation)"]
+pub fn conv_in_size(output_size: PyObject, stride: PyObject, kernel: PyObject, padding: Option<PyObject>, dilation: Option<PyObject>) -> PyResult<Vec<u32>> {
     let gil = Python::acquire_gil();
     let py = gil.python();
     let output_size = arrX(py, &output_size, 1, 1, 1)?;
     let stride = arrX(py, &stride, 1, 1, 1)?;
     let kernel = arrX(py, &kernel, 1, 1, 1)?;
-    let in_size = conv::in_size(&output_size, &stride, &kernel);
+    let dilation = conv_dilation(py, &dilation)?;
+    let (padding_begin, padding_end) = conv_padding(py, &padding, &stride, &kernel, &dilation)?;
+    let in_size = conv::in_size_padded(&output_size, &stride, &kernel, &padding_begin, &padding_end, &dilation);
     Ok(in_size.to_vec())
 }
 
@@ -60,43 +102,70 @@ pub fn conv_in_range_with_custom_size(output_pos: PyObject, output_size: PyObjec
     Ok((in_range.start.to_vec(), in_range.end.to_vec()))
 }
 
-#[pyfunction]
-#[text_signature = "(output_position,stride,kernel)"]
-pub fn conv_in_range(output_pos: PyObject, stride: PyObject, kernel: PyObject) -> PyResult<(Vec<u32>, Vec<u32>)> {
+#[pyfunction(padding = "None", dilation = "None")]
+#[text_signature = "(output_position,stride,kernel,padding,dilation)"]
+/// The range is expressed in coordinates of unpadded input, so with padding it may start at negative positions.
+/// With dilation only every dilation-th input within the range is connected to the output.
+pub fn conv_in_range(output_pos: PyObject, stride: PyObject, kernel: PyObject, padding: Option<PyObject>, dilation: Option<PyObject>) -> PyResult<(Vec<i64>, Vec<i64>)> {
     let gil = Python::acquire_gil();
     let py = gil.python();
     let output_pos = arrX(py, &output_pos, 0, 0, 0)?;
     let stride = arrX(py, &stride, 1, 1, 1)?;
     let kernel = arrX(py, &kernel, 1, 1, 1)?;
-    let in_range = conv::in_range(&output_pos, &stride, &kernel);
-    Ok((in_range.start.to_vec(), in_range.end.to_vec()))
+    let dilation = conv_dilation(py, &dilation)?;
+    let (padding_begin, _) = conv_padding(py, &padding, &stride, &kernel, &dilation)?;
+    let in_range = conv::in_range(&output_pos, &stride, &conv::dilated_kernel(&kernel, &dilation));
+    let padding_begin = padding_begin.as_scalar::<i64>();
+    let start = in_range.start.as_scalar::<i64>()._sub(&padding_begin);
+    let end = in_range.end.as_scalar::<i64>()._sub(&padding_begin);
+    Ok((start.to_vec(), end.to_vec()))
 }
 
-#[pyfunction]
-#[text_signature = "(input_position,stride,kernel)"]
-pub fn conv_out_range(input_pos: PyObject, stride: PyObject, kernel: PyObject) -> PyResult<(Vec<u32>, Vec<u32>)> {
+#[pyfunction(padding = "None", dilation = "None")]
+#[text_signature = "(input_position,stride,kernel,padding,dilation)"]
+pub fn conv_out_range(input_pos: PyObject, stride: PyObject, kernel: PyObject, padding: Option<PyObject>, dilation: Option<PyObject>) -> PyResult<(Vec<u32>, Vec<u32>)> {
     let gil = Python::acquire_gil();
     let py = gil.python();
     let input_pos = arrX(py, &input_pos, 0, 0, 0)?;
     let stride = arrX(py, &stride, 1, 1, 1)?;
     let kernel = arrX(py, &kernel, 1, 1, 1)?;
-    let out_range = conv::out_range_clipped(&input_pos, &stride, &kernel);
+    let dilation = conv_dilation(py, &dilation)?;
+    let (padding_begin, _) = conv_padding(py, &padding, &stride, &kernel, &dilation)?;
+    let out_range = conv::out_range_clipped(&input_pos.add(&padding_begin), &stride, &conv::dilated_kernel(&kernel, &dilation));
     Ok((out_range.start.to_vec(), out_range.end.to_vec()))
 }
 
-#[pyfunction]
-#[text_signature = "(input_position,stride,kernel,max_bounds)"]
-pub fn conv_out_range_clipped_both_sides(input_pos: PyObject, stride: PyObject, kernel: PyObject, max_bounds: PyObject) -> PyResult<(Vec<u32>, Vec<u32>)> {
+#[pyfunction(padding = "None", dilation = "None")]
+#[text_signature = "(input_position,stride,kernel,max_bounds,padding,dilation)"]
+pub fn conv_out_range_clipped_both_sides(input_pos: PyObject, stride: PyObject, kernel: PyObject, max_bounds: PyObject, padding: Option<PyObject>, dilation: Option<PyObject>) -> PyResult<(Vec<u32>, Vec<u32>)> {
     let gil = Python::acquire_gil();
     let py = gil.python();
     let input_pos = arrX(py, &input_pos, 0, 0, 0)?;
     let stride = arrX(py, &stride, 1, 1, 1)?;
     let kernel = arrX(py, &kernel, 1, 1, 1)?;
     let max_bounds = arrX(py, &max_bounds, 0, 0, 0)?;
-    let out_range = conv::out_range_clipped_both_sides(&input_pos, &stride, &kernel, &max_bounds);
+    let dilation = conv_dilation(py, &dilation)?;
+    let (padding_begin, _) = conv_padding(py, &padding, &stride, &kernel, &dilation)?;
+    let out_range = conv::out_range_clipped_padded(&input_pos, &stride, &kernel, &padding_begin, &dilation, &max_bounds);
     Ok((out_range.start.to_vec(), out_range.end.to_vec()))
 }
 
+#[pyfunction(padding = "None", dilation = "None")]
+#[text_signature = "(input_position,output_position,stride,kernel,padding,dilation)"]
+/// Position within the (undilated) kernel through which the input connects to the output. None if they are not connected.
+pub fn conv_kernel_pos(input_pos: PyObject, output_pos: PyObject, stride: PyObject, kernel: PyObject, padding: Option<PyObject>, dilation: Option<PyObject>) -> PyResult<Option<Vec<u32>>> {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let input_pos = arrX(py, &input_pos, 0, 0, 0)?;
+    let output_pos = arrX(py, &output_pos, 0, 0, 0)?;
+    let stride = arrX(py, &stride, 1, 1, 1)?;
+    let kernel = arrX(py, &kernel, 1, 1, 1)?;
+    let dilation = conv_dilation(py, &dilation)?;
+    let (padding_begin, _) = conv_padding(py, &padding, &stride, &kernel, &dilation)?;
+    let pos = conv::kernel_pos(&input_pos, &output_pos, &stride, &kernel, &padding_begin, &dilation);
+    Ok(pos.map(|pos| pos.to_vec()))
+}
+
 #[pyfunction]
 #[text_signature = "(output_position,stride)"]
 pub fn conv_in_range_begin(output_pos: PyObject, stride: PyObject) -> PyResult<Vec<u32>> {
@@ -148,10 +217,40 @@ pub fn conv_compose(stride1: PyObject, kernel1: PyObject, stride2: PyObject, ker
     Ok((stride.to_vec(), kernel.to_vec()))
 }
 
-#[pyfunction]
-#[text_signature = "(stride1,kernel1,stride2,kernel2)"]
+#[pyfunction(padding1 = "None", dilation1 = "None", padding2 = "None", dilation2 = "None")]
+#[text_signature = "(stride1,kernel1,stride2,kernel2,padding1,dilation1,padding2,dilation2)"]
+/// Returns (stride, kernel, padding) of the composed convolution. It is never dilated.
+/// Padding is given by the same formula at both ends of every axis, unless one of the convolutions uses "same" padding.
+/// Then the returned padding is the one at the beginning.
+pub fn conv_compose_padded(stride1: PyObject, kernel1: PyObject, stride2: PyObject, kernel2: PyObject,
+                           padding1: Option<PyObject>, dilation1: Option<PyObject>,
+                           padding2: Option<PyObject>, dilation2: Option<PyObject>) -> PyResult<(Vec<u32>, Vec<u32>, Vec<u32>)> {
+    let gil = Python::acquire_gil();
+    let py = gil.python();
+    let stride1 = arrX(py, &stride1, 1, 1, 1)?;
+    let kernel1 = arrX(py, &kernel1, 1, 1, 1)?;
+    let stride2 = arrX(py, &stride2, 1, 1, 1)?;
+    let kernel2 = arrX(py, &kernel2, 1, 1, 1)?;
+    let dilation1 = conv_dilation(py, &dilation1)?;
+    let dilation2 = conv_dilation(py, &dilation2)?;
+    let (padding1, _) = conv_padding(py, &padding1, &stride1, &kernel1, &dilation1)?;
+    let (padding2, _) = conv_padding(py, &padding2, &stride2, &kernel2, &dilation2)?;
+    let (stride, kernel, padding) = conv::compose_padded(&stride1, &kernel1, &padding1, &dilation1, &stride2, &kernel2, &padding2, &dilation2);
+    Ok((stride.to_vec(), kernel.to_vec(), padding.to_vec()))
+}
+
+#[pyfunction(dilation1 = "None", dilation2 = "None")]
+#[text_signature = "(stride1,weights1,bias1,stride2,weights2,bias2,dilation1,dilation2)"]
+/// Weights are of shape [out_channels, in_channels, kernel_height, kernel_width] (just like in PyTorch).
+/// Padding has no influence on the composed weights (see conv_compose_padded).
 pub fn conv_compose_weights<'py>(stride1:[usize;2], weights1: &'py PyArray4<f32>, bias1: &'py PyArray1<f32>,
-                            stride2:[usize;2], weights2: &'py PyArray4<f32>, bias2: &'py PyArray1<f32>) -> PyResult<([usize;2], &'py PyArray4<f32>, &'py PyArray1<f32>)> {
+                            stride2:[usize;2], weights2: &'py PyArray4<f32>, bias2: &'py PyArray1<f32>,
+                            dilation1: Option<[usize;2]>, dilation2: Option<[usize;2]>) -> PyResult<([usize;2], &'py PyArray4<f32>, &'py PyArray1<f32>)> {
+    let dilation1 = dilation1.unwrap_or([1, 1]);
+    let dilation2 = dilation2.unwrap_or([1, 1]);
+    if dilation1.contains(&0) || dilation2.contains(&0) {
+        return Err(PyValueError::new_err("Dilation must be positive"));
+    }
     if let [self_out_channels, self_in_channels, self_kernel0, self_kernel1] = *weights1.shape() {
         if let [next_out_channels, next_in_channels, next_kernel0, next_kernel1] = *weights2.shape() {
             assert_eq!(self_out_channels, next_in_channels, "self_out_channels != next_in_channels");
@@ -164,14 +263,14 @@ pub fn conv_compose_weights<'py>(stride1:[usize;2], weights1: &'py PyArray4<f32>
             assert_eq!(self_out_channels, self_bias.len(), "self_out_channels != self_bias.len()");
             assert_eq!(next_out_channels, next_bias.len(), "next_out_channels != next_bias.len()");
             let py = weights1.py();
-            let (comp_stride, comp_kernel) = conv::compose(&stride1, &self_kernel, &stride2, &next_kernel);
+            let (comp_stride, comp_kernel, _) = conv::compose_padded(&stride1, &self_kernel, &[0, 0], &dilation1, &stride2, &next_kernel, &[0, 0], &dilation2);
             let mut comp_weigths = PyArray4::<f32>::new(py, [next_out_channels, self_in_channels, comp_kernel[0], comp_kernel[1]], false);
             let mut comp_bias = PyArray1::<f32>::new(py, [next_out_channels], false);
             let w_comp = unsafe { comp_weigths.as_slice_mut()? };
             w_comp.fill(0.);
-            conv::compose_weights2d(self_in_channels, &stride1, &self_kernel, self_weights, self_bias,
-                                    &next_kernel, next_weights, next_bias,
-                                    &comp_kernel,w_comp , unsafe { comp_bias.as_slice_mut()? });
+            conv::compose_weights2d_dilated(self_in_channels, &stride1, &self_kernel, &dilation1, self_weights, self_bias,
+                                            &next_kernel, &dilation2, next_weights, next_bias,
+                                            &comp_kernel,w_comp , unsafe { comp_bias.as_slice_mut()? });
             return Ok((comp_stride, comp_weigths, comp_bias))
         }
     }
@@ -221,13 +320,64 @@ pub fn soft_wta_v_<'py>(v: &'py PyArray2<bool>, s: &'py PyArray1<f32>, y: &'py P
 }
 
 
+#[pyfunction(seed = "None")]
+#[text_signature = "(s,k,seed)"]
+/// Returns sorted indices of the k largest elements of s (of any shape). Ties are broken in favour of lower indices,
+/// unless seed is given, in which case they are broken randomly (reproducibly for the same seed).
+pub fn k_wta<'py>(s: &'py PyArrayDyn<f32>, k: usize, seed: Option<u64>) -> PyResult<&'py PyArray1<Idx>> {
+    let py = s.py();
+    let s = unsafe { s.as_slice()? };
+    Ok(py.allow_threads(|| vf::top_k::k_wta(s, k, seed)).into_pyarray(py))
+}
+
+#[pyfunction(seed = "None")]
+#[text_signature = "(s,k,seed)"]
+/// Shape of s is [height, width, channels]. Returns sorted indices of the k largest elements in every minicolumn.
+/// The result is a sparse vector that can be passed directly to ConvShape.sparse_dot. Ties are broken like in k_wta.
+pub fn k_wta_conv<'py>(s: &'py PyArrayDyn<f32>, k: usize, seed: Option<u64>) -> PyResult<&'py PyArray1<Idx>> {
+    let py = s.py();
+    let shape = match s.shape() {
+        &[h, w, c] => [h, w, c],
+        shape => return Err(PyValueError::new_err(format!("Expected shape [height, width, channels] but got {:?}", shape)))
+    };
+    let s = unsafe { s.as_slice()? };
+    Ok(py.allow_threads(|| vf::top_k::k_wta_conv(&shape, s, k, seed)).into_pyarray(py))
+}
+
+/// Returns [height, width, channels] of y, which is of shape [height, width, channels] or [batch, height, width, channels]
+fn soft_wta_conv_shape(s: &PyArrayDyn<f32>, y: &PyArrayDyn<u8>) -> PyResult<[usize; 3]> {
+    if s.shape() != y.shape() {
+        return Err(PyValueError::new_err(format!("Shape of s {:?} does not match shape of y {:?}", s.shape(), y.shape())));
+    }
+    match y.shape() {
+        &[h, w, c] | &[_, h, w, c] => Ok([h, w, c]),
+        shape => Err(PyValueError::new_err(format!("Expected shape [height, width, channels] or [batch, height, width, channels] but got {:?}", shape)))
+    }
+}
+
+/// Inhibitory weights are of shape [channels, channels] if repeated, or [height, width, channels, channels] otherwise
+fn check_inhibition_shape(u_shape: &[usize], y_shape: &[usize; 3], repeated: bool, name: &str) -> PyResult<()> {
+    let [h, w, c] = *y_shape;
+    let expected = if repeated { vec![c, c] } else { vec![h, w, c, c] };
+    if u_shape != expected.as_slice() {
+        return Err(PyValueError::new_err(format!("Expected {} of shape {:?} but got {:?}", name, expected, u_shape)));
+    }
+    Ok(())
+}
+
 #[pyfunction]
 #[text_signature = "(u,s,y)"]
 /// u is row-major. Element u[k,j]==0 means neuron k (row) can inhibit neuron j (column).
 /// Shape of s is [height, width, channels], shape of u is [height, width, channels, channels],
 /// shape of y is [height, width, channels].
-pub fn soft_wta_u_conv_<'py>(u: &'py PyArray4<f32>, s: &'py PyArray3<f32>, y: &'py PyArray3<u8>) -> PyResult<()> {
-    Ok(top_u_conv_(slice_as_arr(y.shape()),unsafe { u.as_slice()? }, unsafe { s.as_slice()? }, unsafe { y.as_slice_mut()? }))
+/// s and y may also have a leading batch dimension. Minicolumns are processed in parallel without holding the GIL.
+pub fn soft_wta_u_conv_<'py>(u: &'py PyArray4<f32>, s: &'py PyArrayDyn<f32>, y: &'py PyArrayDyn<u8>) -> PyResult<()> {
+    let y_shape = soft_wta_conv_shape(s, y)?;
+    check_inhibition_shape(u.shape(), &y_shape, false, "u")?;
+    let py = u.py();
+    let (u, s, y) = unsafe { (u.as_slice()?, s.as_slice()?, y.as_slice_mut()?) };
+    py.allow_threads(|| top_u_conv_par_(&y_shape, u, s, y));
+    Ok(())
 }
 
 #[pyfunction]
@@ -235,8 +385,14 @@ pub fn soft_wta_u_conv_<'py>(u: &'py PyArray4<f32>, s: &'py PyArray3<f32>, y: &'
 /// v is row-major. Element v[j0,j1,k,j]==1 means neuron k (row) can inhibit neuron j (column).
 /// Shape of s is [height, width, channels], shape of v is [height, width, channels, channels],
 /// shape of y is [height, width, channels].
-pub fn soft_wta_v_conv_<'py>(v: &'py PyArray4<bool>, s: &'py PyArray3<f32>, y: &'py PyArray3<u8>) -> PyResult<()> {
-    Ok(top_v_conv_(slice_as_arr(y.shape()),unsafe { v.as_slice()? }, unsafe { s.as_slice()? }, unsafe { y.as_slice_mut()? }))
+/// s and y may also have a leading batch dimension. Minicolumns are processed in parallel without holding the GIL.
+pub fn soft_wta_v_conv_<'py>(v: &'py PyArray4<bool>, s: &'py PyArrayDyn<f32>, y: &'py PyArrayDyn<u8>) -> PyResult<()> {
+    let y_shape = soft_wta_conv_shape(s, y)?;
+    check_inhibition_shape(v.shape(), &y_shape, false, "v")?;
+    let py = v.py();
+    let (v, s, y) = unsafe { (v.as_slice()?, s.as_slice()?, y.as_slice_mut()?) };
+    py.allow_threads(|| top_v_conv_par_(&y_shape, v, s, y));
+    Ok(())
 }
 
 
@@ -245,8 +401,14 @@ pub fn soft_wta_v_conv_<'py>(v: &'py PyArray4<bool>, s: &'py PyArray3<f32>, y: &
 /// u is row-major. Element u[k,j]==0 means neuron k (row) can inhibit neuron j (column).
 /// Shape of s is [height, width, channels], shape of u is [channels, channels],
 /// shape of y is [height, width, channels].
-pub fn soft_wta_u_repeated_conv_<'py>(u: &'py PyArray2<f32>, s: &'py PyArray3<f32>, y: &'py PyArray3<u8>) -> PyResult<()> {
-    Ok(top_u_repeated_conv_(slice_as_arr(y.shape()),unsafe { u.as_slice()? }, unsafe { s.as_slice()? }, unsafe { y.as_slice_mut()? }))
+/// s and y may also have a leading batch dimension. Minicolumns are processed in parallel without holding the GIL.
+pub fn soft_wta_u_repeated_conv_<'py>(u: &'py PyArray2<f32>, s: &'py PyArrayDyn<f32>, y: &'py PyArrayDyn<u8>) -> PyResult<()> {
+    let y_shape = soft_wta_conv_shape(s, y)?;
+    check_inhibition_shape(u.shape(), &y_shape, true, "u")?;
+    let py = u.py();
+    let (u, s, y) = unsafe { (u.as_slice()?, s.as_slice()?, y.as_slice_mut()?) };
+    py.allow_threads(|| top_u_repeated_conv_par_(&y_shape, u, s, y));
+    Ok(())
 }
 
 #[pyfunction]
@@ -254,8 +416,14 @@ pub fn soft_wta_u_repeated_conv_<'py>(u: &'py PyArray2<f32>, s: &'py PyArray3<f3
 /// v is row-major. Element v[k,j]==1 means neuron k (row) can inhibit neuron j (column).
 /// Shape of s is [height, width, channels], shape of v is [channels, channels],
 /// shape of y is [height, width, channels].
-pub fn soft_wta_v_repeated_conv_<'py>(v: &'py PyArray2<bool>, s: &'py PyArray3<f32>, y: &'py PyArray3<u8>) -> PyResult<()> {
-    Ok(top_v_repeated_conv_(slice_as_arr(y.shape()),unsafe { v.as_slice()? }, unsafe { s.as_slice()? }, unsafe { y.as_slice_mut()? }))
+/// s and y may also have a leading batch dimension. Minicolumns are processed in parallel without holding the GIL.
+pub fn soft_wta_v_repeated_conv_<'py>(v: &'py PyArray2<bool>, s: &'py PyArrayDyn<f32>, y: &'py PyArrayDyn<u8>) -> PyResult<()> {
+    let y_shape = soft_wta_conv_shape(s, y)?;
+    check_inhibition_shape(v.shape(), &y_shape, true, "v")?;
+    let py = v.py();
+    let (v, s, y) = unsafe { (v.as_slice()?, s.as_slice()?, y.as_slice_mut()?) };
+    py.allow_threads(|| top_v_repeated_conv_par_(&y_shape, v, s, y));
+    Ok(())
 }
 
 #[pyfunction]
@@ -269,18 +437,30 @@ pub fn multiplicative_soft_wta_u_<'py>(u: &'py PyArray2<f32>, s: &'py PyArray1<f
 #[text_signature = "(u,s,y)"]
 /// Shape of s is [height, width, channels], shape of u is [height, width, channels, channels],
 ///  shape of y is [height, width, channels].
+/// s and y may also have a leading batch dimension. Minicolumns are processed in parallel without holding the GIL.
 /// u is row-major. Element u[j0,j1,k,j]==0 means neuron k (row) can inhibit neuron j (column).
-pub fn multiplicative_soft_wta_u_conv_<'py>(u: &'py PyArray4<f32>, s: &'py PyArray3<f32>, y: &'py PyArray3<u8>) -> PyResult<()> {
-    Ok(multiplicative_top_u_conv_(slice_as_arr(y.shape()),unsafe { u.as_slice()? }, unsafe { s.as_slice()? }, unsafe { y.as_slice_mut()? }))
+pub fn multiplicative_soft_wta_u_conv_<'py>(u: &'py PyArray4<f32>, s: &'py PyArrayDyn<f32>, y: &'py PyArrayDyn<u8>) -> PyResult<()> {
+    let y_shape = soft_wta_conv_shape(s, y)?;
+    check_inhibition_shape(u.shape(), &y_shape, false, "u")?;
+    let py = u.py();
+    let (u, s, y) = unsafe { (u.as_slice()?, s.as_slice()?, y.as_slice_mut()?) };
+    py.allow_threads(|| multiplicative_top_u_conv_par_(&y_shape, u, s, y));
+    Ok(())
 }
 
 #[pyfunction]
 #[text_signature = "(u,s,y)"]
 /// Shape of s is [height, width, channels], shape of u is [channels, channels],
 ///  shape of y is [height, width, channels].
+/// s and y may also have a leading batch dimension. Minicolumns are processed in parallel without holding the GIL.
 /// u is row-major. Element u[j0,j1,k,j]==0 means neuron k (row) can inhibit neuron j (column).
-pub fn multiplicative_soft_wta_u_repeated_conv_<'py>(u: &'py PyArray2<f32>, s: &'py PyArray3<f32>, y: &'py PyArray3<u8>) -> PyResult<()> {
-    Ok(multiplicative_top_u_repeated_conv_(slice_as_arr(y.shape()),unsafe { u.as_slice()? }, unsafe { s.as_slice()? }, unsafe { y.as_slice_mut()? }))
+pub fn multiplicative_soft_wta_u_repeated_conv_<'py>(u: &'py PyArray2<f32>, s: &'py PyArrayDyn<f32>, y: &'py PyArrayDyn<u8>) -> PyResult<()> {
+    let y_shape = soft_wta_conv_shape(s, y)?;
+    check_inhibition_shape(u.shape(), &y_shape, true, "u")?;
+    let py = u.py();
+    let (u, s, y) = unsafe { (u.as_slice()?, s.as_slice()?, y.as_slice_mut()?) };
+    py.allow_threads(|| multiplicative_top_u_repeated_conv_par_(&y_shape, u, s, y));
+    Ok(())
 }
 
 type Idx = u32;
@@ -293,6 +473,23 @@ pub struct ConvShape {
     pub(crate) cs: vf::conv_shape::ConvShape<Idx>,
 }
 
+impl ConvShape {
+    fn padding_and_dilation(padding: Option<PyObject>, dilation: Option<(Idx,Idx)>, stride: (Idx,Idx)) -> PyResult<(conv::Padding<Idx, 2>, [Idx; 2])> {
+        let dilation = dilation.map(arr2).unwrap_or([1, 1]);
+        if dilation.contains(&0) {
+            return Err(PyValueError::new_err(format!("Dilation must be positive but got {:?}", dilation)));
+        }
+        let padding = match padding {
+            None => conv::Padding::default(),
+            Some(padding) => Python::with_gil(|py| padding_nd(py, &padding))?
+        };
+        if padding == conv::Padding::Same && stride != (1, 1) {
+            return Err(PyValueError::new_err(format!("Padding 'same' requires stride 1 but got {:?}", stride)));
+        }
+        Ok((padding, dilation))
+    }
+}
+
 #[pymethods]
 impl ConvShape {
     ///[out_height, out_width, out_channels, out_channels]
@@ -316,6 +513,15 @@ impl ConvShape {
     ///[height, width]
     #[getter]
     pub fn stride(&self) -> (Idx,Idx) { tup2(self.cs.stride().clone()) }
+    ///[height, width] number of zeros added before the first input
+    #[getter]
+    pub fn padding(&self) -> (Idx,Idx) { tup2(self.cs.padding().clone()) }
+    ///[height, width] number of zeros added after the last input
+    #[getter]
+    pub fn padding_end(&self) -> (Idx,Idx) { tup2(self.cs.padding_end().clone()) }
+    ///[height, width]
+    #[getter]
+    pub fn dilation(&self) -> (Idx,Idx) { tup2(self.cs.dilation().clone()) }
     ///[kernel_height, kernel_width, in_channels]
     /// Kernel column is the shape of receptive field of each output neuron. Don't confuse it with
     /// minicolumn which consists of all the output neurons that have the same receptive field.
@@ -359,6 +565,9 @@ impl ConvShape {
     pub fn in_volume(&self) -> Idx { self.cs.in_volume() }
     #[text_signature = "(output_pos)"]
     pub fn kernel_offset(&self, output_pos: (Idx,Idx,Idx)) -> (Idx,Idx) { tup2(self.cs.kernel_offset(&arr3(output_pos))) }
+    #[text_signature = "(input_column_pos, output_column_pos)"]
+    /// Position within the (undilated) kernel through which the input column connects to the output column. None if they are not connected.
+    pub fn kernel_pos(&self, input_column_pos: (Idx,Idx), output_column_pos: (Idx,Idx)) -> Option<(Idx,Idx)> { self.cs.kernel_pos(&arr2(input_column_pos), &arr2(output_column_pos)).map(tup2) }
     #[text_signature = "(input_pos, output_pos)"]
     pub fn pos_within_kernel(&self, input_pos: (Idx,Idx,Idx), output_pos: (Idx,Idx,Idx)) -> (Idx,Idx,Idx) { tup3(self.cs.pos_within_kernel(&arr3(input_pos), &arr3(output_pos))) }
     #[text_signature = "(input_pos, output_pos)"]
@@ -477,6 +686,94 @@ impl ConvShape {
             self.cs.sparse_unbiased_increment_repeated(conv,epsilon,inp,out)
         }
     }
+    #[text_signature = "(lhs_tensor, rhs_conv_tensor, dot_product_output)"]
+    /// rhs_conv_tensor is of shape [kernel_height, kernel_width, in_channels, out_height, out_width, out_channels].
+    /// lhs_tensor is a dense input of shape [in_height, in_width, in_channels].
+    /// dot_product_output is of shape [out_height, out_width, out_channels]
+    pub fn dense_dot<'py>(&self, lhs_tensor: &'py PyArray3<f32>, rhs_conv_tensor: &'py PyArray6<f32>, dot_product_output: Option<&'py PyArray3<f32>>) -> PyObject {
+        assert_eq!(lhs_tensor.shape(),self.cs.in_shape().as_scalar::<usize>().as_slice(), "Input tensor shape is wrong");
+        assert_eq!(rhs_conv_tensor.shape(),self.cs.w_shape().as_scalar::<usize>().as_slice(), "Convolutional tensor shape is wrong");
+        let lhs = unsafe{lhs_tensor.as_slice()}.expect("Lhs input tensor is not continuous");
+        let rhs = unsafe{rhs_conv_tensor.as_slice()}.expect("Convolutional weights tensor is not continuous");
+        let out_shape = self.cs.out_shape().as_scalar::<usize>();
+        let out_tensor:&'py PyArray3<f32> = dot_product_output.unwrap_or_else(||PyArray3::zeros(lhs_tensor.py(),out_shape, false));
+        assert_eq!(out_tensor.shape(),&out_shape,"Output tensor shape is wrong");
+        let out = unsafe{out_tensor.as_slice_mut()}.expect("Output tensor is not continuous");
+        self.cs.dense_dot_slice_(lhs, rhs, out);
+        out_tensor.to_object(lhs_tensor.py())
+    }
+    #[text_signature = "(lhs_tensor, rhs_conv_tensor, dot_product_output)"]
+    /// rhs_conv_tensor is of shape [kernel_height, kernel_width, in_channels, out_channels].
+    /// lhs_tensor is a dense input of shape [in_height, in_width, in_channels].
+    /// dot_product_output is of shape [out_height, out_width, out_channels]
+    pub fn dense_dot_repeated<'py>(&self, lhs_tensor: &'py PyArray3<f32>, rhs_conv_tensor: &'py PyArray4<f32>, dot_product_output: Option<&'py PyArray3<f32>>) -> PyObject {
+        assert_eq!(lhs_tensor.shape(),self.cs.in_shape().as_scalar::<usize>().as_slice(), "Input tensor shape is wrong");
+        assert_eq!(rhs_conv_tensor.shape(),self.cs.minicolumn_w_shape().as_scalar::<usize>().as_slice(), "Convolutional tensor shape is wrong");
+        let lhs = unsafe{lhs_tensor.as_slice()}.expect("Lhs input tensor is not continuous");
+        let rhs = unsafe{rhs_conv_tensor.as_slice()}.expect("Convolutional weights tensor is not continuous");
+        let out_shape = self.cs.out_shape().as_scalar::<usize>();
+        let out_tensor:&'py PyArray3<f32> = dot_product_output.unwrap_or_else(||PyArray3::zeros(lhs_tensor.py(),out_shape, false));
+        assert_eq!(out_tensor.shape(),&out_shape,"Output tensor shape is wrong");
+        let out = unsafe{out_tensor.as_slice_mut()}.expect("Output tensor is not continuous");
+        self.cs.dense_dot_repeated_slice_(lhs, rhs, out);
+        out_tensor.to_object(lhs_tensor.py())
+    }
+    #[text_signature = "(output_tensor, rhs_conv_tensor, input_reconstruction)"]
+    /// Transposed convolution. rhs_conv_tensor is of shape [kernel_height, kernel_width, in_channels, out_height, out_width, out_channels].
+    /// output_tensor is of shape [out_height, out_width, out_channels].
+    /// input_reconstruction is of shape [in_height, in_width, in_channels]
+    pub fn dense_transpose_dot<'py>(&self, output_tensor: &'py PyArray3<f32>, rhs_conv_tensor: &'py PyArray6<f32>, input_reconstruction: Option<&'py PyArray3<f32>>) -> PyObject {
+        assert_eq!(output_tensor.shape(),self.cs.out_shape().as_scalar::<usize>().as_slice(), "Output tensor shape is wrong");
+        assert_eq!(rhs_conv_tensor.shape(),self.cs.w_shape().as_scalar::<usize>().as_slice(), "Convolutional tensor shape is wrong");
+        let out = unsafe{output_tensor.as_slice()}.expect("Output tensor is not continuous");
+        let rhs = unsafe{rhs_conv_tensor.as_slice()}.expect("Convolutional weights tensor is not continuous");
+        let in_shape = self.cs.in_shape().as_scalar::<usize>();
+        let in_tensor:&'py PyArray3<f32> = input_reconstruction.unwrap_or_else(||PyArray3::zeros(output_tensor.py(),in_shape, false));
+        assert_eq!(in_tensor.shape(),&in_shape,"Input reconstruction tensor shape is wrong");
+        let inp = unsafe{in_tensor.as_slice_mut()}.expect("Input reconstruction tensor is not continuous");
+        self.cs.dense_transpose_dot_slice_(out, rhs, inp);
+        in_tensor.to_object(output_tensor.py())
+    }
+    #[text_signature = "(output_tensor, rhs_conv_tensor, input_reconstruction)"]
+    /// Transposed convolution. rhs_conv_tensor is of shape [kernel_height, kernel_width, in_channels, out_channels].
+    /// output_tensor is of shape [out_height, out_width, out_channels].
+    /// input_reconstruction is of shape [in_height, in_width, in_channels]
+    pub fn dense_transpose_dot_repeated<'py>(&self, output_tensor: &'py PyArray3<f32>, rhs_conv_tensor: &'py PyArray4<f32>, input_reconstruction: Option<&'py PyArray3<f32>>) -> PyObject {
+        assert_eq!(output_tensor.shape(),self.cs.out_shape().as_scalar::<usize>().as_slice(), "Output tensor shape is wrong");
+        assert_eq!(rhs_conv_tensor.shape(),self.cs.minicolumn_w_shape().as_scalar::<usize>().as_slice(), "Convolutional tensor shape is wrong");
+        let out = unsafe{output_tensor.as_slice()}.expect("Output tensor is not continuous");
+        let rhs = unsafe{rhs_conv_tensor.as_slice()}.expect("Convolutional weights tensor is not continuous");
+        let in_shape = self.cs.in_shape().as_scalar::<usize>();
+        let in_tensor:&'py PyArray3<f32> = input_reconstruction.unwrap_or_else(||PyArray3::zeros(output_tensor.py(),in_shape, false));
+        assert_eq!(in_tensor.shape(),&in_shape,"Input reconstruction tensor shape is wrong");
+        let inp = unsafe{in_tensor.as_slice_mut()}.expect("Input reconstruction tensor is not continuous");
+        self.cs.dense_transpose_dot_repeated_slice_(out, rhs, inp);
+        in_tensor.to_object(output_tensor.py())
+    }
+    #[text_signature = "(conv_tensor, epsilon, input, output)"]
+    /// Hebbian update w += epsilon * x * y. conv_tensor is of shape [kernel_height, kernel_width, in_channels, out_height, out_width, out_channels].
+    /// input is of shape [in_height, in_width, in_channels] and output of shape [out_height, out_width, out_channels]
+    pub fn dense_increment<'py>(&self, conv_tensor: &'py PyArray6<f32>, epsilon: f32, input:&'py PyArray3<f32>, output: &'py PyArray3<f32>) {
+        assert_eq!(conv_tensor.shape(),self.cs.w_shape().as_scalar::<usize>().as_slice(), "Convolutional tensor shape is wrong");
+        assert_eq!(input.shape(),self.cs.in_shape().as_scalar::<usize>().as_slice(), "Input tensor shape is wrong");
+        assert_eq!(output.shape(),self.cs.out_shape().as_scalar::<usize>().as_slice(), "Output tensor shape is wrong");
+        let inp = unsafe{input.as_slice()}.expect("Input tensor is not continuous");
+        let out = unsafe{output.as_slice()}.expect("Output tensor is not continuous");
+        let conv = unsafe{conv_tensor.as_slice_mut()}.expect("Convolutional weights tensor is not continuous");
+        self.cs.dense_increment(conv,epsilon,inp,out)
+    }
+    #[text_signature = "(conv_tensor, epsilon, input, output)"]
+    /// Hebbian update w += epsilon * x * y summed over all output columns. conv_tensor is of shape [kernel_height, kernel_width, in_channels, out_channels].
+    /// input is of shape [in_height, in_width, in_channels] and output of shape [out_height, out_width, out_channels]
+    pub fn dense_increment_repeated<'py>(&self, conv_tensor: &'py PyArray4<f32>, epsilon: f32, input:&'py PyArray3<f32>, output: &'py PyArray3<f32>) {
+        assert_eq!(conv_tensor.shape(),self.cs.minicolumn_w_shape().as_scalar::<usize>().as_slice(), "Convolutional tensor shape is wrong");
+        assert_eq!(input.shape(),self.cs.in_shape().as_scalar::<usize>().as_slice(), "Input tensor shape is wrong");
+        assert_eq!(output.shape(),self.cs.out_shape().as_scalar::<usize>().as_slice(), "Output tensor shape is wrong");
+        let inp = unsafe{input.as_slice()}.expect("Input tensor is not continuous");
+        let out = unsafe{output.as_slice()}.expect("Output tensor is not continuous");
+        let conv = unsafe{conv_tensor.as_slice_mut()}.expect("Convolutional weights tensor is not continuous");
+        self.cs.dense_increment_repeated(conv,epsilon,inp,out)
+    }
     #[text_signature = "(conv_tensor, epsilon, s, y, u)"]
     /// u is of shape [out_height, out_width, out_channels, out_channels].
     /// y is a sparse vector of output activations. k and s are of shape [out_height, out_width, out_channels]
@@ -524,14 +821,24 @@ impl ConvShape {
     //     Self{cs:vf::conv_shape::ConvShape::concat(layers.as_slice())}
     // }
     #[staticmethod]
-    #[text_signature = "(input_shape, out_channels, kernel, stride)"]
-    pub fn new_in(input_shape: (Idx,Idx,Idx), out_channels: Idx, kernel: (Idx,Idx), stride: (Idx,Idx)) -> Self {
-        Self{cs:vf::conv_shape::ConvShape::new_in(arr3(input_shape),out_channels, arr2(kernel), arr2(stride))}
+    #[args(padding = "None", dilation = "None")]
+    #[text_signature = "(input_shape, out_channels, kernel, stride, padding, dilation)"]
+    /// padding may be an int, a tuple, "same" or "valid". Without padding and dilation the stride must evenly divide the input.
+    pub fn new_in(input_shape: (Idx,Idx,Idx), out_channels: Idx, kernel: (Idx,Idx), stride: (Idx,Idx), padding: Option<PyObject>, dilation: Option<(Idx,Idx)>) -> PyResult<Self> {
+        Ok(Self{cs: if padding.is_none() && dilation.is_none() {
+            vf::conv_shape::ConvShape::new_in(arr3(input_shape),out_channels, arr2(kernel), arr2(stride))
+        } else {
+            let (padding, dilation) = Self::padding_and_dilation(padding, dilation, stride)?;
+            vf::conv_shape::ConvShape::new_in_padded(arr3(input_shape),out_channels, arr2(kernel), arr2(stride), padding, dilation)
+        }})
     }
     #[staticmethod]
-    #[text_signature = "(in_channels, output_shape, kernel, stride)"]
-    pub fn new_out(in_channels: Idx, output_shape: (Idx,Idx,Idx), kernel: (Idx,Idx), stride: (Idx,Idx)) -> Self {
-        Self{cs:vf::conv_shape::ConvShape::new_out(in_channels,arr3(output_shape), arr2(kernel), arr2(stride))}
+    #[args(padding = "None", dilation = "None")]
+    #[text_signature = "(in_channels, output_shape, kernel, stride, padding, dilation)"]
+    /// padding may be an int, a tuple, "same" or "valid"
+    pub fn new_out(in_channels: Idx, output_shape: (Idx,Idx,Idx), kernel: (Idx,Idx), stride: (Idx,Idx), padding: Option<PyObject>, dilation: Option<(Idx,Idx)>) -> PyResult<Self> {
+        let (padding, dilation) = Self::padding_and_dilation(padding, dilation, stride)?;
+        Ok(Self{cs:vf::conv_shape::ConvShape::new_out_padded(in_channels,arr3(output_shape), arr2(kernel), arr2(stride), padding, dilation)})
     }
     #[text_signature = "(new_stride)"]
     pub fn set_stride(&mut self, new_stride: (Idx,Idx)) {
@@ -558,6 +865,8 @@ impl ConvShape {
     }
 
 }
+impl_save_load!(ConvShape, cs);
+
 #[pyproto]
 impl PyObjectProtocol for ConvShape {
     fn __repr__(&self) -> String {
@@ -578,7 +887,8 @@ pub fn version<'py>() -> u32 {
 #[text_signature = "(bools)"]
 /// Returns a pair of vectors (indices, offsets). First vector contains indices of all
 /// true boolean values within each batch.
-/// The second vector contains offsets to the first one. It works just like [[int]] but is flattened.
+/// The second vector contains end offsets into the first one, so indices of i-th batch are
+/// indices[offsets[i-1]:offsets[i]] (with offsets[-1] being 0). It works just like [[int]] but is flattened.
 /// Batches are assumed to be laid out continuously in memory.
 pub fn batch_dense_to_sparse(bools: &PyArrayDyn<bool>) ->PyResult<(PyObject, PyObject)>{
     assert!(bools.ndim()>1,"Tensor must have at least 2 dimensions!");
@@ -638,231 +948,6 @@ pub fn rand_set(py:Python, cardinality: usize, from_inclusive: usize, to_exclusi
     PyArray1::from_vec(py,v).to_object(py)
 }
 
-#[pyfunction]
-#[text_signature = "(source_image, reference_image, source_mask)"]
-/// images are of shape `[height, width, channels]`
-pub fn match_histogram<'py>(source: &'py PyArray3<u8>, reference: &'py PyArray3<u8>, source_mask:Option<PyObject>) -> PyResult<&'py PyArray3<u8>>{
-    let src_shape = source.shape();
-    let ref_shape = reference.shape();
-    assert_eq!(src_shape.len(),3,"Shape should be [height,width,channels]");
-    assert_eq!(ref_shape.len(),3,"Shape should be [height,width,channels]");
-    let channels = src_shape.get(2).cloned().unwrap_or(1);
-    assert_eq!(channels, ref_shape.get(2).cloned().unwrap_or(1), "Number of channels does not match");
-    let src_shape = [src_shape[0],src_shape[1],channels];
-    let ref_shape = [ref_shape[0],ref_shape[1],channels];
-    let src = unsafe{source.as_slice()?};
-    let rfc = unsafe{reference.as_slice()?};
-    let out = if let Some(mask) = source_mask {
-        let mask:&PyArray2<bool> = mask.extract(source.py())?;
-        let msk_shape = mask.shape();
-        assert_eq!(msk_shape,&src_shape[..2],"Mask shape should match source shape");
-        let msk = unsafe{mask.as_slice()?};
-        vf::histogram::match_images(src, &src_shape, rfc, &ref_shape,|i|msk[i] )
-    }else{
-        vf::histogram::match_images(src, &src_shape, rfc, &ref_shape, |_|true)
-    };
-    let v = PyArray1::from_vec(source.py(),out.into_vec());
-    v.reshape(src_shape)
-}
-
-#[pyfunction]
-#[text_signature = "(source_image, reference_hist, source_mask)"]
-/// `source_image` is of shape `[height, width, channels]`, `reference_hist` is of shape `[channels, 256]`
-pub fn match_precomputed_histogram<'py>(source: &'py PyArray3<u8>, reference_hist: &'py PyArray2<f32>, source_mask:Option<PyObject>) -> PyResult<&'py PyArray3<u8>>{
-    let src_shape = source.shape();
-    let ref_shape = reference_hist.shape();
-    assert_eq!(src_shape.len(),3,"Shape should be [height,width,channels]");
-    assert_eq!(ref_shape.len(),2,"Shape should be [channels, 256]");
-    let channels = src_shape[2];
-    assert_eq!(channels, ref_shape[0], "Number of channels does not match");
-    let src_shape = [src_shape[0],src_shape[1],channels];
-    let src = unsafe{source.as_slice()?};
-    let rfc = unsafe{reference_hist.as_slice()?};
-    let out = if let Some(mask) = source_mask {
-        let mask:&PyArray2<bool> = mask.extract(source.py())?;
-        let msk_shape = mask.shape();
-        assert_eq!(msk_shape,&src_shape[..2],"Mask shape should match source shape");
-        let msk = unsafe{mask.as_slice()?};
-        vf::histogram::match_precomputed_images(src, &src_shape, rfc, |i|msk[i] )
-    }else{
-        vf::histogram::match_precomputed_images(src, &src_shape, rfc,  |_|true)
-    };
-    let v = PyArray1::from_vec(source.py(),out.to_vec());
-    v.reshape(src_shape)
-}
-#[pyfunction]
-#[text_signature = "(histogram)"]
-/// `histogram` shape `[channels, 256]`
-pub fn histogram_interpolate_gaps(histogram: &PyArray1<f32>)->PyResult<()>{
-    let hist = unsafe{histogram.as_slice_mut()?};
-    vf::histogram::interpolate_gaps(hist);
-    Ok(())
-}
-#[pyfunction]
-#[text_signature = "(source_image, source_mask)"]
-/// `source_image` shape `[height, width, channels]`
-pub fn image_histogram<'py>(source: &'py PyArray3<u8>, source_mask:Option<PyObject>) -> PyResult<&'py PyArray2<u32>> {
-    let src_shape = source.shape();
-    assert_eq!(src_shape.len(),3,"Shape should be [height,width,channels]");
-    let hist_shape = [src_shape[2],256];
-    let src = unsafe{source.as_slice()?};
-    let hist = if let Some(mask) = source_mask {
-        let mask:&PyArray2<bool> = mask.extract(source.py())?;
-        let msk_shape = mask.shape();
-        assert_eq!(msk_shape,&src_shape[..2],"Mask shape should match source shape");
-        let msk = unsafe{mask.as_slice()?};
-        vf::histogram::histograms(src, src_shape[2], |i|msk[i] )
-    }else{
-        vf::histogram::histograms(src, src_shape[2],   |_|true)
-    };
-    let hist = vf::flatten_box(hist);
-    let v = PyArray1::from_vec(source.py(),hist.into_vec());
-    v.reshape(hist_shape)
-}
-
-
-#[pyfunction]
-#[text_signature = "(histogram)"]
-/// `histogram` shape `[channels, 256]`
-pub fn normalize_histogram<'py>(histogram: &'py PyArrayDyn<u32>) -> PyResult<&'py PyArrayDyn<f32>> {
-    let src_shape = histogram.shape();
-    assert!(src_shape.len()>=2,"Shape should be [channels, 256]");
-    assert_eq!(src_shape.last().copied().unwrap(),256, "Histograms must have 256 possible pixel values");
-    let src = unsafe{histogram.as_slice()?};
-    let hist = vf::histogram::normalize_histograms(src);
-    let v = PyArray1::from_vec(histogram.py(),hist.into_vec());
-    v.reshape(src_shape)
-}
-
-#[pyfunction]
-#[text_signature = "(histogram, ratio)"]
-/// `histogram` shape `[channels, 256]`.
-/// Find if there exists `c`, `x1` and `w` such that
-/// ```
-/// (y_max-y_min)/w >= ratio
-/// ```
-/// where
-/// ```
-/// y_max=histogram[c,x..x+w].max();
-/// y_min=histogram[c,x].max(histogram[c,x+w]);
-/// ```
-/// and `ratio>1`, `w>0`. Return `(c,x,x+w)`. If no such constants exist then returns `(0,0,0)`
-pub fn find_histogram_anomaly<'py>(histogram: &'py PyArray2<f32>, ratio:f32) -> PyResult<Vec<(usize,isize,isize)>> {
-    assert_eq!(histogram.ndim(),2,"Shape should be [channels, 256]");
-    assert_eq!(histogram.shape()[1],256,"Shape should be [channels, 256]");
-    let src = unsafe{histogram.as_slice()?};
-    let v = vf::histogram::find_n_histograms_anomaly(src, ratio);
-    Ok(v.into_iter().map(|(channel,Range{ start, end })|(channel,start,end)).collect())
-}
-
-
-
-#[pyfunction]
-#[text_signature = "(source_image, reference_histograms, source_mask)"]
-/// `source_image` shape `[height, width, channels]`, `reference_histograms` shape `[batch,channels,256]`, return `(matched_image,best_ref_idx,best_square_dist)`
-pub fn match_best_images<'py>(source: &'py PyArray3<u8>, references: &'py PyArray3<f32>, source_mask:Option<PyObject>) -> PyResult<(&'py PyArray3<u8>,usize,f32)> {
-    let src_shape = source.shape();
-    let ref_shape = references.shape();
-    assert_eq!(src_shape.len(),3,"Shape should be [height,width,channels]");
-    assert_eq!(ref_shape.len(),3,"Shape should be [batch,channels,256]");
-    assert_eq!(ref_shape[1],src_shape[2], "Number of channels does not match");
-    assert_eq!(ref_shape[2],256, "Number of pixel values must be 256");
-    let src_shape = [src_shape[0],src_shape[1],src_shape[2]];
-    let ref_shape = [ref_shape[0],ref_shape[1],ref_shape[2]];
-    let src = unsafe{source.as_slice()?};
-    let rfc = unsafe{references.as_slice()?};
-
-    let (out,best_ref_idx,square_dist) = if let Some(mask) = source_mask {
-        let mask:&PyArray2<bool> = mask.extract(source.py())?;
-        let msk_shape = mask.shape();
-        assert_eq!(msk_shape,&src_shape[..2],"Mask shape should match source shape");
-        let msk = unsafe{mask.as_slice()?};
-        vf::histogram::match_best_images(src, &src_shape, ref_shape[0], rfc,|i|msk[i] )
-    }else{
-        vf::histogram::match_best_images(src, &src_shape, ref_shape[0],rfc,  |_|true)
-    };
-    let v = PyArray1::from_vec(source.py(),out.into_vec());
-    v.reshape(src_shape).map(|a|(a,best_ref_idx,square_dist))
-}
-
-
-#[pyfunction]
-#[text_signature = "(source_image, reference_histograms, blend_policy, source_mask)"]
-/// `source_image` shape `[height, width, channels]`, `reference_histograms` shape `[batch,channels,256]`, return `(matched_image,best_ref_idx,best_square_dist)`.
-/// Before matching, it automatically blends histograms proportionately to their distance. Minimum possible distance is 0, maximum is 1.
-/// blend_policy is one of "PROP_TO_SQUARED", "PROP_TO_DIST",  "PROP_TO_AREA_DIFF", "INV_PROP_TO_SQUARED", "INV_PROP_TO_DIST","INV_PROP_TO_AREA_DIFF", "0.1234". Proportional (PROP)
-/// means that we blend `source*distance + reference * (1-distance)`. Inverse proportional (INV_PROP) means ``source*(1-distance) + reference * distance``.
-/// You can also put there some constant like "0.1234" or anything else between 0 and 1.
-pub fn blend_and_match_best_images<'py>(source: &'py PyArray3<u8>, references: &'py PyArray3<f32>, blend_policy:String, source_mask:Option<PyObject>) -> PyResult<(&'py PyArray3<u8>,usize,f32,f32)> {
-    let src_shape = source.shape();
-    let ref_shape = references.shape();
-    assert_eq!(src_shape.len(),3,"Source image shape should be [height,width,channels]");
-    assert_eq!(ref_shape.len(),3,"References shape should be [batch,channels,256]");
-    assert_eq!(ref_shape[1],src_shape[2], "Number of channels does not match");
-    assert_eq!(ref_shape[2],256, "Number of pixel values must be 256");
-    let src_shape = [src_shape[0],src_shape[1],src_shape[2]];
-    let ref_shape = [ref_shape[0],ref_shape[1],ref_shape[2]];
-    let src = unsafe{source.as_slice()?};
-    let rfc = unsafe{references.as_slice()?};
-    let channels = src_shape[2];
-    let hist_src = if let Some(mask) = &source_mask {
-        let mask:&PyArray2<bool> = mask.extract(source.py())?;
-        let msk_shape = mask.shape();
-        assert_eq!(msk_shape,&src_shape[..2],"Mask shape should match source shape");
-        let msk = unsafe{mask.as_slice()?};
-        vf::histogram::histograms(src, channels, |i|msk[i] )
-    }else{
-        vf::histogram::histograms(src, channels,   |_|true)
-    };
-    let hist_norm_src = vf::histogram::normalize_histograms(hist_src.flatten());
-    let batch = ref_shape[0];
-    let channels256 = channels*256;
-    let (best_ref_idx, square_dist) = vf::histogram::find_closest_n(&hist_norm_src,batch,rfc);
-    let ref_offset = best_ref_idx*channels256;
-    let best_ref_hists = &rfc[ref_offset..ref_offset+channels256];
-    fn area_common(a:&[f32],b:&[f32])->f32{
-        a.iter().zip(b.iter()).map(|(&a,&b)|a.min(b)).sum()
-    }
-    let alpha = match blend_policy.as_str(){
-        "PROP_TO_SQUARED" => square_dist/channels as f32,
-        "PROP_TO_DIST" => (square_dist/channels as f32).sqrt(),
-        "PROP_TO_AREA_DIFF" => 1. - area_common(best_ref_hists,&hist_norm_src)/channels as f32,
-        "INV_PROP_TO_SQUARED" => 1. - square_dist/channels as f32,
-        "INV_PROP_TO_DIST" => 1. - (square_dist/channels as f32).sqrt(),
-        "INV_PROP_TO_AREA_DIFF" => area_common(best_ref_hists,&hist_norm_src)/channels as f32,
-        s => f32::from_str(s).map_err(|_|PyValueError::new_err(format!("Unknown policy {}", blend_policy)))?
-    };
-    let blended = vf::histogram::blend(alpha,&hist_norm_src,1.-alpha,best_ref_hists);
-    let out = if let Some(mask) = source_mask {
-        let mask:&PyArray2<bool> = mask.extract(source.py())?;
-        let msk = unsafe{mask.as_slice()?};
-        vf::histogram::match_2precomputed_images(src, &src_shape, hist_src.flatten(), &blended,|i|msk[i] )
-    }else{
-        vf::histogram::match_2precomputed_images(src, &src_shape, hist_src.flatten(),&blended,  |_|true)
-    };
-    let v = PyArray1::from_vec(source.py(),out.into_vec());
-    v.reshape(src_shape).map(|a|(a,best_ref_idx,square_dist,alpha))
-}
-
-
-
-#[pyfunction]
-#[text_signature = "(scalar1, histogram1, scalar2, histogram2)"]
-/// `histogram1` and `histogram2` shape `[channels,256]`
-pub fn blend_histograms<'py>(scalar1:f32, histogram1: &'py PyArray2<f32>, scalar2:f32, histogram2: &'py PyArray2<f32>) -> PyResult<&'py PyArray2<f32>> {
-    let s1 = histogram1.shape();
-    let s2 = histogram2.shape();
-    assert_eq!(s1.len(),2,"Shape should be [channels,256]");
-    assert_eq!(s2.len(),2,"Shape should be [channels,256]");
-    assert_eq!(s1[0],s2[0], "Number of channels does not match");
-    let so = [s1[0],s1[1]];
-    let h1 = unsafe{histogram1.as_slice()?};
-    let h2 = unsafe{histogram2.as_slice()?};
-    let ho:Vec<f32> = vf::histogram::blend(scalar1, h1, scalar2, h2);
-    let v = PyArray1::from_vec(histogram1.py(),ho);
-    v.reshape(so)
-}
-
 
 #[pyfunction]
 #[text_signature = "(n)"]
@@ -895,6 +980,84 @@ pub fn direct_product<'py>(a: &'py PyArray2<usize>, b: &'py PyArray2<usize>) ->
     PyArray1::from_vec(a.py(),m).reshape([a_shape[0]*b_shape[0],g])
 }
 
+#[pyfunction]
+#[text_signature = "(n)"]
+/// Dihedral group with `2n` elements. Generators are rotation, its inverse and reflection
+pub fn dihedral_group<'py>(py:Python<'py>, n:usize) -> PyResult<&'py PyArray2<usize>> {
+    if n == 0 {
+        return Err(PyValueError::new_err("Dihedral group needs n > 0"));
+    }
+    let (m,l) = vf::cayley::dihedral_group(n);
+    let ll = m.len()/l;
+    PyArray1::from_vec(py,m).reshape([ll,l])
+}
+
+#[pyfunction]
+#[text_signature = "(n)"]
+/// Symmetric group with `n!` elements. Generators are the cycle `(0 1 .. n-1)`, its inverse and transposition `(0 1)`
+pub fn symmetric_group<'py>(py:Python<'py>, n:usize) -> PyResult<&'py PyArray2<usize>> {
+    if n < 2 {
+        return Err(PyValueError::new_err("Symmetric group needs n > 1"));
+    }
+    let (m,l) = vf::cayley::symmetric_group(n);
+    let ll = m.len()/l;
+    PyArray1::from_vec(py,m).reshape([ll,l])
+}
+
+#[pyfunction]
+#[text_signature = "(generators)"]
+/// `generators` is of shape `[generators, degree]` and every row maps `0..degree` into itself.
+/// Returns `(cayley_graph, elements)`, where `cayley_graph` is of shape `[elements, generators]` and `elements` of shape
+/// `[elements, degree]` holds the mapping corresponding to every element. Element 0 is the identity.
+pub fn cayley_closure<'py>(generators: &'py PyArray2<usize>) -> PyResult<(&'py PyArray2<usize>, &'py PyArray2<usize>)> {
+    let py = generators.py();
+    let degree = generators.shape()[1];
+    let g = unsafe{generators.as_slice()?};
+    if degree == 0 {
+        return Err(PyValueError::new_err("Generators must have positive degree"));
+    }
+    if let Some(i) = g.iter().find(|&&i| i >= degree) {
+        return Err(PyValueError::new_err(format!("Generators must map 0..{} into itself but found {}", degree, i)));
+    }
+    let (m,l,elements) = py.allow_threads(||vf::cayley::closure(g, degree));
+    let ll = elements.len()/degree;
+    Ok((PyArray1::from_vec(py,m).reshape([ll,l])?, PyArray1::from_vec(py,elements).reshape([ll,degree])?))
+}
+
+#[pyfunction(walls = "None")]
+#[text_signature = "(height, width, walls)"]
+/// State space of an agent moving in a grid of shape `[height, width]`. Generators move it up, down, left and right.
+/// Moving out of bounds or into a wall (`walls` is a boolean array of shape `[height, width]`) leaves the agent in place.
+pub fn grid_world<'py>(py:Python<'py>, height:usize, width:usize, walls:Option<&PyArray2<bool>>) -> PyResult<&'py PyArray2<usize>> {
+    let no_walls;
+    let walls = if let Some(walls) = walls {
+        if walls.shape() != [height, width] {
+            return Err(PyValueError::new_err(format!("Walls shape {:?} should be {:?}", walls.shape(), [height, width])));
+        }
+        unsafe{walls.as_slice()?}
+    } else {
+        no_walls = vec![false; height * width];
+        &no_walls
+    };
+    let (m,l) = vf::cayley::grid_world(height, width, walls);
+    let ll = m.len()/l;
+    PyArray1::from_vec(py,m).reshape([ll,l])
+}
+
+#[pyfunction]
+#[text_signature = "(generators, depth)"]
+/// Free monoid truncated to words of length at most `depth`. Words of maximal length map to the number of elements
+/// (out of bounds), which `learn_uw` skips.
+pub fn free_monoid<'py>(py:Python<'py>, generators:usize, depth:usize) -> PyResult<&'py PyArray2<usize>> {
+    if generators == 0 {
+        return Err(PyValueError::new_err("Free monoid needs at least one generator"));
+    }
+    let (m,l) = vf::cayley::free_monoid(generators, depth)
+        .ok_or_else(|| PyValueError::new_err(format!("Free monoid with {} generators and depth {} is too large", generators, depth)))?;
+    let ll = m.len()/l;
+    PyArray1::from_vec(py,m).reshape([ll,l])
+}
+
 #[pyfunction]
 #[text_signature = "(state_space, w)"]
 /// `w` are feedforward weights
@@ -948,28 +1111,24 @@ pub fn rle_to_mat(rle:&PyArray1<usize>, shape:Vec<usize>)-> PyResult<&PyArrayDyn
     v.reshape(shape)
 }
 
-#[pymodule]
-fn histogram(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
-    m.add_function(wrap_pyfunction!(find_histogram_anomaly, m)?)?;
-    m.add_function(wrap_pyfunction!(match_histogram, m)?)?;
-    m.add_function(wrap_pyfunction!(match_best_images, m)?)?;
-    m.add_function(wrap_pyfunction!(blend_histograms, m)?)?;
-    m.add_function(wrap_pyfunction!(blend_and_match_best_images, m)?)?;
-    m.add_function(wrap_pyfunction!(normalize_histogram, m)?)?;
-    m.add_function(wrap_pyfunction!(image_histogram, m)?)?;
-    m.add_function(wrap_pyfunction!(match_precomputed_histogram, m)?)?;
-    m.add_function(wrap_pyfunction!(histogram_interpolate_gaps, m)?)?;
-
-    Ok(())
-}
-
-
 /// A Python module implemented in Rust. The name of this function must match
 /// the `lib.name` setting in the `Cargo.toml`, else Python will not be able to
 /// import the module.
 #[pymodule]
 fn ecc_py(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
     m.add_class::<ConvShape>()?;
+    m.add_class::<py_conv_shape_nd::ConvShape1d>()?;
+    m.add_class::<py_conv_shape_nd::ConvShape3d>()?;
+    m.add_class::<py_ecc_layer::EccLayer>()?;
+    m.add_class::<py_ecc_layer::HardWtaL1>()?;
+    m.add_class::<py_stdp::Stdp>()?;
+    #[cfg(not(feature = "pure-rust"))]
+    m.add_class::<py_sfa::Sfa>()?;
+    m.add_class::<py_sdr::Sdr>()?;
+    m.add_class::<py_sdr_dataset::CpuSdrDataset>()?;
+    m.add_class::<py_sdr_dataset::SubregionIndices>()?;
+    m.add_class::<py_sdr_dataset::CpuSdrDatasetIter>()?;
+    m.add_class::<py_sdr_dataset::Occurrences>()?;
     m.add_wrapped(&wrap_pymodule!(histogram))?;
     m.add_function(wrap_pyfunction!(sample, m)?)?;
     m.add_function(wrap_pyfunction!(version, m)?)?;
@@ -979,10 +1138,12 @@ fn ecc_py(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(conv_in_range, m)?)?;
     m.add_function(wrap_pyfunction!(conv_out_range, m)?)?;
     m.add_function(wrap_pyfunction!(conv_out_range_clipped_both_sides, m)?)?;
+    m.add_function(wrap_pyfunction!(conv_kernel_pos, m)?)?;
     m.add_function(wrap_pyfunction!(conv_in_range_begin, m)?)?;
     m.add_function(wrap_pyfunction!(conv_stride, m)?)?;
     m.add_function(wrap_pyfunction!(conv_compose_array, m)?)?;
     m.add_function(wrap_pyfunction!(conv_compose, m)?)?;
+    m.add_function(wrap_pyfunction!(conv_compose_padded, m)?)?;
     m.add_function(wrap_pyfunction!(conv_compose_weights, m)?)?;
     m.add_function(wrap_pyfunction!(soft_wta_u, m)?)?;
     m.add_function(wrap_pyfunction!(soft_wta_v, m)?)?;
@@ -996,12 +1157,19 @@ fn ecc_py(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(soft_wta_v_repeated_conv_, m)?)?;
     m.add_function(wrap_pyfunction!(multiplicative_soft_wta_u_repeated_conv_, m)?)?;
     m.add_function(wrap_pyfunction!(multiplicative_soft_wta_u, m)?)?;
+    m.add_function(wrap_pyfunction!(k_wta, m)?)?;
+    m.add_function(wrap_pyfunction!(k_wta_conv, m)?)?;
     m.add_function(wrap_pyfunction!(dense_to_sparse, m)?)?;
     m.add_function(wrap_pyfunction!(batch_dense_to_sparse, m)?)?;
     m.add_function(wrap_pyfunction!(rand_set, m)?)?;
     m.add_function(wrap_pyfunction!(cyclic_group, m)?)?;
     m.add_function(wrap_pyfunction!(cyclic_monoid, m)?)?;
     m.add_function(wrap_pyfunction!(direct_product, m)?)?;
+    m.add_function(wrap_pyfunction!(dihedral_group, m)?)?;
+    m.add_function(wrap_pyfunction!(symmetric_group, m)?)?;
+    m.add_function(wrap_pyfunction!(cayley_closure, m)?)?;
+    m.add_function(wrap_pyfunction!(grid_world, m)?)?;
+    m.add_function(wrap_pyfunction!(free_monoid, m)?)?;
     m.add_function(wrap_pyfunction!(learn_uw, m)?)?;
     m.add_function(wrap_pyfunction!(sample_of_cardinality, m)?)?;
     m.add_function(wrap_pyfunction!(rle_to_mat, m)?)?;
@@ -0,0 +1,408 @@
+use std::ops::Range;
+use numpy::{PyArray1, PyArrayDyn};
+use pyo3::prelude::*;
+use pyo3::{PyObjectProtocol, PyNativeType};
+use pyo3::exceptions::PyValueError;
+use pyo3::PyResult;
+use vf::{conv, VectorField};
+use crate::util::{padding_nd, impl_save_load, pickle, unpickle};
+
+type Idx = u32;
+
+fn padding_and_dilation<const DIM: usize>(padding: Option<PyObject>, dilation: Option<[Idx; DIM]>, stride: &[Idx; DIM]) -> PyResult<(conv::Padding<Idx, DIM>, [Idx; DIM])> {
+    let dilation = dilation.unwrap_or([1; DIM]);
+    if dilation.contains(&0) {
+        return Err(PyValueError::new_err(format!("Dilation must be positive but got {:?}", dilation)));
+    }
+    let padding = match padding {
+        None => conv::Padding::default(),
+        Some(padding) => Python::with_gil(|py| padding_nd(py, &padding))?
+    };
+    if padding == conv::Padding::Same && stride != &[1; DIM] {
+        return Err(PyValueError::new_err(format!("Padding 'same' requires stride 1 but got {:?}", stride)));
+    }
+    Ok((padding, dilation))
+}
+
+fn shape_of(arr: &[Idx]) -> Vec<usize> {
+    arr.iter().map(|&a| a as usize).collect()
+}
+
+/// Defines a python class wrapping ConvShape with the given number of spatial dimensions. Unlike the
+/// two dimensional ConvShape, all positions and shapes are lists and tensors may be of any numpy shape
+/// as long as it matches the expected one.
+macro_rules! conv_shape_nd {
+    ($class_name:ident, $dim:literal) => {
+        #[pyclass]
+        pub struct $class_name {
+            pub(crate) cs: vf::conv_shape::ConvShape<Idx, $dim>,
+        }
+
+        impl $class_name {
+            fn check_shape<T>(tensor: &PyArrayDyn<T>, expected: &[Idx], name: &str) -> PyResult<()> {
+                let expected = shape_of(expected);
+                if tensor.shape() != expected.as_slice() {
+                    return Err(PyValueError::new_err(format!("{} tensor shape is wrong. Expected {:?} but got {:?}", name, expected, tensor.shape())));
+                }
+                Ok(())
+            }
+            fn output_tensor<'py>(&self, py: Python<'py>, output: Option<&'py PyArrayDyn<f32>>) -> PyResult<&'py PyArrayDyn<f32>> {
+                match output {
+                    Some(output) => {
+                        Self::check_shape(output, self.cs.out_shape(), "Output")?;
+                        Ok(output)
+                    }
+                    None => Ok(PyArrayDyn::zeros(py, shape_of(self.cs.out_shape()), false))
+                }
+            }
+            fn input_tensor<'py>(&self, py: Python<'py>, input: Option<&'py PyArrayDyn<f32>>) -> PyResult<&'py PyArrayDyn<f32>> {
+                match input {
+                    Some(input) => {
+                        Self::check_shape(input, self.cs.in_shape(), "Input")?;
+                        Ok(input)
+                    }
+                    None => Ok(PyArrayDyn::zeros(py, shape_of(self.cs.in_shape()), false))
+                }
+            }
+        }
+
+        #[pymethods]
+        impl $class_name {
+            ///[out_grid..., out_channels, out_channels]
+            #[getter]
+            pub fn u_shape(&self) -> Vec<Idx> { self.cs.u_shape().to_vec() }
+            ///[out_channels, out_channels]
+            #[getter]
+            pub fn minicolumn_u_shape(&self) -> Vec<Idx> { self.cs.minicolumn_u_shape().to_vec() }
+            ///[kernel..., in_channels, out_grid..., out_channels]
+            #[getter]
+            pub fn w_shape(&self) -> Vec<Idx> { self.cs.w_shape().to_vec() }
+            ///[out_grid..., out_channels]
+            #[getter]
+            pub fn out_shape(&self) -> Vec<Idx> { self.cs.output_shape().to_vec() }
+            ///[in_grid..., in_channels]
+            #[getter]
+            pub fn in_shape(&self) -> Vec<Idx> { self.cs.input_shape().to_vec() }
+            #[getter]
+            pub fn kernel(&self) -> Vec<Idx> { self.cs.kernel().to_vec() }
+            #[getter]
+            pub fn stride(&self) -> Vec<Idx> { self.cs.stride().to_vec() }
+            /// number of zeros added before the first input
+            #[getter]
+            pub fn padding(&self) -> Vec<Idx> { self.cs.padding().to_vec() }
+            /// number of zeros added after the last input
+            #[getter]
+            pub fn padding_end(&self) -> Vec<Idx> { self.cs.padding_end().to_vec() }
+            #[getter]
+            pub fn dilation(&self) -> Vec<Idx> { self.cs.dilation().to_vec() }
+            ///[kernel..., in_channels]
+            #[getter]
+            pub fn kernel_column_shape(&self) -> Vec<Idx> { self.cs.kernel_column_shape().to_vec() }
+            ///[kernel..., in_channels, out_channels]
+            #[getter]
+            pub fn minicolumn_w_shape(&self) -> Vec<Idx> { self.cs.minicolumn_w_shape().to_vec() }
+            ///[out_grid..., out_channels, kernel..., in_channels]
+            #[getter]
+            pub fn receptive_field_shape(&self) -> Vec<Idx> { self.cs.receptive_field_shape().to_vec() }
+            ///[out_channels, kernel..., in_channels]
+            #[getter]
+            pub fn minicolumn_receptive_field_shape(&self) -> Vec<Idx> { self.cs.minicolumn_receptive_field_shape().to_vec() }
+            /// product of kernel dimensions
+            #[getter]
+            pub fn kernel_column_area(&self) -> Idx { self.cs.kernel_column_area() }
+            /// product of kernel dimensions times in_channels
+            #[getter]
+            pub fn kernel_column_volume(&self) -> Idx { self.cs.kernel_column_volume() }
+            #[getter]
+            pub fn in_grid(&self) -> Vec<Idx> { self.cs.in_grid().to_vec() }
+            #[getter]
+            pub fn out_grid(&self) -> Vec<Idx> { self.cs.out_grid().to_vec() }
+            #[getter]
+            pub fn out_channels(&self) -> Idx { self.cs.out_channels() }
+            #[getter]
+            pub fn in_channels(&self) -> Idx { self.cs.in_channels() }
+            #[getter]
+            pub fn out_area(&self) -> Idx { self.cs.out_area() }
+            #[getter]
+            pub fn in_area(&self) -> Idx { self.cs.in_area() }
+            #[getter]
+            pub fn out_volume(&self) -> Idx { self.cs.out_volume() }
+            #[getter]
+            pub fn in_volume(&self) -> Idx { self.cs.in_volume() }
+            #[text_signature = "(output_pos)"]
+            pub fn kernel_offset(&self, output_pos: [Idx; $dim + 1]) -> Vec<Idx> { self.cs.kernel_offset(&output_pos).to_vec() }
+            #[text_signature = "(input_column_pos, output_column_pos)"]
+            /// Position within the (undilated) kernel through which the input column connects to the output column. None if they are not connected.
+            pub fn kernel_pos(&self, input_column_pos: [Idx; $dim], output_column_pos: [Idx; $dim]) -> Option<Vec<Idx>> {
+                self.cs.kernel_pos(&input_column_pos, &output_column_pos).map(|k| k.to_vec())
+            }
+            #[text_signature = "(input_pos, output_pos)"]
+            pub fn pos_within_kernel(&self, input_pos: [Idx; $dim + 1], output_pos: [Idx; $dim + 1]) -> Vec<Idx> { self.cs.pos_within_kernel(&input_pos, &output_pos).to_vec() }
+            #[text_signature = "(input_pos, output_pos)"]
+            pub fn idx_within_kernel(&self, input_pos: [Idx; $dim + 1], output_pos: [Idx; $dim + 1]) -> Idx { self.cs.idx_within_kernel(&input_pos, &output_pos) }
+            #[text_signature = "(input_pos, output_pos)"]
+            pub fn idx(&self, input_pos: [Idx; $dim + 1], output_pos: [Idx; $dim + 1]) -> Idx { self.cs.idx(&input_pos, &output_pos) }
+            ///(start, end)
+            #[text_signature = "(output_column_pos)"]
+            pub fn in_range(&self, output_column_pos: [Idx; $dim]) -> (Vec<Idx>, Vec<Idx>) {
+                let Range { start, end } = self.cs.in_range(&output_column_pos);
+                (start.to_vec(), end.to_vec())
+            }
+            ///(start, end)
+            #[text_signature = "(input_pos)"]
+            pub fn out_range(&self, input_pos: [Idx; $dim]) -> (Vec<Idx>, Vec<Idx>) {
+                let Range { start, end } = self.cs.out_range(&input_pos);
+                (start.to_vec(), end.to_vec())
+            }
+            #[text_signature = "(conv_tensor, norm)"]
+            /// conv_tensor is of shape w_shape
+            pub fn normalize_kernel_columns(&self, conv_tensor: &PyArrayDyn<f32>, norm: usize) -> PyResult<()> {
+                Self::check_shape(conv_tensor, &self.cs.w_shape(), "Convolutional")?;
+                let rhs = unsafe { conv_tensor.as_slice_mut()? };
+                self.cs.normalize_kernel_columns(rhs, vf::l(norm));
+                Ok(())
+            }
+            #[text_signature = "(conv_tensor, norm)"]
+            /// conv_tensor is of shape minicolumn_w_shape
+            pub fn normalize_minicolumn(&self, conv_tensor: &PyArrayDyn<f32>, norm: usize) -> PyResult<()> {
+                Self::check_shape(conv_tensor, &self.cs.minicolumn_w_shape(), "Convolutional")?;
+                let rhs = unsafe { conv_tensor.as_slice_mut()? };
+                self.cs.normalize_minicolumn(rhs, vf::l(norm));
+                Ok(())
+            }
+            #[text_signature = "(lhs_tensor, rhs_conv_tensor, dot_product_output)"]
+            /// rhs_conv_tensor is of shape w_shape.
+            /// lhs_tensor is a sparse binary vector (list of indices).
+            /// dot_product_output is of shape out_shape
+            pub fn sparse_dot<'py>(&self, lhs_tensor: &'py PyArray1<Idx>, rhs_conv_tensor: &'py PyArrayDyn<f32>, dot_product_output: Option<&'py PyArrayDyn<f32>>) -> PyResult<PyObject> {
+                Self::check_shape(rhs_conv_tensor, &self.cs.w_shape(), "Convolutional")?;
+                let lhs = unsafe { lhs_tensor.as_slice()? };
+                let rhs = unsafe { rhs_conv_tensor.as_slice()? };
+                let out_tensor = self.output_tensor(lhs_tensor.py(), dot_product_output)?;
+                let out = unsafe { out_tensor.as_slice_mut()? };
+                self.cs.sparse_dot_slice_(lhs, rhs, out);
+                Ok(out_tensor.to_object(lhs_tensor.py()))
+            }
+            #[text_signature = "(lhs_tensor, rhs_conv_tensor, dot_product_output)"]
+            /// rhs_conv_tensor is of shape minicolumn_w_shape.
+            /// lhs_tensor is a sparse binary vector (list of indices).
+            /// dot_product_output is of shape out_shape
+            pub fn sparse_dot_repeated<'py>(&self, lhs_tensor: &'py PyArray1<Idx>, rhs_conv_tensor: &'py PyArrayDyn<f32>, dot_product_output: Option<&'py PyArrayDyn<f32>>) -> PyResult<PyObject> {
+                Self::check_shape(rhs_conv_tensor, &self.cs.minicolumn_w_shape(), "Convolutional")?;
+                let lhs = unsafe { lhs_tensor.as_slice()? };
+                let rhs = unsafe { rhs_conv_tensor.as_slice()? };
+                let out_tensor = self.output_tensor(lhs_tensor.py(), dot_product_output)?;
+                let out = unsafe { out_tensor.as_slice_mut()? };
+                self.cs.sparse_dot_repeated_slice_(lhs, rhs, out);
+                Ok(out_tensor.to_object(lhs_tensor.py()))
+            }
+            /// conv_tensor is of shape w_shape
+            /// input and output are sparse binary vectors (list of indices)
+            #[text_signature = "(conv_tensor, epsilon, input, output)"]
+            pub fn sparse_mul_assign(&self, conv_tensor: &PyArrayDyn<f32>, epsilon: f32, input: &PyArray1<Idx>, output: &PyArray1<Idx>) -> PyResult<()> {
+                Self::check_shape(conv_tensor, &self.cs.w_shape(), "Convolutional")?;
+                let inp = unsafe { input.as_slice()? };
+                let out = unsafe { output.as_slice()? };
+                let conv = unsafe { conv_tensor.as_slice_mut()? };
+                self.cs.sparse_mul_assign(conv, epsilon, inp, out);
+                Ok(())
+            }
+            /// conv_tensor is of shape minicolumn_w_shape
+            /// input and output are sparse binary vectors (list of indices)
+            #[text_signature = "(conv_tensor, epsilon, input, output)"]
+            pub fn sparse_mul_assign_repeated(&self, conv_tensor: &PyArrayDyn<f32>, epsilon: f32, input: &PyArray1<Idx>, output: &PyArray1<Idx>) -> PyResult<()> {
+                Self::check_shape(conv_tensor, &self.cs.minicolumn_w_shape(), "Convolutional")?;
+                let inp = unsafe { input.as_slice()? };
+                let out = unsafe { output.as_slice()? };
+                let conv = unsafe { conv_tensor.as_slice_mut()? };
+                self.cs.sparse_mul_assign_repeated(conv, epsilon, inp, out);
+                Ok(())
+            }
+            #[args(biased = "false")]
+            #[text_signature = "(conv_tensor, epsilon, input, output, biased=False)"]
+            /// conv_tensor is of shape w_shape
+            pub fn sparse_increment(&self, conv_tensor: &PyArrayDyn<f32>, epsilon: f32, input: &PyArray1<Idx>, output: &PyArray1<Idx>, biased: bool) -> PyResult<()> {
+                Self::check_shape(conv_tensor, &self.cs.w_shape(), "Convolutional")?;
+                let inp = unsafe { input.as_slice()? };
+                let out = unsafe { output.as_slice()? };
+                let conv = unsafe { conv_tensor.as_slice_mut()? };
+                if biased {
+                    self.cs.sparse_biased_increment(conv, epsilon, inp, out)
+                } else {
+                    self.cs.sparse_unbiased_increment(conv, epsilon, inp, out)
+                }
+                Ok(())
+            }
+            #[args(biased = "false")]
+            #[text_signature = "(conv_tensor, epsilon, input, output, biased=False)"]
+            /// conv_tensor is of shape minicolumn_w_shape
+            pub fn sparse_increment_repeated(&self, conv_tensor: &PyArrayDyn<f32>, epsilon: f32, input: &PyArray1<Idx>, output: &PyArray1<Idx>, biased: bool) -> PyResult<()> {
+                Self::check_shape(conv_tensor, &self.cs.minicolumn_w_shape(), "Convolutional")?;
+                let inp = unsafe { input.as_slice()? };
+                let out = unsafe { output.as_slice()? };
+                let conv = unsafe { conv_tensor.as_slice_mut()? };
+                if biased {
+                    self.cs.sparse_biased_increment_repeated(conv, epsilon, inp, out)
+                } else {
+                    self.cs.sparse_unbiased_increment_repeated(conv, epsilon, inp, out)
+                }
+                Ok(())
+            }
+            #[text_signature = "(lhs_tensor, rhs_conv_tensor, dot_product_output)"]
+            /// rhs_conv_tensor is of shape w_shape and lhs_tensor of shape in_shape.
+            /// dot_product_output is of shape out_shape
+            pub fn dense_dot<'py>(&self, lhs_tensor: &'py PyArrayDyn<f32>, rhs_conv_tensor: &'py PyArrayDyn<f32>, dot_product_output: Option<&'py PyArrayDyn<f32>>) -> PyResult<PyObject> {
+                Self::check_shape(lhs_tensor, self.cs.in_shape(), "Input")?;
+                Self::check_shape(rhs_conv_tensor, &self.cs.w_shape(), "Convolutional")?;
+                let lhs = unsafe { lhs_tensor.as_slice()? };
+                let rhs = unsafe { rhs_conv_tensor.as_slice()? };
+                let out_tensor = self.output_tensor(lhs_tensor.py(), dot_product_output)?;
+                let out = unsafe { out_tensor.as_slice_mut()? };
+                self.cs.dense_dot_slice_(lhs, rhs, out);
+                Ok(out_tensor.to_object(lhs_tensor.py()))
+            }
+            #[text_signature = "(lhs_tensor, rhs_conv_tensor, dot_product_output)"]
+            /// rhs_conv_tensor is of shape minicolumn_w_shape and lhs_tensor of shape in_shape.
+            /// dot_product_output is of shape out_shape
+            pub fn dense_dot_repeated<'py>(&self, lhs_tensor: &'py PyArrayDyn<f32>, rhs_conv_tensor: &'py PyArrayDyn<f32>, dot_product_output: Option<&'py PyArrayDyn<f32>>) -> PyResult<PyObject> {
+                Self::check_shape(lhs_tensor, self.cs.in_shape(), "Input")?;
+                Self::check_shape(rhs_conv_tensor, &self.cs.minicolumn_w_shape(), "Convolutional")?;
+                let lhs = unsafe { lhs_tensor.as_slice()? };
+                let rhs = unsafe { rhs_conv_tensor.as_slice()? };
+                let out_tensor = self.output_tensor(lhs_tensor.py(), dot_product_output)?;
+                let out = unsafe { out_tensor.as_slice_mut()? };
+                self.cs.dense_dot_repeated_slice_(lhs, rhs, out);
+                Ok(out_tensor.to_object(lhs_tensor.py()))
+            }
+            #[text_signature = "(output_tensor, rhs_conv_tensor, input_reconstruction)"]
+            /// Transposed convolution. rhs_conv_tensor is of shape w_shape and output_tensor of shape out_shape.
+            /// input_reconstruction is of shape in_shape
+            pub fn dense_transpose_dot<'py>(&self, output_tensor: &'py PyArrayDyn<f32>, rhs_conv_tensor: &'py PyArrayDyn<f32>, input_reconstruction: Option<&'py PyArrayDyn<f32>>) -> PyResult<PyObject> {
+                Self::check_shape(output_tensor, self.cs.out_shape(), "Output")?;
+                Self::check_shape(rhs_conv_tensor, &self.cs.w_shape(), "Convolutional")?;
+                let out = unsafe { output_tensor.as_slice()? };
+                let rhs = unsafe { rhs_conv_tensor.as_slice()? };
+                let in_tensor = self.input_tensor(output_tensor.py(), input_reconstruction)?;
+                let inp = unsafe { in_tensor.as_slice_mut()? };
+                self.cs.dense_transpose_dot_slice_(out, rhs, inp);
+                Ok(in_tensor.to_object(output_tensor.py()))
+            }
+            #[text_signature = "(output_tensor, rhs_conv_tensor, input_reconstruction)"]
+            /// Transposed convolution. rhs_conv_tensor is of shape minicolumn_w_shape and output_tensor of shape out_shape.
+            /// input_reconstruction is of shape in_shape
+            pub fn dense_transpose_dot_repeated<'py>(&self, output_tensor: &'py PyArrayDyn<f32>, rhs_conv_tensor: &'py PyArrayDyn<f32>, input_reconstruction: Option<&'py PyArrayDyn<f32>>) -> PyResult<PyObject> {
+                Self::check_shape(output_tensor, self.cs.out_shape(), "Output")?;
+                Self::check_shape(rhs_conv_tensor, &self.cs.minicolumn_w_shape(), "Convolutional")?;
+                let out = unsafe { output_tensor.as_slice()? };
+                let rhs = unsafe { rhs_conv_tensor.as_slice()? };
+                let in_tensor = self.input_tensor(output_tensor.py(), input_reconstruction)?;
+                let inp = unsafe { in_tensor.as_slice_mut()? };
+                self.cs.dense_transpose_dot_repeated_slice_(out, rhs, inp);
+                Ok(in_tensor.to_object(output_tensor.py()))
+            }
+            #[text_signature = "(conv_tensor, epsilon, input, output)"]
+            /// Hebbian update w += epsilon * x * y. conv_tensor is of shape w_shape, input of shape in_shape and output of shape out_shape
+            pub fn dense_increment(&self, conv_tensor: &PyArrayDyn<f32>, epsilon: f32, input: &PyArrayDyn<f32>, output: &PyArrayDyn<f32>) -> PyResult<()> {
+                Self::check_shape(conv_tensor, &self.cs.w_shape(), "Convolutional")?;
+                Self::check_shape(input, self.cs.in_shape(), "Input")?;
+                Self::check_shape(output, self.cs.out_shape(), "Output")?;
+                let inp = unsafe { input.as_slice()? };
+                let out = unsafe { output.as_slice()? };
+                let conv = unsafe { conv_tensor.as_slice_mut()? };
+                self.cs.dense_increment(conv, epsilon, inp, out);
+                Ok(())
+            }
+            #[text_signature = "(conv_tensor, epsilon, input, output)"]
+            /// Hebbian update w += epsilon * x * y summed over all output columns. conv_tensor is of shape minicolumn_w_shape,
+            /// input of shape in_shape and output of shape out_shape
+            pub fn dense_increment_repeated(&self, conv_tensor: &PyArrayDyn<f32>, epsilon: f32, input: &PyArrayDyn<f32>, output: &PyArrayDyn<f32>) -> PyResult<()> {
+                Self::check_shape(conv_tensor, &self.cs.minicolumn_w_shape(), "Convolutional")?;
+                Self::check_shape(input, self.cs.in_shape(), "Input")?;
+                Self::check_shape(output, self.cs.out_shape(), "Output")?;
+                let inp = unsafe { input.as_slice()? };
+                let out = unsafe { output.as_slice()? };
+                let conv = unsafe { conv_tensor.as_slice_mut()? };
+                self.cs.dense_increment_repeated(conv, epsilon, inp, out);
+                Ok(())
+            }
+            #[text_signature = "(next)"]
+            pub fn compose(&self, next: &Self) -> Self {
+                Self { cs: self.cs.compose(&next.cs) }
+            }
+            #[staticmethod]
+            #[text_signature = "(shape)"]
+            pub fn new_identity(shape: [Idx; $dim + 1]) -> Self {
+                Self { cs: vf::conv_shape::ConvShape::new_identity(shape) }
+            }
+            #[staticmethod]
+            /// This convolution is in fact just a dense linear layer with certain number of inputs and outputs.
+            #[text_signature = "(input, output)"]
+            pub fn new_linear(input: Idx, output: Idx) -> Self {
+                Self { cs: vf::conv_shape::ConvShape::new_linear(input, output) }
+            }
+            #[new]
+            pub fn new(output: [Idx; $dim], kernel: [Idx; $dim], stride: [Idx; $dim], in_channels: Idx, out_channels: Idx) -> Self {
+                Self { cs: vf::conv_shape::ConvShape::new(output, kernel, stride, in_channels, out_channels) }
+            }
+            #[staticmethod]
+            #[args(padding = "None", dilation = "None")]
+            #[text_signature = "(input_shape, out_channels, kernel, stride, padding, dilation)"]
+            /// padding may be an int, a list, "same" or "valid". Without padding and dilation the stride must evenly divide the input.
+            pub fn new_in(input_shape: [Idx; $dim + 1], out_channels: Idx, kernel: [Idx; $dim], stride: [Idx; $dim], padding: Option<PyObject>, dilation: Option<[Idx; $dim]>) -> PyResult<Self> {
+                Ok(Self { cs: if padding.is_none() && dilation.is_none() {
+                    vf::conv_shape::ConvShape::new_in(input_shape, out_channels, kernel, stride)
+                } else {
+                    let (padding, dilation) = padding_and_dilation(padding, dilation, &stride)?;
+                    vf::conv_shape::ConvShape::new_in_padded(input_shape, out_channels, kernel, stride, padding, dilation)
+                }})
+            }
+            #[staticmethod]
+            #[args(padding = "None", dilation = "None")]
+            #[text_signature = "(in_channels, output_shape, kernel, stride, padding, dilation)"]
+            /// padding may be an int, a list, "same" or "valid"
+            pub fn new_out(in_channels: Idx, output_shape: [Idx; $dim + 1], kernel: [Idx; $dim], stride: [Idx; $dim], padding: Option<PyObject>, dilation: Option<[Idx; $dim]>) -> PyResult<Self> {
+                let (padding, dilation) = padding_and_dilation(padding, dilation, &stride)?;
+                Ok(Self { cs: vf::conv_shape::ConvShape::new_out_padded(in_channels, output_shape, kernel, stride, padding, dilation) })
+            }
+            #[text_signature = "(new_stride)"]
+            pub fn set_stride(&mut self, new_stride: [Idx; $dim]) {
+                self.cs.set_stride(new_stride)
+            }
+            #[text_signature = "(weights)"]
+            /// Input weights are of shape minicolumn_w_shape. Output is of shape w_shape
+            pub fn repeat_minicolumn(&self, weights: &PyArrayDyn<f32>) -> PyResult<PyObject> {
+                Self::check_shape(weights, &self.cs.minicolumn_w_shape(), "Weight")?;
+                let inp = unsafe { weights.as_slice()? };
+                let out = self.cs.repeat_minicolumn(inp);
+                let out = PyArray1::from_vec(weights.py(), out);
+                let out = out.reshape(shape_of(&self.cs.w_shape()))?;
+                Ok(out.to_object(weights.py()))
+            }
+            #[text_signature = "(minicolumn_receptive_field, x, y)"]
+            /// minicolumn_receptive_field is of shape minicolumn_receptive_field_shape
+            pub fn add_to_receptive_field_repeated(&self, minicolumn_receptive_field: &PyArrayDyn<f32>, x: &PyArray1<Idx>, y: &PyArray1<Idx>) -> PyResult<()> {
+                Self::check_shape(minicolumn_receptive_field, &self.cs.minicolumn_receptive_field_shape(), "Receptive field")?;
+                let minicolumn_receptive_field = unsafe { minicolumn_receptive_field.as_slice_mut()? };
+                let x = unsafe { x.as_slice()? };
+                let y = unsafe { y.as_slice()? };
+                self.cs.add_to_receptive_field_repeated(minicolumn_receptive_field, x, y);
+                Ok(())
+            }
+        }
+
+        impl_save_load!($class_name, cs);
+
+        #[pyproto]
+        impl PyObjectProtocol for $class_name {
+            fn __repr__(&self) -> String {
+                format!("{:?}", &self.cs)
+            }
+            fn __str__(&self) -> String {
+                self.__repr__()
+            }
+        }
+    };
+}
+
+conv_shape_nd!(ConvShape1d, 1);
+conv_shape_nd!(ConvShape3d, 3);
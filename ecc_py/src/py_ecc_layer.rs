@@ -0,0 +1,396 @@
+use numpy::{IntoPyArray, PyArray1, PyArrayDyn};
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+use pyo3::{PyNativeType, PyObjectProtocol, PyResult};
+use vf::ecc_layer::Inhibition;
+use crate::ConvShape;
+use crate::util::{impl_save_load, pickle, unpickle};
+
+type Idx = u32;
+
+///
+/// EccLayer(shape: ConvShape, norm: int = 2, threshold: float = 0., binary_inhibition: bool = False)
+///
+/// Single layer of Early Competition Circuit with weights W, inhibitory weights U (or binary V if binary_inhibition)
+/// and bias r. Excitation is s = xW + r and neurons above threshold compete by soft winner-take-all within their minicolumn.
+/// W is randomly initialised and normalised with respect to the given norm (1 or 2).
+/// Layers created with new_linear use dense numpy shapes, W is then of shape [input, output] and U of shape [output, output].
+///
+#[pyclass]
+#[text_signature = "(shape, norm, threshold, binary_inhibition, /)"]
+pub struct EccLayer {
+    pub(crate) l: vf::ecc_layer::EccLayer<Idx>,
+}
+
+fn is_linear(cs: &vf::conv_shape::ConvShape<Idx>) -> bool {
+    cs.in_grid() == &[1, 1] && cs.out_grid() == &[1, 1]
+}
+
+fn in_shape(cs: &vf::conv_shape::ConvShape<Idx>) -> Vec<usize> {
+    if is_linear(cs) { vec![cs.in_channels() as usize] } else { cs.in_shape().iter().map(|&a| a as usize).collect() }
+}
+
+fn out_shape(cs: &vf::conv_shape::ConvShape<Idx>) -> Vec<usize> {
+    if is_linear(cs) { vec![cs.out_channels() as usize] } else { cs.out_shape().iter().map(|&a| a as usize).collect() }
+}
+
+fn w_shape(cs: &vf::conv_shape::ConvShape<Idx>) -> Vec<usize> {
+    if is_linear(cs) { vec![cs.in_channels() as usize, cs.out_channels() as usize] } else { cs.w_shape().iter().map(|&a| a as usize).collect() }
+}
+
+fn check_shape<T>(tensor: &PyArrayDyn<T>, expected: &[usize], name: &str) -> PyResult<()> {
+    if tensor.shape() != expected {
+        return Err(PyValueError::new_err(format!("{} tensor shape is wrong. Expected {:?} but got {:?}", name, expected, tensor.shape())));
+    }
+    Ok(())
+}
+
+fn check_sparse(indices: &[Idx], volume: Idx, name: &str) -> PyResult<()> {
+    if let Some(i) = indices.iter().find(|&&i| i >= volume) {
+        return Err(PyValueError::new_err(format!("{} index {} is out of bounds for volume {}", name, i, volume)));
+    }
+    Ok(())
+}
+
+impl EccLayer {
+    fn in_shape(&self) -> Vec<usize> {
+        in_shape(self.l.shape())
+    }
+    fn out_shape(&self) -> Vec<usize> {
+        out_shape(self.l.shape())
+    }
+    fn w_shape(&self) -> Vec<usize> {
+        w_shape(self.l.shape())
+    }
+    fn u_shape(&self) -> Vec<usize> {
+        let c = self.l.shape().out_channels() as usize;
+        let mut shape = self.out_shape();
+        shape.push(c);
+        shape
+    }
+    fn check_norm(norm: usize) -> PyResult<()> {
+        if norm != 1 && norm != 2 {
+            return Err(PyValueError::new_err(format!("Norm must be 1 or 2 but got {}", norm)));
+        }
+        Ok(())
+    }
+    fn from_shape(cs: vf::conv_shape::ConvShape<Idx>, norm: usize, threshold: f32, binary_inhibition: bool) -> PyResult<Self> {
+        Self::check_norm(norm)?;
+        let l = if binary_inhibition {
+            vf::ecc_layer::EccLayer::new_v(cs, norm, threshold)
+        } else {
+            vf::ecc_layer::EccLayer::new_u(cs, norm, threshold)
+        };
+        Ok(Self { l })
+    }
+    fn check_x(&self, x: &[Idx]) -> PyResult<()> {
+        check_sparse(x, self.l.shape().in_volume(), "Input")
+    }
+    fn check_y(&self, y: &[Idx]) -> PyResult<()> {
+        check_sparse(y, self.l.shape().out_volume(), "Output")
+    }
+    fn infer<'py>(&self, py: Python<'py>, s: Vec<f32>) -> (&'py PyArrayDyn<f32>, &'py PyArray1<Idx>) {
+        let y = py.allow_threads(|| self.l.soft_wta(&s));
+        let s = s.into_pyarray(py).reshape(self.out_shape()).unwrap();
+        (s, y.into_pyarray(py))
+    }
+}
+
+#[pymethods]
+impl EccLayer {
+    #[new]
+    #[args(norm = "2", threshold = "0.", binary_inhibition = "false")]
+    pub fn new(shape: PyRef<ConvShape>, norm: usize, threshold: f32, binary_inhibition: bool) -> PyResult<Self> {
+        Self::from_shape(shape.cs.clone(), norm, threshold, binary_inhibition)
+    }
+    #[staticmethod]
+    #[args(norm = "2", threshold = "0.", binary_inhibition = "false")]
+    #[text_signature = "(input, output, norm, threshold, binary_inhibition)"]
+    pub fn new_linear(input: Idx, output: Idx, norm: usize, threshold: f32, binary_inhibition: bool) -> PyResult<Self> {
+        Self::from_shape(vf::conv_shape::ConvShape::new_linear(input, output), norm, threshold, binary_inhibition)
+    }
+    #[getter]
+    pub fn shape(&self) -> ConvShape {
+        ConvShape { cs: self.l.shape().clone() }
+    }
+    #[getter]
+    pub fn get_threshold(&self) -> f32 {
+        self.l.threshold()
+    }
+    #[setter]
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.l.set_threshold(threshold)
+    }
+    #[getter]
+    pub fn binary_inhibition(&self) -> bool {
+        matches!(self.l.inhibition(), Inhibition::V(_))
+    }
+    #[text_signature = "()"]
+    /// Returns a copy of weights of shape [kernel_height, kernel_width, in_channels, out_height, out_width, out_channels]
+    pub fn get_w<'py>(&self, py: Python<'py>) -> &'py PyArrayDyn<f32> {
+        self.l.w().to_vec().into_pyarray(py).reshape(self.w_shape()).unwrap()
+    }
+    #[text_signature = "(w)"]
+    pub fn set_w(&mut self, w: &PyArrayDyn<f32>) -> PyResult<()> {
+        check_shape(w, &self.w_shape(), "W")?;
+        self.l.w_mut().copy_from_slice(unsafe { w.as_slice()? });
+        Ok(())
+    }
+    #[text_signature = "()"]
+    /// Returns a copy of inhibitory weights of shape [out_height, out_width, out_channels, out_channels].
+    /// The dtype is bool if inhibition is binary.
+    pub fn get_u(&self, py: Python) -> PyObject {
+        let shape = self.u_shape();
+        match self.l.inhibition() {
+            Inhibition::U(u) => u.to_vec().into_pyarray(py).reshape(shape).unwrap().to_object(py),
+            Inhibition::V(v) => v.to_vec().into_pyarray(py).reshape(shape).unwrap().to_object(py),
+        }
+    }
+    #[text_signature = "(u)"]
+    /// Sets inhibitory weights. Passing an array of bools switches to binary inhibition.
+    pub fn set_u(&mut self, u: &PyAny) -> PyResult<()> {
+        let shape = self.u_shape();
+        let inhibition = if let Ok(u) = u.downcast::<PyArrayDyn<f32>>() {
+            check_shape(u, &shape, "U")?;
+            Inhibition::U(unsafe { u.as_slice()? }.to_vec())
+        } else {
+            let v = u.downcast::<PyArrayDyn<bool>>()?;
+            check_shape(v, &shape, "V")?;
+            Inhibition::V(unsafe { v.as_slice()? }.to_vec())
+        };
+        *self.l.inhibition_mut() = inhibition;
+        Ok(())
+    }
+    #[text_signature = "()"]
+    /// Returns a copy of bias of shape [out_height, out_width, out_channels]
+    pub fn get_r<'py>(&self, py: Python<'py>) -> &'py PyArrayDyn<f32> {
+        self.l.r().to_vec().into_pyarray(py).reshape(self.out_shape()).unwrap()
+    }
+    #[text_signature = "(r)"]
+    pub fn set_r(&mut self, r: &PyArrayDyn<f32>) -> PyResult<()> {
+        check_shape(r, &self.out_shape(), "r")?;
+        self.l.r_mut().copy_from_slice(unsafe { r.as_slice()? });
+        Ok(())
+    }
+    #[text_signature = "(x)"]
+    /// x is a sparse binary vector (list of indices). Returns excitation s and sparse vector of winners y.
+    pub fn infer_sparse<'py>(&self, x: &'py PyArray1<Idx>) -> PyResult<(&'py PyArrayDyn<f32>, &'py PyArray1<Idx>)> {
+        let py = x.py();
+        let x = unsafe { x.as_slice()? };
+        self.check_x(x)?;
+        Ok(self.infer(py, self.l.s_sparse(x)))
+    }
+    #[text_signature = "(x)"]
+    /// x is a dense input of shape [in_height, in_width, in_channels]. Returns excitation s and sparse vector of winners y.
+    pub fn infer_dense<'py>(&self, x: &'py PyArrayDyn<f32>) -> PyResult<(&'py PyArrayDyn<f32>, &'py PyArray1<Idx>)> {
+        check_shape(x, &self.in_shape(), "Input")?;
+        let s = self.l.s_dense(unsafe { x.as_slice()? });
+        Ok(self.infer(x.py(), s))
+    }
+    #[text_signature = "(s)"]
+    /// Runs soft winner-take-all on excitation s of shape [out_height, out_width, out_channels]. Returns sparse vector of winners.
+    pub fn soft_wta<'py>(&self, s: &'py PyArrayDyn<f32>) -> PyResult<&'py PyArray1<Idx>> {
+        check_shape(s, &self.out_shape(), "s")?;
+        let py = s.py();
+        let s = unsafe { s.as_slice()? };
+        Ok(py.allow_threads(|| self.l.soft_wta(s)).into_pyarray(py))
+    }
+    #[text_signature = "(norm)"]
+    pub fn normalize_w(&mut self, norm: usize) -> PyResult<()> {
+        Self::check_norm(norm)?;
+        self.l.normalize_w(norm);
+        Ok(())
+    }
+    #[args(biased = "false", norm = "2")]
+    #[text_signature = "(epsilon, x, y, biased, norm)"]
+    /// Hebbian update of weights between sparse inputs x and winners y, followed by normalisation of the winners' weights.
+    /// Unless biased, the increment epsilon is split evenly among the active inputs. With the defaults this is HardWtaL2
+    /// from py/convergence_tests/single_layer.py, norm 1 gives HardWtaZeroOrder and biased=True gives SoftWta.
+    pub fn learn_w_sparse(&mut self, epsilon: f32, x: &PyArray1<Idx>, y: &PyArray1<Idx>, biased: bool, norm: usize) -> PyResult<()> {
+        let (x, y) = unsafe { (x.as_slice()?, y.as_slice()?) };
+        self.check_x(x)?;
+        self.check_y(y)?;
+        Self::check_norm(norm)?;
+        self.l.learn_w_sparse(epsilon, x, y, biased, norm);
+        Ok(())
+    }
+    #[args(norm = "2")]
+    #[text_signature = "(epsilon, x, y, norm)"]
+    /// Hebbian update w += epsilon * x of weights of winners y, followed by their normalisation.
+    pub fn learn_w_dense(&mut self, epsilon: f32, x: &PyArrayDyn<f32>, y: &PyArray1<Idx>, norm: usize) -> PyResult<()> {
+        check_shape(x, &self.in_shape(), "Input")?;
+        let (x, y) = unsafe { (x.as_slice()?, y.as_slice()?) };
+        self.check_y(y)?;
+        Self::check_norm(norm)?;
+        self.l.learn_w_dense(epsilon, x, y, norm);
+        Ok(())
+    }
+    #[text_signature = "(epsilon, s, y)"]
+    /// Moves U[k,j] of every winner k towards the expected value of s[k]-s[j]
+    pub fn learn_u(&mut self, epsilon: f32, s: &PyArrayDyn<f32>, y: &PyArray1<Idx>) -> PyResult<()> {
+        if self.binary_inhibition() {
+            return Err(PyValueError::new_err("Binary inhibition V can't be learned"));
+        }
+        check_shape(s, &self.out_shape(), "s")?;
+        let (s, y) = unsafe { (s.as_slice()?, y.as_slice()?) };
+        self.check_y(y)?;
+        self.l.learn_u(epsilon, s, y);
+        Ok(())
+    }
+    #[text_signature = "(epsilon, y)"]
+    /// Decreases bias r of winners y by epsilon
+    pub fn learn_r(&mut self, epsilon: f32, y: &PyArray1<Idx>) -> PyResult<()> {
+        let y = unsafe { y.as_slice()? };
+        self.check_y(y)?;
+        self.l.learn_r(epsilon, y);
+        Ok(())
+    }
+}
+
+impl_save_load!(EccLayer, l);
+
+#[pyproto]
+impl PyObjectProtocol for EccLayer {
+    fn __repr__(&self) -> String {
+        format!("EccLayer(shape={:?}, threshold={}, binary_inhibition={})", self.l.shape(), self.l.threshold(), self.binary_inhibition())
+    }
+    fn __str__(&self) -> String {
+        self.__repr__()
+    }
+}
+
+///
+/// HardWtaL1(shape: ConvShape, a: int)
+///
+/// Port of HardWtaL1 from py/convergence_tests/single_layer.py. Every output neuron has permanences Q (randomly
+/// initialised) but is connected only to the a inputs of its kernel column with the largest permanence, so the weights W
+/// are binary. Excitation is s = xW + r and only the neuron with the largest excitation in every minicolumn wins.
+///
+#[pyclass]
+#[text_signature = "(shape, a, /)"]
+pub struct HardWtaL1 {
+    pub(crate) l: vf::ecc_layer::HardWtaL1<Idx>,
+}
+
+impl HardWtaL1 {
+    fn from_shape(cs: vf::conv_shape::ConvShape<Idx>, a: usize) -> PyResult<Self> {
+        let kcv = cs.kernel_column_volume() as usize;
+        if a > kcv {
+            return Err(PyValueError::new_err(format!("Can't connect {} inputs in kernel column of volume {}", a, kcv)));
+        }
+        Ok(Self { l: vf::ecc_layer::HardWtaL1::new(cs, a) })
+    }
+    fn check_x(&self, x: &[Idx]) -> PyResult<()> {
+        check_sparse(x, self.l.shape().in_volume(), "Input")
+    }
+    fn check_y(&self, y: &[Idx]) -> PyResult<()> {
+        check_sparse(y, self.l.shape().out_volume(), "Output")
+    }
+    fn infer<'py>(&self, py: Python<'py>, s: Vec<f32>) -> (&'py PyArrayDyn<f32>, &'py PyArray1<Idx>) {
+        let y = self.l.hard_wta(&s);
+        let s = s.into_pyarray(py).reshape(out_shape(self.l.shape())).unwrap();
+        (s, y.into_pyarray(py))
+    }
+}
+
+#[pymethods]
+impl HardWtaL1 {
+    #[new]
+    pub fn new(shape: PyRef<ConvShape>, a: usize) -> PyResult<Self> {
+        Self::from_shape(shape.cs.clone(), a)
+    }
+    #[staticmethod]
+    #[text_signature = "(input, output, a)"]
+    pub fn new_linear(input: Idx, output: Idx, a: usize) -> PyResult<Self> {
+        Self::from_shape(vf::conv_shape::ConvShape::new_linear(input, output), a)
+    }
+    #[getter]
+    pub fn shape(&self) -> ConvShape {
+        ConvShape { cs: self.l.shape().clone() }
+    }
+    #[getter]
+    pub fn a(&self) -> usize {
+        self.l.a()
+    }
+    #[text_signature = "()"]
+    /// Returns a copy of permanences of shape [kernel_height, kernel_width, in_channels, out_height, out_width, out_channels]
+    pub fn get_q<'py>(&self, py: Python<'py>) -> &'py PyArrayDyn<f32> {
+        self.l.q().to_vec().into_pyarray(py).reshape(w_shape(self.l.shape())).unwrap()
+    }
+    #[text_signature = "(q)"]
+    /// Sets permanences and reconnects every neuron to the a inputs with the largest permanence
+    pub fn set_q(&mut self, q: &PyArrayDyn<f32>) -> PyResult<()> {
+        check_shape(q, &w_shape(self.l.shape()), "Q")?;
+        self.l.set_q(unsafe { q.as_slice()? });
+        Ok(())
+    }
+    #[text_signature = "()"]
+    /// Returns a copy of binary weights (0 or 1) of the same shape as Q
+    pub fn get_w<'py>(&self, py: Python<'py>) -> &'py PyArrayDyn<f32> {
+        self.l.w().to_vec().into_pyarray(py).reshape(w_shape(self.l.shape())).unwrap()
+    }
+    #[text_signature = "()"]
+    /// Returns a copy of bias of shape [out_height, out_width, out_channels]
+    pub fn get_r<'py>(&self, py: Python<'py>) -> &'py PyArrayDyn<f32> {
+        self.l.r().to_vec().into_pyarray(py).reshape(out_shape(self.l.shape())).unwrap()
+    }
+    #[text_signature = "(r)"]
+    pub fn set_r(&mut self, r: &PyArrayDyn<f32>) -> PyResult<()> {
+        check_shape(r, &out_shape(self.l.shape()), "r")?;
+        self.l.r_mut().copy_from_slice(unsafe { r.as_slice()? });
+        Ok(())
+    }
+    #[text_signature = "(x)"]
+    /// x is a sparse binary vector (list of indices). Returns excitation s and sparse vector of winners y.
+    pub fn infer_sparse<'py>(&self, x: &'py PyArray1<Idx>) -> PyResult<(&'py PyArrayDyn<f32>, &'py PyArray1<Idx>)> {
+        let py = x.py();
+        let x = unsafe { x.as_slice()? };
+        self.check_x(x)?;
+        Ok(self.infer(py, self.l.s_sparse(x)))
+    }
+    #[text_signature = "(x)"]
+    /// x is a dense input of shape [in_height, in_width, in_channels]. Returns excitation s and sparse vector of winners y.
+    pub fn infer_dense<'py>(&self, x: &'py PyArrayDyn<f32>) -> PyResult<(&'py PyArrayDyn<f32>, &'py PyArray1<Idx>)> {
+        check_shape(x, &in_shape(self.l.shape()), "Input")?;
+        let s = self.l.s_dense(unsafe { x.as_slice()? });
+        Ok(self.infer(x.py(), s))
+    }
+    #[text_signature = "(epsilon, x, y)"]
+    /// Q[:,k] = (1-epsilon)Q[:,k] + epsilon*x for every winner k in y, where x is a sparse binary vector
+    pub fn learn_q_sparse(&mut self, epsilon: f32, x: &PyArray1<Idx>, y: &PyArray1<Idx>) -> PyResult<()> {
+        let (x, y) = unsafe { (x.as_slice()?, y.as_slice()?) };
+        self.check_x(x)?;
+        self.check_y(y)?;
+        self.l.learn_q_sparse(epsilon, x, y);
+        Ok(())
+    }
+    #[text_signature = "(epsilon, x, y)"]
+    /// Q[:,k] = (1-epsilon)Q[:,k] + epsilon*x for every winner k in y, where x is of shape [in_height, in_width, in_channels]
+    pub fn learn_q_dense(&mut self, epsilon: f32, x: &PyArrayDyn<f32>, y: &PyArray1<Idx>) -> PyResult<()> {
+        check_shape(x, &in_shape(self.l.shape()), "Input")?;
+        let (x, y) = unsafe { (x.as_slice()?, y.as_slice()?) };
+        self.check_y(y)?;
+        self.l.learn_q_dense(epsilon, x, y);
+        Ok(())
+    }
+    #[text_signature = "(epsilon, y)"]
+    /// Decreases bias r of winners y by epsilon
+    pub fn learn_r(&mut self, epsilon: f32, y: &PyArray1<Idx>) -> PyResult<()> {
+        let y = unsafe { y.as_slice()? };
+        self.check_y(y)?;
+        self.l.learn_r(epsilon, y);
+        Ok(())
+    }
+}
+
+impl_save_load!(HardWtaL1, l);
+
+#[pyproto]
+impl PyObjectProtocol for HardWtaL1 {
+    fn __repr__(&self) -> String {
+        format!("HardWtaL1(shape={:?}, a={})", self.l.shape(), self.l.a())
+    }
+    fn __str__(&self) -> String {
+        self.__repr__()
+    }
+}
@@ -0,0 +1,456 @@
+use std::ops::Range;
+use std::str::FromStr;
+use numpy::{DataType, Element, PyArray1, PyArray2, PyArray3, PyArrayDyn};
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+use pyo3::{wrap_pyfunction, PyNativeType, PyResult};
+use vf::color_space::ColorSpace;
+use vf::histogram::{Bins, HistogramDistance, Pixel};
+use crate::util::{py_any_as_numpy, py_any_dtype};
+
+/// Calls f::<P> with P matching the dtype of the image. Supported pixel types are uint8, uint16 and float32.
+macro_rules! dispatch_pixel {
+    ($image:expr, $f:ident($($arg:expr),*)) => {
+        match py_any_dtype($image)? {
+            DataType::Uint8 => $f::<u8>($($arg),*),
+            DataType::Uint16 => $f::<u16>($($arg),*),
+            DataType::Float32 => $f::<f32>($($arg),*),
+            d => Err(PyValueError::new_err(format!("Unsupported pixel dtype {:?}. Expected uint8, uint16 or float32", d)))
+        }
+    };
+}
+
+/// range defaults to [0,256) for uint8, [0,65536) for uint16 and [0,1) for float32
+fn bins_of<P: Pixel>(bins: usize, range: Option<(f32, f32)>) -> PyResult<Bins> {
+    let range = range.map(|(low, high)| low..high).unwrap_or(P::RANGE);
+    if bins == 0 {
+        return Err(PyValueError::new_err("There must be at least one bin"));
+    }
+    if !(range.start < range.end) {
+        return Err(PyValueError::new_err(format!("Range {:?} is empty", range)));
+    }
+    Ok(Bins::new(bins, range))
+}
+
+/// Image of shape [height, width, channels]
+fn image_of<'py, P: Pixel + Element>(image: &'py PyAny) -> PyResult<(&'py [P], [usize; 3])> {
+    let image = py_any_as_numpy::<P>(image)?;
+    match image.shape() {
+        &[h, w, c] => Ok((unsafe { image.as_slice()? }, [h, w, c])),
+        shape => Err(PyValueError::new_err(format!("Image shape should be [height,width,channels] but got {:?}", shape)))
+    }
+}
+
+/// Mask of shape [height, width]
+fn mask_of<'py>(py: Python<'py>, source_mask: &'py Option<PyObject>, src_shape: &[usize; 3]) -> PyResult<Option<&'py [bool]>> {
+    if let Some(mask) = source_mask {
+        let mask: &PyArray2<bool> = mask.extract(py)?;
+        if mask.shape() != &src_shape[..2] {
+            return Err(PyValueError::new_err(format!("Mask shape {:?} should match source shape {:?}", mask.shape(), &src_shape[..2])));
+        }
+        Ok(Some(unsafe { mask.as_slice()? }))
+    } else {
+        Ok(None)
+    }
+}
+
+/// One of "SQUARED_ERROR", "CHI_SQUARE", "BHATTACHARYYA", "KL_DIVERGENCE", "JS_DIVERGENCE", "INTERSECTION", "EMD"
+fn metric_of(metric: &str) -> PyResult<HistogramDistance> {
+    Ok(match metric {
+        "SQUARED_ERROR" => HistogramDistance::SquaredError,
+        "CHI_SQUARE" => HistogramDistance::ChiSquare,
+        "BHATTACHARYYA" => HistogramDistance::Bhattacharyya,
+        "KL_DIVERGENCE" => HistogramDistance::KullbackLeibler,
+        "JS_DIVERGENCE" => HistogramDistance::JensenShannon,
+        "INTERSECTION" => HistogramDistance::Intersection,
+        "EMD" => HistogramDistance::EarthMovers,
+        m => return Err(PyValueError::new_err(format!("Unknown metric {}", m)))
+    })
+}
+
+/// One of "RGB", "HSV", "YCBCR", "LAB"
+fn color_space_of(color_space: &str) -> PyResult<ColorSpace> {
+    Ok(match color_space {
+        "RGB" => ColorSpace::Rgb,
+        "HSV" => ColorSpace::Hsv,
+        "YCBCR" => ColorSpace::YCbCr,
+        "LAB" => ColorSpace::Lab,
+        c => return Err(PyValueError::new_err(format!("Unknown color space {}", c)))
+    })
+}
+
+/// `channels` is either None (all channels), "LUMINANCE" or a list of channel indices
+fn channels_of(color_space: ColorSpace, channels: Option<&PyAny>) -> PyResult<Vec<usize>> {
+    let channels = match channels {
+        None => vec![0, 1, 2],
+        Some(c) => if let Ok(name) = c.extract::<&str>() {
+            if name != "LUMINANCE" {
+                return Err(PyValueError::new_err(format!("Unknown channels {}", name)));
+            }
+            vec![color_space.luminance().ok_or_else(|| PyValueError::new_err(format!("{:?} has no luminance channel", color_space)))?]
+        } else {
+            c.extract::<Vec<usize>>()?
+        }
+    };
+    if let Some(c) = channels.iter().find(|&&c| c >= 3) {
+        return Err(PyValueError::new_err(format!("Channel {} is out of bounds", c)));
+    }
+    Ok(channels)
+}
+
+/// Returns `None` channels if images can be matched directly in RGB
+fn space_and_channels(color_space: &str, channels: Option<&PyAny>) -> PyResult<(ColorSpace, Option<Vec<usize>>)> {
+    let color_space = color_space_of(color_space)?;
+    if color_space == ColorSpace::Rgb && channels.is_none() {
+        Ok((color_space, None))
+    } else {
+        Ok((color_space, Some(channels_of(color_space, channels)?)))
+    }
+}
+
+fn check_rgb(shape: &[usize; 3]) -> PyResult<()> {
+    if shape[2] != 3 {
+        return Err(PyValueError::new_err(format!("Color spaces require RGB images but got {} channels", shape[2])));
+    }
+    Ok(())
+}
+
+/// Colour space conversions clip everything to [0,1], so float images (HDR in particular) would be silently distorted
+fn check_unit<P: Pixel>(image: &[P], name: &str) -> PyResult<()> {
+    if let Some(v) = image.iter().map(|&p| p.to_unit()).find(|v| !(0. ..=1.).contains(v)) {
+        return Err(PyValueError::new_err(format!("Color spaces require {} values in [0,1] but got {}", name, v)));
+    }
+    Ok(())
+}
+
+fn to_image<P: Pixel + Element>(py: Python, image: Box<[P]>, shape: [usize; 3]) -> PyResult<PyObject> {
+    PyArray1::from_vec(py, image.into_vec()).reshape(shape).map(|a| a.to_object(py))
+}
+
+#[pyfunction(source_mask = "None", bins = "256", range = "None", color_space = "\"RGB\"", channels = "None")]
+#[text_signature = "(source_image, reference_image, source_mask, bins, range, color_space, channels)"]
+/// images are of shape `[height, width, channels]` and dtype uint8, uint16 or float32. Pixel values are sorted into
+/// `bins` bins spanning `range` (by default [0,256) for uint8, [0,65536) for uint16 and [0,1) for float32).
+/// `color_space` is one of "RGB", "HSV", "YCBCR", "LAB". Unless it's "RGB" with all channels, images must be RGB and are
+/// converted to that space, where every channel is scaled to [0,1] (and `range` refers to those converted values).
+/// float32 images must then lie in [0,1] (ValueError is raised otherwise, HDR images need to be tone-mapped first).
+/// Then only the chosen `channels` are matched, which is either None (all), a list of indices or "LUMINANCE"
+/// (V of HSV, Y of YCbCr or L of Lab), which matches brightness without shifting hues.
+pub fn match_histogram(source: &PyAny, reference: &PyAny, source_mask: Option<PyObject>, bins: usize, range: Option<(f32, f32)>, color_space: &str, channels: Option<&PyAny>) -> PyResult<PyObject> {
+    fn f<P: Pixel + Element>(source: &PyAny, reference: &PyAny, source_mask: Option<PyObject>, bins: usize, range: Option<(f32, f32)>, color_space: ColorSpace, channels: Option<Vec<usize>>) -> PyResult<PyObject> {
+        let py = source.py();
+        let (src, src_shape) = image_of::<P>(source)?;
+        let (rfc, ref_shape) = image_of::<P>(reference)?;
+        if src_shape[2] != ref_shape[2] {
+            return Err(PyValueError::new_err("Number of channels does not match"));
+        }
+        let mask = mask_of(py, &source_mask, &src_shape)?;
+        let out = if let Some(channels) = channels {
+            check_rgb(&src_shape)?;
+            check_unit(src, "source_image")?;
+            check_unit(rfc, "reference_image")?;
+            let bins = bins_of::<f32>(bins, range)?;
+            match mask {
+                Some(msk) => vf::histogram::match_images_in_space(src, &src_shape, rfc, &ref_shape, &bins, color_space, &channels, |i| msk[i]),
+                None => vf::histogram::match_images_in_space(src, &src_shape, rfc, &ref_shape, &bins, color_space, &channels, |_| true)
+            }
+        } else {
+            let bins = bins_of::<P>(bins, range)?;
+            match mask {
+                Some(msk) => vf::histogram::match_images_binned(src, &src_shape, rfc, &ref_shape, &bins, |i| msk[i]),
+                None => vf::histogram::match_images_binned(src, &src_shape, rfc, &ref_shape, &bins, |_| true)
+            }
+        };
+        to_image(py, out, src_shape)
+    }
+    let (color_space, channels) = space_and_channels(color_space, channels)?;
+    dispatch_pixel!(source, f(source, reference, source_mask, bins, range, color_space, channels))
+}
+
+#[pyfunction(source_mask = "None", range = "None")]
+#[text_signature = "(source_image, reference_hist, source_mask, range)"]
+/// `source_image` is of shape `[height, width, channels]`, `reference_hist` is of shape `[channels, bins]`
+pub fn match_precomputed_histogram(source: &PyAny, reference_hist: &PyArray2<f32>, source_mask: Option<PyObject>, range: Option<(f32, f32)>) -> PyResult<PyObject> {
+    fn f<P: Pixel + Element>(source: &PyAny, reference_hist: &PyArray2<f32>, source_mask: Option<PyObject>, range: Option<(f32, f32)>) -> PyResult<PyObject> {
+        let py = source.py();
+        let ref_shape = reference_hist.shape();
+        let bins = bins_of::<P>(ref_shape[1], range)?;
+        let (src, src_shape) = image_of::<P>(source)?;
+        if src_shape[2] != ref_shape[0] {
+            return Err(PyValueError::new_err("Number of channels does not match"));
+        }
+        let rfc = unsafe { reference_hist.as_slice()? };
+        let out = match mask_of(py, &source_mask, &src_shape)? {
+            Some(msk) => vf::histogram::match_precomputed_images_binned(src, &src_shape, rfc, &bins, |i| msk[i]),
+            None => vf::histogram::match_precomputed_images_binned(src, &src_shape, rfc, &bins, |_| true)
+        };
+        to_image(py, out, src_shape)
+    }
+    dispatch_pixel!(source, f(source, reference_hist, source_mask, range))
+}
+
+#[pyfunction(bins = "None")]
+#[text_signature = "(histogram, bins)"]
+/// `histogram` shape `[channels, bins]`. If bins is not given, it's the size of the last dimension
+/// (or 256 for flat arrays, which are then treated as `[channels, 256]`).
+pub fn histogram_interpolate_gaps(histogram: &PyArrayDyn<f32>, bins: Option<usize>) -> PyResult<()> {
+    let bins = bins.unwrap_or_else(|| if histogram.ndim() > 1 { *histogram.shape().last().unwrap() } else { 256 });
+    if bins == 0 || histogram.len() % bins != 0 {
+        return Err(PyValueError::new_err(format!("Histogram of shape {:?} can't be split into {} bins", histogram.shape(), bins)));
+    }
+    let hist = unsafe { histogram.as_slice_mut()? };
+    vf::histogram::interpolate_gaps_binned(hist, bins);
+    Ok(())
+}
+
+#[pyfunction(source_mask = "None", bins = "256", range = "None", color_space = "\"RGB\"")]
+#[text_signature = "(source_image, source_mask, bins, range, color_space)"]
+/// `source_image` shape `[height, width, channels]`, returns histograms of shape `[channels, bins]`.
+/// If `color_space` is other than "RGB", the image must be RGB and histograms are computed in that space, with every channel scaled to [0,1]
+/// (as expected by `match_best_images`). float32 images must then lie in [0,1].
+pub fn image_histogram<'py>(source: &'py PyAny, source_mask: Option<PyObject>, bins: usize, range: Option<(f32, f32)>, color_space: &str) -> PyResult<&'py PyArray2<u32>> {
+    fn f<'py, P: Pixel + Element>(source: &'py PyAny, source_mask: Option<PyObject>, bins: usize, range: Option<(f32, f32)>, color_space: ColorSpace) -> PyResult<&'py PyArray2<u32>> {
+        let py = source.py();
+        let (src, src_shape) = image_of::<P>(source)?;
+        let mask = mask_of(py, &source_mask, &src_shape)?;
+        let (hist, bins) = if color_space == ColorSpace::Rgb {
+            let bins = bins_of::<P>(bins, range)?;
+            (match mask {
+                Some(msk) => vf::histogram::histograms_binned(src, src_shape[2], &bins, |i| msk[i]),
+                None => vf::histogram::histograms_binned(src, src_shape[2], &bins, |_| true)
+            }, bins)
+        } else {
+            check_rgb(&src_shape)?;
+            check_unit(src, "source_image")?;
+            let bins = bins_of::<f32>(bins, range)?;
+            (match mask {
+                Some(msk) => vf::histogram::histograms_in_space(src, color_space, &bins, |i| msk[i]),
+                None => vf::histogram::histograms_in_space(src, color_space, &bins, |_| true)
+            }, bins)
+        };
+        PyArray1::from_vec(py, hist).reshape([src_shape[2], bins.count()])
+    }
+    let color_space = color_space_of(color_space)?;
+    dispatch_pixel!(source, f(source, source_mask, bins, range, color_space))
+}
+
+#[pyfunction]
+#[text_signature = "(histogram)"]
+/// `histogram` shape `[channels, bins]`
+pub fn normalize_histogram<'py>(histogram: &'py PyArrayDyn<u32>) -> PyResult<&'py PyArrayDyn<f32>> {
+    let src_shape = histogram.shape();
+    assert!(src_shape.len() >= 2, "Shape should be [channels, bins]");
+    let bins = src_shape.last().copied().unwrap();
+    let src = unsafe { histogram.as_slice()? };
+    let hist = vf::histogram::normalize_histograms_binned(src, bins);
+    let v = PyArray1::from_vec(histogram.py(), hist.into_vec());
+    v.reshape(src_shape)
+}
+
+#[pyfunction]
+#[text_signature = "(histogram, ratio)"]
+/// `histogram` shape `[channels, bins]`.
+/// Find if there exists `c`, `x1` and `w` such that
+/// ```
+/// (y_max-y_min)/w >= ratio
+/// ```
+/// where
+/// ```
+/// y_max=histogram[c,x..x+w].max();
+/// y_min=histogram[c,x].max(histogram[c,x+w]);
+/// ```
+/// and `ratio>1`, `w>0`. Return `(c,x,x+w)`. If no such constants exist then returns `(0,0,0)`
+pub fn find_histogram_anomaly<'py>(histogram: &'py PyArray2<f32>, ratio: f32) -> PyResult<Vec<(usize, isize, isize)>> {
+    let bins = histogram.shape()[1];
+    let src = unsafe { histogram.as_slice()? };
+    let v = vf::histogram::find_histograms_anomaly_binned(src, bins, ratio / src.len() as f32);
+    Ok(v.into_iter().map(|(channel, Range { start, end })| (channel, start, end)).collect())
+}
+
+#[pyfunction(source_mask = "None", range = "None", metric = "\"SQUARED_ERROR\"", color_space = "\"RGB\"", channels = "None")]
+#[text_signature = "(source_image, reference_histograms, source_mask, range, metric, color_space, channels)"]
+/// `source_image` shape `[height, width, channels]`, `reference_histograms` shape `[batch,channels,bins]`, return `(matched_image,best_ref_idx,best_dist)`.
+/// The closest reference is chosen according to `metric`, which is one of "SQUARED_ERROR", "CHI_SQUARE", "BHATTACHARYYA",
+/// "KL_DIVERGENCE", "JS_DIVERGENCE", "INTERSECTION", "EMD" (earth mover's distance). Distances of all channels are summed up.
+/// `color_space` and `channels` work as in `match_histogram`, in which case only the chosen channels are compared and matched
+/// and `reference_histograms` must hold all three channels of that space (as computed by `image_histogram`).
+pub fn match_best_images(source: &PyAny, references: &PyArray3<f32>, source_mask: Option<PyObject>, range: Option<(f32, f32)>, metric: &str, color_space: &str, channels: Option<&PyAny>) -> PyResult<(PyObject, usize, f32)> {
+    fn f<P: Pixel + Element>(source: &PyAny, references: &PyArray3<f32>, source_mask: Option<PyObject>, range: Option<(f32, f32)>, metric: HistogramDistance, color_space: ColorSpace, channels: Option<Vec<usize>>) -> PyResult<(PyObject, usize, f32)> {
+        let py = source.py();
+        let ref_shape = references.shape();
+        let (src, src_shape) = image_of::<P>(source)?;
+        if ref_shape[1] != src_shape[2] {
+            return Err(PyValueError::new_err("Number of channels does not match"));
+        }
+        let rfc = unsafe { references.as_slice()? };
+        let mask = mask_of(py, &source_mask, &src_shape)?;
+        let (out, best_ref_idx, dist) = if let Some(channels) = channels {
+            check_rgb(&src_shape)?;
+            check_unit(src, "source_image")?;
+            let bins = bins_of::<f32>(ref_shape[2], range)?;
+            match mask {
+                Some(msk) => vf::histogram::match_best_images_in_space(src, &src_shape, ref_shape[0], rfc, &bins, metric, color_space, &channels, |i| msk[i]),
+                None => vf::histogram::match_best_images_in_space(src, &src_shape, ref_shape[0], rfc, &bins, metric, color_space, &channels, |_| true)
+            }
+        } else {
+            let bins = bins_of::<P>(ref_shape[2], range)?;
+            match mask {
+                Some(msk) => vf::histogram::match_best_images_binned(src, &src_shape, ref_shape[0], rfc, &bins, metric, |i| msk[i]),
+                None => vf::histogram::match_best_images_binned(src, &src_shape, ref_shape[0], rfc, &bins, metric, |_| true)
+            }
+        };
+        Ok((to_image(py, out, src_shape)?, best_ref_idx, dist))
+    }
+    let metric = metric_of(metric)?;
+    let (color_space, channels) = space_and_channels(color_space, channels)?;
+    dispatch_pixel!(source, f(source, references, source_mask, range, metric, color_space, channels))
+}
+
+#[pyfunction(source_mask = "None", range = "None", metric = "\"SQUARED_ERROR\"")]
+#[text_signature = "(source_image, reference_histograms, blend_policy, source_mask, range, metric)"]
+/// `source_image` shape `[height, width, channels]`, `reference_histograms` shape `[batch,channels,bins]`, return `(matched_image,best_ref_idx,best_dist,alpha)`.
+/// The closest reference is chosen according to `metric` (same as in `match_best_images`).
+/// Before matching, it automatically blends histograms proportionately to their distance, which is between 0 and 1.
+/// blend_policy is one of "PROP_TO_SQUARED", "PROP_TO_DIST",  "PROP_TO_AREA_DIFF", "PROP_TO_METRIC", "INV_PROP_TO_SQUARED", "INV_PROP_TO_DIST","INV_PROP_TO_AREA_DIFF", "INV_PROP_TO_METRIC", "0.1234". Proportional (PROP)
+/// means that we blend `source*distance + reference * (1-distance)`. Inverse proportional (INV_PROP) means ``source*(1-distance) + reference * distance``.
+/// Distances are averaged over channels and clipped at 1. SQUARED, DIST and AREA_DIFF policies measure squared error,
+/// its square root and the non-shared area of histograms respectively, while METRIC policies use `best_dist`.
+/// You can also put there some constant like "0.1234" or anything else between 0 and 1.
+pub fn blend_and_match_best_images(source: &PyAny, references: &PyArray3<f32>, blend_policy: String, source_mask: Option<PyObject>, range: Option<(f32, f32)>, metric: &str) -> PyResult<(PyObject, usize, f32, f32)> {
+    fn f<P: Pixel + Element>(source: &PyAny, references: &PyArray3<f32>, blend_policy: String, source_mask: Option<PyObject>, range: Option<(f32, f32)>, metric: HistogramDistance) -> PyResult<(PyObject, usize, f32, f32)> {
+        let py = source.py();
+        let ref_shape = references.shape();
+        let bins = bins_of::<P>(ref_shape[2], range)?;
+        let (src, src_shape) = image_of::<P>(source)?;
+        if ref_shape[1] != src_shape[2] {
+            return Err(PyValueError::new_err("Number of channels does not match"));
+        }
+        let rfc = unsafe { references.as_slice()? };
+        let channels = src_shape[2];
+        let mask = mask_of(py, &source_mask, &src_shape)?;
+        let hist_src = match mask {
+            Some(msk) => vf::histogram::histograms_binned(src, channels, &bins, |i| msk[i]),
+            None => vf::histogram::histograms_binned(src, channels, &bins, |_| true)
+        };
+        let hist_norm_src = vf::histogram::normalize_histograms_binned(&hist_src, bins.count());
+        let batch = ref_shape[0];
+        let channels_bins = channels * bins.count();
+        let (best_ref_idx, dist) = vf::histogram::find_closest_n_binned(&hist_norm_src, bins.count(), batch, rfc, metric);
+        let ref_offset = best_ref_idx * channels_bins;
+        let best_ref_hists = &rfc[ref_offset..ref_offset + channels_bins];
+        // squared error of a single channel reaches 2, so it's clipped just like the METRIC policies
+        let mean_dist = |metric: HistogramDistance| (metric.distance_n(&hist_norm_src, best_ref_hists, bins.count()) / channels as f32).min(1.);
+        let alpha = match blend_policy.as_str() {
+            "PROP_TO_SQUARED" => mean_dist(HistogramDistance::SquaredError),
+            "PROP_TO_DIST" => mean_dist(HistogramDistance::SquaredError).sqrt(),
+            "PROP_TO_AREA_DIFF" => mean_dist(HistogramDistance::Intersection),
+            "PROP_TO_METRIC" => (dist / channels as f32).min(1.),
+            "INV_PROP_TO_SQUARED" => 1. - mean_dist(HistogramDistance::SquaredError),
+            "INV_PROP_TO_DIST" => 1. - mean_dist(HistogramDistance::SquaredError).sqrt(),
+            "INV_PROP_TO_AREA_DIFF" => 1. - mean_dist(HistogramDistance::Intersection),
+            "INV_PROP_TO_METRIC" => 1. - (dist / channels as f32).min(1.),
+            s => f32::from_str(s).map_err(|_| PyValueError::new_err(format!("Unknown policy {}", blend_policy)))?
+        };
+        let blended = vf::histogram::blend(alpha, &hist_norm_src, 1. - alpha, best_ref_hists);
+        let out = match mask {
+            Some(msk) => vf::histogram::match_2precomputed_images_binned(src, &src_shape, &hist_src, &blended, &bins, |i| msk[i]),
+            None => vf::histogram::match_2precomputed_images_binned(src, &src_shape, &hist_src, &blended, &bins, |_| true)
+        };
+        Ok((to_image(py, out, src_shape)?, best_ref_idx, dist, alpha))
+    }
+    let metric = metric_of(metric)?;
+    dispatch_pixel!(source, f(source, references, blend_policy, source_mask, range, metric))
+}
+
+#[pyfunction]
+#[text_signature = "(scalar1, histogram1, scalar2, histogram2)"]
+/// `histogram1` and `histogram2` shape `[channels,bins]`
+pub fn blend_histograms<'py>(scalar1: f32, histogram1: &'py PyArray2<f32>, scalar2: f32, histogram2: &'py PyArray2<f32>) -> PyResult<&'py PyArray2<f32>> {
+    let s1 = histogram1.shape();
+    let s2 = histogram2.shape();
+    assert_eq!(s1, s2, "Shapes of histograms do not match");
+    let so = [s1[0], s1[1]];
+    let h1 = unsafe { histogram1.as_slice()? };
+    let h2 = unsafe { histogram2.as_slice()? };
+    let ho: Vec<f32> = vf::histogram::blend(scalar1, h1, scalar2, h2);
+    let v = PyArray1::from_vec(histogram1.py(), ho);
+    v.reshape(so)
+}
+
+#[pyfunction(metric = "\"SQUARED_ERROR\"")]
+#[text_signature = "(histogram1, histogram2, metric)"]
+/// `histogram1` and `histogram2` shape `[channels,bins]` (normalised). Returns the sum of distances of all channels.
+/// `metric` is one of "SQUARED_ERROR", "CHI_SQUARE", "BHATTACHARYYA", "KL_DIVERGENCE", "JS_DIVERGENCE", "INTERSECTION", "EMD"
+pub fn histogram_distance(histogram1: &PyArray2<f32>, histogram2: &PyArray2<f32>, metric: &str) -> PyResult<f32> {
+    let metric = metric_of(metric)?;
+    let s1 = histogram1.shape();
+    if s1 != histogram2.shape() {
+        return Err(PyValueError::new_err(format!("Shapes of histograms do not match {:?} != {:?}", s1, histogram2.shape())));
+    }
+    let h1 = unsafe { histogram1.as_slice()? };
+    let h2 = unsafe { histogram2.as_slice()? };
+    Ok(metric.distance_n(h1, h2, s1[1]))
+}
+
+#[pyfunction(source_mask = "None", bins = "256", range = "None")]
+#[text_signature = "(source_image, source_mask, bins, range)"]
+/// Global histogram equalization of every channel. `source_image` shape `[height, width, channels]` and dtype uint8, uint16 or float32.
+/// Only pixels selected by `source_mask` of shape `[height, width]` are counted and changed.
+pub fn equalize_histogram(source: &PyAny, source_mask: Option<PyObject>, bins: usize, range: Option<(f32, f32)>) -> PyResult<PyObject> {
+    fn f<P: Pixel + Element>(source: &PyAny, source_mask: Option<PyObject>, bins: usize, range: Option<(f32, f32)>) -> PyResult<PyObject> {
+        let py = source.py();
+        let bins = bins_of::<P>(bins, range)?;
+        let (src, src_shape) = image_of::<P>(source)?;
+        let out = match mask_of(py, &source_mask, &src_shape)? {
+            Some(msk) => vf::histogram::equalize_images_binned(src, &src_shape, &bins, |i| msk[i]),
+            None => vf::histogram::equalize_images_binned(src, &src_shape, &bins, |_| true)
+        };
+        to_image(py, out, src_shape)
+    }
+    dispatch_pixel!(source, f(source, source_mask, bins, range))
+}
+
+#[pyfunction(tiles = "(8, 8)", clip_limit = "40.", source_mask = "None", bins = "256", range = "None")]
+#[text_signature = "(source_image, tiles, clip_limit, source_mask, bins, range)"]
+/// Contrast limited adaptive histogram equalization. `source_image` shape `[height, width, channels]` is split into
+/// `tiles=(tiles_y, tiles_x)` rectangles, each equalised separately after clipping its histogram at `clip_limit` times
+/// the mean bin count (pass `float('inf')` to disable clipping). Neighbouring tiles are blended bilinearly.
+/// Only pixels selected by `source_mask` of shape `[height, width]` are counted and changed.
+pub fn clahe(source: &PyAny, tiles: (usize, usize), clip_limit: f32, source_mask: Option<PyObject>, bins: usize, range: Option<(f32, f32)>) -> PyResult<PyObject> {
+    fn f<P: Pixel + Element + Send + Sync>(source: &PyAny, tiles: (usize, usize), clip_limit: f32, source_mask: Option<PyObject>, bins: usize, range: Option<(f32, f32)>) -> PyResult<PyObject> {
+        let py = source.py();
+        let bins = bins_of::<P>(bins, range)?;
+        let (src, src_shape) = image_of::<P>(source)?;
+        let (tiles_y, tiles_x) = tiles;
+        if tiles_y == 0 || tiles_y > src_shape[0] || tiles_x == 0 || tiles_x > src_shape[1] {
+            return Err(PyValueError::new_err(format!("Can't split image of shape {:?} into {:?} tiles", &src_shape[..2], tiles)));
+        }
+        if !(clip_limit > 0.) {
+            return Err(PyValueError::new_err(format!("Clip limit {} must be positive", clip_limit)));
+        }
+        let mask = mask_of(py, &source_mask, &src_shape)?;
+        let out = py.allow_threads(|| match mask {
+            Some(msk) => vf::histogram::clahe_images_binned(src, &src_shape, [tiles_y, tiles_x], clip_limit, &bins, |i| msk[i]),
+            None => vf::histogram::clahe_images_binned(src, &src_shape, [tiles_y, tiles_x], clip_limit, &bins, |_| true)
+        });
+        to_image(py, out, src_shape)
+    }
+    dispatch_pixel!(source, f(source, tiles, clip_limit, source_mask, bins, range))
+}
+
+#[pymodule]
+pub fn histogram(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(find_histogram_anomaly, m)?)?;
+    m.add_function(wrap_pyfunction!(match_histogram, m)?)?;
+    m.add_function(wrap_pyfunction!(match_best_images, m)?)?;
+    m.add_function(wrap_pyfunction!(blend_histograms, m)?)?;
+    m.add_function(wrap_pyfunction!(histogram_distance, m)?)?;
+    m.add_function(wrap_pyfunction!(blend_and_match_best_images, m)?)?;
+    m.add_function(wrap_pyfunction!(normalize_histogram, m)?)?;
+    m.add_function(wrap_pyfunction!(image_histogram, m)?)?;
+    m.add_function(wrap_pyfunction!(match_precomputed_histogram, m)?)?;
+    m.add_function(wrap_pyfunction!(histogram_interpolate_gaps, m)?)?;
+    m.add_function(wrap_pyfunction!(equalize_histogram, m)?)?;
+    m.add_function(wrap_pyfunction!(clahe, m)?)?;
+    Ok(())
+}
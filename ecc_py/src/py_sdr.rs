@@ -0,0 +1,137 @@
+use numpy::{PyArray1, PyArray3, PyArray4};
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+use pyo3::{PyObjectProtocol, PyResult, PySequenceProtocol};
+use vf::{arr3, VectorFieldPartialOrd};
+use vf::shape::Shape;
+use crate::Idx;
+use crate::util::{impl_save_load, pickle, unpickle};
+
+///
+/// Sdr(shape: (int,int,int), indices: [int] = None)
+///
+/// Sparse binary tensor of shape [height, width, channels]. Only the (sorted and unique) indices of ones are stored.
+///
+#[pyclass]
+#[text_signature = "(shape, indices, /)"]
+pub struct Sdr {
+    pub(crate) sdr: vf::sdr::Sdr<Idx>,
+}
+
+impl Sdr {
+    fn check_shape(&self, other: &Self) -> PyResult<()> {
+        if self.sdr.shape() != other.sdr.shape() {
+            Err(PyValueError::new_err(format!("Shapes {:?} and {:?} don't match", self.sdr.shape(), other.sdr.shape())))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[pymethods]
+impl Sdr {
+    #[new]
+    pub fn new(shape: (Idx, Idx, Idx), indices: Option<Vec<Idx>>) -> PyResult<Self> {
+        let shape = arr3(shape);
+        let indices = indices.unwrap_or_default();
+        if let Some(&i) = indices.iter().find(|&&i| i >= shape.size()) {
+            return Err(PyValueError::new_err(format!("Index {} is out of bounds for shape {:?}", i, shape)));
+        }
+        Ok(Self { sdr: vf::sdr::Sdr::from_indices(shape, indices) })
+    }
+    #[staticmethod]
+    #[text_signature = "(bools)"]
+    /// Converts dense boolean tensor of shape [height, width, channels]
+    pub fn from_numpy(bools: &PyArray3<bool>) -> PyResult<Self> {
+        let shape = [bools.shape()[0], bools.shape()[1], bools.shape()[2]].map(|d| d as Idx);
+        Ok(Self { sdr: vf::sdr::Sdr::from_dense(shape, unsafe { bools.as_slice()? }) })
+    }
+    #[staticmethod]
+    #[text_signature = "(bools)"]
+    /// Converts a batch of dense boolean tensors of shape [batch, height, width, channels]
+    pub fn batch_from_numpy(bools: &PyArray4<bool>) -> PyResult<Vec<Self>> {
+        let shape = [bools.shape()[1], bools.shape()[2], bools.shape()[3]].map(|d| d as Idx);
+        let batch = vf::sdr::Sdr::batch_from_dense(shape, unsafe { bools.as_slice()? });
+        Ok(batch.into_iter().map(|sdr| Self { sdr }).collect())
+    }
+    #[getter]
+    pub fn shape(&self) -> Vec<Idx> {
+        self.sdr.shape().to_vec()
+    }
+    #[getter]
+    pub fn volume(&self) -> Idx {
+        self.sdr.volume()
+    }
+    #[getter]
+    pub fn card(&self) -> usize {
+        self.sdr.card()
+    }
+    #[getter]
+    pub fn indices<'py>(&self, py: Python<'py>) -> &'py PyArray1<Idx> {
+        PyArray1::from_slice(py, self.sdr.indices())
+    }
+    #[text_signature = "()"]
+    /// Dense boolean tensor of shape [height, width, channels]
+    pub fn to_numpy<'py>(&self, py: Python<'py>) -> PyResult<&'py PyArray3<bool>> {
+        PyArray1::from_vec(py, self.sdr.to_dense()).reshape(self.sdr.shape().map(|d| d as usize))
+    }
+    #[text_signature = "(other)"]
+    pub fn union(&self, other: &Self) -> PyResult<Self> {
+        self.check_shape(other)?;
+        Ok(Self { sdr: self.sdr.union(&other.sdr) })
+    }
+    #[text_signature = "(other)"]
+    pub fn intersection(&self, other: &Self) -> PyResult<Self> {
+        self.check_shape(other)?;
+        Ok(Self { sdr: self.sdr.intersection(&other.sdr) })
+    }
+    #[text_signature = "(other)"]
+    /// Removes (in-place) all elements present in other
+    pub fn subtract(&mut self, other: &Self) -> PyResult<()> {
+        self.check_shape(other)?;
+        self.sdr.subtract(&other.sdr);
+        Ok(())
+    }
+    #[text_signature = "(other)"]
+    /// Number of elements shared by both SDRs
+    pub fn overlap(&self, other: &Self) -> PyResult<usize> {
+        self.check_shape(other)?;
+        Ok(self.sdr.overlap(&other.sdr))
+    }
+    #[text_signature = "(offset)"]
+    /// Moves all elements by offset (y, x, channel). Elements that fall outside of the shape are dropped
+    pub fn shift(&self, offset: (isize, isize, isize)) -> Self {
+        Self { sdr: self.sdr.shift(&arr3(offset)) }
+    }
+    #[text_signature = "(start, end)"]
+    /// Crops the SDR to the box [start, end). The result has shape end-start
+    pub fn subregion(&self, start: (Idx, Idx, Idx), end: (Idx, Idx, Idx)) -> PyResult<Self> {
+        let range = arr3(start)..arr3(end);
+        if !range.start.all_le(&range.end) || !range.end.all_le(self.sdr.shape()) {
+            return Err(PyValueError::new_err(format!("Subregion {:?} is out of bounds for shape {:?}", range, self.sdr.shape())));
+        }
+        Ok(Self { sdr: self.sdr.subregion(&range) })
+    }
+}
+
+impl_save_load!(Sdr, sdr);
+
+#[pyproto]
+impl PySequenceProtocol for Sdr {
+    fn __len__(&self) -> usize {
+        self.sdr.card()
+    }
+    fn __contains__(&self, idx: Idx) -> bool {
+        self.sdr.contains(idx)
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for Sdr {
+    fn __repr__(&self) -> String {
+        format!("Sdr(shape={:?}, indices={:?})", self.sdr.shape(), self.sdr.indices())
+    }
+    fn __str__(&self) -> String {
+        self.__repr__()
+    }
+}
@@ -1,493 +1,503 @@
-// use pyo3::prelude::*;
-// use pyo3::{wrap_pyfunction, wrap_pymodule, PyObjectProtocol, PyIterProtocol, PySequenceProtocol, PyTypeInfo, PyDowncastError, AsPyPointer, PyNumberProtocol};
-//
-// ///
-// /// CpuSdrDataset(shape, sdr)
-// ///
-// ///
-// #[pyclass]
-// pub struct CpuSdrDataset {
-//     pub(crate) sdr: htm::CpuSdrDataset,
-// }
-//
-// #[pyclass]
-// pub struct SubregionIndices {
-//     pub(crate) sdr: htm::SubregionIndices,
-// }
-//
-// #[pymethods]
-// impl SubregionIndices {
-//     #[getter]
-//     fn get_in_shape(&self) -> Vec<Idx> {
-//         self.sdr.shape().to_vec()
-//     }
-//     #[getter]
-//     fn get_dataset_len(&self) -> usize {
-//         self.sdr.dataset_len()
-//     }
-//     #[text_signature = "(idx)"]
-//     fn get_sample_idx(&self, idx: usize) -> u32 {
-//         self.sdr[idx].channels()
-//     }
-//     #[text_signature = "(idx)"]
-//     fn get_output_column_pos(&self, idx: usize) -> Vec<Idx> {
-//         self.sdr[idx].grid().to_vec()
-//     }
-// }
-//
-// #[pyclass]
-// pub struct Occurrences {
-//     pub(crate) c: htm::Occurrences,
-// }
-//
-// #[pymethods]
-// impl Occurrences {
-//     #[text_signature = "(input_idx,label)"]
-//     pub fn prob(&self, input_idx: usize, label: usize) -> f32 {
-//         self.c.prob(input_idx, label)
-//     }
-//     #[text_signature = "()"]
-//     pub fn normalise_wrt_labels(&mut self) {
-//         self.c.normalise_wrt_labels()
-//     }
-//     #[text_signature = "()"]
-//     pub fn normalise_class_probs(&mut self) {
-//         self.c.normalise_class_probs()
-//     }
-//     #[text_signature = "()"]
-//     pub fn log(&mut self) {
-//         self.c.log()
-//     }
-//     #[text_signature = "()"]
-//     pub fn occurrences(&self) -> Vec<f32> {
-//         self.c.occurrences().to_vec()
-//     }
-//     #[text_signature = "()"]
-//     pub fn square_weights(&mut self) {
-//         self.c.sqrt_weights()
-//     }
-//     #[text_signature = "()"]
-//     pub fn sqrt_weights(&mut self) {
-//         self.c.sqrt_weights()
-//     }
-//     #[text_signature = "()"]
-//     pub fn exp_weights(&mut self) {
-//         self.c.exp_weights()
-//     }
-//     #[text_signature = "()"]
-//     pub fn log_weights(&mut self) {
-//         self.c.log_weights()
-//     }
-//     #[text_signature = "()"]
-//     pub fn square_class_probs(&mut self) {
-//         self.c.sqrt_class_probs()
-//     }
-//     #[text_signature = "()"]
-//     pub fn sqrt_class_probs(&mut self) {
-//         self.c.sqrt_class_probs()
-//     }
-//     #[text_signature = "()"]
-//     pub fn exp_class_probs(&mut self) {
-//         self.c.exp_class_probs()
-//     }
-//     #[text_signature = "()"]
-//     pub fn log_class_probs(&mut self) {
-//         self.c.log_class_probs()
-//     }
-//     #[text_signature = "(label)"]
-//     pub fn occurrences_for_label(&self, lbl: usize) -> Vec<f32> {
-//         self.c.occurrences_for_label(lbl).to_vec()
-//     }
-//     #[text_signature = "(sdr)"]
-//     pub fn collect_votes_per_column_and_lbl(&self, sdr: &CpuSDR) -> Tensor {
-//         Tensor { ecc: self.c.collect_votes_per_column_and_lbl(&sdr.sdr) }
-//     }
-//     #[text_signature = "()"]
-//     pub fn aggregate_invariant_to_column(&mut self) {
-//         self.c.aggregate_invariant_to_column()
-//     }
-//     #[text_signature = "(sdr,min_deviation_from_mean)"]
-//     pub fn classify_and_count_votes_per_lbl(&self, sdr: &CpuSDR, min_deviation_from_mean: f32) -> Vec<u32> {
-//         self.c.classify_and_count_votes_per_lbl(&sdr.sdr, min_deviation_from_mean)
-//     }
-//     #[text_signature = "(sdr,min_deviation_from_mean)"]
-//     pub fn classify_per_column(&self, sdr: &CpuSDR, min_deviation_from_mean: f32) -> Vec<isize> {
-//         self.c.classify_per_column(&sdr.sdr, min_deviation_from_mean).into_vec()
-//     }
-//     #[text_signature = "(sdr)"]
-//     pub fn compute_class_sums(&mut self) {
-//         self.c.compute_class_sums()
-//     }
-//     #[text_signature = "(sdr)"]
-//     pub fn compute_class_probs(&mut self) {
-//         self.c.compute_class_probs()
-//     }
-//     #[text_signature = "(class_idx)"]
-//     fn class_prob_of(&self, class_idx: usize) -> f32 {
-//         self.c.class_prob()[class_idx]
-//     }
-//     #[getter]
-//     fn class_prob(&self) -> Vec<f32> {
-//         self.c.class_prob().to_vec()
-//     }
-//     #[getter]
-//     fn num_classes(&self) -> usize {
-//         self.c.num_classes()
-//     }
-//     #[getter]
-//     fn get_shape(&self) -> Vec<Idx> {
-//         self.c.shape().to_vec()
-//     }
-//     #[text_signature = "()"]
-//     fn clear_class_prob(&mut self) {
-//         self.c.clear_class_prob()
-//     }
-//     #[text_signature = "(sdr,min_deviation_from_mean)"]
-//     fn classify(&self, sdr: &CpuSDR, min_deviation_from_mean: Option<f32>) -> usize {
-//         if let Some(min_deviation_from_mean) = min_deviation_from_mean {
-//             self.c.classify_with_most_votes(&sdr.sdr, min_deviation_from_mean)
-//         } else {
-//             self.c.classify(&sdr.sdr)
-//         }
-//     }
-//     #[text_signature = "(sdr_dataset, min_deviation_from_mean)"]
-//     fn batch_classify<'py>(&self, py: Python<'py>, sdr: &CpuSdrDataset, min_deviation_from_mean: Option<f32>) -> &'py PyArray<u32, Ix1> {
-//         let v = if let Some(min_deviation_from_mean) = min_deviation_from_mean {
-//             self.c.batch_classify_invariant_to_column(&sdr.sdr, min_deviation_from_mean)
-//         } else {
-//             self.c.batch_classify(&sdr.sdr)
-//         };
-//         PyArray::from_vec(py, v)
-//     }
-// }
-//
-// impl CpuSdrDataset {
-//     fn to_numpy_<'py, T: Element + Copy>(&self, py: Python<'py>, idx: usize, one: T) -> &'py PyArray<T, Ix3> {
-//         let sdr = &self.sdr[idx];
-//         let mut arr = PyArray3::zeros(py, self.sdr.shape().map(Idx::as_usize), false);
-//         let s = unsafe { arr.as_slice_mut().unwrap() };
-//         sdr.iter().for_each(|&i| s[i.as_usize()] = one);
-//         arr
-//     }
-// }
-//
-// #[pymethods]
-// impl CpuSdrDataset {
-//     #[new]
-//     pub fn new(shape: [Idx; 3], sdr: Option<Vec<PyRef<CpuSDR>>>) -> Self {
-//         Self {
-//             sdr: if let Some(s) = sdr {
-//                 let mut d = htm::CpuSdrDataset::with_capacity(s.len(), shape);
-//                 d.extend(s.iter().map(|p| p.sdr.clone()));
-//                 d
-//             } else {
-//                 htm::CpuSdrDataset::new(shape)
-//             }
-//         }
-//     }
-//     #[text_signature = "(idx)"]
-//     fn to_numpy<'py>(&self, py: Python<'py>, idx: usize) -> &'py PyArray<u32, Ix3> {
-//         self.to_numpy_(py, idx, 1)
-//     }
-//     #[text_signature = "(idx)"]
-//     fn to_bool_numpy<'py>(&self, py: Python<'py>, idx: usize) -> &'py PyArray<bool, Ix3> {
-//         self.to_numpy_(py, idx, true)
-//     }
-//     #[text_signature = "(idx)"]
-//     fn to_f32_numpy<'py>(&self, py: Python<'py>, idx: usize) -> &'py PyArray<f32, Ix3> {
-//         self.to_numpy_(py, idx, 1.)
-//     }
-//     #[text_signature = "(idx)"]
-//     fn to_f64_numpy<'py>(&self, py: Python<'py>, idx: usize) -> &'py PyArray<f64, Ix3> {
-//         self.to_numpy_(py, idx, 1.)
-//     }
-//     #[getter]
-//     fn get_shape(&self) -> Vec<Idx> {
-//         self.sdr.shape().to_vec()
-//     }
-//     #[getter]
-//     fn get_grid(&self) -> Vec<Idx> {
-//         self.sdr.shape().grid().to_vec()
-//     }
-//     #[getter]
-//     fn get_volume(&self) -> Idx {
-//         self.sdr.shape().size()
-//     }
-//     #[getter]
-//     fn get_channels(&self) -> Idx {
-//         self.sdr.shape().channels()
-//     }
-//     #[getter]
-//     fn get_width(&self) -> Idx {
-//         self.sdr.shape().width()
-//     }
-//     #[getter]
-//     fn get_height(&self) -> Idx {
-//         self.sdr.shape().height()
-//     }
-//     #[getter]
-//     fn get_area(&self) -> Idx {
-//         self.sdr.shape().grid().product()
-//     }
-//     #[text_signature = "(min_cardinality)"]
-//     pub fn filter_by_cardinality_threshold(&mut self, min_cardinality: Idx) {
-//         self.sdr.filter_by_cardinality_threshold(min_cardinality)
-//     }
-//     // #[text_signature = "(number_of_samples,ecc,log)"]
-//     // fn train_machine_with_patches(&self, number_of_samples: usize, ecc: &mut EccNet,log:Option<usize>){
-//     //     if let Some(log) = log {
-//     //         assert!(log > 0, "Logging interval must be greater than 0");
-//     //         self.sdr.train_machine_with_patches(number_of_samples, &mut ecc.ecc, &mut rand::thread_rng(), |i| if i % log == 0 { println!("Processed samples {}", i + 1) })
-//     //     } else {
-//     //         self.sdr.train_machine_with_patches(number_of_samples, &mut ecc.ecc, &mut rand::thread_rng(), |i| {})
-//     //     }
-//     // }
-//     #[text_signature = "(number_of_samples,drift,patches_per_sample,ecc,log)"]
-//     pub fn train_with_patches(&self, number_of_samples: usize, drift: [Idx; 2], patches_per_sample: usize, ecc: &mut EccLayer, log: Option<usize>) {
-//         if let Some(log) = log {
-//             assert!(log > 0, "Logging interval must be greater than 0");
-//             self.sdr.train_with_patches(number_of_samples, drift, patches_per_sample, &mut ecc.ecc, &mut rand::thread_rng(), |i| if i % log == 0 { println!("Processed samples {}", i + 1) })
-//         } else {
-//             self.sdr.train_with_patches(number_of_samples, drift, patches_per_sample, &mut ecc.ecc, &mut rand::thread_rng(), |i| {})
-//         }
-//     }
-//     #[text_signature = "(number_of_samples,ecc,log)"]
-//     pub fn train(&self, number_of_samples: usize, ecc: &mut EccLayer, log: Option<usize>) {
-//         if let Some(log) = log {
-//             self.sdr.train(number_of_samples, &mut ecc.ecc, &mut rand::thread_rng(), |i| if i % log == 0 { println!("Processed samples {}", i + 1) })
-//         } else {
-//             self.sdr.train(number_of_samples, &mut ecc.ecc, &mut rand::thread_rng(), |i| {})
-//         }
-//     }
-//     #[text_signature = "()"]
-//     fn clear(&mut self) {
-//         self.sdr.clear()
-//     }
-//     #[text_signature = "(sdr)"]
-//     fn push(&mut self, sdr: &CpuSDR) {
-//         self.sdr.push(sdr.sdr.clone())
-//     }
-//     #[text_signature = "()"]
-//     fn pop(&mut self) -> Option<CpuSDR> {
-//         self.sdr.pop().map(|sdr| CpuSDR { sdr })
-//     }
-//     #[text_signature = "()"]
-//     fn rand(&self) -> Option<CpuSDR> {
-//         self.sdr.rand(&mut rand::thread_rng()).map(|sdr| CpuSDR { sdr: sdr.clone() })
-//     }
-//     #[text_signature = "(ecc,indices)"]
-//     fn conv_subregion_indices_with_ecc(&self, ecc: &EccLayer, indices: &SubregionIndices) -> Self {
-//         Self { sdr: self.sdr.conv_subregion_indices_with_ecc(&ecc.ecc, &indices.sdr) }
-//     }
-//     #[text_signature = "(ecc,indices)"]
-//     fn conv_subregion_indices_with_net(&self, ecc: &EccNet, indices: &SubregionIndices) -> Self {
-//         Self { sdr: self.sdr.conv_subregion_indices_with_net(&ecc.ecc, &indices.sdr) }
-//     }
-//     #[text_signature = "(kernel,stride,indices)"]
-//     fn conv_subregion_indices_with_ker(&self, kernel: [Idx; 2], stride: [Idx; 2], indices: &SubregionIndices) -> Self {
-//         Self { sdr: self.sdr.conv_subregion_indices_with_ker(kernel, stride, &indices.sdr) }
-//     }
-//     #[text_signature = "(number_of_samples,original_dataset)"]
-//     pub fn extend_from_rand_subregions(&mut self, number_of_samples: usize, original: &CpuSdrDataset) {
-//         self.sdr.extend_from_rand_subregions(number_of_samples, &original.sdr, &mut rand::thread_rng())
-//     }
-//     #[text_signature = "(kernel,stride,number_of_samples,original_dataset)"]
-//     fn extend_from_conv_rand_subregion(&mut self, kernel: [Idx; 2], stride: [Idx; 2], number_of_samples: usize, original: &CpuSdrDataset) {
-//         let conv = ConvShape::new_in(*original.sdr.shape(), 1, kernel, stride);
-//         self.sdr.extend_from_conv_rand_subregion(&conv, number_of_samples, &original.sdr, &mut rand::thread_rng())
-//     }
-//     #[text_signature = "(kernel,stride,indices,original_dataset)"]
-//     fn extend_from_conv_subregion_indices(&mut self, kernel: [Idx; 2], stride: [Idx; 2], indices: &SubregionIndices, original: &CpuSdrDataset) {
-//         let conv = ConvShape::new_in(*original.sdr.shape(), 1, kernel, stride);
-//         self.sdr.extend_from_conv_subregion_indices(&conv, &indices.sdr, &original.sdr)
-//     }
-//     #[text_signature = "(patch_size,number_of_samples)"]
-//     fn gen_rand_2d_patches(&self, patch_size: [Idx; 2], number_of_samples: usize) -> Self {
-//         Self { sdr: self.sdr.gen_rand_2d_patches(patch_size, number_of_samples, &mut rand::thread_rng()) }
-//     }
-//     #[text_signature = "(subregion_size,number_of_samples)"]
-//     fn gen_rand_subregions(&self, subregion: [Idx; 3], number_of_samples: usize) -> Self {
-//         Self { sdr: self.sdr.gen_rand_subregions(subregion, number_of_samples, &mut rand::thread_rng()) }
-//     }
-//     #[text_signature = "(out_shape,number_of_samples)"]
-//     fn gen_rand_conv_subregion_indices(&self, out_shape: [Idx; 2], number_of_samples: usize) -> SubregionIndices {
-//         SubregionIndices { sdr: self.sdr.gen_rand_conv_subregion_indices(out_shape, number_of_samples, &mut rand::thread_rng()) }
-//     }
-//     #[text_signature = "(kernel,stride,number_of_samples)"]
-//     fn gen_rand_conv_subregion_indices_with_ker(&self, kernel: [Idx; 2], stride: [Idx; 2], number_of_samples: usize) -> SubregionIndices {
-//         SubregionIndices { sdr: self.sdr.gen_rand_conv_subregion_indices_with_ker(&kernel, &stride, number_of_samples, &mut rand::thread_rng()) }
-//     }
-//     #[text_signature = "(ecc_dense,number_of_samples)"]
-//     fn gen_rand_conv_subregion_indices_with_ecc(&self, ecc: &EccLayer, number_of_samples: usize) -> SubregionIndices {
-//         SubregionIndices { sdr: self.sdr.gen_rand_conv_subregion_indices_with_ecc(&ecc.ecc, number_of_samples, &mut rand::thread_rng()) }
-//     }
-//     #[text_signature = "(ecc_net,number_of_samples)"]
-//     fn gen_rand_conv_subregion_indices_with_net(&self, ecc: &EccNet, number_of_samples: usize) -> SubregionIndices {
-//         SubregionIndices { sdr: self.sdr.gen_rand_conv_subregion_indices_with_net(&ecc.ecc, number_of_samples, &mut rand::thread_rng()) }
-//     }
-//
-//     #[text_signature = "(labels,number_of_classes)"]
-//     fn count_per_label(&self, labels: &PyAny, number_of_classes: usize) -> PyResult<Occurrences> {
-//         let array = unsafe {
-//             if npyffi::PyArray_Check(labels.as_ptr()) == 0 {
-//                 return Err(PyDowncastError::new(labels, "PyArray<T, D>").into());
-//             }
-//             &*(labels as *const PyAny as *const PyArrayDyn<u8>)
-//         };
-//         if !array.is_c_contiguous() {
-//             return Err(PyValueError::new_err("Numpy array is not C contiguous"));
-//         }
-//         let dtype = array.dtype().get_datatype().ok_or_else(|| PyValueError::new_err("No numpy array has no dtype"))?;
-//         fn f<T: Element>(sdr: &htm::CpuSdrDataset, labels: &PyAny, number_of_classes: usize, f: impl Fn(&T) -> usize) -> PyResult<Occurrences> {
-//             let labels = unsafe { &*(labels as *const PyAny as *const PyArrayDyn<T>) };
-//             let labels = unsafe { labels.as_slice()? };
-//             Ok(Occurrences { c: sdr.count_per_label(labels, number_of_classes, f) })
-//         }
-//         match dtype {
-//             u8::DATA_TYPE => f(&self.sdr, labels, number_of_classes, |f: &u8| *f as usize),
-//             u32::DATA_TYPE => f(&self.sdr, labels, number_of_classes, |f: &u32| *f as usize),
-//             u64::DATA_TYPE => f(&self.sdr, labels, number_of_classes, |f: &u64| *f as usize),
-//             i8::DATA_TYPE => f(&self.sdr, labels, number_of_classes, |f: &i8| *f as usize),
-//             i32::DATA_TYPE => f(&self.sdr, labels, number_of_classes, |f: &i32| *f as usize),
-//             i64::DATA_TYPE => f(&self.sdr, labels, number_of_classes, |f: &i64| *f as usize),
-//             usize::DATA_TYPE => f(&self.sdr, labels, number_of_classes, |f: &usize| *f),
-//             d => Err(PyValueError::new_err(format!("Unexpected dtype {:?} of numpy array ", d)))
-//         }
-//     }
-//     #[text_signature = "()"]
-//     fn count<'py>(&self, py: Python<'py>) -> PyResult<&'py PyArray<u32, Ix3>> {
-//         let v = self.sdr.count();
-//         let a = PyArray::from_vec(py, v);
-//         a.reshape(self.sdr.shape().map(Idx::as_usize))
-//     }
-//     // #[text_signature = "(outputs)"]
-//     // fn measure_receptive_fields<'py>(&self, py: Python<'py>, outputs: &CpuSdrDataset) -> PyResult<&'py PyArray<u32, Ix4>> {
-//     //     let ov = outputs.sdr.shape().size();
-//     //     let v = self.sdr.measure_receptive_fields(&outputs.sdr);
-//     //     let a = PyArray::from_vec(py, v);
-//     //     let s = self.sdr.shape();
-//     //     let s = [s[0], s[1], s[2], ov].map(Idx::as_usize);
-//     //     a.reshape(s)
-//     // }
-//     // #[text_signature = "(ecc_dense)"]
-//     // fn batch_infer(&self, ecc: &EccLayer) -> CpuSdrDataset {
-//     //     Self { sdr: self.sdr.batch_infer(&ecc.ecc) }
-//     // }
-//     #[text_signature = "(ecc_net,learn)"]
-//     fn net_infer(&self, ecc: &mut EccNet,learn:Option<bool>) -> CpuSdrDataset {
-//         Self { sdr: self.sdr.net_infer(&mut ecc.ecc,learn.unwrap_or(false)) }
-//     }
-//     #[text_signature = "(start,end)"]
-//     pub fn subdataset(&self, start: usize, end: Option<usize>) -> Self {
-//         let end = end.unwrap_or(self.sdr.len());
-//         Self { sdr: self.sdr.subdataset(start..end) }
-//     }
-//
-//     // #[text_signature = "(ecc_dense,target)"]
-//     // fn batch_infer_conv_weights(&self, ecc: &ConvWeights, target: &CpuEccPopulation) -> CpuSdrDataset {
-//     //     Self { sdr: self.sdr.batch_infer_conv_weights(&ecc.ecc, target.ecc.clone()) }
-//     // }
-//     // #[text_signature = "(ecc_dense)"]
-//     // fn batch_infer_and_measure_s_expectation(&self, ecc: &EccLayer) -> (CpuSdrDataset, f32, u32) {
-//     //     let (sdr, s_exp, missed) = self.sdr.batch_infer_and_measure_s_expectation(&ecc.ecc);
-//     //     (Self { sdr }, s_exp, missed)
-//     // }
-//     // #[text_signature = "(ecc_dense,target)"]
-//     // fn batch_infer_conv_weights_and_measure_s_expectation(&self, ecc: &ConvWeights, target: &CpuEccPopulation) -> (CpuSdrDataset, f32, u32) {
-//     //     let (sdr, s_exp, missed) = self.sdr.batch_infer_conv_weights_and_measure_s_expectation(&ecc.ecc, target.ecc.clone());
-//     //     (Self { sdr }, s_exp, missed)
-//     // }
-//     #[text_signature = "(labels,number_of_classes,invariant_to_column)"]
-//     fn fit_naive_bayes(&self, labels: &PyAny, number_of_classes: usize, invariant_to_column: Option<bool>) -> PyResult<Occurrences> {
-//         let invariant_to_column = invariant_to_column.unwrap_or(false);
-//         let array = unsafe {
-//             if npyffi::PyArray_Check(labels.as_ptr()) == 0 {
-//                 return Err(PyDowncastError::new(labels, "PyArray<T, D>").into());
-//             }
-//             &*(labels as *const PyAny as *const PyArrayDyn<u8>)
-//         };
-//         if !array.is_c_contiguous() {
-//             return Err(PyValueError::new_err("Numpy array is not C contiguous"));
-//         }
-//         let dtype = array.dtype().get_datatype().ok_or_else(|| PyValueError::new_err("No numpy array has no dtype"))?;
-//         fn f<T: Element>(sdr: &htm::CpuSdrDataset, labels: &PyAny, number_of_classes: usize, invariant_to_column: bool, f: impl Fn(&T) -> usize) -> PyResult<htm::Occurrences> {
-//             let labels = unsafe { &*(labels as *const PyAny as *const PyArrayDyn<T>) };
-//             let labels = unsafe { labels.as_slice()? };
-//             Ok(sdr.fit_naive_bayes(labels, number_of_classes, invariant_to_column, f))
-//         }
-//         match dtype {
-//             u8::DATA_TYPE => f(&self.sdr, labels, number_of_classes, invariant_to_column, |f: &u8| *f as usize),
-//             u32::DATA_TYPE => f(&self.sdr, labels, number_of_classes, invariant_to_column, |f: &u32| *f as usize),
-//             u64::DATA_TYPE => f(&self.sdr, labels, number_of_classes, invariant_to_column, |f: &u64| *f as usize),
-//             i8::DATA_TYPE => f(&self.sdr, labels, number_of_classes, invariant_to_column, |f: &i8| *f as usize),
-//             i32::DATA_TYPE => f(&self.sdr, labels, number_of_classes, invariant_to_column, |f: &i32| *f as usize),
-//             i64::DATA_TYPE => f(&self.sdr, labels, number_of_classes, invariant_to_column, |f: &i64| *f as usize),
-//             usize::DATA_TYPE => f(&self.sdr, labels, number_of_classes, invariant_to_column, |f: &usize| *f),
-//             d => Err(PyValueError::new_err(format!("Unexpected dtype {:?} of numpy array ", d)))
-//         }.map(|c| Occurrences { c })
-//     }
-//
-// }
-//
-// #[pyproto]
-// impl PyIterProtocol for CpuSdrDataset{
-//     fn __iter__(slf: PyRef<Self>) -> PyResult<Py<CpuSdrDatasetIter>> {
-//         let iter = CpuSdrDatasetIter {
-//             inner: slf.sdr.to_vec().into_iter(),
-//         };
-//         Py::new(slf.py(), iter)
-//     }
-// }
-// #[pyproto]
-// impl PyIterProtocol for CpuSdrDatasetIter {
-//     fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
-//         slf
-//     }
-//
-//     fn __next__(mut slf: PyRefMut<Self>) -> Option<CpuSDR> {
-//         slf.inner.next().map(|sdr|CpuSDR{sdr})
-//     }
-// }
-// #[pyclass]
-// pub struct CpuSdrDatasetIter {
-//     inner: std::vec::IntoIter<htm::CpuSDR>,
-// }
-//
-//
-//
-// impl_save_load!(CpuSdrDataset,sdr);
-// impl_save_load!(SubregionIndices,sdr);
-// impl_save_load!(Occurrences,c);
-//
-// #[pyproto]
-// impl PySequenceProtocol for CpuSdrDataset {
-//     fn __len__(&self) -> usize {
-//         self.sdr.len()
-//     }
-//     fn __getitem__(&self, idx: isize) -> CpuSDR {
-//         assert!(idx >= 0);
-//         CpuSDR { sdr: self.sdr[idx as usize].clone() }
-//     }
-//
-//     fn __setitem__(&mut self, idx: isize, value: PyRef<CpuSDR>) {
-//         assert!(idx >= 0);
-//         self.sdr[idx as usize] = value.sdr.clone();
-//     }
-// }
-//
-//
-// #[pyproto]
-// impl PySequenceProtocol for SubregionIndices {
-//     fn __len__(&self) -> usize {
-//         self.sdr.len()
-//     }
-//     fn __getitem__(&self, idx: isize) -> Vec<u32> {
-//         assert!(idx >= 0);
-//         self.sdr[idx as usize].to_vec()
-//     }
-//
-//     fn __setitem__(&mut self, idx: isize, value: [Idx; 3]) {
-//         assert!(idx >= 0);
-//         self.sdr[idx as usize] = value;
-//     }
-// }
\ No newline at end of file
+use numpy::{DataType, Element, PyArray1, PyArray4, PyArrayDyn};
+use pyo3::prelude::*;
+use pyo3::exceptions::{PyIndexError, PyValueError};
+use pyo3::{PyIterProtocol, PyObjectProtocol, PyResult, PySequenceProtocol};
+use vf::{arr2, arr3, VectorFieldPartialOrd, VectorFieldRem, VectorFieldSub};
+use vf::conv_shape::grid;
+use crate::{ConvShape, Idx};
+use crate::py_sdr::Sdr;
+use crate::util::{impl_save_load, pickle, py_any_as_numpy, py_any_dtype, unpickle};
+
+///
+/// CpuSdrDataset(shape: (int,int,int), sdrs: [Sdr] = None)
+///
+/// Collection of SDRs that all share the same shape [height, width, channels]. All indices are stored in
+/// a single buffer, one sample after another.
+///
+#[pyclass]
+#[text_signature = "(shape, sdrs, /)"]
+pub struct CpuSdrDataset {
+    pub(crate) sdr: vf::sdr_dataset::CpuSdrDataset<Idx>,
+}
+
+///
+/// Naive Bayes classifier of SDRs. Obtain it with CpuSdrDataset.fit_naive_bayes(labels, number_of_classes)
+///
+#[pyclass]
+pub struct Occurrences {
+    pub(crate) c: vf::naive_bayes::Occurrences<Idx>,
+}
+
+///
+/// Randomly chosen output columns of some convolution, applied to randomly chosen samples of a dataset.
+/// Every entry is a triple (y, x, sample_idx).
+///
+#[pyclass]
+pub struct SubregionIndices {
+    pub(crate) sdr: vf::sdr_dataset::SubregionIndices<Idx>,
+}
+
+#[pyclass]
+pub struct CpuSdrDatasetIter {
+    dataset: Py<CpuSdrDataset>,
+    idx: usize,
+}
+
+fn normalize_idx(idx: isize, len: usize) -> PyResult<usize> {
+    let i = if idx < 0 { idx + len as isize } else { idx };
+    if 0 <= i && (i as usize) < len {
+        Ok(i as usize)
+    } else {
+        Err(PyIndexError::new_err(format!("Index {} is out of range for length {}", idx, len)))
+    }
+}
+
+impl CpuSdrDataset {
+    fn check_shape(&self, sdr: &Sdr) -> PyResult<()> {
+        if self.sdr.shape() != sdr.sdr.shape() {
+            Err(PyValueError::new_err(format!("Shape {:?} doesn't match dataset shape {:?}", sdr.sdr.shape(), self.sdr.shape())))
+        } else {
+            Ok(())
+        }
+    }
+    fn check_not_empty(&self) -> PyResult<()> {
+        if self.sdr.is_empty() {
+            Err(PyValueError::new_err("Dataset is empty"))
+        } else {
+            Ok(())
+        }
+    }
+    fn conv(&self, kernel: (Idx, Idx), stride: (Idx, Idx)) -> PyResult<vf::conv_shape::ConvShape<Idx>> {
+        let (kernel, stride) = (arr2(kernel), arr2(stride));
+        let g = grid(self.sdr.shape());
+        if !kernel.all_le(g) || stride.contains(&0) || g.sub(&kernel).rem(&stride) != [0, 0] {
+            return Err(PyValueError::new_err(format!("Kernel {:?} with stride {:?} can't be applied to {:?}", kernel, stride, g)));
+        }
+        Ok(vf::conv_shape::ConvShape::new_in(*self.sdr.shape(), 1, kernel, stride))
+    }
+    fn check_conv(&self, conv: &vf::conv_shape::ConvShape<Idx>, indices: &SubregionIndices) -> PyResult<()> {
+        if conv.in_shape() != self.sdr.shape() {
+            Err(PyValueError::new_err(format!("Convolution input shape {:?} doesn't match dataset shape {:?}", conv.in_shape(), self.sdr.shape())))
+        } else if indices.sdr.dataset_len() != self.sdr.len() || indices.sdr.shape() != self.sdr.shape() {
+            Err(PyValueError::new_err("Subregion indices were generated for a different dataset"))
+        } else if indices.sdr.iter().any(|[y, x, _]| ![*y, *x].all_lt(conv.out_grid())) {
+            Err(PyValueError::new_err(format!("Subregion indices exceed output grid {:?}", conv.out_grid())))
+        } else {
+            Ok(())
+        }
+    }
+    fn to_numpy_<'py, T: Element + Copy + Default>(&self, py: Python<'py>, idx: Option<isize>, one: T) -> PyResult<&'py PyArrayDyn<T>> {
+        let shape = self.sdr.shape().map(|d| d as usize);
+        if let Some(idx) = idx {
+            let idx = normalize_idx(idx, self.sdr.len())?;
+            PyArray1::from_vec(py, self.sdr.to_dense(idx, one)).reshape(shape.to_vec())
+        } else {
+            PyArray1::from_vec(py, self.sdr.batch_to_dense(one)).reshape(vec![self.sdr.len(), shape[0], shape[1], shape[2]])
+        }
+    }
+}
+
+#[pymethods]
+impl CpuSdrDataset {
+    #[new]
+    pub fn new(shape: (Idx, Idx, Idx), sdrs: Option<Vec<PyRef<Sdr>>>) -> PyResult<Self> {
+        let mut d = Self { sdr: vf::sdr_dataset::CpuSdrDataset::new(arr3(shape)) };
+        for sdr in sdrs.iter().flatten() {
+            d.check_shape(sdr)?;
+            d.sdr.push(&sdr.sdr);
+        }
+        Ok(d)
+    }
+    #[staticmethod]
+    #[text_signature = "(bools)"]
+    /// Converts a batch of dense boolean tensors of shape [batch, height, width, channels]
+    pub fn from_numpy(bools: &PyArray4<bool>) -> PyResult<Self> {
+        let shape = [bools.shape()[1], bools.shape()[2], bools.shape()[3]].map(|d| d as Idx);
+        Ok(Self { sdr: vf::sdr_dataset::CpuSdrDataset::from_dense(shape, unsafe { bools.as_slice()? }) })
+    }
+    #[text_signature = "(idx)"]
+    /// Dense tensor of idx-th sample (or of the entire dataset with an additional batch dimension if idx is None)
+    pub fn to_numpy<'py>(&self, py: Python<'py>, idx: Option<isize>) -> PyResult<&'py PyArrayDyn<u32>> {
+        self.to_numpy_(py, idx, 1)
+    }
+    #[text_signature = "(idx)"]
+    pub fn to_bool_numpy<'py>(&self, py: Python<'py>, idx: Option<isize>) -> PyResult<&'py PyArrayDyn<bool>> {
+        self.to_numpy_(py, idx, true)
+    }
+    #[text_signature = "(idx)"]
+    pub fn to_f32_numpy<'py>(&self, py: Python<'py>, idx: Option<isize>) -> PyResult<&'py PyArrayDyn<f32>> {
+        self.to_numpy_(py, idx, 1.)
+    }
+    #[text_signature = "(idx)"]
+    pub fn to_f64_numpy<'py>(&self, py: Python<'py>, idx: Option<isize>) -> PyResult<&'py PyArrayDyn<f64>> {
+        self.to_numpy_(py, idx, 1.)
+    }
+    #[getter]
+    pub fn shape(&self) -> Vec<Idx> {
+        self.sdr.shape().to_vec()
+    }
+    #[getter]
+    pub fn grid(&self) -> Vec<Idx> {
+        grid(self.sdr.shape()).to_vec()
+    }
+    #[getter]
+    pub fn volume(&self) -> Idx {
+        self.sdr.volume()
+    }
+    #[getter]
+    pub fn channels(&self) -> Idx {
+        self.sdr.shape()[2]
+    }
+    #[getter]
+    pub fn width(&self) -> Idx {
+        self.sdr.shape()[1]
+    }
+    #[getter]
+    pub fn height(&self) -> Idx {
+        self.sdr.shape()[0]
+    }
+    #[getter]
+    pub fn area(&self) -> Idx {
+        self.sdr.shape()[0] * self.sdr.shape()[1]
+    }
+    #[text_signature = "(min_cardinality)"]
+    /// Removes all samples that have fewer than min_cardinality ones
+    pub fn filter_by_cardinality_threshold(&mut self, min_cardinality: usize) {
+        self.sdr.filter_by_cardinality_threshold(min_cardinality)
+    }
+    #[text_signature = "()"]
+    pub fn clear(&mut self) {
+        self.sdr.clear()
+    }
+    #[text_signature = "(sdr)"]
+    pub fn push(&mut self, sdr: &Sdr) -> PyResult<()> {
+        self.check_shape(sdr)?;
+        self.sdr.push(&sdr.sdr);
+        Ok(())
+    }
+    #[text_signature = "()"]
+    pub fn pop(&mut self) -> Option<Sdr> {
+        self.sdr.pop().map(|sdr| Sdr { sdr })
+    }
+    #[text_signature = "()"]
+    /// Random sample or None if dataset is empty
+    pub fn rand(&self) -> Option<Sdr> {
+        let shape = *self.sdr.shape();
+        self.sdr.rand(&mut rand::thread_rng()).map(|s| Sdr { sdr: vf::sdr::Sdr::from_indices(shape, s.to_vec()) })
+    }
+    #[text_signature = "(start, end)"]
+    pub fn subdataset(&self, start: usize, end: Option<usize>) -> PyResult<Self> {
+        let end = end.unwrap_or(self.sdr.len());
+        if start > end || end > self.sdr.len() {
+            return Err(PyIndexError::new_err(format!("Range {}..{} is out of bounds for length {}", start, end, self.sdr.len())));
+        }
+        Ok(Self { sdr: self.sdr.subdataset(start..end) })
+    }
+    #[text_signature = "()"]
+    /// For every input index, the number of samples in which it is active. Returns tensor of shape [height, width, channels]
+    pub fn count<'py>(&self, py: Python<'py>) -> PyResult<&'py PyArrayDyn<u32>> {
+        PyArray1::from_vec(py, self.sdr.count()).reshape(self.sdr.shape().map(|d| d as usize).to_vec())
+    }
+    #[text_signature = "(out_shape, number_of_samples)"]
+    /// Picks random output columns (within out_shape grid) of random samples. If out_shape has no columns, no indices are picked
+    pub fn gen_rand_conv_subregion_indices(&self, out_shape: (Idx, Idx), number_of_samples: usize) -> PyResult<SubregionIndices> {
+        self.check_not_empty()?;
+        Ok(SubregionIndices { sdr: self.sdr.gen_rand_conv_subregion_indices(arr2(out_shape), number_of_samples, &mut rand::thread_rng()) })
+    }
+    #[text_signature = "(kernel, stride, number_of_samples)"]
+    pub fn gen_rand_conv_subregion_indices_with_ker(&self, kernel: (Idx, Idx), stride: (Idx, Idx), number_of_samples: usize) -> PyResult<SubregionIndices> {
+        self.check_not_empty()?;
+        let conv = self.conv(kernel, stride)?;
+        Ok(SubregionIndices { sdr: self.sdr.gen_rand_conv_subregion_indices_with_conv(&conv, number_of_samples, &mut rand::thread_rng()) })
+    }
+    #[text_signature = "(conv, indices)"]
+    /// New dataset of input patches of shape conv.kernel_column_shape, seen by the output columns listed in indices
+    pub fn conv_subregion_indices(&self, conv: &ConvShape, indices: &SubregionIndices) -> PyResult<Self> {
+        self.check_conv(&conv.cs, indices)?;
+        Ok(Self { sdr: self.sdr.conv_subregion_indices(&conv.cs, &indices.sdr) })
+    }
+    #[text_signature = "(kernel, stride, indices)"]
+    pub fn conv_subregion_indices_with_ker(&self, kernel: (Idx, Idx), stride: (Idx, Idx), indices: &SubregionIndices) -> PyResult<Self> {
+        let conv = self.conv(kernel, stride)?;
+        self.check_conv(&conv, indices)?;
+        Ok(Self { sdr: self.sdr.conv_subregion_indices(&conv, &indices.sdr) })
+    }
+    #[text_signature = "(kernel, stride, indices, original)"]
+    /// Appends input patches of original dataset, seen by the output columns listed in indices.
+    /// The shape of this dataset must be equal to (kernel[0], kernel[1], original.channels)
+    pub fn extend_from_conv_subregion_indices(&mut self, kernel: (Idx, Idx), stride: (Idx, Idx), indices: &SubregionIndices, original: &CpuSdrDataset) -> PyResult<()> {
+        let conv = original.conv(kernel, stride)?;
+        original.check_conv(&conv, indices)?;
+        self.check_kernel_column(&conv)?;
+        self.sdr.extend_from_conv_subregion_indices(&conv, &indices.sdr, &original.sdr);
+        Ok(())
+    }
+    #[text_signature = "(kernel, stride, number_of_samples, original)"]
+    /// Appends input patches of original dataset, seen by random output columns in random samples
+    pub fn extend_from_conv_rand_subregion(&mut self, kernel: (Idx, Idx), stride: (Idx, Idx), number_of_samples: usize, original: &CpuSdrDataset) -> PyResult<()> {
+        original.check_not_empty()?;
+        let conv = original.conv(kernel, stride)?;
+        self.check_kernel_column(&conv)?;
+        self.sdr.extend_from_conv_rand_subregion(&conv, number_of_samples, &original.sdr, &mut rand::thread_rng());
+        Ok(())
+    }
+    #[text_signature = "(number_of_samples, original)"]
+    /// Appends random crops (of the shape of this dataset) of random samples of original dataset
+    pub fn extend_from_rand_subregions(&mut self, number_of_samples: usize, original: &CpuSdrDataset) -> PyResult<()> {
+        original.check_not_empty()?;
+        if !self.sdr.shape().all_le(original.sdr.shape()) {
+            return Err(PyValueError::new_err(format!("Subregion {:?} is larger than the original shape {:?}", self.sdr.shape(), original.sdr.shape())));
+        }
+        self.sdr.extend_from_rand_subregions(number_of_samples, &original.sdr, &mut rand::thread_rng());
+        Ok(())
+    }
+    #[text_signature = "(subregion_size, number_of_samples)"]
+    /// New dataset of random crops of the given shape
+    pub fn gen_rand_subregions(&self, subregion: (Idx, Idx, Idx), number_of_samples: usize) -> PyResult<Self> {
+        let mut d = Self { sdr: vf::sdr_dataset::CpuSdrDataset::with_capacity(number_of_samples, arr3(subregion)) };
+        d.extend_from_rand_subregions(number_of_samples, self)?;
+        Ok(d)
+    }
+}
+
+impl CpuSdrDataset {
+    fn check_kernel_column(&self, conv: &vf::conv_shape::ConvShape<Idx>) -> PyResult<()> {
+        if &conv.kernel_column_shape() != self.sdr.shape() {
+            Err(PyValueError::new_err(format!("Dataset shape {:?} doesn't match kernel column shape {:?}", self.sdr.shape(), conv.kernel_column_shape())))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Converts numpy array of integer labels, checking that all are less than number_of_classes
+fn labels_as_usize(labels: &PyAny, number_of_classes: usize, expected_len: usize) -> PyResult<Vec<usize>> {
+    fn f<T: Element + Copy + TryInto<usize>>(labels: &PyAny) -> PyResult<Vec<usize>> {
+        let labels = py_any_as_numpy::<T>(labels)?;
+        unsafe { labels.as_slice()? }.iter().map(|&l| l.try_into().map_err(|_| PyValueError::new_err("Labels can't be negative"))).collect()
+    }
+    let v = match py_any_dtype(labels)? {
+        DataType::Uint8 => f::<u8>(labels),
+        DataType::Uint32 => f::<u32>(labels),
+        DataType::Uint64 => f::<u64>(labels),
+        DataType::Int8 => f::<i8>(labels),
+        DataType::Int32 => f::<i32>(labels),
+        DataType::Int64 => f::<i64>(labels),
+        d => Err(PyValueError::new_err(format!("Unexpected dtype {:?} of labels", d)))
+    }?;
+    if v.len() != expected_len {
+        Err(PyValueError::new_err(format!("Expected {} labels but got {}", expected_len, v.len())))
+    } else if let Some(l) = v.iter().find(|&&l| l >= number_of_classes) {
+        Err(PyValueError::new_err(format!("Label {} is out of range for {} classes", l, number_of_classes)))
+    } else {
+        Ok(v)
+    }
+}
+
+#[pymethods]
+impl CpuSdrDataset {
+    #[text_signature = "(labels, number_of_classes)"]
+    /// Counts co-occurrences of every input index and label. labels is a numpy array of integers, one per sample
+    pub fn count_per_label(&self, labels: &PyAny, number_of_classes: usize) -> PyResult<Occurrences> {
+        let labels = labels_as_usize(labels, number_of_classes, self.sdr.len())?;
+        Ok(Occurrences { c: self.sdr.count_per_label(&labels, number_of_classes) })
+    }
+    #[text_signature = "(labels, number_of_classes, invariant_to_column)"]
+    /// Fits naive Bayes classifier. Its weights are log-probabilities ln P(input|label).
+    /// If invariant_to_column is True then all columns share the same weights.
+    pub fn fit_naive_bayes(&self, labels: &PyAny, number_of_classes: usize, invariant_to_column: Option<bool>) -> PyResult<Occurrences> {
+        let labels = labels_as_usize(labels, number_of_classes, self.sdr.len())?;
+        Ok(Occurrences { c: self.sdr.fit_naive_bayes(&labels, number_of_classes, invariant_to_column.unwrap_or(false)) })
+    }
+}
+
+impl Occurrences {
+    /// The given convolution, or the one produced by column_conv if None
+    fn conv(&self, conv: Option<PyRef<ConvShape>>, in_shape: &[Idx; 3]) -> PyResult<vf::conv_shape::ConvShape<Idx>> {
+        let conv = if let Some(conv) = conv {
+            conv.cs.clone()
+        } else {
+            let kernel = [self.c.shape()[0], self.c.shape()[1]];
+            if !kernel.all_le(grid(in_shape)) || self.c.shape()[2] != in_shape[2] {
+                return Err(PyValueError::new_err(format!("Classifier of shape {:?} can't be applied to {:?}", self.c.shape(), in_shape)));
+            }
+            self.c.column_conv(*in_shape)
+        };
+        if &conv.kernel_column_shape() != self.c.shape() {
+            Err(PyValueError::new_err(format!("Kernel column shape {:?} doesn't match classifier shape {:?}", conv.kernel_column_shape(), self.c.shape())))
+        } else if conv.in_shape() != in_shape {
+            Err(PyValueError::new_err(format!("Convolution input shape {:?} doesn't match {:?}", conv.in_shape(), in_shape)))
+        } else {
+            Ok(conv)
+        }
+    }
+}
+
+#[pymethods]
+impl Occurrences {
+    #[text_signature = "(input_idx, label)"]
+    pub fn prob(&self, input_idx: usize, label: usize) -> f32 {
+        self.c.prob(input_idx, label)
+    }
+    #[text_signature = "()"]
+    /// Turns co-occurrence counts into (Laplace smoothed) probabilities P(input|label)
+    pub fn normalise_wrt_labels(&mut self) {
+        self.c.normalise_wrt_labels()
+    }
+    #[text_signature = "()"]
+    pub fn log(&mut self) {
+        self.c.log()
+    }
+    #[text_signature = "()"]
+    /// Averages weights across all columns. The shape becomes (1, 1, channels)
+    pub fn aggregate_invariant_to_column(&mut self) {
+        self.c.aggregate_invariant_to_column()
+    }
+    #[text_signature = "()"]
+    /// Weights of shape [height, width, channels, num_classes]
+    pub fn occurrences<'py>(&self, py: Python<'py>) -> PyResult<&'py PyArrayDyn<f32>> {
+        let [h, w, c] = self.c.shape().map(|d| d as usize);
+        PyArray1::from_slice(py, self.c.occurrences()).reshape(vec![h, w, c, self.c.num_classes()])
+    }
+    #[text_signature = "(label)"]
+    pub fn occurrences_for_label<'py>(&self, py: Python<'py>, label: usize) -> PyResult<&'py PyArrayDyn<f32>> {
+        if label >= self.c.num_classes() {
+            return Err(PyValueError::new_err(format!("Label {} is out of range for {} classes", label, self.c.num_classes())));
+        }
+        PyArray1::from_vec(py, self.c.occurrences_for_label(label)).reshape(self.c.shape().map(|d| d as usize).to_vec())
+    }
+    #[getter]
+    pub fn class_prob(&self) -> Vec<f32> {
+        self.c.class_prob()
+    }
+    #[getter]
+    pub fn num_classes(&self) -> usize {
+        self.c.num_classes()
+    }
+    #[getter]
+    pub fn shape(&self) -> Vec<Idx> {
+        self.c.shape().to_vec()
+    }
+    #[text_signature = "(sdr, conv, min_deviation_from_mean)"]
+    /// If neither conv nor min_deviation_from_mean is given, the whole SDR is classified at once. Otherwise every
+    /// output column of conv votes (see classify_per_column) and the label with most votes wins.
+    pub fn classify(&self, sdr: &Sdr, conv: Option<PyRef<ConvShape>>, min_deviation_from_mean: Option<f32>) -> PyResult<usize> {
+        if conv.is_none() && min_deviation_from_mean.is_none() {
+            if sdr.sdr.shape() != self.c.shape() {
+                return Err(PyValueError::new_err(format!("Shape {:?} doesn't match classifier shape {:?}", sdr.sdr.shape(), self.c.shape())));
+            }
+            Ok(self.c.classify(&sdr.sdr))
+        } else {
+            let conv = self.conv(conv, sdr.sdr.shape())?;
+            Ok(self.c.classify_with_most_votes(&sdr.sdr, &conv, min_deviation_from_mean.unwrap_or(0.)))
+        }
+    }
+    #[text_signature = "(sdr_dataset, conv, min_deviation_from_mean)"]
+    /// Classifies every sample of dataset (see classify)
+    pub fn batch_classify<'py>(&self, py: Python<'py>, sdr_dataset: &CpuSdrDataset, conv: Option<PyRef<ConvShape>>, min_deviation_from_mean: Option<f32>) -> PyResult<&'py PyArray1<u32>> {
+        let v = if conv.is_none() && min_deviation_from_mean.is_none() {
+            if sdr_dataset.sdr.shape() != self.c.shape() {
+                return Err(PyValueError::new_err(format!("Shape {:?} doesn't match classifier shape {:?}", sdr_dataset.sdr.shape(), self.c.shape())));
+            }
+            let (c, d) = (&self.c, &sdr_dataset.sdr);
+            py.allow_threads(|| c.batch_classify(d))
+        } else {
+            let conv = self.conv(conv, sdr_dataset.sdr.shape())?;
+            let (c, d, min_dev) = (&self.c, &sdr_dataset.sdr, min_deviation_from_mean.unwrap_or(0.));
+            py.allow_threads(|| c.batch_classify_with_most_votes(d, &conv, min_dev))
+        };
+        Ok(PyArray1::from_vec(py, v))
+    }
+    #[text_signature = "(sdr, conv, min_deviation_from_mean)"]
+    /// Label chosen by each output column of conv (laid out according to conv.out_grid), or -1 if the column abstains.
+    /// If conv is None, the classifier slides with stride 1 over the SDR.
+    pub fn classify_per_column(&self, sdr: &Sdr, conv: Option<PyRef<ConvShape>>, min_deviation_from_mean: Option<f32>) -> PyResult<Vec<isize>> {
+        let conv = self.conv(conv, sdr.sdr.shape())?;
+        let v = self.c.classify_per_column(&sdr.sdr, &conv, min_deviation_from_mean.unwrap_or(0.));
+        Ok(v.into_iter().map(|l| l.map(|l| l as isize).unwrap_or(-1)).collect())
+    }
+}
+
+#[pymethods]
+impl SubregionIndices {
+    #[getter]
+    pub fn in_shape(&self) -> Vec<Idx> {
+        self.sdr.shape().to_vec()
+    }
+    #[getter]
+    pub fn dataset_len(&self) -> usize {
+        self.sdr.dataset_len()
+    }
+    #[text_signature = "(idx)"]
+    pub fn get_sample_idx(&self, idx: isize) -> PyResult<Idx> {
+        Ok(self.sdr[normalize_idx(idx, self.sdr.len())?][2])
+    }
+    #[text_signature = "(idx)"]
+    pub fn get_output_column_pos(&self, idx: isize) -> PyResult<Vec<Idx>> {
+        Ok(self.sdr[normalize_idx(idx, self.sdr.len())?][..2].to_vec())
+    }
+}
+
+impl_save_load!(CpuSdrDataset, sdr);
+impl_save_load!(SubregionIndices, sdr);
+impl_save_load!(Occurrences, c);
+
+#[pyproto]
+impl PySequenceProtocol for CpuSdrDataset {
+    fn __len__(&self) -> usize {
+        self.sdr.len()
+    }
+    fn __getitem__(&self, idx: isize) -> PyResult<Sdr> {
+        Ok(Sdr { sdr: self.sdr.get_sdr(normalize_idx(idx, self.sdr.len())?) })
+    }
+    fn __setitem__(&mut self, idx: isize, value: PyRef<Sdr>) -> PyResult<()> {
+        let idx = normalize_idx(idx, self.sdr.len())?;
+        self.check_shape(&value)?;
+        self.sdr.set(idx, &value.sdr);
+        Ok(())
+    }
+}
+
+#[pyproto]
+impl PyIterProtocol for CpuSdrDataset {
+    fn __iter__(slf: PyRef<Self>) -> PyResult<Py<CpuSdrDatasetIter>> {
+        let dataset: Py<CpuSdrDataset> = slf.into();
+        Python::with_gil(|py| Py::new(py, CpuSdrDatasetIter { dataset, idx: 0 }))
+    }
+}
+
+#[pyproto]
+impl PyIterProtocol for CpuSdrDatasetIter {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+    fn __next__(mut slf: PyRefMut<Self>) -> Option<Sdr> {
+        let idx = slf.idx;
+        let sdr = {
+            let dataset = slf.dataset.as_ref(slf.py()).borrow();
+            if idx >= dataset.sdr.len() {
+                return None;
+            }
+            dataset.sdr.get_sdr(idx)
+        };
+        slf.idx += 1;
+        Some(Sdr { sdr })
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for CpuSdrDataset {
+    fn __repr__(&self) -> String {
+        format!("CpuSdrDataset(shape={:?}, len={})", self.sdr.shape(), self.sdr.len())
+    }
+}
+
+#[pyproto]
+impl PySequenceProtocol for SubregionIndices {
+    fn __len__(&self) -> usize {
+        self.sdr.len()
+    }
+    fn __getitem__(&self, idx: isize) -> PyResult<Vec<Idx>> {
+        Ok(self.sdr[normalize_idx(idx, self.sdr.len())?].to_vec())
+    }
+}
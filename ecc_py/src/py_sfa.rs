@@ -0,0 +1,97 @@
+use numpy::{IntoPyArray, PyArray1, PyArray2};
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+use pyo3::{PyNativeType, PyResult};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+///
+/// Sfa(input_dim: int, whitened_dim: int, output_dim: int, quadratic: bool = False, learning_rate: float = 0.001, seed: int = None)
+///
+/// Slow feature analysis. Use fit(x) for batch SFA on entire sequence and partial_fit(x) for incremental SFA.
+/// Both can be mixed. The sequence x is of shape [time, input_dim]. If quadratic is True, the input is first expanded
+/// into all pairwise products of its features. Initial state of incremental SFA is random, pass seed to make it reproducible.
+///
+#[pyclass]
+#[text_signature = "(input_dim, whitened_dim, output_dim, quadratic, learning_rate, seed, /)"]
+pub struct Sfa {
+    pub(crate) sfa: vf::sfa::Sfa<f64>,
+}
+
+impl Sfa {
+    fn check_input(&self, x: &PyArray2<f64>) -> PyResult<()> {
+        let dim = x.shape()[1];
+        if dim != self.sfa.input_dim() {
+            Err(PyValueError::new_err(format!("Expected input of shape [time, {}] but got {:?}", self.sfa.input_dim(), x.shape())))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[pymethods]
+impl Sfa {
+    #[new]
+    #[args(quadratic = "false", learning_rate = "0.001", seed = "None")]
+    pub fn new(input_dim: usize, whitened_dim: usize, output_dim: usize, quadratic: bool, learning_rate: f64, seed: Option<u64>) -> PyResult<Self> {
+        let dim = if quadratic { vf::sfa::quadratic_expansion_dim(input_dim) } else { input_dim };
+        if whitened_dim > dim {
+            return Err(PyValueError::new_err(format!("Whitened dimensionality {} can't exceed (expanded) input dimensionality {}", whitened_dim, dim)));
+        }
+        if output_dim > whitened_dim {
+            return Err(PyValueError::new_err(format!("Number of slow features {} can't exceed whitened dimensionality {}", output_dim, whitened_dim)));
+        }
+        let sfa = match seed {
+            Some(seed) => vf::sfa::Sfa::new_with_rng(input_dim, whitened_dim, output_dim, quadratic, learning_rate, &mut StdRng::seed_from_u64(seed)),
+            None => vf::sfa::Sfa::new(input_dim, whitened_dim, output_dim, quadratic, learning_rate)
+        };
+        Ok(Self { sfa })
+    }
+    #[getter]
+    pub fn input_dim(&self) -> usize { self.sfa.input_dim() }
+    #[getter]
+    pub fn whitened_dim(&self) -> usize { self.sfa.whitened_dim() }
+    #[getter]
+    pub fn output_dim(&self) -> usize { self.sfa.output_dim() }
+    #[getter]
+    pub fn quadratic(&self) -> bool { self.sfa.is_quadratic() }
+    #[getter]
+    pub fn learning_rate(&self) -> f64 { self.sfa.mca().learning_rate() }
+    #[setter]
+    pub fn set_learning_rate(&mut self, learning_rate: f64) { self.sfa.mca_mut().set_learning_rate(learning_rate) }
+    /// Mean of (expanded) input
+    #[getter]
+    pub fn mean<'py>(&self, py: Python<'py>) -> &'py PyArray1<f64> {
+        PyArray1::from_slice(py, self.sfa.mu())
+    }
+    #[text_signature = "(x)"]
+    /// Batch SFA. x is of shape [time, input_dim]. Overwrites any previously learned state.
+    pub fn fit(&mut self, x: &PyArray2<f64>) -> PyResult<()> {
+        self.check_input(x)?;
+        let py = x.py();
+        let x = unsafe { x.as_slice()? };
+        let sfa = &mut self.sfa;
+        py.allow_threads(|| sfa.fit(x)).map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+    #[text_signature = "(x)"]
+    /// Incremental SFA. x is of shape [time, input_dim] and continues the sequence passed in previous calls.
+    pub fn partial_fit(&mut self, x: &PyArray2<f64>) -> PyResult<()> {
+        self.check_input(x)?;
+        let py = x.py();
+        let x = unsafe { x.as_slice()? };
+        let sfa = &mut self.sfa;
+        py.allow_threads(|| sfa.partial_fit(x));
+        Ok(())
+    }
+    #[text_signature = "(x)"]
+    /// Returns slow features of shape [time, output_dim], slowest first.
+    pub fn transform<'py>(&self, x: &'py PyArray2<f64>) -> PyResult<&'py PyArray2<f64>> {
+        self.check_input(x)?;
+        let time = x.shape()[0];
+        let py = x.py();
+        let x = unsafe { x.as_slice()? };
+        let sfa = &self.sfa;
+        let y = py.allow_threads(|| sfa.transform(x));
+        y.into_pyarray(py).reshape([time, self.sfa.output_dim()])
+    }
+}
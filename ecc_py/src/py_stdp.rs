@@ -0,0 +1,176 @@
+use numpy::{IntoPyArray, PyArray1, PyArrayDyn};
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+use pyo3::{PyNativeType, PyObjectProtocol, PyResult};
+use crate::ConvShape;
+use crate::util::{impl_save_load, pickle, unpickle};
+
+type Idx = u32;
+
+///
+/// Stdp(shape: ConvShape, norm: int = 2, threshold: float = 5., w_step: float = 0.0001, r_step: float = 1/512)
+///
+/// Layer driven by ordered input spikes. Input x is a list of indices sorted by spike time. Every minicolumn accumulates
+/// weights of incoming spikes and fires its strongest neuron as soon as it crosses the threshold. Weights are shared
+/// between minicolumns and have shape [kernel_height, kernel_width, in_channels, out_channels]. Layers created with
+/// new_linear have weights of shape [input, output].
+///
+#[pyclass]
+#[text_signature = "(shape, norm, threshold, w_step, r_step, /)"]
+pub struct Stdp {
+    pub(crate) s: vf::stdp::Stdp<Idx>,
+}
+
+impl Stdp {
+    fn from_shape(cs: vf::conv_shape::ConvShape<Idx>, norm: usize, threshold: f32, w_step: f32, r_step: f32) -> PyResult<Self> {
+        if norm != 1 && norm != 2 {
+            return Err(PyValueError::new_err(format!("Norm must be 1 or 2 but got {}", norm)));
+        }
+        Ok(Self { s: vf::stdp::Stdp::new(cs, norm, threshold, w_step, r_step) })
+    }
+    fn w_shape(&self) -> Vec<usize> {
+        let cs = self.s.shape();
+        if cs.in_grid() == &[1, 1] && cs.out_grid() == &[1, 1] {
+            vec![cs.in_channels() as usize, cs.out_channels() as usize]
+        } else {
+            cs.minicolumn_w_shape().iter().map(|&a| a as usize).collect()
+        }
+    }
+    fn check_x<'py>(&self, x: &'py PyArray1<Idx>) -> PyResult<&'py [Idx]> {
+        let x = unsafe { x.as_slice()? };
+        let v = self.s.shape().in_volume();
+        if let Some(i) = x.iter().find(|&&i| i >= v) {
+            return Err(PyValueError::new_err(format!("Input index {} is out of bounds for volume {}", i, v)));
+        }
+        Ok(x)
+    }
+}
+
+#[pymethods]
+impl Stdp {
+    #[new]
+    #[args(norm = "2", threshold = "5.", w_step = "0.0001", r_step = "0.001953125")]
+    pub fn new(shape: PyRef<ConvShape>, norm: usize, threshold: f32, w_step: f32, r_step: f32) -> PyResult<Self> {
+        Self::from_shape(shape.cs.clone(), norm, threshold, w_step, r_step)
+    }
+    #[staticmethod]
+    #[args(norm = "2", threshold = "5.", w_step = "0.0001", r_step = "0.001953125")]
+    #[text_signature = "(input, output, norm, threshold, w_step, r_step)"]
+    pub fn new_linear(input: Idx, output: Idx, norm: usize, threshold: f32, w_step: f32, r_step: f32) -> PyResult<Self> {
+        Self::from_shape(vf::conv_shape::ConvShape::new_linear(input, output), norm, threshold, w_step, r_step)
+    }
+    #[getter]
+    pub fn shape(&self) -> ConvShape {
+        ConvShape { cs: self.s.shape().clone() }
+    }
+    #[getter]
+    pub fn norm(&self) -> usize {
+        self.s.norm()
+    }
+    #[getter]
+    pub fn get_threshold(&self) -> f32 {
+        self.s.threshold()
+    }
+    #[setter]
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.s.set_threshold(threshold)
+    }
+    #[getter]
+    pub fn get_w_step(&self) -> f32 {
+        self.s.w_step()
+    }
+    #[setter]
+    pub fn set_w_step(&mut self, w_step: f32) {
+        self.s.set_w_step(w_step)
+    }
+    #[getter]
+    pub fn get_r_step(&self) -> f32 {
+        self.s.r_step()
+    }
+    #[setter]
+    pub fn set_r_step(&mut self, r_step: f32) {
+        self.s.set_r_step(r_step)
+    }
+    #[text_signature = "()"]
+    /// Returns a copy of weights of shape [kernel_height, kernel_width, in_channels, out_channels]
+    pub fn get_w<'py>(&self, py: Python<'py>) -> &'py PyArrayDyn<f32> {
+        self.s.w().to_vec().into_pyarray(py).reshape(self.w_shape()).unwrap()
+    }
+    #[text_signature = "(w)"]
+    pub fn set_w(&mut self, w: &PyArrayDyn<f32>) -> PyResult<()> {
+        let shape = self.w_shape();
+        if w.shape() != shape.as_slice() {
+            return Err(PyValueError::new_err(format!("W tensor shape is wrong. Expected {:?} but got {:?}", shape, w.shape())));
+        }
+        self.s.w_mut().copy_from_slice(unsafe { w.as_slice()? });
+        Ok(())
+    }
+    #[text_signature = "()"]
+    /// Returns a copy of bias of shape [out_channels]
+    pub fn get_r<'py>(&self, py: Python<'py>) -> &'py PyArray1<f32> {
+        self.s.r().to_vec().into_pyarray(py)
+    }
+    #[text_signature = "(r)"]
+    pub fn set_r(&mut self, r: &PyArray1<f32>) -> PyResult<()> {
+        let r = unsafe { r.as_slice()? };
+        if r.len() != self.s.r().len() {
+            return Err(PyValueError::new_err(format!("Expected bias of length {} but got {}", self.s.r().len(), r.len())));
+        }
+        self.s.r_mut().copy_from_slice(r);
+        Ok(())
+    }
+    #[text_signature = "()"]
+    pub fn normalize_w(&mut self) {
+        self.s.normalize_w()
+    }
+    #[text_signature = "(x)"]
+    /// Returns output spikes (one per minicolumn, in the order in which they fired) and, for each of them,
+    /// the number of input spikes that arrived before it.
+    pub fn infer_with_times<'py>(&self, x: &'py PyArray1<Idx>) -> PyResult<(&'py PyArray1<Idx>, &'py PyArray1<usize>)> {
+        let py = x.py();
+        let x = self.check_x(x)?;
+        let (y, times) = py.allow_threads(|| self.s.infer_with_times(x));
+        Ok((y.into_pyarray(py), times.into_pyarray(py)))
+    }
+    #[text_signature = "(x, y, times)"]
+    /// Strengthens weights between every output spike y[i] and the first times[i] input spikes
+    pub fn learn(&mut self, x: &PyArray1<Idx>, y: &PyArray1<Idx>, times: &PyArray1<usize>) -> PyResult<()> {
+        let py = x.py();
+        let x = self.check_x(x)?;
+        let (y, times) = unsafe { (y.as_slice()?, times.as_slice()?) };
+        if y.len() != times.len() {
+            return Err(PyValueError::new_err(format!("Got {} output spikes but {} times", y.len(), times.len())));
+        }
+        let v = self.s.shape().out_volume();
+        if let Some(k) = y.iter().find(|&&k| k >= v) {
+            return Err(PyValueError::new_err(format!("Output index {} is out of bounds for volume {}", k, v)));
+        }
+        if let Some(t) = times.iter().find(|&&t| t > x.len()) {
+            return Err(PyValueError::new_err(format!("Time {} exceeds the number of input spikes {}", t, x.len())));
+        }
+        let s = &mut self.s;
+        py.allow_threads(|| s.learn(x, y, times));
+        Ok(())
+    }
+    #[args(learn = "false")]
+    #[text_signature = "(x, learn)"]
+    /// Returns output spikes, one per minicolumn, in the order in which they fired
+    pub fn run<'py>(&mut self, x: &'py PyArray1<Idx>, learn: bool) -> PyResult<&'py PyArray1<Idx>> {
+        let py = x.py();
+        let x = self.check_x(x)?;
+        let s = &mut self.s;
+        Ok(py.allow_threads(|| s.run(x, learn)).into_pyarray(py))
+    }
+}
+
+impl_save_load!(Stdp, s);
+
+#[pyproto]
+impl PyObjectProtocol for Stdp {
+    fn __repr__(&self) -> String {
+        format!("Stdp(shape={:?}, norm={}, threshold={}, w_step={}, r_step={})", self.s.shape(), self.s.norm(), self.s.threshold(), self.s.w_step(), self.s.r_step())
+    }
+    fn __str__(&self) -> String {
+        self.__repr__()
+    }
+}
@@ -10,6 +10,7 @@ use std::fs::{File, OpenOptions};
 use std::io::{BufReader, BufWriter};
 use pyo3::exceptions::PyValueError;
 use serde::{Serialize, Deserialize};
+use vf::conv::Padding;
 
 
 macro_rules! impl_save_load {
@@ -86,6 +87,39 @@ pub fn arr2<'py,T:Element+Copy+FromPyObject<'py>>(py: Python<'py>, t: &'py PyObj
     })
 }
 
+/// Padding can be given as an int, a tuple (missing axes are not padded) or one of the strings "same" and "valid"
+/// (just like in PyTorch). None means no padding.
+pub fn padding3<'py>(py: Python<'py>, t: &'py PyObject) -> PyResult<Padding<u32, 3>> {
+    if let Ok(mode) = t.extract::<&str>(py) {
+        padding_mode(mode)
+    } else {
+        Ok(Padding::Zero(arrX(py, t, 0, 0, 0)?))
+    }
+}
+
+/// Same as padding3 but for convolutions with DIM spatial axes. A single int pads all axes.
+pub fn padding_nd<'py, const DIM: usize>(py: Python<'py>, t: &'py PyObject) -> PyResult<Padding<u32, DIM>> {
+    if t.is_none(py) {
+        Ok(Padding::default())
+    } else if let Ok(mode) = t.extract::<&str>(py) {
+        padding_mode(mode)
+    } else if let Ok(p) = t.extract::<u32>(py) {
+        Ok(Padding::Zero([p; DIM]))
+    } else {
+        let p = t.extract::<Vec<u32>>(py)?;
+        let len = p.len();
+        p.try_into().map(Padding::Zero).map_err(|_| PyValueError::new_err(format!("Expected padding of {} axes but got {}", DIM, len)))
+    }
+}
+
+fn padding_mode<const DIM: usize>(mode: &str) -> PyResult<Padding<u32, DIM>> {
+    match mode {
+        "same" => Ok(Padding::Same),
+        "valid" => Ok(Padding::default()),
+        _ => Err(PyValueError::new_err(format!("Unknown padding mode '{}'. Expected 'same' or 'valid'", mode)))
+    }
+}
+
 pub fn pickle<T: Serialize>(val: &T, file: String) -> PyResult<()> {
     let o = OpenOptions::new()
         .write(true)
@@ -105,6 +139,15 @@ pub fn unpickle<T:Deserialize<'static>>(file: String) -> PyResult<T> {
         .map_err(|e| PyValueError::new_err(e.to_string()))?;
     ciborium::de::from_reader(&mut BufReader::new(o)).map_err(|err|PyValueError::new_err(err.to_string()))
 }
+pub fn py_any_dtype(input: &PyAny) -> Result<DataType, PyErr> {
+    let array = unsafe {
+        if npyffi::PyArray_Check(input.as_ptr()) == 0 {
+            return Err(PyDowncastError::new(input, "PyArray<T, D>").into());
+        }
+        &*(input as *const PyAny as *const PyArrayDyn<u8>)
+    };
+    array.dtype().get_datatype().ok_or_else(|| PyValueError::new_err("No numpy array has no dtype"))
+}
 pub fn py_any_as_numpy<T:Element>(input: &PyAny) -> Result<&PyArrayDyn<T>, PyErr> {
     let array = unsafe {
         if npyffi::PyArray_Check(input.as_ptr()) == 0 {
@@ -99,7 +99,38 @@ impl <X:Num> Debug for Node<X>{
     }
 }
 
+/**Activation functions are stored by their name. Functions outside of `Num::ALL_ACT_FN` have no name, so they can't
+be serialized (otherwise the result could not be deserialized back)*/
+#[cfg(feature = "serde")]
+impl<X: Num> serde::Serialize for Node<X> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::Error;
+        let name = match self.activation {
+            None => None,
+            Some(a_f) => Some(X::try_act_fn_name(a_f).ok_or_else(|| S::Error::custom("Unknown activation function can't be serialized"))?),
+        };
+        serde::Serialize::serialize(&name, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, X: Num> serde::Deserialize<'de> for Node<X> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        let activation = match <Option<String> as serde::Deserialize>::deserialize(deserializer)? {
+            None => None,
+            Some(name) => {
+                let i = ALL_ACT_FN_NAME.iter().position(|&n| n == name)
+                    .ok_or_else(|| D::Error::custom(format!("Unknown activation function {}", name)))?;
+                Some(X::ALL_ACT_FN[i])
+            }
+        };
+        Ok(Node { activation })
+    }
+}
+
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CPPN<X: Num> {
     nodes: Vec<Node<X>>,
     edges: Vec<Edge<X>>,
@@ -109,6 +140,7 @@ pub struct CPPN<X: Num> {
 }
 
 #[derive(Clone,Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Edge<X: Num> {
     innovation_no: usize,
     enabled: bool,
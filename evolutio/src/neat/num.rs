@@ -34,7 +34,11 @@ pub trait Num: Debug + num_traits::Num + Copy + Display + std::ops::AddAssign +
     fn random_walk(self) -> Self;
 
     fn act_fn_name(a_f:fn(Self)->Self)->&'static str{
-        Self::ALL_ACT_FN.iter().position(|&f|f==a_f).map(|i|ALL_ACT_FN_NAME[i]).unwrap_or("???")
+        Self::try_act_fn_name(a_f).unwrap_or("???")
+    }
+    /**None if the function is not one of ALL_ACT_FN*/
+    fn try_act_fn_name(a_f:fn(Self)->Self)->Option<&'static str>{
+        Self::ALL_ACT_FN.iter().position(|&f|f==a_f).map(|i|ALL_ACT_FN_NAME[i])
     }
     fn random_activation_fn() -> fn(Self)->Self;
     fn lerp(self, other:Self, fraction:Self)->Self{
@@ -1,20 +1,32 @@
+import gzip
+import os
 import ecc_py
-import torch
-import torchvision
-import matplotlib.pyplot as plt
 import numpy as np
 
-MNIST = torchvision.datasets.MNIST('../../data/', train=False, download=True)
-MNIST = MNIST.data.numpy()
+# Raw MNIST files, in the same location where torchvision used to download them
+MNIST_DIR = '../../data/MNIST/raw/'
+
+
+def load_idx(file):
+    path = os.path.join(MNIST_DIR, file)
+    with (gzip.open(path + '.gz') if os.path.exists(path + '.gz') else open(path, 'rb')) as f:
+        data = f.read()
+    ndim = data[3]
+    shape = tuple(int.from_bytes(data[4 + 4 * i:8 + 4 * i], 'big') for i in range(ndim))
+    return np.frombuffer(data, dtype=np.uint8, offset=4 + 4 * ndim).reshape(shape)
+
+
+MNIST = load_idx('t10k-images-idx3-ubyte')
+LABELS = load_idx('t10k-labels-idx1-ubyte')
 TRAINSET_SIZE = 1000
 TESTSET_SIZE = 1000
 MNIST = MNIST / 255 > 0.8
 TRAIN_SET = MNIST[:TRAINSET_SIZE]
+TRAIN_LABELS = LABELS[:TRAINSET_SIZE]
 TEST_SET = MNIST[TRAINSET_SIZE:TRAINSET_SIZE + TESTSET_SIZE]
-# TEST_SET_INDICES, TEST_SET_OFFSETS = ecc_py.batch_dense_to_sparse(TEST_SET)
-# TRAIN_SET_INDICES, TRAIN_SET_OFFSETS = ecc_py.batch_dense_to_sparse(TRAIN_SET)
+TEST_LABELS = LABELS[TRAINSET_SIZE:TRAINSET_SIZE + TESTSET_SIZE]
 INPUT_SHAPE = (28, 28, 1)
-KERNELS = [5, 5, 5, 5, 5, 5, 5]
+KERNELS = [5, 5, 5, 5, 5, 5]
 CHANNELS = [16, 32, 64, 64, 64, 64]
 CONV_SHAPES = []
 WEIGHTS = []
@@ -55,42 +67,39 @@ def run(x, layer, learn):
     if learn:
         y_sparse = ecc_py.dense_to_sparse(y)
         conv_shape.sparse_increment_repeated(W, W_epsilon, x, y_sparse, biased=True)
-        conv_shape.update_u_as_expected_sk_minus_sj_repeated(W, W_epsilon, s, y_sparse, U)
+        conv_shape.update_u_as_expected_sk_minus_sj_repeated(U_epsilon, s, y_sparse, U)
     return y
 
 
-def train():
-    for layer in range(len(KERNELS)):
-        shape = CONV_SHAPES[layer]
-        for _ in range(1000):
-            x = rand_patch(TRAIN_SET, shape.kernel)
-            run(x, layer, True)
-        for _ in range(1000):
-            x = rand_patch(TEST_SET, shape.kernel)
-            y = run(x, layer, False)
+def encode(images, layers, learn=False):
+    """Passes every image through the given number of layers and returns outputs of the last one"""
+    outputs = []
+    for x in images:
+        x = x.reshape(INPUT_SHAPE)
+        for layer in range(layers):
+            x = run(x, layer, learn and layer == layers - 1)
+        outputs.append(x)
+    return np.stack(outputs)
+
+
+def accuracy(layers):
+    """Fits naive Bayes classifier to the output of the given number of layers and measures test accuracy,
+    both for the whole output and with every output column voting separately"""
+    train = ecc_py.CpuSdrDataset.from_numpy(encode(TRAIN_SET, layers))
+    test = ecc_py.CpuSdrDataset.from_numpy(encode(TEST_SET, layers))
+    whole = train.fit_naive_bayes(TRAIN_LABELS, 10, False)
+    whole_acc = (whole.batch_classify(test) == TEST_LABELS).mean()
+    per_column = train.fit_naive_bayes(TRAIN_LABELS, 10, True)
+    per_column_acc = (per_column.batch_classify(test, None, 0.) == TEST_LABELS).mean()
+    return whole_acc, per_column_acc
 
 
 def experiment():
-    fig, axs = plt.subplots(w, h)
-    test_patches = [rand_patch() for _ in range(20000)]
-    for idx in range(200000):
-        x = rand_patch()
-        y = run(x, True)
-        if idx % 2000 == 0:
-            stats = np.zeros((y_len, PATCH_SIZE.prod()))
-            probability = np.zeros(y_len)
-            for x in test_patches:
-                y = run(x, False)
-                probability[y] += 1
-                stats[y] += x
-            print(probability)
-            print(probability / probability.sum())
-
-            for i in range(w):
-                for j in range(h):
-                    s = stats[i + j * w].reshape(PATCH_SIZE)
-                    axs[i, j].imshow(s)
-            plt.pause(0.01)
-
-
-train()
+    # each layer is trained on the outputs of the (already trained) layers below it
+    for layer in range(len(CONV_SHAPES)):
+        encode(TRAIN_SET, layer + 1, learn=True)
+        whole_acc, per_column_acc = accuracy(layer + 1)
+        print("layer", layer, "accuracy", whole_acc, "per column", per_column_acc)
+
+
+experiment()
@@ -0,0 +1,77 @@
+#![feature(test)]
+extern crate test;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use test::Bencher;
+use vf::soft_wta::{NULL, top_u_repeated_conv_, top_u_repeated_conv_par_, top_u_slice_, top_v_slice_};
+
+fn rand_vec(rng: &mut StdRng, len: usize) -> Vec<f32> {
+    (0..len).map(|_| rng.gen()).collect()
+}
+
+fn bench_top_u(b: &mut Bencher, channels: usize) {
+    let mut rng = StdRng::seed_from_u64(4);
+    let s = rand_vec(&mut rng, channels);
+    let u = rand_vec(&mut rng, channels * channels);
+    let mut y = vec![NULL; channels];
+    b.iter(|| {
+        y.fill(NULL);
+        top_u_slice_(&u, &s, &mut y);
+    })
+}
+
+fn bench_top_v(b: &mut Bencher, channels: usize) {
+    let mut rng = StdRng::seed_from_u64(4);
+    let s = rand_vec(&mut rng, channels);
+    let v: Vec<bool> = (0..channels * channels).map(|_| rng.gen_bool(0.5)).collect();
+    let mut y = vec![NULL; channels];
+    b.iter(|| {
+        y.fill(NULL);
+        top_v_slice_(&v, &s, &mut y);
+    })
+}
+
+#[bench]
+fn top_u_64(b: &mut Bencher) { bench_top_u(b, 64) }
+
+#[bench]
+fn top_u_256(b: &mut Bencher) { bench_top_u(b, 256) }
+
+#[bench]
+fn top_u_1024(b: &mut Bencher) { bench_top_u(b, 1024) }
+
+#[bench]
+fn top_v_64(b: &mut Bencher) { bench_top_v(b, 64) }
+
+#[bench]
+fn top_v_256(b: &mut Bencher) { bench_top_v(b, 256) }
+
+#[bench]
+fn top_v_1024(b: &mut Bencher) { bench_top_v(b, 1024) }
+
+#[bench]
+fn top_u_repeated_conv_16x16x256(b: &mut Bencher) {
+    let shape = [16, 16, 256];
+    let mut rng = StdRng::seed_from_u64(4);
+    let s = rand_vec(&mut rng, shape.iter().product());
+    let u = rand_vec(&mut rng, shape[2] * shape[2]);
+    let mut y = vec![NULL; s.len()];
+    b.iter(|| {
+        y.fill(NULL);
+        top_u_repeated_conv_(&shape, &u, &s, &mut y);
+    })
+}
+
+#[bench]
+fn top_u_repeated_conv_par_16x16x256(b: &mut Bencher) {
+    let shape = [16, 16, 256];
+    let mut rng = StdRng::seed_from_u64(4);
+    let s = rand_vec(&mut rng, shape.iter().product());
+    let u = rand_vec(&mut rng, shape[2] * shape[2]);
+    let mut y = vec![NULL; s.len()];
+    b.iter(|| {
+        y.fill(NULL);
+        top_u_repeated_conv_par_(&shape, &u, &s, &mut y);
+    })
+}
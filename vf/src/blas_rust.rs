@@ -0,0 +1,862 @@
+//! Portable implementation of the BLAS routines used by `blas_safe`. Every function mirrors the signature
+//! of its counterpart in the `blas` crate (column-major storage, Fortran-style `u8` flags and `i32` sizes),
+//! so that enabling the `pure-rust` feature only swaps the module that `blas_safe` calls into.
+//! Contiguous real vectors are processed with `portable_simd`.
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub};
+use std::simd::Simd;
+use num_complex::{Complex32, Complex64};
+use num_traits::{Float, One, Zero};
+
+const LANES: usize = 8;
+
+pub trait Scalar: Copy + PartialEq + Zero + One + AddAssign + Add<Output=Self> + Sub<Output=Self> + Mul<Output=Self> + Div<Output=Self> + Neg<Output=Self> {
+    type Real: Float + AddAssign;
+    fn conj(self) -> Self;
+    fn re(self) -> Self::Real;
+    fn im(self) -> Self::Real;
+    fn from_real(r: Self::Real) -> Self;
+    /// `|Re(x)| + |Im(x)|`, which is what BLAS uses for `asum` and `iamax`
+    fn abs1(self) -> Self::Real;
+    /// `|x|^2`
+    fn norm_sqr(self) -> Self::Real;
+    fn scale(self, r: Self::Real) -> Self;
+    fn dot_unit(x: &[Self], y: &[Self]) -> Self {
+        x.iter().zip(y).fold(Self::zero(), |s, (&a, &b)| s + a * b)
+    }
+    /// Conjugates `x`
+    fn dotc_unit(x: &[Self], y: &[Self]) -> Self {
+        x.iter().zip(y).fold(Self::zero(), |s, (&a, &b)| s + a.conj() * b)
+    }
+    fn axpy_unit(alpha: Self, x: &[Self], y: &mut [Self]) {
+        y.iter_mut().zip(x).for_each(|(b, &a)| *b += alpha * a)
+    }
+    fn scal_unit(alpha: Self, x: &mut [Self]) {
+        x.iter_mut().for_each(|a| *a = alpha * *a)
+    }
+}
+
+macro_rules! impl_real {
+    ($t:ident) => {
+        impl Scalar for $t {
+            type Real = $t;
+            fn conj(self) -> Self { self }
+            fn re(self) -> Self::Real { self }
+            fn im(self) -> Self::Real { 0. }
+            fn from_real(r: Self::Real) -> Self { r }
+            fn abs1(self) -> Self::Real { self.abs() }
+            fn norm_sqr(self) -> Self::Real { self * self }
+            fn scale(self, r: Self::Real) -> Self { self * r }
+            fn dot_unit(x: &[Self], y: &[Self]) -> Self {
+                let n = x.len().min(y.len());
+                let split = n - n % LANES;
+                let mut acc = Simd::<$t, LANES>::splat(0.);
+                for i in (0..split).step_by(LANES) {
+                    acc += Simd::from_slice(&x[i..]) * Simd::from_slice(&y[i..]);
+                }
+                acc.to_array().iter().sum::<$t>() + x[split..n].iter().zip(&y[split..n]).map(|(a, b)| a * b).sum::<$t>()
+            }
+            fn dotc_unit(x: &[Self], y: &[Self]) -> Self {
+                Self::dot_unit(x, y)
+            }
+            fn axpy_unit(alpha: Self, x: &[Self], y: &mut [Self]) {
+                let n = x.len().min(y.len());
+                let split = n - n % LANES;
+                let a = Simd::<$t, LANES>::splat(alpha);
+                for i in (0..split).step_by(LANES) {
+                    let b = a * Simd::from_slice(&x[i..]) + Simd::from_slice(&y[i..]);
+                    b.copy_to_slice(&mut y[i..]);
+                }
+                y[split..n].iter_mut().zip(&x[split..n]).for_each(|(b, &a)| *b += alpha * a)
+            }
+            fn scal_unit(alpha: Self, x: &mut [Self]) {
+                let split = x.len() - x.len() % LANES;
+                let a = Simd::<$t, LANES>::splat(alpha);
+                for i in (0..split).step_by(LANES) {
+                    let b = a * Simd::from_slice(&x[i..]);
+                    b.copy_to_slice(&mut x[i..]);
+                }
+                x[split..].iter_mut().for_each(|b| *b *= alpha)
+            }
+        }
+    };
+}
+impl_real!(f32);
+impl_real!(f64);
+
+macro_rules! impl_complex {
+    ($t:ident, $r:ident) => {
+        impl Scalar for $t {
+            type Real = $r;
+            fn conj(self) -> Self { $t::conj(&self) }
+            fn re(self) -> Self::Real { self.re }
+            fn im(self) -> Self::Real { self.im }
+            fn from_real(r: Self::Real) -> Self { $t::new(r, 0.) }
+            fn abs1(self) -> Self::Real { self.re.abs() + self.im.abs() }
+            fn norm_sqr(self) -> Self::Real { $t::norm_sqr(&self) }
+            fn scale(self, r: Self::Real) -> Self { self * r }
+        }
+    };
+}
+impl_complex!(Complex32, f32);
+impl_complex!(Complex64, f64);
+
+/// Position of the `i`-th of `n` elements of a strided vector. As in BLAS, negative increments
+/// traverse the vector backwards starting from the end.
+fn at(i: usize, n: usize, inc: i32) -> usize {
+    if inc >= 0 {
+        i * inc as usize
+    } else {
+        (n - 1 - i) * (-inc) as usize
+    }
+}
+
+fn dim(n: i32) -> usize {
+    n.max(0) as usize
+}
+
+fn is_trans(t: u8) -> bool {
+    match t.to_ascii_uppercase() {
+        b'N' => false,
+        b'T' | b'C' => true,
+        _ => panic!("Illegal transpose flag {}", t as char),
+    }
+}
+
+fn is_conj(t: u8) -> bool {
+    t.to_ascii_uppercase() == b'C'
+}
+
+fn is_upper(uplo: u8) -> bool {
+    match uplo.to_ascii_uppercase() {
+        b'U' => true,
+        b'L' => false,
+        _ => panic!("Illegal uplo flag {}", uplo as char),
+    }
+}
+
+fn is_unit(diag: u8) -> bool {
+    match diag.to_ascii_uppercase() {
+        b'U' => true,
+        b'N' => false,
+        _ => panic!("Illegal diag flag {}", diag as char),
+    }
+}
+
+fn op<S: Scalar>(conj: bool, a: S) -> S {
+    if conj { a.conj() } else { a }
+}
+
+fn gather<S: Scalar>(n: usize, x: &[S], incx: i32) -> Vec<S> {
+    (0..n).map(|i| x[at(i, n, incx)]).collect()
+}
+
+/// `y = beta * y`, where `beta == 0` overwrites `y` (even if it contained NaNs)
+fn scale_by_beta<S: Scalar>(n: usize, beta: S, y: &mut [S], incy: i32) {
+    if beta.is_zero() {
+        (0..n).for_each(|i| y[at(i, n, incy)] = S::zero())
+    } else if beta != S::one() {
+        if incy == 1 {
+            S::scal_unit(beta, &mut y[..n])
+        } else {
+            (0..n).for_each(|i| {
+                let y = &mut y[at(i, n, incy)];
+                *y = beta * *y
+            })
+        }
+    }
+}
+
+pub fn copy<S: Scalar>(n: i32, x: &[S], incx: i32, y: &mut [S], incy: i32) {
+    let n = dim(n);
+    if incx == 1 && incy == 1 {
+        y[..n].copy_from_slice(&x[..n])
+    } else {
+        (0..n).for_each(|i| y[at(i, n, incy)] = x[at(i, n, incx)])
+    }
+}
+
+pub fn axpy<S: Scalar>(n: i32, alpha: S, x: &[S], incx: i32, y: &mut [S], incy: i32) {
+    let n = dim(n);
+    if alpha.is_zero() {
+        return;
+    }
+    if incx == 1 && incy == 1 {
+        S::axpy_unit(alpha, &x[..n], &mut y[..n])
+    } else {
+        (0..n).for_each(|i| y[at(i, n, incy)] += alpha * x[at(i, n, incx)])
+    }
+}
+
+pub fn scal<S: Scalar>(n: i32, alpha: S, x: &mut [S], incx: i32) {
+    let n = dim(n);
+    if incx == 1 {
+        S::scal_unit(alpha, &mut x[..n])
+    } else if incx > 0 {
+        (0..n).for_each(|i| {
+            let x = &mut x[at(i, n, incx)];
+            *x = alpha * *x
+        })
+    }
+}
+
+pub fn swap<S: Scalar>(n: i32, x: &mut [S], incx: i32, y: &mut [S], incy: i32) {
+    let n = dim(n);
+    (0..n).for_each(|i| std::mem::swap(&mut x[at(i, n, incx)], &mut y[at(i, n, incy)]))
+}
+
+pub fn dotu<S: Scalar>(n: i32, x: &[S], incx: i32, y: &[S], incy: i32) -> S {
+    let n = dim(n);
+    if incx == 1 && incy == 1 {
+        S::dot_unit(&x[..n], &y[..n])
+    } else {
+        (0..n).fold(S::zero(), |s, i| s + x[at(i, n, incx)] * y[at(i, n, incy)])
+    }
+}
+
+pub fn dotc<S: Scalar>(n: i32, x: &[S], incx: i32, y: &[S], incy: i32) -> S {
+    let n = dim(n);
+    if incx == 1 && incy == 1 {
+        S::dotc_unit(&x[..n], &y[..n])
+    } else {
+        (0..n).fold(S::zero(), |s, i| s + x[at(i, n, incx)].conj() * y[at(i, n, incy)])
+    }
+}
+
+pub fn asum<S: Scalar>(n: i32, x: &[S], incx: i32) -> S::Real {
+    if incx <= 0 {
+        return S::Real::zero();
+    }
+    let n = dim(n);
+    (0..n).fold(S::Real::zero(), |s, i| s + x[at(i, n, incx)].abs1())
+}
+
+pub fn nrm2<S: Scalar>(n: i32, x: &[S], incx: i32) -> S::Real {
+    if incx <= 0 {
+        return S::Real::zero();
+    }
+    // Same scaled sum of squares as in reference BLAS, so that squaring doesn't overflow or underflow.
+    // The result is scale·sqrt(ssq) where every |x_i|/scale is at most 1.
+    let n = dim(n);
+    let mut scale = S::Real::zero();
+    let mut ssq = S::Real::one();
+    for i in 0..n {
+        let xi = x[at(i, n, incx)];
+        for part in [xi.re(), xi.im()] {
+            if part != S::Real::zero() {
+                let abs = part.abs();
+                if scale < abs {
+                    ssq = S::Real::one() + ssq * (scale / abs) * (scale / abs);
+                    scale = abs;
+                } else {
+                    ssq += (abs / scale) * (abs / scale);
+                }
+            }
+        }
+    }
+    scale * ssq.sqrt()
+}
+
+/// Returns 1-based index, or 0 if the vector is empty
+pub fn iamax<S: Scalar>(n: i32, x: &[S], incx: i32) -> usize {
+    if n < 1 || incx <= 0 {
+        return 0;
+    }
+    let n = dim(n);
+    let mut best = 0;
+    let mut max = x[0].abs1();
+    for i in 1..n {
+        let v = x[at(i, n, incx)].abs1();
+        if v > max {
+            max = v;
+            best = i;
+        }
+    }
+    best + 1
+}
+
+pub fn rot<S: Scalar>(n: i32, x: &mut [S], incx: i32, y: &mut [S], incy: i32, c: S::Real, s: S::Real) {
+    let n = dim(n);
+    for i in 0..n {
+        let xi = &mut x[at(i, n, incx)];
+        let yi = &mut y[at(i, n, incy)];
+        let (a, b) = (*xi, *yi);
+        *xi = a.scale(c) + b.scale(s);
+        *yi = b.scale(c) - a.scale(s);
+    }
+}
+
+pub fn gemm<S: Scalar>(transa: u8, transb: u8, m: i32, n: i32, k: i32, alpha: S, a: &[S], lda: i32, b: &[S], ldb: i32, beta: S, c: &mut [S], ldc: i32) {
+    let (m, n, k) = (dim(m), dim(n), dim(k));
+    let (lda, ldb, ldc) = (dim(lda), dim(ldb), dim(ldc));
+    let (ta, ca) = (is_trans(transa), is_conj(transa));
+    let (tb, cb) = (is_trans(transb), is_conj(transb));
+    let op_b = |l: usize, j: usize| op(cb, if tb { b[j + l * ldb] } else { b[l + j * ldb] });
+    for j in 0..n {
+        let cj = &mut c[j * ldc..j * ldc + m];
+        scale_by_beta(m, beta, cj, 1);
+        if alpha.is_zero() {
+            continue;
+        }
+        if ta {
+            for (i, cij) in cj.iter_mut().enumerate() {
+                let ai = &a[i * lda..i * lda + k];
+                let s = if !tb && !cb {
+                    let bj = &b[j * ldb..j * ldb + k];
+                    if ca { S::dotc_unit(ai, bj) } else { S::dot_unit(ai, bj) }
+                } else {
+                    ai.iter().enumerate().fold(S::zero(), |s, (l, &ail)| s + op(ca, ail) * op_b(l, j))
+                };
+                *cij += alpha * s;
+            }
+        } else {
+            for l in 0..k {
+                let t = alpha * op_b(l, j);
+                if !t.is_zero() {
+                    S::axpy_unit(t, &a[l * lda..l * lda + m], cj)
+                }
+            }
+        }
+    }
+}
+
+pub fn gemv<S: Scalar>(trans: u8, m: i32, n: i32, alpha: S, a: &[S], lda: i32, x: &[S], incx: i32, beta: S, y: &mut [S], incy: i32) {
+    let (m, n, lda) = (dim(m), dim(n), dim(lda));
+    let (t, conj) = (is_trans(trans), is_conj(trans));
+    let (lenx, leny) = if t { (m, n) } else { (n, m) };
+    scale_by_beta(leny, beta, y, incy);
+    if alpha.is_zero() {
+        return;
+    }
+    let x = gather(lenx, x, incx);
+    if t {
+        for j in 0..n {
+            let aj = &a[j * lda..j * lda + m];
+            let s = if conj { S::dotc_unit(aj, &x) } else { S::dot_unit(aj, &x) };
+            y[at(j, n, incy)] += alpha * s;
+        }
+    } else {
+        let mut acc = vec![S::zero(); m];
+        for j in 0..n {
+            let t = alpha * x[j];
+            if !t.is_zero() {
+                S::axpy_unit(t, &a[j * lda..j * lda + m], &mut acc)
+            }
+        }
+        (0..m).for_each(|i| y[at(i, m, incy)] += acc[i])
+    }
+}
+
+pub fn gbmv<S: Scalar>(trans: u8, m: i32, n: i32, kl: i32, ku: i32, alpha: S, a: &[S], lda: i32, x: &[S], incx: i32, beta: S, y: &mut [S], incy: i32) {
+    let (m, n, kl, ku, lda) = (dim(m), dim(n), dim(kl), dim(ku), dim(lda));
+    let (t, conj) = (is_trans(trans), is_conj(trans));
+    let (lenx, leny) = if t { (m, n) } else { (n, m) };
+    scale_by_beta(leny, beta, y, incy);
+    if alpha.is_zero() {
+        return;
+    }
+    let x = gather(lenx, x, incx);
+    let mut acc = vec![S::zero(); leny];
+    for j in 0..n {
+        // A(i,j) is stored at a[ku + i - j + j * lda]
+        for i in j.saturating_sub(ku)..m.min(j + kl + 1) {
+            let aij = op(conj, a[ku + i - j + j * lda]);
+            if t {
+                acc[j] += aij * x[i]
+            } else {
+                acc[i] += aij * x[j]
+            }
+        }
+    }
+    (0..leny).for_each(|i| y[at(i, leny, incy)] += alpha * acc[i])
+}
+
+/// Element `(i,j)` of the Hermitian (or symmetric, for real `S`) matrix whose `uplo` triangle is stored in `a`
+fn hermitian_at<S: Scalar>(upper: bool, a: &[S], lda: usize, i: usize, j: usize) -> S {
+    if i == j {
+        S::from_real(a[i + i * lda].re())
+    } else if (i < j) == upper {
+        a[i + j * lda]
+    } else {
+        a[j + i * lda].conj()
+    }
+}
+
+pub fn hemv<S: Scalar>(uplo: u8, n: i32, alpha: S, a: &[S], lda: i32, x: &[S], incx: i32, beta: S, y: &mut [S], incy: i32) {
+    let (n, lda) = (dim(n), dim(lda));
+    let upper = is_upper(uplo);
+    scale_by_beta(n, beta, y, incy);
+    if alpha.is_zero() {
+        return;
+    }
+    let x = gather(n, x, incx);
+    for i in 0..n {
+        let s = (0..n).fold(S::zero(), |s, j| s + hermitian_at(upper, a, lda, i, j) * x[j]);
+        y[at(i, n, incy)] += alpha * s;
+    }
+}
+
+/// Element `(i,j)` of `op(A)` where `A` is triangular
+fn triangular_at<S: Scalar>(upper: bool, trans: u8, unit: bool, a: &[S], lda: usize, i: usize, j: usize) -> S {
+    let (r, c) = if is_trans(trans) { (j, i) } else { (i, j) };
+    if r == c && unit {
+        S::one()
+    } else if r == c || (r < c) == upper {
+        op(is_conj(trans), a[r + c * lda])
+    } else {
+        S::zero()
+    }
+}
+
+pub fn trmv<S: Scalar>(uplo: u8, trans: u8, diag: u8, n: i32, a: &[S], lda: i32, x: &mut [S], incx: i32) {
+    let (n, lda) = (dim(n), dim(lda));
+    let (upper, unit) = (is_upper(uplo), is_unit(diag));
+    let b = gather(n, x, incx);
+    for i in 0..n {
+        x[at(i, n, incx)] = (0..n).fold(S::zero(), |s, j| s + triangular_at(upper, trans, unit, a, lda, i, j) * b[j]);
+    }
+}
+
+pub fn trsv<S: Scalar>(uplo: u8, trans: u8, diag: u8, n: i32, a: &[S], lda: i32, x: &mut [S], incx: i32) {
+    let (n, lda) = (dim(n), dim(lda));
+    let (upper, unit) = (is_upper(uplo), is_unit(diag));
+    // op(A) is upper triangular if exactly one of upper, trans holds
+    let backward = upper != is_trans(trans);
+    let mut b = gather(n, x, incx);
+    let order: Box<dyn Iterator<Item=usize>> = if backward { Box::new((0..n).rev()) } else { Box::new(0..n) };
+    for i in order {
+        let solved = if backward { i + 1..n } else { 0..i };
+        let s = solved.fold(b[i], |s, j| s - triangular_at(upper, trans, unit, a, lda, i, j) * b[j]);
+        b[i] = if unit { s } else { s / triangular_at(upper, trans, unit, a, lda, i, i) };
+    }
+    (0..n).for_each(|i| x[at(i, n, incx)] = b[i])
+}
+
+pub fn geru<S: Scalar>(m: i32, n: i32, alpha: S, x: &[S], incx: i32, y: &[S], incy: i32, a: &mut [S], lda: i32) {
+    let (m, n, lda) = (dim(m), dim(n), dim(lda));
+    let x = gather(m, x, incx);
+    for j in 0..n {
+        let t = alpha * y[at(j, n, incy)];
+        if !t.is_zero() {
+            S::axpy_unit(t, &x, &mut a[j * lda..j * lda + m])
+        }
+    }
+}
+
+/// `A = alpha * x * x^H + A` on the `uplo` triangle of `A`
+pub fn her<S: Scalar>(uplo: u8, n: i32, alpha: S::Real, x: &[S], incx: i32, a: &mut [S], lda: i32) {
+    let (n, lda) = (dim(n), dim(lda));
+    let upper = is_upper(uplo);
+    let x = gather(n, x, incx);
+    for j in 0..n {
+        let t = x[j].conj().scale(alpha);
+        let rows = if upper { 0..j + 1 } else { j..n };
+        for i in rows {
+            a[i + j * lda] += x[i] * t;
+        }
+        let d = &mut a[j + j * lda];
+        *d = S::from_real(d.re());
+    }
+}
+
+macro_rules! level1 {
+    ($t:ty, $r:ty, $copy:ident, $axpy:ident, $scal:ident, $swap:ident, $asum:ident, $nrm2:ident, $iamax:ident, $rot:ident) => {
+        pub unsafe fn $copy(n: i32, x: &[$t], incx: i32, y: &mut [$t], incy: i32) { copy(n, x, incx, y, incy) }
+        pub unsafe fn $axpy(n: i32, alpha: $t, x: &[$t], incx: i32, y: &mut [$t], incy: i32) { axpy(n, alpha, x, incx, y, incy) }
+        pub unsafe fn $scal(n: i32, a: $t, x: &mut [$t], incx: i32) { scal(n, a, x, incx) }
+        pub unsafe fn $swap(n: i32, x: &mut [$t], incx: i32, y: &mut [$t], incy: i32) { swap(n, x, incx, y, incy) }
+        pub unsafe fn $asum(n: i32, x: &[$t], incx: i32) -> $r { asum(n, x, incx) }
+        pub unsafe fn $nrm2(n: i32, x: &[$t], incx: i32) -> $r { nrm2(n, x, incx) }
+        pub unsafe fn $iamax(n: i32, x: &[$t], incx: i32) -> usize { iamax(n, x, incx) }
+        pub unsafe fn $rot(n: i32, x: &mut [$t], incx: i32, y: &mut [$t], incy: i32, c: $r, s: $r) { rot(n, x, incx, y, incy, c, s) }
+    };
+}
+level1!(f32, f32, scopy, saxpy, sscal, sswap, sasum, snrm2, isamax, srot);
+level1!(f64, f64, dcopy, daxpy, dscal, dswap, dasum, dnrm2, idamax, drot);
+level1!(Complex32, f32, ccopy, caxpy, cscal, cswap, scasum, scnrm2, icamax, csrot);
+level1!(Complex64, f64, zcopy, zaxpy, zscal, zswap, dzasum, dznrm2, izamax, zdrot);
+
+pub unsafe fn sdot(n: i32, x: &[f32], incx: i32, y: &[f32], incy: i32) -> f32 { dotu(n, x, incx, y, incy) }
+pub unsafe fn ddot(n: i32, x: &[f64], incx: i32, y: &[f64], incy: i32) -> f64 { dotu(n, x, incx, y, incy) }
+pub unsafe fn cdotu(pres: &mut [Complex32], n: i32, x: &[Complex32], incx: i32, y: &[Complex32], incy: i32) { pres[0] = dotu(n, x, incx, y, incy) }
+pub unsafe fn zdotu(pres: &mut [Complex64], n: i32, x: &[Complex64], incx: i32, y: &[Complex64], incy: i32) { pres[0] = dotu(n, x, incx, y, incy) }
+pub unsafe fn cdotc(pres: &mut [Complex32], n: i32, x: &[Complex32], incx: i32, y: &[Complex32], incy: i32) { pres[0] = dotc(n, x, incx, y, incy) }
+pub unsafe fn zdotc(pres: &mut [Complex64], n: i32, x: &[Complex64], incx: i32, y: &[Complex64], incy: i32) { pres[0] = dotc(n, x, incx, y, incy) }
+
+macro_rules! level23 {
+    ($t:ty, $gemm:ident, $gemv:ident, $gbmv:ident, $trmv:ident, $trsv:ident, $ger:ident) => {
+        pub unsafe fn $gemm(transa: u8, transb: u8, m: i32, n: i32, k: i32, alpha: $t, a: &[$t], lda: i32, b: &[$t], ldb: i32, beta: $t, c: &mut [$t], ldc: i32) {
+            gemm(transa, transb, m, n, k, alpha, a, lda, b, ldb, beta, c, ldc)
+        }
+        pub unsafe fn $gemv(trans: u8, m: i32, n: i32, alpha: $t, a: &[$t], lda: i32, x: &[$t], incx: i32, beta: $t, y: &mut [$t], incy: i32) {
+            gemv(trans, m, n, alpha, a, lda, x, incx, beta, y, incy)
+        }
+        pub unsafe fn $gbmv(trans: u8, m: i32, n: i32, kl: i32, ku: i32, alpha: $t, a: &[$t], lda: i32, x: &[$t], incx: i32, beta: $t, y: &mut [$t], incy: i32) {
+            gbmv(trans, m, n, kl, ku, alpha, a, lda, x, incx, beta, y, incy)
+        }
+        pub unsafe fn $trmv(uplo: u8, trans: u8, diag: u8, n: i32, a: &[$t], lda: i32, x: &mut [$t], incx: i32) {
+            trmv(uplo, trans, diag, n, a, lda, x, incx)
+        }
+        pub unsafe fn $trsv(uplo: u8, trans: u8, diag: u8, n: i32, a: &[$t], lda: i32, x: &mut [$t], incx: i32) {
+            trsv(uplo, trans, diag, n, a, lda, x, incx)
+        }
+        pub unsafe fn $ger(m: i32, n: i32, alpha: $t, x: &[$t], incx: i32, y: &[$t], incy: i32, a: &mut [$t], lda: i32) {
+            geru(m, n, alpha, x, incx, y, incy, a, lda)
+        }
+    };
+}
+level23!(f32, sgemm, sgemv, sgbmv, strmv, strsv, sger);
+level23!(f64, dgemm, dgemv, dgbmv, dtrmv, dtrsv, dger);
+level23!(Complex32, cgemm, cgemv, cgbmv, ctrmv, ctrsv, cgeru);
+level23!(Complex64, zgemm, zgemv, zgbmv, ztrmv, ztrsv, zgeru);
+
+macro_rules! hermitian {
+    ($t:ty, $r:ty, $hemv:ident, $her:ident) => {
+        pub unsafe fn $hemv(uplo: u8, n: i32, alpha: $t, a: &[$t], lda: i32, x: &[$t], incx: i32, beta: $t, y: &mut [$t], incy: i32) {
+            hemv(uplo, n, alpha, a, lda, x, incx, beta, y, incy)
+        }
+        pub unsafe fn $her(uplo: u8, n: i32, alpha: $r, x: &[$t], incx: i32, a: &mut [$t], lda: i32) {
+            her(uplo, n, alpha, x, incx, a, lda)
+        }
+    };
+}
+hermitian!(f32, f32, ssymv, ssyr);
+hermitian!(f64, f64, dsymv, dsyr);
+hermitian!(Complex32, f32, chemv, cher);
+hermitian!(Complex64, f64, zhemv, zher);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rand_vec(n: usize) -> Vec<f64> {
+        (0..n).map(|_| rand::random::<f64>() * 2. - 1.).collect()
+    }
+
+    fn rand_cvec(n: usize) -> Vec<Complex64> {
+        (0..n).map(|_| Complex64::new(rand::random::<f64>() * 2. - 1., rand::random::<f64>() * 2. - 1.)).collect()
+    }
+
+    fn assert_close<S: Scalar<Real=f64>>(a: &[S], b: &[S]) {
+        assert_eq!(a.len(), b.len());
+        for (&x, &y) in a.iter().zip(b) {
+            assert!((x - y).abs1() < 1e-9, "{} != {}", x.abs1(), y.abs1());
+        }
+    }
+
+    #[test]
+    fn test1() {
+        // Compare the SIMD paths against plain loops, including lengths that aren't a multiple of LANES
+        for n in [0, 1, 7, 8, 9, 33] {
+            let x = rand_vec(n);
+            let y = rand_vec(n);
+            let d: f64 = x.iter().zip(&y).map(|(a, b)| a * b).sum();
+            assert!((unsafe { ddot(n as i32, &x, 1, &y, 1) } - d).abs() < 1e-9);
+            let mut z = y.clone();
+            unsafe { daxpy(n as i32, 0.5, &x, 1, &mut z, 1) };
+            let expected: Vec<f64> = x.iter().zip(&y).map(|(a, b)| 0.5 * a + b).collect();
+            assert_close(&z, &expected);
+            let norm = x.iter().map(|a| a * a).sum::<f64>().sqrt();
+            assert!((unsafe { dnrm2(n as i32, &x, 1) } - norm).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test2() {
+        // negative increments traverse the vector backwards
+        let x = [1f32, 2., 3.];
+        let mut y = [0f32; 6];
+        unsafe { scopy(3, &x, 1, &mut y, -2) };
+        assert_eq!(y, [3., 0., 2., 0., 1., 0.]);
+        assert_eq!(unsafe { isamax(3, &[1., -5., 5.], 1) }, 2);
+        assert_eq!(unsafe { isamax(0, &[], 1) }, 0);
+    }
+
+    #[test]
+    fn test3() {
+        let (m, n, k) = (5, 3, 4);
+        for (ta, tb) in [(b'N', b'N'), (b'T', b'N'), (b'N', b'C'), (b'C', b'T')] {
+            let a = rand_cvec(m * k);
+            let b = rand_cvec(k * n);
+            let c0 = rand_cvec(m * n);
+            let (lda, ldb) = (if ta == b'N' { m } else { k }, if tb == b'N' { k } else { n });
+            let op_a = |i: usize, l: usize| match ta { b'N' => a[i + l * lda], b'T' => a[l + i * lda], _ => a[l + i * lda].conj() };
+            let op_b = |l: usize, j: usize| match tb { b'N' => b[l + j * ldb], b'T' => b[j + l * ldb], _ => b[j + l * ldb].conj() };
+            let alpha = Complex64::new(0.5, -1.);
+            let beta = Complex64::new(2., 0.25);
+            let mut expected = c0.clone();
+            for i in 0..m {
+                for j in 0..n {
+                    let s = (0..k).map(|l| op_a(i, l) * op_b(l, j)).sum::<Complex64>();
+                    expected[i + j * m] = alpha * s + beta * c0[i + j * m];
+                }
+            }
+            let mut c = c0.clone();
+            unsafe { zgemm(ta, tb, m as i32, n as i32, k as i32, alpha, &a, lda as i32, &b, ldb as i32, beta, &mut c, m as i32) };
+            assert_close(&c, &expected);
+        }
+    }
+
+    #[test]
+    fn test4() {
+        let n = 6;
+        let a = rand_vec(n * n);
+        let x = rand_vec(n);
+        for uplo in [b'U', b'L'] {
+            for trans in [b'N', b'T'] {
+                for diag in [b'N', b'U'] {
+                    // make the system well conditioned
+                    let mut a = a.clone();
+                    (0..n).for_each(|i| a[i + i * n] += 4.);
+                    let mut b = x.clone();
+                    unsafe { dtrmv(uplo, trans, diag, n as i32, &a, n as i32, &mut b, 1) };
+                    unsafe { dtrsv(uplo, trans, diag, n as i32, &a, n as i32, &mut b, 1) };
+                    assert_close(&b, &x);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test5() {
+        let n = 4;
+        let x = rand_cvec(n);
+        let y = rand_cvec(n);
+        for uplo in [b'U', b'L'] {
+            // her on a zero matrix builds x*x^H, which hemv must then apply as a full Hermitian matrix
+            let mut a = vec![Complex64::zero(); n * n];
+            unsafe { zher(uplo, n as i32, 2., &x, 1, &mut a, n as i32) };
+            let mut z = vec![Complex64::zero(); n];
+            unsafe { zhemv(uplo, n as i32, Complex64::one(), &a, n as i32, &y, 1, Complex64::zero(), &mut z, 1) };
+            let xy = dotc(n as i32, &x, 1, &y, 1);
+            let expected: Vec<Complex64> = x.iter().map(|&xi| xi * xy * 2.).collect();
+            assert_close(&z, &expected);
+        }
+    }
+
+    #[test]
+    fn test6() {
+        // a tridiagonal band matrix compared against its dense equivalent
+        let (m, n, kl, ku) = (5, 4, 1, 1);
+        let lda = kl + ku + 1;
+        let band = rand_vec(lda * n);
+        let mut dense = vec![0.; m * n];
+        for j in 0..n {
+            for i in j.saturating_sub(ku)..m.min(j + kl + 1) {
+                dense[i + j * m] = band[ku + i - j + j * lda];
+            }
+        }
+        for (trans, lenx, leny) in [(b'N', n, m), (b'T', m, n)] {
+            let x = rand_vec(lenx);
+            let y0 = rand_vec(leny);
+            let mut y1 = y0.clone();
+            let mut y2 = y0.clone();
+            unsafe { dgbmv(trans, m as i32, n as i32, kl as i32, ku as i32, 1.5, &band, lda as i32, &x, 1, 0.5, &mut y1, 1) };
+            unsafe { dgemv(trans, m as i32, n as i32, 1.5, &dense, m as i32, &x, 1, 0.5, &mut y2, 1) };
+            assert_close(&y1, &y2);
+        }
+    }
+    #[cfg(feature = "pure-rust")]
+    #[test]
+    fn test7() {
+        // row-major matrices go through blas_safe, which reinterprets them as transposed column-major ones
+        use crate::blas_safe::{Diagonal, Symmetry, Transpose, Vector};
+        use crate::lin_alg::Mat;
+        let (m, k, n) = (4, 3, 5);
+        let a = Mat::new([m, k], rand_vec((m * k) as usize).into_boxed_slice());
+        let b = Mat::new([k, n], rand_vec((k * n) as usize).into_boxed_slice());
+        let (a_, b_) = (a.as_slice(), b.as_slice());
+        let (m, k, n) = (m as usize, k as usize, n as usize);
+        let c = a.matmul(&b).unwrap();
+        let expected: Vec<f64> = (0..m * n).map(|ij| (0..k).map(|l| a_[ij / n * k + l] * b_[l * n + ij % n]).sum()).collect();
+        assert_close(c.as_slice(), &expected);
+        let ct = c.t_matmul(&a).unwrap();
+        let expected: Vec<f64> = (0..n * k).map(|ij| (0..m).map(|l| c.as_slice()[l * n + ij / k] * a_[l * k + ij % k]).sum()).collect();
+        assert_close(ct.as_slice(), &expected);
+        let x = Mat::from(rand_vec(k));
+        let y = a.matvec(&x).unwrap();
+        let expected: Vec<f64> = (0..m).map(|i| (0..k).map(|l| a_[i * k + l] * x.as_slice()[l]).sum()).collect();
+        assert_close(y.as_slice(), &expected);
+        let z = a.t_matvec(&y).unwrap();
+        let expected: Vec<f64> = (0..k).map(|l| (0..m).map(|i| a_[i * k + l] * y.as_slice()[i]).sum()).collect();
+        assert_close(z.as_slice(), &expected);
+        let mut g = a.clone();
+        g.ger_(2., &y, &x).unwrap();
+        let expected: Vec<f64> = (0..m * k).map(|ij| a_[ij] + 2. * y.as_slice()[ij / k] * x.as_slice()[ij % k]).collect();
+        assert_close(g.as_slice(), &expected);
+        // only the lower triangle of the symmetric matrix is touched and read
+        let mut s = Mat::new([k as u32, k as u32], vec![0.; k * k].into_boxed_slice());
+        s.syr_(Symmetry::Lower, 1., &x).unwrap();
+        assert!((0..k).all(|i| (i + 1..k).all(|j| s.as_slice()[i * k + j] == 0.)));
+        let sx = s.symv(Symmetry::Lower, &x).unwrap();
+        let xx = dotu(k as i32, x.as_slice(), 1, x.as_slice(), 1);
+        assert_close(sx.as_slice(), &x.as_slice().iter().map(|v| v * xx).collect::<Vec<f64>>());
+        let mut t = Mat::new([k as u32, k as u32], rand_vec(k * k).into_boxed_slice());
+        (0..k).for_each(|i| t.as_mut_slice()[i * k + i] += 4.);
+        for (symmetry, trans) in [(Symmetry::Upper, Transpose::NoTrans), (Symmetry::Lower, Transpose::Trans)] {
+            let mut w = x.clone();
+            t.trmv(symmetry, trans, Diagonal::NonUnit, &mut w).unwrap();
+            // an upper triangular row-major matrix, or the transpose of a lower one, multiplies from the diagonal rightwards
+            let expected: Vec<f64> = (0..k).map(|i| (i..k).map(|j| match trans {
+                Transpose::NoTrans => t.as_slice()[i * k + j],
+                _ => t.as_slice()[j * k + i],
+            } * x.as_slice()[j]).sum()).collect();
+            assert_close(w.as_slice(), &expected);
+            t.trsv(symmetry, trans, Diagonal::NonUnit, &mut w).unwrap();
+            assert_close(w.as_slice(), x.as_slice());
+        }
+    }
+
+    #[test]
+    fn test8() {
+        // squares of these would overflow or underflow
+        for s in [1e200, 1e-200, 1.] {
+            assert!((unsafe { dnrm2(2, &[3. * s, 7., 4. * s], 2) } / (5. * s) - 1.).abs() < 1e-12);
+            let z = [Complex64::new(3. * s, -4. * s), Complex64::new(0., 12. * s)];
+            assert!((unsafe { dznrm2(2, &z, 1) } / (13. * s) - 1.).abs() < 1e-12);
+        }
+        assert_eq!(unsafe { snrm2(2, &[0., 0.], 1) }, 0.);
+    }
+}
+
+#[cfg(all(test, feature = "native"))]
+mod compare_backends {
+    use super::*;
+
+    fn rand_vec(n: usize) -> Vec<f64> {
+        (0..n).map(|_| rand::random::<f64>() * 2. - 1.).collect()
+    }
+
+    fn rand_cvec(n: usize) -> Vec<Complex64> {
+        (0..n).map(|_| Complex64::new(rand::random::<f64>() * 2. - 1., rand::random::<f64>() * 2. - 1.)).collect()
+    }
+
+    fn assert_close<S: Scalar<Real=f64>>(a: &[S], b: &[S]) {
+        for (&x, &y) in a.iter().zip(b) {
+            assert!((x - y).abs1() < 1e-9, "{} != {}", x.abs1(), y.abs1());
+        }
+    }
+
+    #[test]
+    fn level1() {
+        let n = 37;
+        let x = rand_vec(3 * n);
+        let y = rand_vec(2 * n);
+        for (incx, incy) in [(1, 1), (3, 2), (-3, 2), (1, -1)] {
+            unsafe {
+                assert!((ddot(n as i32, &x, incx, &y, incy) - blas::ddot(n as i32, &x, incx, &y, incy)).abs() < 1e-9);
+                let (mut a, mut b) = (y.clone(), y.clone());
+                daxpy(n as i32, 0.7, &x, incx, &mut a, incy);
+                blas::daxpy(n as i32, 0.7, &x, incx, &mut b, incy);
+                assert_close(&a, &b);
+                let (mut a, mut b, mut c, mut d) = (x.clone(), y.clone(), x.clone(), y.clone());
+                drot(n as i32, &mut a, incx, &mut b, incy, 0.6, 0.8);
+                blas::drot(n as i32, &mut c, incx, &mut d, incy, 0.6, 0.8);
+                assert_close(&a, &c);
+                assert_close(&b, &d);
+            }
+        }
+        let z = rand_cvec(2 * n);
+        let w = rand_cvec(n);
+        for inc in [1, 2] {
+            unsafe {
+                assert!((dznrm2(n as i32, &z, inc) - blas::dznrm2(n as i32, &z, inc)).abs() < 1e-9);
+                assert!((dzasum(n as i32, &z, inc) - blas::dzasum(n as i32, &z, inc)).abs() < 1e-9);
+                assert_eq!(izamax(n as i32, &z, inc), blas::izamax(n as i32, &z, inc));
+                let (mut a, mut b) = ([Complex64::zero()], [Complex64::zero()]);
+                zdotc(&mut a, n as i32, &z, inc, &w, 1);
+                blas::zdotc(&mut b, n as i32, &z, inc, &w, 1);
+                assert_close(&a, &b);
+                let (mut a, mut b) = (z.clone(), z.clone());
+                zscal(n as i32, w[0], &mut a, inc);
+                blas::zscal(n as i32, w[0], &mut b, inc);
+                assert_close(&a, &b);
+            }
+        }
+    }
+
+    #[test]
+    fn level3() {
+        let (m, n, k) = (13, 9, 11);
+        for ta in [b'N', b'T', b'C'] {
+            for tb in [b'N', b'T', b'C'] {
+                let a = rand_cvec(m * k);
+                let b = rand_cvec(k * n);
+                let c = rand_cvec(m * n);
+                let (lda, ldb) = (if ta == b'N' { m } else { k } as i32, if tb == b'N' { k } else { n } as i32);
+                let alpha = Complex64::new(0.3, 0.9);
+                let beta = Complex64::new(-1.1, 0.2);
+                let (mut c1, mut c2) = (c.clone(), c.clone());
+                unsafe {
+                    zgemm(ta, tb, m as i32, n as i32, k as i32, alpha, &a, lda, &b, ldb, beta, &mut c1, m as i32);
+                    blas::zgemm(ta, tb, m as i32, n as i32, k as i32, alpha, &a, lda, &b, ldb, beta, &mut c2, m as i32);
+                }
+                assert_close(&c1, &c2);
+            }
+        }
+    }
+
+    #[test]
+    fn level2() {
+        let (m, n) = (7, 5);
+        let a = rand_cvec(m * n);
+        for trans in [b'N', b'T', b'C'] {
+            let (lenx, leny) = if trans == b'N' { (n, m) } else { (m, n) };
+            let x = rand_cvec(2 * lenx);
+            let y = rand_cvec(leny);
+            let (mut y1, mut y2) = (y.clone(), y.clone());
+            unsafe {
+                zgemv(trans, m as i32, n as i32, x[0], &a, m as i32, &x, 2, x[1], &mut y1, 1);
+                blas::zgemv(trans, m as i32, n as i32, x[0], &a, m as i32, &x, 2, x[1], &mut y2, 1);
+            }
+            assert_close(&y1, &y2);
+            let (kl, ku) = (2, 1);
+            let band = rand_cvec((kl + ku + 1) * n);
+            let (mut y1, mut y2) = (y.clone(), y.clone());
+            unsafe {
+                zgbmv(trans, m as i32, n as i32, kl as i32, ku as i32, x[0], &band, (kl + ku + 1) as i32, &x, 2, x[1], &mut y1, 1);
+                blas::zgbmv(trans, m as i32, n as i32, kl as i32, ku as i32, x[0], &band, (kl + ku + 1) as i32, &x, 2, x[1], &mut y2, 1);
+            }
+            assert_close(&y1, &y2);
+        }
+        let mut s = rand_cvec(n * n);
+        (0..n).for_each(|i| s[i + i * n] += 4.);
+        let x = rand_cvec(n);
+        for uplo in [b'U', b'L'] {
+            let (mut y1, mut y2) = (x.clone(), x.clone());
+            unsafe {
+                zhemv(uplo, n as i32, x[0], &s, n as i32, &x, 1, x[1], &mut y1, 1);
+                blas::zhemv(uplo, n as i32, x[0], &s, n as i32, &x, 1, x[1], &mut y2, 1);
+            }
+            assert_close(&y1, &y2);
+            let (mut a1, mut a2) = (s.clone(), s.clone());
+            unsafe {
+                zher(uplo, n as i32, 0.5, &x, 1, &mut a1, n as i32);
+                blas::zher(uplo, n as i32, 0.5, &x, 1, &mut a2, n as i32);
+            }
+            assert_close(&a1, &a2);
+            for trans in [b'N', b'T', b'C'] {
+                for diag in [b'N', b'U'] {
+                    let (mut y1, mut y2) = (x.clone(), x.clone());
+                    unsafe {
+                        ztrmv(uplo, trans, diag, n as i32, &s, n as i32, &mut y1, 1);
+                        blas::ztrmv(uplo, trans, diag, n as i32, &s, n as i32, &mut y2, 1);
+                    }
+                    assert_close(&y1, &y2);
+                    unsafe {
+                        ztrsv(uplo, trans, diag, n as i32, &s, n as i32, &mut y1, 1);
+                        blas::ztrsv(uplo, trans, diag, n as i32, &s, n as i32, &mut y2, 1);
+                    }
+                    assert_close(&y1, &y2);
+                }
+            }
+        }
+        let (mut a1, mut a2) = (a.clone(), a.clone());
+        let y = rand_cvec(n);
+        let x = rand_cvec(m);
+        unsafe {
+            zgeru(m as i32, n as i32, y[0], &x, 1, &y, 1, &mut a1, m as i32);
+            blas::zgeru(m as i32, n as i32, y[0], &x, 1, &y, 1, &mut a2, m as i32);
+        }
+        assert_close(&a1, &a2);
+    }
+}
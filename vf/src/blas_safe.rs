@@ -1,4 +1,21 @@
+use std::fmt::{Display, Formatter};
 use num_complex::{Complex32, Complex64};
+#[cfg(not(feature = "pure-rust"))]
+use blas as backend;
+#[cfg(feature = "pure-rust")]
+use crate::blas_rust as backend;
+
+/**Operands of a BLAS routine have incompatible shapes, or the routine can't be applied to given layout*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlasError(pub String);
+
+impl Display for BlasError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for BlasError {}
 
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -91,7 +108,7 @@ impl Copy for f32 {
     fn copy(src: &(impl ?Sized + Vector<Self>), dst: &mut (impl ?Sized + Vector<Self>)) {
         unsafe{
             let i = dst.inc();
-            blas::scopy(dst.len(), src.as_slice(), src.inc(), dst.as_mut_slice(), i);
+            backend::scopy(dst.len(), src.as_slice(), src.inc(), dst.as_mut_slice(), i);
         }
     }
 }
@@ -99,7 +116,7 @@ impl Copy for f64 {
     fn copy(src: &(impl ?Sized + Vector<Self>), dst: &mut (impl ?Sized + Vector<Self>)) {
         unsafe{
             let i = dst.inc();
-            blas::dcopy(dst.len(), src.as_slice(), src.inc(), dst.as_mut_slice(), i)
+            backend::dcopy(dst.len(), src.as_slice(), src.inc(), dst.as_mut_slice(), i)
         }
     }
 }
@@ -107,7 +124,7 @@ impl Copy for Complex32 {
     fn copy(src: &(impl ?Sized + Vector<Self>), dst: &mut (impl ?Sized + Vector<Self>)) {
         unsafe{
             let i = dst.inc();
-            blas::ccopy(dst.len(), src.as_slice(), src.inc(), dst.as_mut_slice(), i)
+            backend::ccopy(dst.len(), src.as_slice(), src.inc(), dst.as_mut_slice(), i)
         }
     }
 }
@@ -115,7 +132,7 @@ impl Copy for Complex64 {
     fn copy(src: &(impl ?Sized + Vector<Self>), dst: &mut (impl ?Sized + Vector<Self>)) {
         unsafe{
             let i = dst.inc();
-            blas::zcopy(dst.len(), src.as_slice(), src.inc(), dst.as_mut_slice(), i)
+            backend::zcopy(dst.len(), src.as_slice(), src.inc(), dst.as_mut_slice(), i)
         }
     }
 }
@@ -129,7 +146,7 @@ impl Axpy for f32 {
         assert_eq!(x.len(),y.len());
         unsafe {
             let i = y.inc();
-            blas::saxpy(x.len(),*alpha,
+            backend::saxpy(x.len(),*alpha,
                               x.as_slice(), x.inc(),
                               y.as_mut_slice(), i);
         }
@@ -140,7 +157,7 @@ impl Axpy for f64 {
         assert_eq!(x.len(),y.len());
         unsafe {
             let i = y.inc();
-            blas::daxpy(x.len(),*alpha,
+            backend::daxpy(x.len(),*alpha,
                         x.as_slice(), x.inc(),
                         y.as_mut_slice(), i);
         }
@@ -151,7 +168,7 @@ impl Axpy for Complex32 {
         assert_eq!(x.len(),y.len());
         unsafe {
             let i = y.inc();
-            blas::caxpy(x.len(),*alpha,
+            backend::caxpy(x.len(),*alpha,
                         x.as_slice(), x.inc(),
                         y.as_mut_slice(), i);
         }
@@ -162,7 +179,7 @@ impl Axpy for Complex64 {
         assert_eq!(x.len(),y.len());
         unsafe {
             let i = y.inc();
-            blas::zaxpy(x.len(),*alpha,
+            backend::zaxpy(x.len(),*alpha,
                         x.as_slice(), x.inc(),
                         y.as_mut_slice(), i);
         }
@@ -176,7 +193,7 @@ impl Scal for f32{
     fn scal(alpha: &Self, x: &mut (impl ?Sized + Vector<Self>)) {
         unsafe {
             let i = x.inc();
-            blas::sscal(x.len(), *alpha, x.as_mut_slice(),i);
+            backend::sscal(x.len(), *alpha, x.as_mut_slice(),i);
         }
     }
 }
@@ -184,7 +201,7 @@ impl Scal for f64{
     fn scal(alpha: &Self, x: &mut (impl ?Sized + Vector<Self>)) {
         unsafe {
             let i = x.inc();
-            blas::dscal(x.len(), *alpha, x.as_mut_slice(),i);
+            backend::dscal(x.len(), *alpha, x.as_mut_slice(),i);
         }
     }
 }
@@ -192,7 +209,7 @@ impl Scal for Complex32{
     fn scal(alpha: &Self, x: &mut (impl ?Sized + Vector<Self>)) {
         unsafe {
             let i = x.inc();
-            blas::cscal(x.len(), *alpha, x.as_mut_slice(),i);
+            backend::cscal(x.len(), *alpha, x.as_mut_slice(),i);
         }
     }
 }
@@ -200,7 +217,7 @@ impl Scal for Complex64{
     fn scal(alpha: &Self, x: &mut (impl ?Sized + Vector<Self>)) {
         unsafe {
             let i = x.inc();
-            blas::zscal(x.len(), *alpha, x.as_mut_slice(),i);
+            backend::zscal(x.len(), *alpha, x.as_mut_slice(),i);
         }
     }
 }
@@ -216,7 +233,7 @@ impl Swap for f32{
         unsafe {
             let i = x.inc();
             let j = y.inc();
-            blas::sswap(x.len(), x.as_mut_slice(), i, y.as_mut_slice(), j);
+            backend::sswap(x.len(), x.as_mut_slice(), i, y.as_mut_slice(), j);
         }
     }
 }
@@ -226,7 +243,7 @@ impl Swap for f64{
         unsafe {
             let i = x.inc();
             let j = y.inc();
-            blas::dswap(x.len(), x.as_mut_slice(), i, y.as_mut_slice(), j);
+            backend::dswap(x.len(), x.as_mut_slice(), i, y.as_mut_slice(), j);
         }
     }
 }
@@ -236,7 +253,7 @@ impl Swap for Complex32{
         unsafe {
             let i = x.inc();
             let j = y.inc();
-            blas::cswap(x.len(), x.as_mut_slice(), i, y.as_mut_slice(), j);
+            backend::cswap(x.len(), x.as_mut_slice(), i, y.as_mut_slice(), j);
         }
     }
 }
@@ -246,7 +263,7 @@ impl Swap for Complex64{
         unsafe {
             let i = x.inc();
             let j = y.inc();
-            blas::zswap(x.len(), x.as_mut_slice(), i, y.as_mut_slice(), j);
+            backend::zswap(x.len(), x.as_mut_slice(), i, y.as_mut_slice(), j);
         }
     }
 }
@@ -259,7 +276,7 @@ impl Dot for f32{
     fn dot(x: &(impl ?Sized + Vector<Self>), y: &(impl ?Sized + Vector<Self>)) -> Self {
         assert_eq!(x.len(),y.len());
         unsafe {
-            blas::sdot(x.len(),x.as_slice(), x.inc(),y.as_slice(), y.inc())
+            backend::sdot(x.len(),x.as_slice(), x.inc(),y.as_slice(), y.inc())
         }
     }
 }
@@ -267,7 +284,7 @@ impl Dot for f64{
     fn dot(x: &(impl ?Sized + Vector<Self>), y: &(impl ?Sized + Vector<Self>)) -> Self {
         assert_eq!(x.len(),y.len());
         unsafe {
-            blas::ddot(x.len(),x.as_slice(), x.inc(),y.as_slice(), y.inc())
+            backend::ddot(x.len(),x.as_slice(), x.inc(),y.as_slice(), y.inc())
         }
     }
 }
@@ -276,7 +293,7 @@ impl Dot for Complex32{
         assert_eq!(x.len(),y.len());
         let mut pres = Self::default();
         unsafe {
-            blas::cdotu(std::slice::from_mut(&mut pres), x.len(),x.as_slice(), x.inc(),y.as_slice(), y.inc())
+            backend::cdotu(std::slice::from_mut(&mut pres), x.len(),x.as_slice(), x.inc(),y.as_slice(), y.inc())
         }
         pres
     }
@@ -286,7 +303,7 @@ impl Dot for Complex64{
         assert_eq!(x.len(),y.len());
         let mut pres = Self::default();
         unsafe {
-            blas::zdotu(std::slice::from_mut(&mut pres), x.len(),x.as_slice(), x.inc(),y.as_slice(), y.inc())
+            backend::zdotu(std::slice::from_mut(&mut pres), x.len(),x.as_slice(), x.inc(),y.as_slice(), y.inc())
         }
         pres
     }
@@ -300,7 +317,7 @@ impl Dotc for Complex32{
         assert_eq!(x.len(),y.len());
         let mut pres = Self::default();
         unsafe {
-            blas::cdotc(std::slice::from_mut(&mut pres), x.len(),x.as_slice(), x.inc(),y.as_slice(), y.inc())
+            backend::cdotc(std::slice::from_mut(&mut pres), x.len(),x.as_slice(), x.inc(),y.as_slice(), y.inc())
         }
         pres
     }
@@ -310,7 +327,7 @@ impl Dotc for Complex64{
         assert_eq!(x.len(),y.len());
         let mut pres = Self::default();
         unsafe {
-            blas::zdotc(std::slice::from_mut(&mut pres), x.len(),x.as_slice(), x.inc(),y.as_slice(), y.inc())
+            backend::zdotc(std::slice::from_mut(&mut pres), x.len(),x.as_slice(), x.inc(),y.as_slice(), y.inc())
         }
         pres
     }
@@ -327,7 +344,7 @@ impl Asum for f32{
     type Output = Self;
     fn asum(x: &(impl ?Sized + Vector<Self>)) -> Self {
         unsafe{
-            blas::sasum(x.len(),x.as_slice(),x.inc())
+            backend::sasum(x.len(),x.as_slice(),x.inc())
         }
     }
 }
@@ -335,7 +352,7 @@ impl Asum for f64{
     type Output = Self;
     fn asum(x: &(impl ?Sized + Vector<Self>)) -> Self {
         unsafe{
-            blas::dasum(x.len(),x.as_slice(),x.inc())
+            backend::dasum(x.len(),x.as_slice(),x.inc())
         }
     }
 }
@@ -343,7 +360,7 @@ impl Asum for Complex32{
     type Output = f32;
     fn asum(x: &(impl ?Sized + Vector<Self>)) -> Self::Output {
         unsafe{
-            blas::scasum(x.len(),x.as_slice(),x.inc())
+            backend::scasum(x.len(),x.as_slice(),x.inc())
         }
     }
 }
@@ -351,7 +368,7 @@ impl Asum for Complex64{
     type Output = f64;
     fn asum(x: &(impl ?Sized + Vector<Self>)) -> Self::Output {
         unsafe{
-            blas::dzasum(x.len(),x.as_slice(),x.inc())
+            backend::dzasum(x.len(),x.as_slice(),x.inc())
         }
     }
 }
@@ -366,7 +383,7 @@ impl Nrm2 for f32{
 
     fn nrm2(x: &(impl ?Sized + Vector<Self>)) -> Self {
         unsafe{
-            blas::snrm2(x.len(),x.as_slice(),x.inc())
+            backend::snrm2(x.len(),x.as_slice(),x.inc())
         }
     }
 }
@@ -375,7 +392,7 @@ impl Nrm2 for f64{
 
     fn nrm2(x: &(impl ?Sized + Vector<Self>)) -> Self {
         unsafe{
-            blas::dnrm2(x.len(),x.as_slice(),x.inc())
+            backend::dnrm2(x.len(),x.as_slice(),x.inc())
         }
     }
 }
@@ -384,7 +401,7 @@ impl Nrm2 for Complex32{
 
     fn nrm2(x: &(impl ?Sized + Vector<Self>)) -> Self::Output {
         unsafe{
-            blas::scnrm2(x.len(),x.as_slice(),x.inc())
+            backend::scnrm2(x.len(),x.as_slice(),x.inc())
         }
     }
 }
@@ -393,7 +410,7 @@ impl Nrm2 for Complex64{
 
     fn nrm2(x: &(impl ?Sized + Vector<Self>)) -> Self::Output {
         unsafe{
-            blas::dznrm2(x.len(),x.as_slice(),x.inc())
+            backend::dznrm2(x.len(),x.as_slice(),x.inc())
         }
     }
 }
@@ -409,28 +426,28 @@ pub trait Iamax: Sized {
 impl Iamax for f32{
     fn iamax(x: &(impl ?Sized + Vector<Self>)) -> usize {
         unsafe{
-            blas::isamax(x.len(),x.as_slice(),x.inc())
+            backend::isamax(x.len(),x.as_slice(),x.inc())
         }
     }
 }
 impl Iamax for f64{
     fn iamax(x: &(impl ?Sized + Vector<Self>)) -> usize {
         unsafe{
-            blas::idamax(x.len(),x.as_slice(),x.inc())
+            backend::idamax(x.len(),x.as_slice(),x.inc())
         }
     }
 }
 impl Iamax for Complex32{
     fn iamax(x: &(impl ?Sized + Vector<Self>)) -> usize {
         unsafe{
-            blas::icamax(x.len(),x.as_slice(),x.inc())
+            backend::icamax(x.len(),x.as_slice(),x.inc())
         }
     }
 }
 impl Iamax for Complex64{
     fn iamax(x: &(impl ?Sized + Vector<Self>)) -> usize {
         unsafe{
-            blas::izamax(x.len(),x.as_slice(),x.inc())
+            backend::izamax(x.len(),x.as_slice(),x.inc())
         }
     }
 }
@@ -450,7 +467,7 @@ impl Rot for f32{
         let incx = x.inc();
         let incy = y.inc();
         unsafe{
-            blas::srot(x.len(),x.as_mut_slice(),incx,y.as_mut_slice(),incy,*cos,*sin)
+            backend::srot(x.len(),x.as_mut_slice(),incx,y.as_mut_slice(),incy,*cos,*sin)
         }
     }
 }
@@ -462,7 +479,7 @@ impl Rot for f64{
         let incx = x.inc();
         let incy = y.inc();
         unsafe{
-            blas::drot(x.len(),x.as_mut_slice(),incx,y.as_mut_slice(),incy,*cos,*sin)
+            backend::drot(x.len(),x.as_mut_slice(),incx,y.as_mut_slice(),incy,*cos,*sin)
         }
     }
 }
@@ -474,7 +491,7 @@ impl Rot for Complex32{
         let incx = x.inc();
         let incy = y.inc();
         unsafe{
-            blas::csrot(x.len(),x.as_mut_slice(),incx,y.as_mut_slice(),incy,*cos,*sin)
+            backend::csrot(x.len(),x.as_mut_slice(),incx,y.as_mut_slice(),incy,*cos,*sin)
         }
     }
 }
@@ -486,12 +503,13 @@ impl Rot for Complex64{
         let incx = x.inc();
         let incy = y.inc();
         unsafe{
-            blas::zdrot(x.len(),x.as_mut_slice(),incx,y.as_mut_slice(),incy,*cos,*sin)
+            backend::zdrot(x.len(),x.as_mut_slice(),incx,y.as_mut_slice(),incy,*cos,*sin)
         }
     }
 }
 
 
+/// Computes `alpha * op(a) * op(b) + beta * c` and stores the result in `c`. All matrices must have the same order.
 pub trait Gemm: Sized {
     fn gemm(
         alpha: &Self,
@@ -504,6 +522,652 @@ pub trait Gemm: Sized {
     );
 }
 
+fn trans_char(t: Transpose) -> u8 {
+    match t {
+        Transpose::NoTrans => b'N',
+        Transpose::Trans => b'T',
+        Transpose::ConjTrans => b'C',
+    }
+}
+
+fn op_shape<T>(t: Transpose, a: &dyn Matrix<T>) -> (u32, u32) {
+    match t {
+        Transpose::NoTrans => (a.rows(), a.cols()),
+        _ => (a.cols(), a.rows()),
+    }
+}
+
+/**Returns `(m, n, k)` such that `op(a)` is `m x k`, `op(b)` is `k x n` and `c` is `m x n`*/
+fn gemm_dims<T>(at: Transpose, a: &dyn Matrix<T>, bt: Transpose, b: &dyn Matrix<T>, c: &dyn Matrix<T>) -> (i32, i32, i32) {
+    let (m, k) = op_shape(at, a);
+    let (k2, n) = op_shape(bt, b);
+    assert_eq!(k, k2, "Inner dimensions of matrix product don't match");
+    assert_eq!((c.rows(), c.cols()), (m, n), "Output matrix has wrong shape");
+    assert!(a.order() as i32 == c.order() as i32 && b.order() as i32 == c.order() as i32, "All matrices must have the same order");
+    (m as i32, n as i32, k as i32)
+}
+
+impl Gemm for f32 {
+    fn gemm(alpha: &Self, at: Transpose, a: &dyn Matrix<Self>, bt: Transpose, b: &dyn Matrix<Self>, beta: &Self, c: &mut dyn Matrix<Self>) {
+        let (m, n, k) = gemm_dims(at, a, bt, b, c);
+        unsafe {
+            match c.order() {
+                Order::ColMajor => {
+                    let ldc = c.lead_dim() as i32;
+                    backend::sgemm(trans_char(at), trans_char(bt), m, n, k, *alpha, a.as_slice(), a.lead_dim() as i32, b.as_slice(), b.lead_dim() as i32, *beta, c.as_mut_slice(), ldc)
+                }
+                Order::RowMajor => {
+                    let ldc = c.lead_dim() as i32;
+                    backend::sgemm(trans_char(bt), trans_char(at), n, m, k, *alpha, b.as_slice(), b.lead_dim() as i32, a.as_slice(), a.lead_dim() as i32, *beta, c.as_mut_slice(), ldc)
+                }
+            }
+        }
+    }
+}
+
+impl Gemm for f64 {
+    fn gemm(alpha: &Self, at: Transpose, a: &dyn Matrix<Self>, bt: Transpose, b: &dyn Matrix<Self>, beta: &Self, c: &mut dyn Matrix<Self>) {
+        let (m, n, k) = gemm_dims(at, a, bt, b, c);
+        unsafe {
+            match c.order() {
+                Order::ColMajor => {
+                    let ldc = c.lead_dim() as i32;
+                    backend::dgemm(trans_char(at), trans_char(bt), m, n, k, *alpha, a.as_slice(), a.lead_dim() as i32, b.as_slice(), b.lead_dim() as i32, *beta, c.as_mut_slice(), ldc)
+                }
+                Order::RowMajor => {
+                    let ldc = c.lead_dim() as i32;
+                    backend::dgemm(trans_char(bt), trans_char(at), n, m, k, *alpha, b.as_slice(), b.lead_dim() as i32, a.as_slice(), a.lead_dim() as i32, *beta, c.as_mut_slice(), ldc)
+                }
+            }
+        }
+    }
+}
+
+impl Gemm for Complex32 {
+    fn gemm(alpha: &Self, at: Transpose, a: &dyn Matrix<Self>, bt: Transpose, b: &dyn Matrix<Self>, beta: &Self, c: &mut dyn Matrix<Self>) {
+        let (m, n, k) = gemm_dims(at, a, bt, b, c);
+        unsafe {
+            match c.order() {
+                Order::ColMajor => {
+                    let ldc = c.lead_dim() as i32;
+                    backend::cgemm(trans_char(at), trans_char(bt), m, n, k, *alpha, a.as_slice(), a.lead_dim() as i32, b.as_slice(), b.lead_dim() as i32, *beta, c.as_mut_slice(), ldc)
+                }
+                Order::RowMajor => {
+                    let ldc = c.lead_dim() as i32;
+                    backend::cgemm(trans_char(bt), trans_char(at), n, m, k, *alpha, b.as_slice(), b.lead_dim() as i32, a.as_slice(), a.lead_dim() as i32, *beta, c.as_mut_slice(), ldc)
+                }
+            }
+        }
+    }
+}
+
+impl Gemm for Complex64 {
+    fn gemm(alpha: &Self, at: Transpose, a: &dyn Matrix<Self>, bt: Transpose, b: &dyn Matrix<Self>, beta: &Self, c: &mut dyn Matrix<Self>) {
+        let (m, n, k) = gemm_dims(at, a, bt, b, c);
+        unsafe {
+            match c.order() {
+                Order::ColMajor => {
+                    let ldc = c.lead_dim() as i32;
+                    backend::zgemm(trans_char(at), trans_char(bt), m, n, k, *alpha, a.as_slice(), a.lead_dim() as i32, b.as_slice(), b.lead_dim() as i32, *beta, c.as_mut_slice(), ldc)
+                }
+                Order::RowMajor => {
+                    let ldc = c.lead_dim() as i32;
+                    backend::zgemm(trans_char(bt), trans_char(at), n, m, k, *alpha, b.as_slice(), b.lead_dim() as i32, a.as_slice(), a.lead_dim() as i32, *beta, c.as_mut_slice(), ldc)
+                }
+            }
+        }
+    }
+}
+
+/// Computes `alpha * op(a) * x + beta * y` and stores the result in `y`. Fails for `ConjTrans` of row-major matrix.
+pub trait Gemv: Sized {
+    fn gemv(trans: Transpose, alpha: &Self, a: &dyn Matrix<Self>, x: &dyn Vector<Self>, beta: &Self, y: &mut dyn Vector<Self>) -> Result<(), BlasError>;
+}
+
+/**Returns `(m, n, trans)` as expected by column-major BLAS. Row-major matrix is seen by BLAS as its own transpose,
+ hence the transposition flag needs to be flipped. Conjugation without transposition is not supported by BLAS.*/
+fn gemv_dims<T>(trans: Transpose, a: &dyn Matrix<T>, x: &dyn Vector<T>, y: &dyn Vector<T>) -> Result<(i32, i32, u8), BlasError> {
+    let (rows, cols) = op_shape(trans, a);
+    assert_eq!(x.len(), cols as i32, "Vector length doesn't match number of matrix columns");
+    assert_eq!(y.len(), rows as i32, "Output vector length doesn't match number of matrix rows");
+    match a.order() {
+        Order::ColMajor => Ok((a.rows() as i32, a.cols() as i32, trans_char(trans))),
+        Order::RowMajor => {
+            let t = match trans {
+                Transpose::NoTrans => b'T',
+                Transpose::Trans => b'N',
+                Transpose::ConjTrans => return Err(conj_row_major()),
+            };
+            Ok((a.cols() as i32, a.rows() as i32, t))
+        }
+    }
+}
+
+fn conj_row_major() -> BlasError {
+    BlasError("BLAS can't conjugate a row-major matrix without transposing it, hence ConjTrans is only supported for column-major matrices".to_string())
+}
+
+impl Gemv for f32 {
+    fn gemv(trans: Transpose, alpha: &Self, a: &dyn Matrix<Self>, x: &dyn Vector<Self>, beta: &Self, y: &mut dyn Vector<Self>) -> Result<(), BlasError> {
+        let (m, n, t) = gemv_dims(trans, a, x, y)?;
+        unsafe {
+            let incy = y.inc();
+            backend::sgemv(t, m, n, *alpha, a.as_slice(), a.lead_dim() as i32, x.as_slice(), x.inc(), *beta, y.as_mut_slice(), incy)
+        }
+        Ok(())
+    }
+}
+
+impl Gemv for f64 {
+    fn gemv(trans: Transpose, alpha: &Self, a: &dyn Matrix<Self>, x: &dyn Vector<Self>, beta: &Self, y: &mut dyn Vector<Self>) -> Result<(), BlasError> {
+        let (m, n, t) = gemv_dims(trans, a, x, y)?;
+        unsafe {
+            let incy = y.inc();
+            backend::dgemv(t, m, n, *alpha, a.as_slice(), a.lead_dim() as i32, x.as_slice(), x.inc(), *beta, y.as_mut_slice(), incy)
+        }
+        Ok(())
+    }
+}
+
+impl Gemv for Complex32 {
+    fn gemv(trans: Transpose, alpha: &Self, a: &dyn Matrix<Self>, x: &dyn Vector<Self>, beta: &Self, y: &mut dyn Vector<Self>) -> Result<(), BlasError> {
+        let (m, n, t) = gemv_dims(trans, a, x, y)?;
+        unsafe {
+            let incy = y.inc();
+            backend::cgemv(t, m, n, *alpha, a.as_slice(), a.lead_dim() as i32, x.as_slice(), x.inc(), *beta, y.as_mut_slice(), incy)
+        }
+        Ok(())
+    }
+}
+
+impl Gemv for Complex64 {
+    fn gemv(trans: Transpose, alpha: &Self, a: &dyn Matrix<Self>, x: &dyn Vector<Self>, beta: &Self, y: &mut dyn Vector<Self>) -> Result<(), BlasError> {
+        let (m, n, t) = gemv_dims(trans, a, x, y)?;
+        unsafe {
+            let incy = y.inc();
+            backend::zgemv(t, m, n, *alpha, a.as_slice(), a.lead_dim() as i32, x.as_slice(), x.inc(), *beta, y.as_mut_slice(), incy)
+        }
+        Ok(())
+    }
+}
+
+fn uplo_char(s: Symmetry, order: Order) -> u8 {
+    // upper triangle of row-major matrix is the lower triangle of its column-major transpose
+    match (s, order) {
+        (Symmetry::Upper, Order::ColMajor) | (Symmetry::Lower, Order::RowMajor) => b'U',
+        (Symmetry::Lower, Order::ColMajor) | (Symmetry::Upper, Order::RowMajor) => b'L',
+    }
+}
+
+fn diag_char(d: Diagonal) -> u8 {
+    match d {
+        Diagonal::NonUnit => b'N',
+        Diagonal::Unit => b'U',
+    }
+}
+
+fn trans_char_for(t: Transpose, order: Order) -> Result<u8, BlasError> {
+    match order {
+        Order::ColMajor => Ok(trans_char(t)),
+        Order::RowMajor => match t {
+            Transpose::NoTrans => Ok(b'T'),
+            Transpose::Trans => Ok(b'N'),
+            Transpose::ConjTrans => Err(conj_row_major()),
+        }
+    }
+}
+
+/**Number of elements between consecutive entries of a vector. As in BLAS, negative increment means that the
+ vector is traversed backwards, but it still occupies the same elements of the slice.*/
+fn stride(inc: i32) -> Result<usize, BlasError> {
+    if inc == 0 {
+        Err(BlasError("Vector increment can't be zero".to_string()))
+    } else {
+        Ok(inc.unsigned_abs() as usize)
+    }
+}
+
+/**Conjugated copy of strided vector, stored contiguously in the same order as in the slice. It should be
+ passed to BLAS with increment `inc.signum()`.*/
+fn conj_copy<T: num_traits::Float>(x: &[num_complex::Complex<T>], n: i32, inc: i32) -> Result<Vec<num_complex::Complex<T>>, BlasError> {
+    Ok(x.iter().step_by(stride(inc)?).take(n as usize).map(|e| e.conj()).collect())
+}
+
+fn conj_in_place<T: num_traits::Float>(x: &mut [num_complex::Complex<T>], n: i32, inc: i32) -> Result<(), BlasError> {
+    x.iter_mut().step_by(stride(inc)?).take(n as usize).for_each(|e| *e = e.conj());
+    Ok(())
+}
+
+fn square_dim<T>(a: &dyn Matrix<T>) -> i32 {
+    assert_eq!(a.rows(), a.cols(), "Matrix must be square");
+    a.rows() as i32
+}
+
+/// Computes `alpha * op(a) * x + beta * y` and stores the result in `y`, where `a` is a band matrix
+/// with `sub_diagonals` below and `sup_diagonals` above the main diagonal. Its `lead_dim` must be at least
+/// `sub_diagonals + sup_diagonals + 1`. In row-major order each row of the storage holds one row of the band.
+/// Fails for `ConjTrans` of row-major matrix.
+pub trait Gbmv: Sized {
+    fn gbmv(trans: Transpose, alpha: &Self, a: &dyn BandMatrix<Self>, x: &dyn Vector<Self>, beta: &Self, y: &mut dyn Vector<Self>) -> Result<(), BlasError>;
+}
+
+impl Gbmv for f32 {
+    fn gbmv(trans: Transpose, alpha: &Self, a: &dyn BandMatrix<Self>, x: &dyn Vector<Self>, beta: &Self, y: &mut dyn Vector<Self>) -> Result<(), BlasError> {
+        let (m, n, t) = gemv_dims(trans, a.as_matrix(), x, y)?;
+        let (kl, ku) = match a.order() {
+            Order::ColMajor => (a.sub_diagonals(), a.sup_diagonals()),
+            Order::RowMajor => (a.sup_diagonals(), a.sub_diagonals()),
+        };
+        assert!(a.lead_dim() > kl + ku, "Leading dimension of band matrix is too small");
+        unsafe {
+            let incy = y.inc();
+            backend::sgbmv(t, m, n, kl as i32, ku as i32, *alpha, a.as_slice(), a.lead_dim() as i32, x.as_slice(), x.inc(), *beta, y.as_mut_slice(), incy)
+        }
+        Ok(())
+    }
+}
+
+impl Gbmv for f64 {
+    fn gbmv(trans: Transpose, alpha: &Self, a: &dyn BandMatrix<Self>, x: &dyn Vector<Self>, beta: &Self, y: &mut dyn Vector<Self>) -> Result<(), BlasError> {
+        let (m, n, t) = gemv_dims(trans, a.as_matrix(), x, y)?;
+        let (kl, ku) = match a.order() {
+            Order::ColMajor => (a.sub_diagonals(), a.sup_diagonals()),
+            Order::RowMajor => (a.sup_diagonals(), a.sub_diagonals()),
+        };
+        assert!(a.lead_dim() > kl + ku, "Leading dimension of band matrix is too small");
+        unsafe {
+            let incy = y.inc();
+            backend::dgbmv(t, m, n, kl as i32, ku as i32, *alpha, a.as_slice(), a.lead_dim() as i32, x.as_slice(), x.inc(), *beta, y.as_mut_slice(), incy)
+        }
+        Ok(())
+    }
+}
+
+impl Gbmv for Complex32 {
+    fn gbmv(trans: Transpose, alpha: &Self, a: &dyn BandMatrix<Self>, x: &dyn Vector<Self>, beta: &Self, y: &mut dyn Vector<Self>) -> Result<(), BlasError> {
+        let (m, n, t) = gemv_dims(trans, a.as_matrix(), x, y)?;
+        let (kl, ku) = match a.order() {
+            Order::ColMajor => (a.sub_diagonals(), a.sup_diagonals()),
+            Order::RowMajor => (a.sup_diagonals(), a.sub_diagonals()),
+        };
+        assert!(a.lead_dim() > kl + ku, "Leading dimension of band matrix is too small");
+        unsafe {
+            let incy = y.inc();
+            backend::cgbmv(t, m, n, kl as i32, ku as i32, *alpha, a.as_slice(), a.lead_dim() as i32, x.as_slice(), x.inc(), *beta, y.as_mut_slice(), incy)
+        }
+        Ok(())
+    }
+}
+
+impl Gbmv for Complex64 {
+    fn gbmv(trans: Transpose, alpha: &Self, a: &dyn BandMatrix<Self>, x: &dyn Vector<Self>, beta: &Self, y: &mut dyn Vector<Self>) -> Result<(), BlasError> {
+        let (m, n, t) = gemv_dims(trans, a.as_matrix(), x, y)?;
+        let (kl, ku) = match a.order() {
+            Order::ColMajor => (a.sub_diagonals(), a.sup_diagonals()),
+            Order::RowMajor => (a.sup_diagonals(), a.sub_diagonals()),
+        };
+        assert!(a.lead_dim() > kl + ku, "Leading dimension of band matrix is too small");
+        unsafe {
+            let incy = y.inc();
+            backend::zgbmv(t, m, n, kl as i32, ku as i32, *alpha, a.as_slice(), a.lead_dim() as i32, x.as_slice(), x.inc(), *beta, y.as_mut_slice(), incy)
+        }
+        Ok(())
+    }
+}
+
+/// Computes `alpha * a * x + beta * y` and stores the result in `y`, where `a` is symmetric and only
+/// its `symmetry` triangle is referenced.
+pub trait Symv: Sized {
+    fn symv(symmetry: Symmetry, alpha: &Self, a: &dyn Matrix<Self>, x: &dyn Vector<Self>, beta: &Self, y: &mut dyn Vector<Self>);
+}
+
+impl Symv for f32 {
+    fn symv(symmetry: Symmetry, alpha: &Self, a: &dyn Matrix<Self>, x: &dyn Vector<Self>, beta: &Self, y: &mut dyn Vector<Self>) {
+        let n = square_dim(a);
+        assert_eq!(x.len(), n, "Vector length doesn't match size of matrix");
+        assert_eq!(y.len(), n, "Output vector length doesn't match size of matrix");
+        unsafe {
+            let incy = y.inc();
+            backend::ssymv(uplo_char(symmetry, a.order()), n, *alpha, a.as_slice(), a.lead_dim() as i32, x.as_slice(), x.inc(), *beta, y.as_mut_slice(), incy)
+        }
+    }
+}
+
+impl Symv for f64 {
+    fn symv(symmetry: Symmetry, alpha: &Self, a: &dyn Matrix<Self>, x: &dyn Vector<Self>, beta: &Self, y: &mut dyn Vector<Self>) {
+        let n = square_dim(a);
+        assert_eq!(x.len(), n, "Vector length doesn't match size of matrix");
+        assert_eq!(y.len(), n, "Output vector length doesn't match size of matrix");
+        unsafe {
+            let incy = y.inc();
+            backend::dsymv(uplo_char(symmetry, a.order()), n, *alpha, a.as_slice(), a.lead_dim() as i32, x.as_slice(), x.inc(), *beta, y.as_mut_slice(), incy)
+        }
+    }
+}
+
+/// Computes `alpha * a * x + beta * y` and stores the result in `y`, where `a` is hermitian and only
+/// its `symmetry` triangle is referenced. This is the complex counterpart of `Symv`. Row-major matrices need
+/// a conjugated copy of the vectors, which fails if an increment is zero.
+pub trait Hemv: Sized {
+    fn hemv(symmetry: Symmetry, alpha: &Self, a: &dyn Matrix<Self>, x: &dyn Vector<Self>, beta: &Self, y: &mut dyn Vector<Self>) -> Result<(), BlasError>;
+}
+
+impl Hemv for Complex32 {
+    fn hemv(symmetry: Symmetry, alpha: &Self, a: &dyn Matrix<Self>, x: &dyn Vector<Self>, beta: &Self, y: &mut dyn Vector<Self>) -> Result<(), BlasError> {
+        let n = square_dim(a);
+        assert_eq!(x.len(), n, "Vector length doesn't match size of matrix");
+        assert_eq!(y.len(), n, "Output vector length doesn't match size of matrix");
+        let uplo = uplo_char(symmetry, a.order());
+        let incy = y.inc();
+        match a.order() {
+            Order::ColMajor => unsafe {
+                backend::chemv(uplo, n, *alpha, a.as_slice(), a.lead_dim() as i32, x.as_slice(), x.inc(), *beta, y.as_mut_slice(), incy)
+            },
+            Order::RowMajor => {
+                // column-major view of hermitian row-major matrix is its conjugate, so we compute
+                // conj(conj(alpha) * conj(a) * conj(x) + conj(beta) * conj(y))
+                let x_conj = conj_copy(x.as_slice(), n, x.inc())?;
+                conj_in_place(y.as_mut_slice(), n, incy)?;
+                unsafe {
+                    backend::chemv(uplo, n, alpha.conj(), a.as_slice(), a.lead_dim() as i32, &x_conj, x.inc().signum(), beta.conj(), y.as_mut_slice(), incy)
+                }
+                conj_in_place(y.as_mut_slice(), n, incy)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Hemv for Complex64 {
+    fn hemv(symmetry: Symmetry, alpha: &Self, a: &dyn Matrix<Self>, x: &dyn Vector<Self>, beta: &Self, y: &mut dyn Vector<Self>) -> Result<(), BlasError> {
+        let n = square_dim(a);
+        assert_eq!(x.len(), n, "Vector length doesn't match size of matrix");
+        assert_eq!(y.len(), n, "Output vector length doesn't match size of matrix");
+        let uplo = uplo_char(symmetry, a.order());
+        let incy = y.inc();
+        match a.order() {
+            Order::ColMajor => unsafe {
+                backend::zhemv(uplo, n, *alpha, a.as_slice(), a.lead_dim() as i32, x.as_slice(), x.inc(), *beta, y.as_mut_slice(), incy)
+            },
+            Order::RowMajor => {
+                // column-major view of hermitian row-major matrix is its conjugate, so we compute
+                // conj(conj(alpha) * conj(a) * conj(x) + conj(beta) * conj(y))
+                let x_conj = conj_copy(x.as_slice(), n, x.inc())?;
+                conj_in_place(y.as_mut_slice(), n, incy)?;
+                unsafe {
+                    backend::zhemv(uplo, n, alpha.conj(), a.as_slice(), a.lead_dim() as i32, &x_conj, x.inc().signum(), beta.conj(), y.as_mut_slice(), incy)
+                }
+                conj_in_place(y.as_mut_slice(), n, incy)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Computes `op(a) * x` and stores the result in `x`, where `a` is triangular and only its `symmetry` triangle
+/// is referenced. If `diag` is `Unit`, the diagonal of `a` is assumed to be all ones. Fails for `ConjTrans` of
+/// row-major matrix.
+pub trait Trmv: Sized {
+    fn trmv(symmetry: Symmetry, trans: Transpose, diag: Diagonal, a: &dyn Matrix<Self>, x: &mut dyn Vector<Self>) -> Result<(), BlasError>;
+}
+
+impl Trmv for f32 {
+    fn trmv(symmetry: Symmetry, trans: Transpose, diag: Diagonal, a: &dyn Matrix<Self>, x: &mut dyn Vector<Self>) -> Result<(), BlasError> {
+        let n = square_dim(a);
+        let t = trans_char_for(trans, a.order())?;
+        assert_eq!(x.len(), n, "Vector length doesn't match size of matrix");
+        unsafe {
+            let incx = x.inc();
+            backend::strmv(uplo_char(symmetry, a.order()), t, diag_char(diag), n, a.as_slice(), a.lead_dim() as i32, x.as_mut_slice(), incx)
+        }
+        Ok(())
+    }
+}
+
+impl Trmv for f64 {
+    fn trmv(symmetry: Symmetry, trans: Transpose, diag: Diagonal, a: &dyn Matrix<Self>, x: &mut dyn Vector<Self>) -> Result<(), BlasError> {
+        let n = square_dim(a);
+        let t = trans_char_for(trans, a.order())?;
+        assert_eq!(x.len(), n, "Vector length doesn't match size of matrix");
+        unsafe {
+            let incx = x.inc();
+            backend::dtrmv(uplo_char(symmetry, a.order()), t, diag_char(diag), n, a.as_slice(), a.lead_dim() as i32, x.as_mut_slice(), incx)
+        }
+        Ok(())
+    }
+}
+
+impl Trmv for Complex32 {
+    fn trmv(symmetry: Symmetry, trans: Transpose, diag: Diagonal, a: &dyn Matrix<Self>, x: &mut dyn Vector<Self>) -> Result<(), BlasError> {
+        let n = square_dim(a);
+        let t = trans_char_for(trans, a.order())?;
+        assert_eq!(x.len(), n, "Vector length doesn't match size of matrix");
+        unsafe {
+            let incx = x.inc();
+            backend::ctrmv(uplo_char(symmetry, a.order()), t, diag_char(diag), n, a.as_slice(), a.lead_dim() as i32, x.as_mut_slice(), incx)
+        }
+        Ok(())
+    }
+}
+
+impl Trmv for Complex64 {
+    fn trmv(symmetry: Symmetry, trans: Transpose, diag: Diagonal, a: &dyn Matrix<Self>, x: &mut dyn Vector<Self>) -> Result<(), BlasError> {
+        let n = square_dim(a);
+        let t = trans_char_for(trans, a.order())?;
+        assert_eq!(x.len(), n, "Vector length doesn't match size of matrix");
+        unsafe {
+            let incx = x.inc();
+            backend::ztrmv(uplo_char(symmetry, a.order()), t, diag_char(diag), n, a.as_slice(), a.lead_dim() as i32, x.as_mut_slice(), incx)
+        }
+        Ok(())
+    }
+}
+
+/// Solves `op(a) * z = x` and stores the solution `z` in `x`, where `a` is triangular and only its `symmetry`
+/// triangle is referenced. No singularity checks are performed. Fails for `ConjTrans` of row-major matrix.
+pub trait Trsv: Sized {
+    fn trsv(symmetry: Symmetry, trans: Transpose, diag: Diagonal, a: &dyn Matrix<Self>, x: &mut dyn Vector<Self>) -> Result<(), BlasError>;
+}
+
+impl Trsv for f32 {
+    fn trsv(symmetry: Symmetry, trans: Transpose, diag: Diagonal, a: &dyn Matrix<Self>, x: &mut dyn Vector<Self>) -> Result<(), BlasError> {
+        let n = square_dim(a);
+        let t = trans_char_for(trans, a.order())?;
+        assert_eq!(x.len(), n, "Vector length doesn't match size of matrix");
+        unsafe {
+            let incx = x.inc();
+            backend::strsv(uplo_char(symmetry, a.order()), t, diag_char(diag), n, a.as_slice(), a.lead_dim() as i32, x.as_mut_slice(), incx)
+        }
+        Ok(())
+    }
+}
+
+impl Trsv for f64 {
+    fn trsv(symmetry: Symmetry, trans: Transpose, diag: Diagonal, a: &dyn Matrix<Self>, x: &mut dyn Vector<Self>) -> Result<(), BlasError> {
+        let n = square_dim(a);
+        let t = trans_char_for(trans, a.order())?;
+        assert_eq!(x.len(), n, "Vector length doesn't match size of matrix");
+        unsafe {
+            let incx = x.inc();
+            backend::dtrsv(uplo_char(symmetry, a.order()), t, diag_char(diag), n, a.as_slice(), a.lead_dim() as i32, x.as_mut_slice(), incx)
+        }
+        Ok(())
+    }
+}
+
+impl Trsv for Complex32 {
+    fn trsv(symmetry: Symmetry, trans: Transpose, diag: Diagonal, a: &dyn Matrix<Self>, x: &mut dyn Vector<Self>) -> Result<(), BlasError> {
+        let n = square_dim(a);
+        let t = trans_char_for(trans, a.order())?;
+        assert_eq!(x.len(), n, "Vector length doesn't match size of matrix");
+        unsafe {
+            let incx = x.inc();
+            backend::ctrsv(uplo_char(symmetry, a.order()), t, diag_char(diag), n, a.as_slice(), a.lead_dim() as i32, x.as_mut_slice(), incx)
+        }
+        Ok(())
+    }
+}
+
+impl Trsv for Complex64 {
+    fn trsv(symmetry: Symmetry, trans: Transpose, diag: Diagonal, a: &dyn Matrix<Self>, x: &mut dyn Vector<Self>) -> Result<(), BlasError> {
+        let n = square_dim(a);
+        let t = trans_char_for(trans, a.order())?;
+        assert_eq!(x.len(), n, "Vector length doesn't match size of matrix");
+        unsafe {
+            let incx = x.inc();
+            backend::ztrsv(uplo_char(symmetry, a.order()), t, diag_char(diag), n, a.as_slice(), a.lead_dim() as i32, x.as_mut_slice(), incx)
+        }
+        Ok(())
+    }
+}
+
+/// Rank-1 update `a = alpha * x * y^T + a`. For complex numbers `y` is not conjugated.
+pub trait Ger: Sized {
+    fn ger(alpha: &Self, x: &dyn Vector<Self>, y: &dyn Vector<Self>, a: &mut dyn Matrix<Self>);
+}
+
+impl Ger for f32 {
+    fn ger(alpha: &Self, x: &dyn Vector<Self>, y: &dyn Vector<Self>, a: &mut dyn Matrix<Self>) {
+        assert_eq!(x.len(), a.rows() as i32, "Length of x doesn't match number of matrix rows");
+        assert_eq!(y.len(), a.cols() as i32, "Length of y doesn't match number of matrix columns");
+        let lda = a.lead_dim() as i32;
+        unsafe {
+            match a.order() {
+                Order::ColMajor => backend::sger(a.rows() as i32, a.cols() as i32, *alpha, x.as_slice(), x.inc(), y.as_slice(), y.inc(), a.as_mut_slice(), lda),
+                // (x * y^T)^T = y * x^T
+                Order::RowMajor => backend::sger(a.cols() as i32, a.rows() as i32, *alpha, y.as_slice(), y.inc(), x.as_slice(), x.inc(), a.as_mut_slice(), lda),
+            }
+        }
+    }
+}
+
+impl Ger for f64 {
+    fn ger(alpha: &Self, x: &dyn Vector<Self>, y: &dyn Vector<Self>, a: &mut dyn Matrix<Self>) {
+        assert_eq!(x.len(), a.rows() as i32, "Length of x doesn't match number of matrix rows");
+        assert_eq!(y.len(), a.cols() as i32, "Length of y doesn't match number of matrix columns");
+        let lda = a.lead_dim() as i32;
+        unsafe {
+            match a.order() {
+                Order::ColMajor => backend::dger(a.rows() as i32, a.cols() as i32, *alpha, x.as_slice(), x.inc(), y.as_slice(), y.inc(), a.as_mut_slice(), lda),
+                // (x * y^T)^T = y * x^T
+                Order::RowMajor => backend::dger(a.cols() as i32, a.rows() as i32, *alpha, y.as_slice(), y.inc(), x.as_slice(), x.inc(), a.as_mut_slice(), lda),
+            }
+        }
+    }
+}
+
+impl Ger for Complex32 {
+    fn ger(alpha: &Self, x: &dyn Vector<Self>, y: &dyn Vector<Self>, a: &mut dyn Matrix<Self>) {
+        assert_eq!(x.len(), a.rows() as i32, "Length of x doesn't match number of matrix rows");
+        assert_eq!(y.len(), a.cols() as i32, "Length of y doesn't match number of matrix columns");
+        let lda = a.lead_dim() as i32;
+        unsafe {
+            match a.order() {
+                Order::ColMajor => backend::cgeru(a.rows() as i32, a.cols() as i32, *alpha, x.as_slice(), x.inc(), y.as_slice(), y.inc(), a.as_mut_slice(), lda),
+                // (x * y^T)^T = y * x^T
+                Order::RowMajor => backend::cgeru(a.cols() as i32, a.rows() as i32, *alpha, y.as_slice(), y.inc(), x.as_slice(), x.inc(), a.as_mut_slice(), lda),
+            }
+        }
+    }
+}
+
+impl Ger for Complex64 {
+    fn ger(alpha: &Self, x: &dyn Vector<Self>, y: &dyn Vector<Self>, a: &mut dyn Matrix<Self>) {
+        assert_eq!(x.len(), a.rows() as i32, "Length of x doesn't match number of matrix rows");
+        assert_eq!(y.len(), a.cols() as i32, "Length of y doesn't match number of matrix columns");
+        let lda = a.lead_dim() as i32;
+        unsafe {
+            match a.order() {
+                Order::ColMajor => backend::zgeru(a.rows() as i32, a.cols() as i32, *alpha, x.as_slice(), x.inc(), y.as_slice(), y.inc(), a.as_mut_slice(), lda),
+                // (x * y^T)^T = y * x^T
+                Order::RowMajor => backend::zgeru(a.cols() as i32, a.rows() as i32, *alpha, y.as_slice(), y.inc(), x.as_slice(), x.inc(), a.as_mut_slice(), lda),
+            }
+        }
+    }
+}
+
+/// Symmetric rank-1 update `a = alpha * x * x^T + a`. Only the `symmetry` triangle of `a` is referenced and updated.
+pub trait Syr: Sized {
+    fn syr(symmetry: Symmetry, alpha: &Self, x: &dyn Vector<Self>, a: &mut dyn Matrix<Self>);
+}
+
+impl Syr for f32 {
+    fn syr(symmetry: Symmetry, alpha: &Self, x: &dyn Vector<Self>, a: &mut dyn Matrix<Self>) {
+        let n = square_dim(a);
+        assert_eq!(x.len(), n, "Vector length doesn't match size of matrix");
+        let uplo = uplo_char(symmetry, a.order());
+        let lda = a.lead_dim() as i32;
+        unsafe {
+            backend::ssyr(uplo, n, *alpha, x.as_slice(), x.inc(), a.as_mut_slice(), lda)
+        }
+    }
+}
+
+impl Syr for f64 {
+    fn syr(symmetry: Symmetry, alpha: &Self, x: &dyn Vector<Self>, a: &mut dyn Matrix<Self>) {
+        let n = square_dim(a);
+        assert_eq!(x.len(), n, "Vector length doesn't match size of matrix");
+        let uplo = uplo_char(symmetry, a.order());
+        let lda = a.lead_dim() as i32;
+        unsafe {
+            backend::dsyr(uplo, n, *alpha, x.as_slice(), x.inc(), a.as_mut_slice(), lda)
+        }
+    }
+}
+
+/// Hermitian rank-1 update `a = alpha * x * x^H + a`. This is the complex counterpart of `Syr`.
+/// Fails for row-major matrix if the increment of `x` is zero.
+pub trait Her: Sized {
+    type R;
+    fn her(symmetry: Symmetry, alpha: &Self::R, x: &dyn Vector<Self>, a: &mut dyn Matrix<Self>) -> Result<(), BlasError>;
+}
+
+impl Her for Complex32 {
+    type R = f32;
+    fn her(symmetry: Symmetry, alpha: &Self::R, x: &dyn Vector<Self>, a: &mut dyn Matrix<Self>) -> Result<(), BlasError> {
+        let n = square_dim(a);
+        assert_eq!(x.len(), n, "Vector length doesn't match size of matrix");
+        let uplo = uplo_char(symmetry, a.order());
+        let lda = a.lead_dim() as i32;
+        match a.order() {
+            Order::ColMajor => unsafe {
+                backend::cher(uplo, n, *alpha, x.as_slice(), x.inc(), a.as_mut_slice(), lda)
+            },
+            Order::RowMajor => {
+                // transpose of x * x^H is conj(x) * conj(x)^H
+                let x_conj = conj_copy(x.as_slice(), n, x.inc())?;
+                unsafe {
+                    backend::cher(uplo, n, *alpha, &x_conj, x.inc().signum(), a.as_mut_slice(), lda)
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Her for Complex64 {
+    type R = f64;
+    fn her(symmetry: Symmetry, alpha: &Self::R, x: &dyn Vector<Self>, a: &mut dyn Matrix<Self>) -> Result<(), BlasError> {
+        let n = square_dim(a);
+        assert_eq!(x.len(), n, "Vector length doesn't match size of matrix");
+        let uplo = uplo_char(symmetry, a.order());
+        let lda = a.lead_dim() as i32;
+        match a.order() {
+            Order::ColMajor => unsafe {
+                backend::zher(uplo, n, *alpha, x.as_slice(), x.inc(), a.as_mut_slice(), lda)
+            },
+            Order::RowMajor => {
+                // transpose of x * x^H is conj(x) * conj(x)^H
+                let x_conj = conj_copy(x.as_slice(), n, x.inc())?;
+                unsafe {
+                    backend::zher(uplo, n, *alpha, &x_conj, x.inc().signum(), a.as_mut_slice(), lda)
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 /*
 
 
@@ -775,4 +1439,464 @@ macro_rules! syrk_impl(($($t: ident), +) => (
 ));
 
 syrk_impl!(f32, f64, Complex32, Complex64);
-*/
\ No newline at end of file
+*/
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Debug;
+    use num_traits::{NumCast, One, ToPrimitive, Zero};
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+    use crate::blas_rust::Scalar;
+
+    trait TestScalar: Scalar + Debug {
+        const TOL: f64;
+        fn rand(rng: &mut StdRng) -> Self;
+    }
+
+    impl TestScalar for f32 {
+        const TOL: f64 = 1e-4;
+        fn rand(rng: &mut StdRng) -> Self {
+            rng.gen::<f32>() * 2. - 1.
+        }
+    }
+
+    impl TestScalar for f64 {
+        const TOL: f64 = 1e-10;
+        fn rand(rng: &mut StdRng) -> Self {
+            rng.gen::<f64>() * 2. - 1.
+        }
+    }
+
+    impl TestScalar for Complex64 {
+        const TOL: f64 = 1e-10;
+        fn rand(rng: &mut StdRng) -> Self {
+            Complex64::new(rng.gen::<f64>() * 2. - 1., rng.gen::<f64>() * 2. - 1.)
+        }
+    }
+
+    fn assert_close<S: TestScalar>(a: &[S], b: &[S]) {
+        assert_eq!(a.len(), b.len());
+        for (&x, &y) in a.iter().zip(b) {
+            assert!((x - y).abs1().to_f64().unwrap() < S::TOL, "{:?} != {:?}", a, b);
+        }
+    }
+
+    fn real<S: TestScalar>(r: f64) -> S::Real {
+        <S::Real as NumCast>::from(r).unwrap()
+    }
+
+    /**Dense matrix in either order*/
+    #[derive(Clone)]
+    struct M<S> {
+        rows: u32,
+        cols: u32,
+        order: Order,
+        data: Vec<S>,
+    }
+
+    impl<S: TestScalar> M<S> {
+        fn rand(rng: &mut StdRng, rows: u32, cols: u32, order: Order) -> Self {
+            Self { rows, cols, order, data: (0..rows * cols).map(|_| S::rand(rng)).collect() }
+        }
+        fn idx(&self, i: usize, j: usize) -> usize {
+            match self.order {
+                Order::RowMajor => i * self.cols as usize + j,
+                Order::ColMajor => j * self.rows as usize + i,
+            }
+        }
+        fn get(&self, i: usize, j: usize) -> S {
+            self.data[self.idx(i, j)]
+        }
+        fn op(&self, t: Transpose, i: usize, j: usize) -> S {
+            match t {
+                Transpose::NoTrans => self.get(i, j),
+                Transpose::Trans => self.get(j, i),
+                Transpose::ConjTrans => self.get(j, i).conj(),
+            }
+        }
+    }
+
+    impl<S> Vector<S> for M<S> {
+        fn len(&self) -> i32 {
+            self.data.len() as i32
+        }
+        fn as_slice(&self) -> &[S] {
+            &self.data
+        }
+        fn as_mut_slice(&mut self) -> &mut [S] {
+            &mut self.data
+        }
+    }
+
+    impl<S> Matrix<S> for M<S> {
+        fn order(&self) -> Order {
+            self.order
+        }
+        fn rows(&self) -> u32 {
+            self.rows
+        }
+        fn cols(&self) -> u32 {
+            self.cols
+        }
+    }
+
+    /**Band matrix storage of `m`, which must be zero outside of the band*/
+    struct Band<S> {
+        m: M<S>,
+        kl: u32,
+        ku: u32,
+        storage: Vec<S>,
+    }
+
+    impl<S: TestScalar> Band<S> {
+        fn new(mut m: M<S>, kl: u32, ku: u32) -> Self {
+            let lda = (kl + ku + 1) as usize;
+            let (rows, cols) = (m.rows as usize, m.cols as usize);
+            let mut storage = vec![S::zero(); lda * rows.max(cols)];
+            for i in 0..rows {
+                for j in 0..cols {
+                    let idx = m.idx(i, j);
+                    if i + (ku as usize) < j || j + (kl as usize) < i {
+                        m.data[idx] = S::zero();
+                    } else {
+                        let k = match m.order {
+                            Order::ColMajor => j * lda + ku as usize + i - j,
+                            Order::RowMajor => i * lda + kl as usize + j - i,
+                        };
+                        storage[k] = m.data[idx];
+                    }
+                }
+            }
+            Self { m, kl, ku, storage }
+        }
+    }
+
+    impl<S> Vector<S> for Band<S> {
+        fn len(&self) -> i32 {
+            self.storage.len() as i32
+        }
+        fn as_slice(&self) -> &[S] {
+            &self.storage
+        }
+        fn as_mut_slice(&mut self) -> &mut [S] {
+            &mut self.storage
+        }
+    }
+
+    impl<S> Matrix<S> for Band<S> {
+        fn lead_dim(&self) -> u32 {
+            self.kl + self.ku + 1
+        }
+        fn order(&self) -> Order {
+            self.m.order
+        }
+        fn rows(&self) -> u32 {
+            self.m.rows
+        }
+        fn cols(&self) -> u32 {
+            self.m.cols
+        }
+    }
+
+    impl<S> BandMatrix<S> for Band<S> {
+        fn sub_diagonals(&self) -> u32 {
+            self.kl
+        }
+        fn sup_diagonals(&self) -> u32 {
+            self.ku
+        }
+        fn as_matrix(&self) -> &dyn Matrix<S> {
+            self
+        }
+    }
+
+    /**Strided vector. Elements in between must stay untouched.*/
+    #[derive(Clone)]
+    struct V<S> {
+        n: usize,
+        inc: i32,
+        data: Vec<S>,
+    }
+
+    impl<S: TestScalar> V<S> {
+        fn rand(rng: &mut StdRng, n: usize, inc: i32) -> Self {
+            let len = if n == 0 { 0 } else { (n - 1) * inc.unsigned_abs() as usize + 1 };
+            Self { n, inc, data: (0..len).map(|_| S::rand(rng)).collect() }
+        }
+        fn idx(&self, i: usize) -> usize {
+            let stride = self.inc.unsigned_abs() as usize;
+            if self.inc > 0 { i * stride } else { (self.n - 1 - i) * stride }
+        }
+        fn get(&self, i: usize) -> S {
+            self.data[self.idx(i)]
+        }
+        fn with(&self, values: impl Fn(usize) -> S) -> Self {
+            let mut v = self.clone();
+            for i in 0..self.n {
+                let idx = v.idx(i);
+                v.data[idx] = values(i);
+            }
+            v
+        }
+    }
+
+    impl<S> Vector<S> for V<S> {
+        fn inc(&self) -> i32 {
+            self.inc
+        }
+        fn len(&self) -> i32 {
+            self.n as i32
+        }
+        fn as_slice(&self) -> &[S] {
+            &self.data
+        }
+        fn as_mut_slice(&mut self) -> &mut [S] {
+            &mut self.data
+        }
+    }
+
+    const ORDERS: [Order; 2] = [Order::RowMajor, Order::ColMajor];
+    const TRANSPOSES: [Transpose; 3] = [Transpose::NoTrans, Transpose::Trans, Transpose::ConjTrans];
+    const SYMMETRIES: [Symmetry; 2] = [Symmetry::Upper, Symmetry::Lower];
+
+    fn is_row_major_conj(order: Order, t: Transpose) -> bool {
+        matches!((order, t), (Order::RowMajor, Transpose::ConjTrans))
+    }
+
+    fn in_triangle(symmetry: Symmetry, i: usize, j: usize) -> bool {
+        match symmetry {
+            Symmetry::Upper => i <= j,
+            Symmetry::Lower => i >= j,
+        }
+    }
+
+    /**`alpha·op(a)·x + beta·y` where `a` is given by `get`*/
+    fn naive_mv<S: TestScalar>(rows: usize, cols: usize, get: impl Fn(usize, usize) -> S, alpha: S, x: &V<S>, beta: S, y: &V<S>) -> V<S> {
+        y.with(|i| alpha * (0..cols).fold(S::zero(), |s, l| s + get(i, l) * x.get(l)) + beta * y.get(i))
+    }
+
+    fn check_gemv<S: TestScalar + Gemv>(rng: &mut StdRng) {
+        let (m, n) = (4, 3);
+        for order in ORDERS {
+            for trans in TRANSPOSES {
+                for inc in [1, -2] {
+                    let a = M::<S>::rand(rng, m, n, order);
+                    let (rows, cols) = if matches!(trans, Transpose::NoTrans) { (m, n) } else { (n, m) };
+                    let x = V::rand(rng, cols as usize, inc);
+                    let y0 = V::rand(rng, rows as usize, -inc);
+                    let (alpha, beta) = (S::rand(rng), S::rand(rng));
+                    let mut y = y0.clone();
+                    let r = S::gemv(trans, &alpha, &a, &x, &beta, &mut y);
+                    if is_row_major_conj(order, trans) {
+                        assert!(r.is_err());
+                        continue;
+                    }
+                    r.unwrap();
+                    let expected = naive_mv(rows as usize, cols as usize, |i, j| a.op(trans, i, j), alpha, &x, beta, &y0);
+                    assert_close(&y.data, &expected.data);
+                }
+            }
+        }
+    }
+
+    fn check_gbmv<S: TestScalar + Gbmv>(rng: &mut StdRng) {
+        let (m, n) = (5, 4);
+        for order in ORDERS {
+            for trans in TRANSPOSES {
+                for (kl, ku) in [(1, 2), (0, 0), (3, 1)] {
+                    let a = Band::new(M::<S>::rand(rng, m, n, order), kl, ku);
+                    let (rows, cols) = if matches!(trans, Transpose::NoTrans) { (m, n) } else { (n, m) };
+                    let x = V::rand(rng, cols as usize, 2);
+                    let y0 = V::rand(rng, rows as usize, -1);
+                    let (alpha, beta) = (S::rand(rng), S::rand(rng));
+                    let mut y = y0.clone();
+                    let r = S::gbmv(trans, &alpha, &a, &x, &beta, &mut y);
+                    if is_row_major_conj(order, trans) {
+                        assert!(r.is_err());
+                        continue;
+                    }
+                    r.unwrap();
+                    let expected = naive_mv(rows as usize, cols as usize, |i, j| a.m.op(trans, i, j), alpha, &x, beta, &y0);
+                    assert_close(&y.data, &expected.data);
+                }
+            }
+        }
+    }
+
+    /**Full hermitian (or symmetric, if `S` is real) matrix of which only the `symmetry` triangle of `a` is stored.
+     Imaginary part of diagonal is ignored.*/
+    fn hermitian<S: TestScalar>(a: &M<S>, symmetry: Symmetry, i: usize, j: usize) -> S {
+        if i == j {
+            S::from_real(a.get(i, i).re())
+        } else if in_triangle(symmetry, i, j) {
+            a.get(i, j)
+        } else {
+            a.get(j, i).conj()
+        }
+    }
+
+    fn check_hemv<S: TestScalar>(rng: &mut StdRng, hemv: impl Fn(Symmetry, &S, &M<S>, &V<S>, &S, &mut V<S>) -> Result<(), BlasError>) {
+        let n = 4;
+        for order in ORDERS {
+            for symmetry in SYMMETRIES {
+                for inc in [1, 3, -2] {
+                    let a = M::<S>::rand(rng, n, n, order);
+                    let x = V::rand(rng, n as usize, inc);
+                    let y0 = V::rand(rng, n as usize, -inc);
+                    let (alpha, beta) = (S::rand(rng), S::rand(rng));
+                    let mut y = y0.clone();
+                    hemv(symmetry, &alpha, &a, &x, &beta, &mut y).unwrap();
+                    let expected = naive_mv(n as usize, n as usize, |i, j| hermitian(&a, symmetry, i, j), alpha, &x, beta, &y0);
+                    assert_close(&y.data, &expected.data);
+                }
+            }
+        }
+    }
+
+    /**Triangular matrix stored in the `symmetry` triangle of `a`*/
+    fn triangular<S: TestScalar>(a: &M<S>, symmetry: Symmetry, diag: Diagonal, i: usize, j: usize) -> S {
+        if i == j && matches!(diag, Diagonal::Unit) {
+            S::one()
+        } else if in_triangle(symmetry, i, j) {
+            a.get(i, j)
+        } else {
+            S::zero()
+        }
+    }
+
+    fn check_trmv_trsv<S: TestScalar + Trmv + Trsv>(rng: &mut StdRng) {
+        let n = 4;
+        for order in ORDERS {
+            for trans in TRANSPOSES {
+                for symmetry in SYMMETRIES {
+                    for diag in [Diagonal::NonUnit, Diagonal::Unit] {
+                        let mut a = M::<S>::rand(rng, n, n, order);
+                        // keeps the triangular system well conditioned
+                        (0..n as usize).for_each(|i| {
+                            let idx = a.idx(i, i);
+                            a.data[idx] = a.data[idx] + S::from_real(real::<S>(4.))
+                        });
+                        let op = |i: usize, j: usize| match trans {
+                            Transpose::NoTrans => triangular(&a, symmetry, diag, i, j),
+                            Transpose::Trans => triangular(&a, symmetry, diag, j, i),
+                            Transpose::ConjTrans => triangular(&a, symmetry, diag, j, i).conj(),
+                        };
+                        let x0 = V::rand(rng, n as usize, -2);
+                        let mut x = x0.clone();
+                        let r = S::trmv(symmetry, trans, diag, &a, &mut x);
+                        if is_row_major_conj(order, trans) {
+                            assert!(r.is_err());
+                            assert!(S::trsv(symmetry, trans, diag, &a, &mut x).is_err());
+                            continue;
+                        }
+                        r.unwrap();
+                        let expected = naive_mv(n as usize, n as usize, op, S::one(), &x0, S::zero(), &x0);
+                        assert_close(&x.data, &expected.data);
+                        S::trsv(symmetry, trans, diag, &a, &mut x).unwrap();
+                        assert_close(&x.data, &x0.data);
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_ger<S: TestScalar + Ger>(rng: &mut StdRng) {
+        let (m, n) = (4, 3);
+        for order in ORDERS {
+            for inc in [1, -2] {
+                let a0 = M::<S>::rand(rng, m, n, order);
+                let x = V::rand(rng, m as usize, inc);
+                let y = V::rand(rng, n as usize, 3);
+                let alpha = S::rand(rng);
+                let mut a = a0.clone();
+                S::ger(&alpha, &x, &y, &mut a);
+                let mut expected = a0.clone();
+                for i in 0..m as usize {
+                    for j in 0..n as usize {
+                        let idx = expected.idx(i, j);
+                        expected.data[idx] = a0.get(i, j) + alpha * x.get(i) * y.get(j);
+                    }
+                }
+                assert_close(&a.data, &expected.data);
+            }
+        }
+    }
+
+    /**`a + alpha·x·x^H` in the `symmetry` triangle. The rest of `a` is left as it was.*/
+    fn naive_rank1<S: TestScalar>(a: &M<S>, symmetry: Symmetry, alpha: S, x: &V<S>) -> M<S> {
+        let mut out = a.clone();
+        for i in 0..x.n {
+            for j in 0..x.n {
+                if in_triangle(symmetry, i, j) {
+                    let v = a.get(i, j) + alpha * x.get(i) * x.get(j).conj();
+                    let idx = out.idx(i, j);
+                    out.data[idx] = if i == j { S::from_real(v.re()) } else { v };
+                }
+            }
+        }
+        out
+    }
+
+    fn check_syr<S: TestScalar>(rng: &mut StdRng, syr: impl Fn(Symmetry, S::Real, &V<S>, &mut M<S>) -> Result<(), BlasError>) {
+        let n = 4;
+        for order in ORDERS {
+            for symmetry in SYMMETRIES {
+                for inc in [1, -3] {
+                    let a0 = M::<S>::rand(rng, n, n, order);
+                    let x = V::rand(rng, n as usize, inc);
+                    let alpha = S::rand(rng).re();
+                    let mut a = a0.clone();
+                    syr(symmetry, alpha, &x, &mut a).unwrap();
+                    let expected = naive_rank1(&a0, symmetry, S::from_real(alpha), &x);
+                    assert_close(&a.data, &expected.data);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test1() {
+        let mut rng = StdRng::seed_from_u64(1);
+        check_gemv::<f32>(&mut rng);
+        check_gemv::<f64>(&mut rng);
+        check_gemv::<Complex64>(&mut rng);
+        check_gbmv::<f32>(&mut rng);
+        check_gbmv::<f64>(&mut rng);
+        check_gbmv::<Complex64>(&mut rng);
+    }
+
+    #[test]
+    fn test2() {
+        let mut rng = StdRng::seed_from_u64(2);
+        check_hemv::<f32>(&mut rng, |s, alpha, a, x, beta, y| Ok(f32::symv(s, alpha, a, x, beta, y)));
+        check_hemv::<f64>(&mut rng, |s, alpha, a, x, beta, y| Ok(f64::symv(s, alpha, a, x, beta, y)));
+        check_hemv::<Complex64>(&mut rng, |s, alpha, a, x, beta, y| Complex64::hemv(s, alpha, a, x, beta, y));
+        check_syr::<f32>(&mut rng, |s, alpha, x, a| Ok(f32::syr(s, &alpha, x, a)));
+        check_syr::<f64>(&mut rng, |s, alpha, x, a| Ok(f64::syr(s, &alpha, x, a)));
+        check_syr::<Complex64>(&mut rng, |s, alpha, x, a| Complex64::her(s, &alpha, x, a));
+    }
+
+    #[test]
+    fn test3() {
+        let mut rng = StdRng::seed_from_u64(3);
+        check_trmv_trsv::<f32>(&mut rng);
+        check_trmv_trsv::<f64>(&mut rng);
+        check_trmv_trsv::<Complex64>(&mut rng);
+        check_ger::<f32>(&mut rng);
+        check_ger::<f64>(&mut rng);
+        check_ger::<Complex64>(&mut rng);
+    }
+
+    #[test]
+    fn test4() {
+        // zero increment can't be conjugated
+        let mut rng = StdRng::seed_from_u64(4);
+        let a = M::<Complex64>::rand(&mut rng, 3, 3, Order::RowMajor);
+        let x = V { n: 3, inc: 0, data: vec![Complex64::new(1., 1.)] };
+        let mut y = V::<Complex64>::rand(&mut rng, 3, 1);
+        assert!(Complex64::hemv(Symmetry::Upper, &Complex64::one(), &a, &x, &Complex64::zero(), &mut y).is_err());
+        let mut a = a;
+        assert!(Complex64::her(Symmetry::Upper, &1., &x, &mut a).is_err());
+    }
+}
@@ -6,6 +6,41 @@ The element at index 0 is neutral by convention.
 If `a`-th generator element has an inverse, then by convention it is either `a+1` or `a-1`*/
 pub type CayleyGraph = [usize];
 
+/**Owned Cayley graph together with the number of its generators, as returned by `cyclic_monoid`, `cyclic_group`,
+`direct_product`, etc. Can be converted to and from the `(graph, generators)` tuple.*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedCayleyGraph {
+    /**of shape `[group_elements, group_generators]`*/
+    pub graph: Vec<usize>,
+    pub generators: usize,
+}
+
+impl OwnedCayleyGraph {
+    pub fn elements(&self) -> usize {
+        assert!(self.generators > 0, "Cayley graph must have at least one generator");
+        self.graph.len() / self.generators
+    }
+    /**Element reached from `element` by applying `generator`*/
+    pub fn neighbour(&self, element: usize, generator: usize) -> usize {
+        self.graph[element * self.generators + generator]
+    }
+}
+
+impl From<(Vec<usize>, usize)> for OwnedCayleyGraph {
+    fn from((graph, generators): (Vec<usize>, usize)) -> Self {
+        assert!(generators > 0, "Cayley graph must have at least one generator");
+        assert_eq!(graph.len() % generators, 0, "Length of graph {} is not divisible by the number of generators {}", graph.len(), generators);
+        Self { graph, generators }
+    }
+}
+
+impl From<OwnedCayleyGraph> for (Vec<usize>, usize) {
+    fn from(c: OwnedCayleyGraph) -> Self {
+        (c.graph, c.generators)
+    }
+}
+
 pub fn cyclic_monoid(n: usize) -> (Vec<usize>, usize) {
     let mut b:Vec<usize> = (0..n).map(|i|i+1).collect();
     b[n - 1] = 0;
@@ -57,6 +92,104 @@ pub fn direct_product(a: &[usize], gen_len_a: usize, b: &[usize], gen_len_b: usi
     (c, gen_len_c)
 }
 
+/**Dihedral group of symmetries of a regular `n`-gon, with `2n` elements. Generator `0` is the rotation `r`, `1` is its
+inverse and `2` is the reflection `s`, which is its own inverse. Element `f*n+k` stands for `r^k s^f`.*/
+pub fn dihedral_group(n: usize) -> (Vec<usize>, usize) {
+    assert!(n > 0);
+    let mut b = Vec::with_capacity(2 * n * 3);
+    for f in 0..2 {
+        for k in 0..n {
+            // r^k s r = r^(k-1) s, so rotations go the other way once the element is reflected
+            let (next, prev) = ((k + 1) % n, (k + n - 1) % n);
+            let (r, r_inv) = if f == 0 { (next, prev) } else { (prev, next) };
+            b.push(f * n + r);
+            b.push(f * n + r_inv);
+            b.push((1 - f) * n + k);
+        }
+    }
+    (b, 3)
+}
+
+/**`generators` is of shape `[generators, degree]` and every row is a mapping of `0..degree` into itself (usually a permutation).
+Returns Cayley graph of the monoid generated by them (a group if all of them are permutations). Element `0` is the identity
+and the remaining ones are numbered in the order in which they are first reached by breadth-first search. Applying generator
+`a` to element `x` yields `x` followed by `a`. Together with the graph, returns all elements as mappings of shape `[elements, degree]`.*/
+pub fn closure(generators: &[usize], degree: usize) -> (Vec<usize>, usize, Vec<usize>) {
+    assert!(degree > 0);
+    assert_eq!(generators.len() % degree, 0);
+    assert!(generators.iter().all(|&i| i < degree), "Generators must map 0..{} into itself", degree);
+    let gen_len = generators.len() / degree;
+    let mut elements: Vec<usize> = (0..degree).collect();
+    let mut indices = std::collections::HashMap::new();
+    indices.insert(elements.clone(), 0);
+    let mut b = Vec::new();
+    let mut element = 0;
+    while element * degree < elements.len() {
+        for a in 0..gen_len {
+            let g = &generators[a * degree..(a + 1) * degree];
+            let next: Vec<usize> = elements[element * degree..(element + 1) * degree].iter().map(|&i| g[i]).collect();
+            let len = indices.len();
+            let idx = *indices.entry(next).or_insert_with_key(|next| {
+                elements.extend_from_slice(next);
+                len
+            });
+            b.push(idx);
+        }
+        element += 1;
+    }
+    (b, gen_len, elements)
+}
+
+/**Symmetric group of all `n!` permutations of `n` items. Generator `0` is the cycle `(0 1 .. n-1)`, `1` is its inverse
+and `2` is the transposition `(0 1)`, which is its own inverse. See `closure` for numbering of elements.*/
+pub fn symmetric_group(n: usize) -> (Vec<usize>, usize) {
+    assert!(n > 1);
+    let mut generators: Vec<usize> = (0..n).map(|i| (i + 1) % n).collect();
+    generators.extend((0..n).map(|i| (i + n - 1) % n));
+    generators.extend([1, 0]);
+    generators.extend(2..n);
+    let (b, gen_len, _) = closure(&generators, n);
+    (b, gen_len)
+}
+
+/**Grid world of shape `[height, width]`, whose state `y*width+x` is the position of an agent. Generators `0` and `1`
+move it up and down, `2` and `3` left and right. Moving into a wall or out of bounds leaves the agent where it was.
+`walls` of shape `[height, width]` marks cells that can't be entered (they still have their states, which map to themselves).*/
+pub fn grid_world(height: usize, width: usize, walls: &[bool]) -> (Vec<usize>, usize) {
+    assert_eq!(walls.len(), height * width);
+    let mut b = Vec::with_capacity(height * width * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let state = y * width + x;
+            let moves = [
+                (y > 0).then(|| state - width),
+                (y + 1 < height).then(|| state + width),
+                (x > 0).then(|| state - 1),
+                (x + 1 < width).then(|| state + 1),
+            ];
+            for m in moves {
+                b.push(m.filter(|&m| !walls[state] && !walls[m]).unwrap_or(state));
+            }
+        }
+    }
+    (b, 4)
+}
+
+/**Free monoid over `generators` letters truncated to words of length at most `depth`. Element `0` is the empty word and
+the words are ordered by length, so that appending letter `a` to the `i`-th word yields word `i*generators+1+a`.
+Words of maximal length have nowhere to go, so they map to `elements` (one past the last element), which
+`learn_u` and `learn_w` skip. Returns `None` if the size of the Cayley graph overflows `usize`.*/
+pub fn free_monoid(generators: usize, depth: usize) -> Option<(Vec<usize>, usize)> {
+    assert!(generators > 0);
+    let depth = u32::try_from(depth).ok()?;
+    let longest = generators.checked_pow(depth)?;
+    let elements = (0..=depth).try_fold(0usize, |sum, l| sum.checked_add(generators.checked_pow(l)?))?;
+    elements.checked_mul(generators)?;
+    let inner = elements - longest;
+    let b = (0..elements).flat_map(|i| (0..generators).map(move |a| if i < inner { i * generators + 1 + a } else { elements })).collect();
+    Some((b, generators))
+}
+
 /**`state_space` has shape `[states,generator_elements]`, `w` has shape `[states,quotient_monoid_elements]` and its rows sum up to `1`.
  `u` has shape `[generator_elements,quotient_monoid_elements,quotient_monoid_elements]`*/
 pub fn learn_u(state_space: &[usize], generator_elements: usize, w: &[f32], quotient_monoid_elements: usize, u: &mut [f32]) {
@@ -86,7 +219,9 @@ pub fn learn_u(state_space: &[usize], generator_elements: usize, w: &[f32], quot
 }
 
 /**`state_space` has shape `[states,generator_elements]`, `w` has shape `[states,quotient_monoid_elements]` and its rows sum up to `1`.
- `u` has shape `[generator_elements,quotient_monoid_elements,quotient_monoid_elements]`. `new_w` is of same shape as `w` and is filled with zeroes*/
+ `u` has shape `[generator_elements,quotient_monoid_elements,quotient_monoid_elements]`. `new_w` is of same shape as `w` and is filled with zeroes.
+ Every row of `new_w` is the average prediction made by all the states with an edge leading to it. States without
+ incoming edges (like the neutral element of `free_monoid`) can't be predicted, so their rows are copied from `w`.*/
 pub fn learn_w(state_space: &[usize], generator_elements: usize, w: &[f32], quotient_monoid_elements: usize, u: &[f32], new_w: &mut [f32]) {
     assert_eq!(state_space.len() % generator_elements, 0);
     let n = state_space.len() / generator_elements;
@@ -113,7 +248,9 @@ pub fn learn_w(state_space: &[usize], generator_elements: usize, w: &[f32], quot
             }
         }
     }
-    new_w.iter_mut().zip(neighbour_count.iter()).for_each(|(w, &n)| *w /= n as f32)
+    for ((new_w, &w), &n) in new_w.iter_mut().zip(w.iter()).zip(neighbour_count.iter()) {
+        *new_w = if n == 0 { w } else { *new_w / n as f32 }
+    }
 }
 
 #[cfg(test)]
@@ -126,4 +263,90 @@ mod tests {
         let (c2, g2) = cyclic_group(4);
         let (c12, g12) = direct_product(&c1, g1, &c2, g2);
     }
-}
\ No newline at end of file
+
+    fn compose(c: &[usize], g: usize, element: usize, word: &[usize]) -> usize {
+        word.iter().fold(element, |e, &a| c[e * g + a])
+    }
+
+    #[test]
+    fn test7() {
+        let n = 5;
+        let (c, g) = dihedral_group(n);
+        assert_eq!((c.len() / g, g), (2 * n, 3));
+        for e in 0..2 * n {
+            assert_eq!(compose(&c, g, e, &[0, 1]), e);
+            assert_eq!(compose(&c, g, e, &[1, 0]), e);
+            assert_eq!(compose(&c, g, e, &[2, 2]), e);
+            assert_eq!(compose(&c, g, e, &[0, 2, 0, 2]), e);
+            assert_eq!(compose(&c, g, e, &[0; 5]), e);
+        }
+        assert_ne!(compose(&c, g, 0, &[0, 2]), compose(&c, g, 0, &[2, 0]));
+    }
+
+    #[test]
+    fn test8() {
+        let (c, g) = symmetric_group(4);
+        assert_eq!((c.len() / g, g), (24, 3));
+        for e in 0..24 {
+            assert_eq!(compose(&c, g, e, &[0, 1]), e);
+            assert_eq!(compose(&c, g, e, &[2, 2]), e);
+        }
+        // the same group can be generated by different permutations
+        let (c2, _, elements) = closure(&[1, 0, 2, 3, 0, 2, 1, 3, 0, 1, 3, 2], 4);
+        assert_eq!(c2.len() / 3, 24);
+        assert_eq!(&elements[..4], &[0, 1, 2, 3]);
+        // cyclic group is generated by a single cycle and its inverse
+        assert_eq!(closure(&[1, 2, 0, 2, 0, 1], 3).0, cyclic_group(3).0);
+        // non-permutations generate monoids
+        let (c3, g3, _) = closure(&[0, 0], 2);
+        assert_eq!((c3, g3), (vec![1, 1], 1));
+    }
+
+    #[test]
+    fn test9() {
+        let mut walls = vec![false; 6];
+        walls[4] = true;
+        let (c, g) = grid_world(2, 3, &walls);
+        assert_eq!(g, 4);
+        assert_eq!(&c[0..4], &[0, 3, 0, 1]);
+        assert_eq!(&c[4..8], &[1, 1, 0, 2]);
+        assert_eq!(&c[12..16], &[0, 3, 3, 3]);
+        assert_eq!(&c[16..20], &[4, 4, 4, 4]);
+        assert_eq!(&c[20..24], &[2, 5, 5, 5]);
+    }
+
+    #[test]
+    fn test10() {
+        let (c, g) = free_monoid(2, 2).unwrap();
+        assert_eq!(g, 2);
+        assert_eq!(c, vec![1, 2, 3, 4, 5, 6, 7, 7, 7, 7, 7, 7, 7, 7]);
+        assert_eq!(free_monoid(3, 0).unwrap().0, vec![1, 1, 1]);
+        assert_eq!(free_monoid(10, 30), None);
+        assert_eq!(free_monoid(2, usize::BITS as usize), None);
+    }
+
+    #[test]
+    fn test11() {
+        // the empty word has no incoming edges, but learn_w must not turn it into NaN
+        let (c, g) = free_monoid(2, 2).unwrap();
+        let n = c.len() / g;
+        let m = 3;
+        let w: Vec<f32> = (0..n * m).map(|i| if i % m == i / m % m { 1. } else { 0. }).collect();
+        let mut u = vec![0.; g * m * m];
+        learn_u(&c, g, &w, m, &mut u);
+        assert!(u.iter().all(|u| u.is_finite()));
+        let mut new_w = vec![0.; n * m];
+        learn_w(&c, g, &w, m, &u, &mut new_w);
+        assert!(new_w.iter().all(|w| w.is_finite()));
+        assert_eq!(&new_w[..m], &w[..m]);
+        for row in new_w.chunks(m) {
+            assert!((row.iter().sum::<f32>() - 1.).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one generator")]
+    fn test12() {
+        OwnedCayleyGraph::from((vec![], 0));
+    }
+}
@@ -1,19 +1,431 @@
-use std::ops::Mul;
 use num_traits::{Float, MulAdd};
-use crate::{AffTrans, VectorFieldSub};
-use crate::dot_arr::dot0;
+use crate::{AffTrans, cross, line, mul_row_wise, transpose, VectorFieldAdd, VectorFieldDiv, VectorFieldMul, VectorFieldMulAdd, VectorFieldSub};
+use crate::dot_mad_arr::{dot0, dot1};
+use crate::line::Line;
 
-pub fn line_segment_to_line_segment() {
+/**Result of narrow-phase collision detection between two shapes `a` and `b`*/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Contact<F, const DIM: usize> {
+    /**How deep the shapes penetrate each other. If the shapes are disjoint, then it's negative and its absolute value is
+     the distance between them.*/
+    pub penetration: F,
+    /**Unit vector pointing from `a` towards `b`. Moving `b` by `normal*penetration` separates the shapes.*/
+    pub normal: [F; DIM],
+    /**Point of `a` that is closest to `b` (or lies deepest inside of `b`)*/
+    pub point_a: [F; DIM],
+    /**Point of `b` that is closest to `a` (or lies deepest inside of `a`)*/
+    pub point_b: [F; DIM],
+}
+
+impl<F: Float, const DIM: usize> Contact<F, DIM> {
+    pub fn intersects(&self) -> bool {
+        self.penetration > F::zero()
+    }
+}
+
+fn length<F: Float + MulAdd<Output=F>, const DIM: usize>(v: &[F; DIM]) -> F {
+    dot0(v, v).sqrt()
+}
+
+/**Any unit vector perpendicular to `v`*/
+fn perpendicular(v: &[f32; 3]) -> [f32; 3] {
+    let [x, y, z] = v.map(f32::abs);
+    let other = if x <= y && x <= z { [1., 0., 0.] } else if y <= z { [0., 1., 0.] } else { [0., 0., 1.] };
+    let p = cross(v, &other);
+    p.mul_scalar(1. / length(&p))
+}
+
+/**Segments are lines `P+s*U` with `0<=s<=1` (see `line::closest_points_on_segments`). They have no volume,
+ so `penetration` is always the negative distance between them. If the segments intersect, then
+ the normal is undefined and left as zero vector.*/
+pub fn line_segment_to_line_segment<F: Float + MulAdd<Output=F>, const DIM: usize>(a: &Line<F, DIM>, b: &Line<F, DIM>) -> Contact<F, DIM> {
+    let [s, t] = line::closest_points_on_segments(a, b);
+    let point_a = line::pos(a, s);
+    let point_b = line::pos(b, t);
+    let diff = point_b.sub(&point_a);
+    let distance = length(&diff);
+    let normal = if distance > F::zero() { diff.mul_scalar(F::one() / distance) } else { [F::zero(); DIM] };
+    Contact { penetration: -distance, normal, point_a, point_b }
+}
+
+/**Pair `[radius, half_height]`. In local coordinates the cylinder is aligned with Y axis and centered at origin
+ (the same convention as bones in evolutio).*/
+pub type Cylinder = [f32; 2];
 
+/**Axis of the cylinder in world coordinates as a line segment (see `line_segment_to_line_segment`).
+ The transformation is expected to be rigid (its axes must be normal vectors).*/
+pub fn cylinder_axis(cyl: &Cylinder, tran: &AffTrans<f32, 3>) -> Line<f32, 3> {
+    let half_height = cyl[1];
+    let y_axis = &tran.axis()[1];
+    let start = y_axis.mul_scalar_add(-half_height, tran.translation());
+    [start, y_axis.mul_scalar(2. * half_height)]
 }
 
-pub fn cylinder_to_cylinder() {}
+/**Pair `[radius, half_height]`, where `half_height` is measured between centers of the two hemispherical caps,
+ so in local coordinates (aligned with Y axis like `Cylinder`) the capsule spans `-half_height-radius..=half_height+radius`.*/
+pub type Capsule = [f32; 2];
 
+/**Capsules are segments (see `cylinder_axis`) inflated by their radius, so the contact is that of the axes, pushed apart by both radii.*/
+pub fn capsule_to_capsule(cap1: &Capsule, tran1: &AffTrans<f32, 3>, cap2: &Capsule, tran2: &AffTrans<f32, 3>) -> Contact<f32, 3> {
+    let axis1 = cylinder_axis(cap1, tran1);
+    let axis2 = cylinder_axis(cap2, tran2);
+    let mut c = line_segment_to_line_segment(&axis1, &axis2);
+    let [r1, r2] = [cap1[0], cap2[0]];
+    if -c.penetration <= 1e-5 * (r1 + r2) {
+        // axes (nearly) intersect, so the difference vector is too short to give a reliable normal.
+        // Instead we push the capsules apart in the direction perpendicular to both axes
+        let n = cross(&axis1[1], &axis2[1]);
+        let l = length(&n);
+        c.normal = if l > f32::epsilon() { n.mul_scalar(1. / l) } else { perpendicular(&axis1[1]) };
+    }
+    c.penetration += r1 + r2;
+    c.point_a = c.normal.mul_scalar_add(r1, &c.point_a);
+    c.point_b = c.normal.mul_scalar_add(-r2, &c.point_b);
+    c
+}
+
+/**Point of the cylinder (relative to its center) that lies furthest in the direction of unit vector `n`, together with
+ the support function `h(n) = radius*|n - (n·a)a| + half_height*|n·a|`, where `a` is the axis. If `n` is (nearly) parallel
+ to the axis, the furthest points form a whole cap and if it's perpendicular, they form a segment along the side.
+ In such cases the point closest to `towards` (also relative to the center) is chosen.*/
+fn cylinder_support(cyl: &Cylinder, a: &[f32; 3], n: &[f32; 3], towards: &[f32; 3]) -> ([f32; 3], f32) {
+    const EPSILON: f32 = 1e-4;
+    let [r, half_height] = *cyl;
+    let na = dot0(n, a);
+    let n_perp = a.mul_scalar_add(-na, n);
+    let l = length(&n_perp);
+    let axial = if na.abs() > EPSILON { half_height.copysign(na) } else { dot0(towards, a).clamp(-half_height, half_height) };
+    let radial = if l > EPSILON {
+        n_perp.mul_scalar(r / l)
+    } else {
+        let t = a.mul_scalar_add(-dot0(towards, a), towards);
+        let lt = length(&t);
+        if lt > r { t.mul_scalar(r / lt) } else { t }
+    };
+    (a.mul_scalar_add(axial, &radial), r * l + half_height * na.abs())
+}
+
+/**Gradient of the overlap `f(n) = h1(n) + h2(n) - n·d` of cylinders `(cylinder, axis)`, projected onto the tangent plane of unit
+ sphere at `n`. Support functions of cylinders have kinks where `n` is perpendicular to the axis (edge of the side) or parallel
+ to it (cap). There, the subgradient is chosen so that the result is as short as possible, which lets the descent slide
+ along the kink instead of zig-zagging across it.*/
+fn cylinders_subgradient(cylinders: &[(&Cylinder, &[f32; 3])], n: &[f32; 3], d: &[f32; 3]) -> [f32; 3] {
+    const KINK: f32 = 1e-3;
+    let mut g = d.map(|x| -x);
+    for &(&[r, half_height], a) in cylinders {
+        let na = dot0(n, a);
+        let n_perp = a.mul_scalar_add(-na, n);
+        let l = length(&n_perp);
+        if na.abs() > KINK {
+            g = a.mul_scalar_add(half_height.copysign(na), &g);
+        }
+        if l > KINK {
+            g = n_perp.mul_scalar_add(r / l, &g);
+        }
+    }
+    g = n.mul_scalar_add(-dot0(&g, n), &g);
+    for &(&[r, half_height], a) in cylinders {
+        let na = dot0(n, a);
+        if na.abs() <= KINK {
+            // subgradient of half_height*|n·a| is anything between -half_height*a and half_height*a
+            let ga = dot0(&g, a);
+            g = a.mul_scalar_add((ga.abs() - half_height).max(0.).copysign(ga) - ga, &g);
+        } else if length(&a.mul_scalar_add(-na, n)) <= KINK {
+            // subgradient of radius*|n_perp| is anything within the disc of that radius perpendicular to a
+            let g_perp = a.mul_scalar_add(-dot0(&g, a), &g);
+            let lg = length(&g_perp);
+            if lg > 0. {
+                g = g_perp.mul_scalar_add((lg - r).max(0.) / lg - 1., &g);
+            }
+        }
+    }
+    g
+}
+
+/**Contact of two cylinders with real flat caps, so that end-to-end contact (as in bone joints) is handled correctly.
+ Works the same way as `ellipsoid_to_ellipsoid`, that is, it minimises the overlap of the projections of both cylinders onto `n`
+ over all unit vectors `n`. The overlap isn't convex, so the descent is started from several directions (the normal of
+ `capsule_to_capsule`, both axes, their sum, difference and cross product, the vector between centers and its cross
+ products with the axes), which covers side-by-side, end-to-end and cap-to-side contacts, and the best result is kept.
+ The transformations are expected to be rigid.*/
+pub fn cylinder_to_cylinder(cyl1: &Cylinder, tran1: &AffTrans<f32, 3>, cyl2: &Cylinder, tran2: &AffTrans<f32, 3>) -> Contact<f32, 3> {
+    let a1 = &tran1.axis()[1];
+    let a2 = &tran2.axis()[1];
+    let c1 = tran1.translation();
+    let c2 = tran2.translation();
+    let d = c2.sub(c1);
+    let support = |n: &[f32; 3]| {
+        let (_, h1) = cylinder_support(cyl1, a1, n, &d);
+        let (_, h2) = cylinder_support(cyl2, a2, n, &d);
+        (cylinders_subgradient(&[(cyl1, a1), (cyl2, a2)], n, &d).add(&d), h1 + h2)
+    };
+    let starts = [capsule_to_capsule(cyl1, tran1, cyl2, tran2).normal, *a1, *a2, cross(a1, a2), d, a1.add(a2), a1.sub(a2), cross(a1, &d), cross(a2, &d)];
+    let (n, value) = starts.iter().filter_map(|n| {
+        let l = length(n);
+        if l <= f32::epsilon() {
+            return None;
+        }
+        // the normal points from the first cylinder towards the second one
+        let n = n.mul_scalar(if dot0(n, &d) < 0. { -1. / l } else { 1. / l });
+        Some(minimise_overlap(&d, n, &support))
+    }).min_by(|(_, v1), (_, v2)| v1.total_cmp(v2)).unwrap();
+    let (offset1, _) = cylinder_support(cyl1, a1, &n, &d);
+    let (offset2, _) = cylinder_support(cyl2, a2, &n.map(|x| -x), &d.map(|x| -x));
+    Contact { penetration: value, normal: n, point_a: c1.add(&offset1), point_b: c2.add(&offset2) }
+}
 
 /**Vector holding length of x,y,z radii.*/
 pub type Ellipsoid = [f32; 3];
 
-pub fn ellipsoid_to_plane(ell1: Ellipsoid, tran1: AffTrans<f32, 3>, ell2: Ellipsoid, tran2: AffTrans<f32, 3>) {}
+/**Pair `[P, N]` where `P` is any point on the plane and `N` is the unit normal vector. Everything that
+ lies on the side opposite to `N` is considered to be inside.*/
+pub type Plane = [[f32; 3]; 2];
 
-/** https://matthias-research.github.io/pages/publications/orientedParticles.pdf */
-pub fn ellipsoid_to_ellipsoid(ell1: Ellipsoid, tran1: AffTrans<f32, 3>, ell2: Ellipsoid, tran2: AffTrans<f32, 3>) {}
\ No newline at end of file
+/**Ellipsoid is the image of unit sphere under linear map `M` followed by translation. This returns `M`.*/
+fn ellipsoid_mat(ell: &Ellipsoid, tran: &AffTrans<f32, 3>) -> [[f32; 3]; 3] {
+    mul_row_wise(&tran.rot3(), ell)
+}
+
+/**Support mapping of ellipsoid `M` centered at origin. Returns the point of ellipsoid that lies furthest in the direction of `n`
+ and the support function `h(n) = |M^T n|` (distance of the tangent plane from the center). */
+fn ellipsoid_support(m: &[[f32; 3]; 3], n: &[f32; 3]) -> ([f32; 3], f32) {
+    let mt_n = dot1(n, m);
+    let h = length(&mt_n);
+    let m_mt_n = dot1(&mt_n, &transpose(m));
+    (m_mt_n.mul_scalar(1. / h), h)
+}
+
+pub fn ellipsoid_to_plane(ell: &Ellipsoid, tran: &AffTrans<f32, 3>, plane: &Plane) -> Contact<f32, 3> {
+    let [p, n] = plane;
+    let m = ellipsoid_mat(ell, tran);
+    let center = tran.translation();
+    let (offset, h) = ellipsoid_support(&m, n);
+    // the deepest point lies in the direction opposite to plane normal
+    let point_a = center.sub(&offset);
+    let distance_of_center = dot0(n, &center.sub(p));
+    let penetration = h - distance_of_center;
+    let point_b = n.mul_scalar_add(penetration, &point_a);
+    Contact { penetration, normal: n.map(|x| -x), point_a, point_b }
+}
+
+/**Minimises the overlap `f(n) = h(n) - n·d` of two convex shapes (whose centers differ by `d`) by gradient descent on
+ the unit sphere, starting from unit vector `n`. `support(n)` returns the gradient of `h` (for smooth shapes that's the sum of
+ support points of both shapes, relative to their centers) and the sum of their support functions `h(n)`. Returns the minimising `n` and `f(n)`.*/
+fn minimise_overlap(d: &[f32; 3], mut n: [f32; 3], support: impl Fn(&[f32; 3]) -> ([f32; 3], f32)) -> ([f32; 3], f32) {
+    const MAX_ITERATIONS: usize = 64;
+    let f = |n: &[f32; 3]| -> f32 { support(n).1 - dot0(n, d) };
+    let mut value = f(&n);
+    let mut angle = 0.5f32;
+    for _ in 0..MAX_ITERATIONS {
+        let gradient = support(&n).0.sub(d);
+        // project gradient onto the tangent plane of unit sphere
+        let tangent = n.mul_scalar_add(-dot0(&gradient, &n), &gradient);
+        let tangent_len = length(&tangent);
+        if tangent_len <= 1e-6 * (length(d) + value.abs()) {
+            break;
+        }
+        let direction = tangent.mul_scalar(-1. / tangent_len);
+        // backtracking line search along the great circle
+        loop {
+            let candidate = n.mul_scalar(angle.cos()).add(&direction.mul_scalar(angle.sin()));
+            let candidate_value = f(&candidate);
+            if candidate_value < value {
+                n = candidate.mul_scalar(1. / length(&candidate));
+                value = candidate_value;
+                angle = (angle * 2.).min(0.5);
+                break;
+            }
+            angle /= 2.;
+            if angle < 1e-7 {
+                break;
+            }
+        }
+        if angle < 1e-7 {
+            break;
+        }
+    }
+    (n, value)
+}
+
+/** https://matthias-research.github.io/pages/publications/orientedParticles.pdf
+
+ Particles are ellipsoids represented (as in the paper) by their center, rotation and radii, which together form a map
+ `x = M u + c` of a unit sphere `u`. For any unit vector `n` the function
+ `f(n) = h1(n) + h2(-n) - n·(c2 - c1)` (where `h` is the support function of each ellipsoid) measures how much the ellipsoids overlap
+ when projected onto `n`. Its minimum over all directions is the penetration depth (or minus distance if they are disjoint), and
+ the minimising `n` is the contact normal. We start from the gradient of the implicit function of the first ellipsoid
+ `|M1^-1 (x - c1)|^2` evaluated at `c2` (the collision normal used in the paper) and then minimise `f` by gradient
+ descent on the unit sphere. The result is a local minimum, which for nearly spherical particles is also the global one.*/
+pub fn ellipsoid_to_ellipsoid(ell1: &Ellipsoid, tran1: &AffTrans<f32, 3>, ell2: &Ellipsoid, tran2: &AffTrans<f32, 3>) -> Contact<f32, 3> {
+    let m1 = ellipsoid_mat(ell1, tran1);
+    let m2 = ellipsoid_mat(ell2, tran2);
+    let c1 = tran1.translation();
+    let c2 = tran2.translation();
+    let d = c2.sub(c1);
+    let n = {
+        // M1^-1 = diag(1/ell1) R1^T when the rotation R1 is orthogonal
+        let local = dot1(&d, &tran1.rot3()).div(ell1);
+        let gradient = dot1(&local.div(ell1), &transpose(&tran1.rot3()));
+        let l = length(&gradient);
+        if l > f32::epsilon() { gradient.mul_scalar(1. / l) } else { [1., 0., 0.] }
+    };
+    let (n, value) = minimise_overlap(&d, n, |n| {
+        let (offset1, h1) = ellipsoid_support(&m1, n);
+        let (offset2, h2) = ellipsoid_support(&m2, n);
+        (offset1.add(&offset2), h1 + h2)
+    });
+    let (offset1, _) = ellipsoid_support(&m1, &n);
+    let (offset2, _) = ellipsoid_support(&m2, &n);
+    Contact { penetration: value, normal: n, point_a: c1.add(&offset1), point_b: c2.sub(&offset2) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(translation: [f32; 3]) -> AffTrans<f32, 3> {
+        AffTrans::new([[1., 0., 0.], [0., 1., 0.]], translation)
+    }
+
+    fn assert_close(a: &[f32; 3], b: &[f32; 3]) {
+        assert!(a.sub(b).iter().all(|x| x.abs() < 1e-4), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test1() {
+        let a = [[0f32, 0., 0.], [2., 0., 0.]];
+        let b = [[1f32, -1., 1.], [0., 2., 0.]];
+        let c = line_segment_to_line_segment(&a, &b);
+        assert!(!c.intersects());
+        assert!((c.penetration + 1.).abs() < 1e-6);
+        assert_close(&c.normal, &[0., 0., 1.]);
+        assert_close(&c.point_a, &[1., 0., 0.]);
+        assert_close(&c.point_b, &[1., 0., 1.]);
+    }
+
+    #[test]
+    fn test2() {
+        let cap = [0.5, 1.];
+        let c = capsule_to_capsule(&cap, &at([0., 0., 0.]), &cap, &at([0.8, 0.5, 0.]));
+        assert!(c.intersects());
+        assert!((c.penetration - 0.2).abs() < 1e-6);
+        assert_close(&c.normal, &[1., 0., 0.]);
+        let c = capsule_to_capsule(&cap, &at([0., 0., 0.]), &cap, &at([1.5, 0., 0.]));
+        assert!(!c.intersects());
+        assert!((c.penetration + 0.5).abs() < 1e-6);
+        // end to end the hemispherical caps add both radii
+        let c = capsule_to_capsule(&cap, &at([0., 0., 0.]), &cap, &at([0., 2.8, 0.]));
+        assert!((c.penetration - 0.2).abs() < 1e-6);
+        assert_close(&c.normal, &[0., 1., 0.]);
+        // nearly crossing axes still give a normal perpendicular to both of them
+        let across = AffTrans::new([[0., 1., 0.], [-1., 0., 0.]], [0., 0., 1e-7]);
+        let c = capsule_to_capsule(&cap, &at([0., 0., 0.]), &cap, &across);
+        assert!((c.penetration - 1.).abs() < 1e-5);
+        assert!(c.normal[2].abs() > 0.999, "{:?}", c);
+    }
+
+    #[test]
+    fn test3() {
+        let c = ellipsoid_to_plane(&[1., 2., 1.], &at([3., 1.5, -1.]), &[[0., 0., 0.], [0., 1., 0.]]);
+        assert!(c.intersects());
+        assert!((c.penetration - 0.5).abs() < 1e-6);
+        assert_close(&c.normal, &[0., -1., 0.]);
+        assert_close(&c.point_a, &[3., -0.5, -1.]);
+        assert_close(&c.point_b, &[3., 0., -1.]);
+    }
+
+    #[test]
+    fn test4() {
+        // spheres
+        let c = ellipsoid_to_ellipsoid(&[1., 1., 1.], &at([0., 0., 0.]), &[2., 2., 2.], &at([1., 2., 2.]));
+        assert!((c.penetration - 0.).abs() < 1e-4, "{:?}", c);
+        assert_close(&c.normal, &[1. / 3., 2. / 3., 2. / 3.]);
+        let c = ellipsoid_to_ellipsoid(&[1., 1., 1.], &at([0., 0., 0.]), &[1., 1., 1.], &at([0., 3., 0.]));
+        assert!((c.penetration + 1.).abs() < 1e-4, "{:?}", c);
+        assert_close(&c.point_a, &[0., 1., 0.]);
+        assert_close(&c.point_b, &[0., 2., 0.]);
+    }
+
+    #[test]
+    fn test5() {
+        let c = ellipsoid_to_ellipsoid(&[2., 1., 1.], &at([0., 0., 0.]), &[1., 1., 1.], &at([2.5, 0., 0.]));
+        assert!(c.intersects());
+        assert!((c.penetration - 0.5).abs() < 1e-4, "{:?}", c);
+        assert_close(&c.normal, &[1., 0., 0.]);
+        // rotate the first ellipsoid so that its long axis points along Y
+        let rotated = AffTrans::new([[0., 1., 0.], [-1., 0., 0.]], [0., 0., 0.]);
+        let c = ellipsoid_to_ellipsoid(&[2., 1., 1.], &rotated, &[1., 1., 1.], &at([2.5, 0., 0.]));
+        assert!(!c.intersects());
+        assert!((c.penetration + 0.5).abs() < 1e-4, "{:?}", c);
+    }
+
+    #[test]
+    fn test6() {
+        let cyl = [0.5, 1.];
+        // side by side it agrees with capsules
+        let c = cylinder_to_cylinder(&cyl, &at([0., 0., 0.]), &cyl, &at([0.8, 0.5, 0.]));
+        assert!((c.penetration - 0.2).abs() < 1e-5, "{:?}", c);
+        assert_close(&c.normal, &[1., 0., 0.]);
+        assert_close(&c.point_a, &[0.5, 0.5, 0.]);
+        assert_close(&c.point_b, &[0.3, 0., 0.]);
+        let c = cylinder_to_cylinder(&cyl, &at([0., 0., 0.]), &cyl, &at([1.5, 0., 0.]));
+        assert!((c.penetration + 0.5).abs() < 1e-5, "{:?}", c);
+        // end to end the flat caps touch, without any hemispheres sticking out
+        let c = cylinder_to_cylinder(&cyl, &at([0., 0., 0.]), &cyl, &at([0., 1.8, 0.]));
+        assert!((c.penetration - 0.2).abs() < 1e-5, "{:?}", c);
+        assert_close(&c.normal, &[0., 1., 0.]);
+        assert_close(&c.point_a, &[0., 1., 0.]);
+        assert_close(&c.point_b, &[0., 0.8, 0.]);
+        let c = cylinder_to_cylinder(&cyl, &at([0., 0., 0.]), &cyl, &at([0.3, 2.5, 0.]));
+        assert!((c.penetration + 0.5).abs() < 1e-5, "{:?}", c);
+        assert_close(&c.point_a, &[0.3, 1., 0.]);
+        assert_close(&c.point_b, &[0., 1.5, 0.]);
+        // second cylinder lies across the top cap of the first one
+        let across = AffTrans::new([[0., 1., 0.], [-1., 0., 0.]], [0., 1.4, 0.]);
+        let c = cylinder_to_cylinder(&cyl, &at([0., 0., 0.]), &cyl, &across);
+        assert!((c.penetration - 0.1).abs() < 1e-5, "{:?}", c);
+        assert_close(&c.normal, &[0., 1., 0.]);
+        assert_close(&c.point_b, &[0., 0.9, 0.]);
+        // tilted cylinders are compared against brute force search over directions
+        let (a, b) = (0.7f32, 1.1f32);
+        let tran1 = AffTrans::new([[1., 0., 0.], [0., a.cos(), a.sin()]], [0., 0., 0.]);
+        let tran2 = AffTrans::new([[b.cos(), b.sin(), 0.], [-b.sin(), b.cos(), 0.]], [0.6, 1.3, 0.4]);
+        let c = cylinder_to_cylinder(&cyl, &tran1, &[0.3, 0.8], &tran2);
+        let brute_force = brute_force_overlap(&cyl, &tran1, &[0.3, 0.8], &tran2, 200);
+        assert!((c.penetration - brute_force).abs() < 1e-3, "{:?} {}", c, brute_force);
+    }
+
+    /**Minimum of the overlap over a grid of directions*/
+    fn brute_force_overlap(cyl1: &Cylinder, tran1: &AffTrans<f32, 3>, cyl2: &Cylinder, tran2: &AffTrans<f32, 3>, samples: usize) -> f32 {
+        let d = tran2.translation().sub(tran1.translation());
+        (0..samples * samples).map(|i| {
+            let theta = std::f32::consts::PI * (i / samples) as f32 / (samples - 1) as f32;
+            let phi = 2. * std::f32::consts::PI * (i % samples) as f32 / samples as f32;
+            let n = [theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()];
+            cylinder_support(cyl1, &tran1.axis()[1], &n, &d).1 + cylinder_support(cyl2, &tran2.axis()[1], &n, &d).1 - dot0(&n, &d)
+        }).fold(f32::INFINITY, f32::min)
+    }
+
+    #[test]
+    fn test7() {
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(5);
+        let rigid = |rng: &mut rand::rngs::StdRng| {
+            let x = [rng.gen_range(-1f32..1.), rng.gen_range(-1f32..1.), rng.gen_range(-1f32..1.)];
+            let x = x.mul_scalar(1. / length(&x));
+            let t = [rng.gen_range(-1.5f32..1.5), rng.gen_range(-1.5f32..1.5), rng.gen_range(-1.5f32..1.5)];
+            AffTrans::new([x, perpendicular(&x)], t)
+        };
+        for _ in 0..30 {
+            let (tran1, tran2) = (rigid(&mut rng), rigid(&mut rng));
+            let cyl1 = [rng.gen_range(0.1f32..0.8), rng.gen_range(0.1f32..1.)];
+            let cyl2 = [rng.gen_range(0.1f32..0.8), rng.gen_range(0.1f32..1.)];
+            let c = cylinder_to_cylinder(&cyl1, &tran1, &cyl2, &tran2);
+            let brute_force = brute_force_overlap(&cyl1, &tran1, &cyl2, &tran2, 100);
+            // brute force only samples some directions, so it can't find a lower value, but it can miss the exact minimum
+            assert!(c.penetration <= brute_force + 1e-4, "{:?} {}", c, brute_force);
+            assert!(c.penetration >= brute_force - 0.05, "{:?} {}", c, brute_force);
+            assert!((c.point_b.sub(&c.point_a).iter().zip(&c.normal).map(|(p, n)| p * n).sum::<f32>() + c.penetration).abs() < 1e-3, "{:?}", c);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use crate::histogram::Pixel;
+
+/**Colour spaces that RGB images can be converted to. Functions `rgb_to_*` and `*_to_rgb` work in the conventional units
+of every space, while `ColorSpace::from_rgb` and `ColorSpace::to_rgb` scale all channels to `0..=1`, so that they can be
+sorted into the same histogram bins.*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ColorSpace {
+    Rgb,
+    /**Hue, saturation, value. Hue is divided by 360 degrees*/
+    Hsv,
+    /**Full range BT.601 luma and chroma (as used by JPEG)*/
+    YCbCr,
+    /**CIELab under D65 white point. `L` is divided by 100, while `a` and `b` are divided by 256 and shifted by 0.5*/
+    Lab,
+}
+
+impl ColorSpace {
+    /**Index of the channel that carries brightness. It's `None` for RGB, where brightness is spread across all channels.*/
+    pub fn luminance(&self) -> Option<usize> {
+        match self {
+            Self::Rgb => None,
+            Self::Hsv => Some(2),
+            Self::YCbCr | Self::Lab => Some(0),
+        }
+    }
+    /**`rgb` is in `0..=1`*/
+    pub fn from_rgb(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let c = match self {
+            Self::Rgb => rgb,
+            Self::Hsv => {
+                let [h, s, v] = rgb_to_hsv(rgb);
+                [h / 360., s, v]
+            }
+            Self::YCbCr => rgb_to_ycbcr(rgb),
+            Self::Lab => {
+                let [l, a, b] = rgb_to_lab(rgb);
+                [l / 100., a / 256. + 0.5, b / 256. + 0.5]
+            }
+        };
+        // rounding errors could otherwise push values slightly outside of the range
+        c.map(|v| v.clamp(0., 1.))
+    }
+    /**Inverse of `from_rgb`. Colours outside of the RGB gamut are clamped to `0..=1`*/
+    pub fn to_rgb(&self, c: [f32; 3]) -> [f32; 3] {
+        let rgb = match self {
+            Self::Rgb => c,
+            Self::Hsv => hsv_to_rgb([c[0] * 360., c[1], c[2]]),
+            Self::YCbCr => ycbcr_to_rgb(c),
+            Self::Lab => lab_to_rgb([c[0] * 100., (c[1] - 0.5) * 256., (c[2] - 0.5) * 256.]),
+        };
+        rgb.map(|v| v.clamp(0., 1.))
+    }
+    /**`image` of shape `[height, width, 3]` in RGB is converted to this colour space. All channels are in `0..=1`.
+     Float images are expected to lie in `0..=1` as well, anything outside (such as HDR values) gets clipped.*/
+    pub fn image_from_rgb<P: Pixel>(&self, image: &[P]) -> Vec<f32> {
+        assert_eq!(image.len() % 3, 0);
+        let mut out = Vec::with_capacity(image.len());
+        for rgb in image.chunks_exact(3) {
+            out.extend(self.from_rgb([rgb[0].to_unit(), rgb[1].to_unit(), rgb[2].to_unit()]));
+        }
+        out
+    }
+    /**Inverse of `image_from_rgb`*/
+    pub fn image_to_rgb<P: Pixel>(&self, image: &[f32]) -> Vec<P> {
+        assert_eq!(image.len() % 3, 0);
+        let mut out = Vec::with_capacity(image.len());
+        for c in image.chunks_exact(3) {
+            out.extend(self.to_rgb([c[0], c[1], c[2]]).map(P::from_unit));
+        }
+        out
+    }
+}
+
+/**Hue is in degrees `0..360`, saturation and value in `0..=1`*/
+pub fn rgb_to_hsv([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let d = max - min;
+    let s = if max > 0. { d / max } else { 0. };
+    let h = if d == 0. {
+        0.
+    } else if max == r {
+        60. * ((g - b) / d).rem_euclid(6.)
+    } else if max == g {
+        60. * ((b - r) / d + 2.)
+    } else {
+        60. * ((r - g) / d + 4.)
+    };
+    [h, s, max]
+}
+
+pub fn hsv_to_rgb([h, s, v]: [f32; 3]) -> [f32; 3] {
+    let c = v * s;
+    let h = h.rem_euclid(360.) / 60.;
+    let x = c * (1. - (h % 2. - 1.).abs());
+    let [r, g, b] = match h as usize {
+        0 => [c, x, 0.],
+        1 => [x, c, 0.],
+        2 => [0., c, x],
+        3 => [0., x, c],
+        4 => [x, 0., c],
+        _ => [c, 0., x],
+    };
+    let m = v - c;
+    [r + m, g + m, b + m]
+}
+
+/**Chroma channels are shifted by 0.5, so all channels are in `0..=1`*/
+pub fn rgb_to_ycbcr([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let y = 0.299 * r + 0.587 * g + 0.114 * b;
+    [y, 0.5 + (b - y) / 1.772, 0.5 + (r - y) / 1.402]
+}
+
+pub fn ycbcr_to_rgb([y, cb, cr]: [f32; 3]) -> [f32; 3] {
+    let r = y + 1.402 * (cr - 0.5);
+    let b = y + 1.772 * (cb - 0.5);
+    let g = (y - 0.299 * r - 0.114 * b) / 0.587;
+    [r, g, b]
+}
+
+const D65: [f32; 3] = [0.95047, 1., 1.08883];
+const DELTA: f32 = 6. / 29.;
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 { 12.92 * c } else { 1.055 * c.powf(1. / 2.4) - 0.055 }
+}
+
+/**`rgb` is sRGB in `0..=1`. `L` is in `0..=100`, while `a` and `b` are roughly in `-128..128`*/
+pub fn rgb_to_lab(rgb: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb.map(srgb_to_linear);
+    let xyz = [
+        0.4124564 * r + 0.3575761 * g + 0.1804375 * b,
+        0.2126729 * r + 0.7151522 * g + 0.0721750 * b,
+        0.0193339 * r + 0.1191920 * g + 0.9503041 * b,
+    ];
+    let f = |t: f32| if t > DELTA * DELTA * DELTA { t.cbrt() } else { t / (3. * DELTA * DELTA) + 4. / 29. };
+    let [fx, fy, fz] = [f(xyz[0] / D65[0]), f(xyz[1] / D65[1]), f(xyz[2] / D65[2])];
+    [116. * fy - 16., 500. * (fx - fy), 200. * (fy - fz)]
+}
+
+pub fn lab_to_rgb([l, a, b]: [f32; 3]) -> [f32; 3] {
+    let fy = (l + 16.) / 116.;
+    let f_inv = |t: f32| if t > DELTA { t * t * t } else { 3. * DELTA * DELTA * (t - 4. / 29.) };
+    let [x, y, z] = [D65[0] * f_inv(fy + a / 500.), D65[1] * f_inv(fy), D65[2] * f_inv(fy - b / 200.)];
+    [
+        3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+        -0.9692660 * x + 1.8760108 * y + 0.0415560 * z,
+        0.0556434 * x - 0.2040259 * y + 1.0572252 * z,
+    ].map(linear_to_srgb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: [f32; 3], b: [f32; 3], eps: f32) -> bool {
+        a.iter().zip(b).all(|(a, b)| (a - b).abs() < eps)
+    }
+
+    #[test]
+    fn test1() {
+        assert_eq!(rgb_to_hsv([1., 0., 0.]), [0., 1., 1.]);
+        assert_eq!(rgb_to_hsv([0., 0., 1.]), [240., 1., 1.]);
+        assert!(close(rgb_to_ycbcr([1., 1., 1.]), [1., 0.5, 0.5], 1e-6));
+        assert!(close(rgb_to_lab([1., 1., 1.]), [100., 0., 0.], 1e-2));
+        assert!(close(rgb_to_lab([1., 0., 0.]), [53.24, 80.09, 67.20], 1e-1));
+    }
+
+    #[test]
+    fn test2() {
+        // every u8 colour survives the round trip
+        for cs in [ColorSpace::Rgb, ColorSpace::Hsv, ColorSpace::YCbCr, ColorSpace::Lab] {
+            let image: Vec<u8> = (0..=255u8).step_by(5).flat_map(|r| (0..=255u8).step_by(15).flat_map(move |g| (0..=255u8).step_by(51).flat_map(move |b| [r, g, b]))).collect();
+            let c = cs.image_from_rgb(&image);
+            assert!(c.iter().all(|&v| 0. <= v && v <= 1.), "{:?}", cs);
+            assert_eq!(cs.image_to_rgb::<u8>(&c), image, "{:?}", cs);
+        }
+    }
+}
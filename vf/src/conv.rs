@@ -95,6 +95,99 @@ pub fn stride<T: Debug + Rem<Output=T> + Copy + Div<Output=T> + Add<Output=T> +
     //(input-kernel)/(output-1) == stride
 }
 
+/**Padding of a convolution. Both variants follow PyTorch semantics.*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Padding<T, const DIM: usize> {
+    /**Adds the given number of zeros on both sides of every axis. `Zero([0;DIM])` means no padding at all*/
+    Zero([T; DIM]),
+    /**Chooses padding so that the output is as large as the input. It requires stride 1. When the total padding
+    along some axis is odd, the extra zero goes to the end (just like in PyTorch)*/
+    Same,
+}
+
+impl<T: Copy + Zero, const DIM: usize> Default for Padding<T, DIM> {
+    fn default() -> Self {
+        Padding::Zero([T::zero(); DIM])
+    }
+}
+
+impl<T: Copy + Add<Output=T> + Sub<Output=T> + Mul<Output=T> + Div<Output=T> + One, const DIM: usize> Padding<T, DIM> {
+    /**Number of zeros added at the beginning and at the end of every axis*/
+    pub fn begin_end(&self, kernel: &[T; DIM], dilation: &[T; DIM]) -> ([T; DIM], [T; DIM]) {
+        match self {
+            Padding::Zero(p) => (*p, *p),
+            Padding::Same => {
+                let total = dilated_kernel(kernel, dilation)._sub_scalar(T::one());
+                let begin = total.div_scalar(T::one() + T::one());
+                (begin, total._sub(&begin))
+            }
+        }
+    }
+}
+
+/**Span of kernel after dilation. Dilation `d` inserts `d-1` gaps between neighbouring weights of the kernel*/
+pub fn dilated_kernel<T: Copy + Mul<Output=T> + Add<Output=T> + Sub<Output=T> + One, const DIM: usize>(kernel: &[T; DIM], dilation: &[T; DIM]) -> [T; DIM] {
+    kernel.sub_scalar(T::one())._mul(dilation)._add_scalar(T::one())
+}
+
+/**Same as `out_size` but for convolution whose input is padded with `padding_begin` and `padding_end` zeros and whose kernel is dilated.
+Just like in PyTorch, the trailing inputs that don't fill an entire stride are ignored, so unlike `out_size` this function doesn't require
+the stride to evenly divide the input*/
+pub fn out_size_padded<T: Debug + Copy + Mul<Output=T> + Div<Output=T> + Add<Output=T> + Sub<Output=T> + Ord + One, const DIM: usize>(input: &[T; DIM], stride: &[T; DIM], kernel_size: &[T; DIM], padding_begin: &[T; DIM], padding_end: &[T; DIM], dilation: &[T; DIM]) -> [T; DIM] {
+    let kernel_size = dilated_kernel(kernel_size, dilation);
+    let padded = input.add(padding_begin)._add(padding_end);
+    assert!(kernel_size.all_le(&padded), "Dilated kernel size {:?} is larger than the padded input shape {:?} ", kernel_size, padded);
+    padded._sub(&kernel_size)._div(stride)._add_scalar(T::one())
+    //(input+padding_begin+padding_end-dilation*(kernel-1)-1)/stride+1 == output
+}
+
+/**Smallest input that yields the given output. It's the inverse of `out_size_padded`. If the padding alone is larger
+than necessary, the output cannot be that small and then the returned input is 1 (which yields more outputs than requested)*/
+pub fn in_size_padded<T: Debug + Copy + Mul<Output=T> + Div<Output=T> + Add<Output=T> + Sub<Output=T> + Zero + Ord + One, const DIM: usize>(output: &[T; DIM], stride: &[T; DIM], kernel_size: &[T; DIM], padding_begin: &[T; DIM], padding_end: &[T; DIM], dilation: &[T; DIM]) -> [T; DIM] {
+    let padded = in_size(output, stride, &dilated_kernel(kernel_size, dilation));
+    let padding = padding_begin.add(padding_end);
+    padded._max(&padding.add_scalar(T::one()))._sub(&padding)
+}
+
+/**Same as `in_range` but for convolution with padding and dilation. The range is expressed in coordinates of
+the original (unpadded) input and it's clipped to `input_size`, so near the borders it may be smaller than the dilated kernel.
+With dilation, only some of the inputs within the range are connected to the output (see `kernel_pos`).*/
+pub fn in_range_clipped_padded<T: Copy + Mul<Output=T> + Add<Output=T> + Sub<Output=T> + One + Zero + Ord, const DIM: usize>(out_position: &[T; DIM], stride: &[T; DIM], kernel_size: &[T; DIM], padding_begin: &[T; DIM], dilation: &[T; DIM], input_size: &[T; DIM]) -> Range<[T; DIM]> {
+    let r = in_range(out_position, stride, &dilated_kernel(kernel_size, dilation));
+    let from = r.start._max(padding_begin)._sub(padding_begin)._min(input_size);
+    let to = r.end._max(padding_begin)._sub(padding_begin)._min(input_size);
+    from..to
+}
+
+/**Same as `out_range_clipped_both_sides` but for convolution with padding and dilation. The input position is
+expressed in coordinates of the original (unpadded) input. With dilation, only some of the outputs within the range
+are connected to the input (see `kernel_pos`).*/
+pub fn out_range_clipped_padded<T: Copy + Mul<Output=T> + Div<Output=T> + Add<Output=T> + One + Sub<Output=T> + Ord, const DIM: usize>(in_position: &[T; DIM], stride: &[T; DIM], kernel_size: &[T; DIM], padding_begin: &[T; DIM], dilation: &[T; DIM], max_bounds: &[T; DIM]) -> Range<[T; DIM]> {
+    out_range_clipped_both_sides(&in_position.add(padding_begin), stride, &dilated_kernel(kernel_size, dilation), max_bounds)
+}
+
+/**Position within the (undilated) kernel, through which the input at `in_position` (in coordinates of unpadded input)
+is connected to the output at `out_position`. Returns `None` if they are not connected at all.*/
+pub fn kernel_pos<T: Copy + Mul<Output=T> + Div<Output=T> + Rem<Output=T> + Add<Output=T> + Sub<Output=T> + Zero + Ord, const DIM: usize>(in_position: &[T; DIM], out_position: &[T; DIM], stride: &[T; DIM], kernel_size: &[T; DIM], padding_begin: &[T; DIM], dilation: &[T; DIM]) -> Option<[T; DIM]> {
+    let mut pos = [T::zero(); DIM];
+    for i in 0..DIM {
+        let begin = out_position[i] * stride[i];
+        let padded = in_position[i] + padding_begin[i];
+        if padded < begin {
+            return None;
+        }
+        let offset = padded - begin;
+        if !(offset % dilation[i]).is_zero() {
+            return None;
+        }
+        pos[i] = offset / dilation[i];
+        if pos[i] >= kernel_size[i] {
+            return None;
+        }
+    }
+    Some(pos)
+}
+
 pub fn compose<T: Copy + Div<Output=T> + Add<Output=T> + Sub<Output=T> + Ord + One, const DIM: usize>(self_stride: &[T; DIM], self_kernel: &[T; DIM], next_stride: &[T; DIM], next_kernel: &[T; DIM]) -> ([T; DIM], [T; DIM]) {
     //(A-kernelA)/strideA+1 == B
     //(B-kernelB)/strideB+1 == C
@@ -109,6 +202,19 @@ pub fn compose<T: Copy + Div<Output=T> + Add<Output=T> + Sub<Output=T> + Ord + O
     (composed_stride, composed_kernel)
 }
 
+/**Same as `compose` but for convolutions with padding and dilation. Returns composed stride, kernel and padding
+(the same formula works for padding at the beginning and at the end). The composed convolution is never dilated.
+Keep in mind that the second convolution pads hidden activations with zeros rather than with outputs of the first
+convolution applied to padded input. Hence, the composition agrees with the two original convolutions only away from the borders.*/
+pub fn compose_padded<T: Copy + Mul<Output=T> + Div<Output=T> + Add<Output=T> + Sub<Output=T> + Ord + One, const DIM: usize>(self_stride: &[T; DIM], self_kernel: &[T; DIM], self_padding: &[T; DIM], self_dilation: &[T; DIM], next_stride: &[T; DIM], next_kernel: &[T; DIM], next_padding: &[T; DIM], next_dilation: &[T; DIM]) -> ([T; DIM], [T; DIM], [T; DIM]) {
+    //hidden position h of next output z is z*next_stride - next_padding + j*next_dilation
+    //input position x of hidden h is h*self_stride - self_padding + i*self_dilation
+    //therefore x == z*next_stride*self_stride - (next_padding*self_stride + self_padding) + j*next_dilation*self_stride + i*self_dilation
+    let (composed_stride, composed_kernel) = compose(self_stride, &dilated_kernel(self_kernel, self_dilation), next_stride, &dilated_kernel(next_kernel, next_dilation));
+    let composed_padding = next_padding.mul(self_stride)._add(self_padding);
+    (composed_stride, composed_kernel, composed_padding)
+}
+
 /**First run `compose` to obtain `comp_stride` and `comp_kernel`. The shapes of tensors are
  ```
 self_weights.shape==[self_out_channels, self_in_channels, self_kernel[0], self_kernel[1]]
@@ -130,6 +236,18 @@ pub fn compose_weights2d<T: Copy + Mul<Output=T> + Add<Output=T> + One + AsPrimi
     self_stride: &[T; 2], self_kernel: &[T; 2], self_weights: &[f32], self_bias: &[f32],
     next_kernel: &[T; 2], next_weights: &[f32], next_bias: &[f32],
     comp_kernel: &[T; 2], comp_weights: &mut [f32], comp_bias: &mut [f32]) {
+    compose_weights2d_dilated(self_in_channels, self_stride, self_kernel, &[T::one(); 2], self_weights, self_bias,
+                              next_kernel, &[T::one(); 2], next_weights, next_bias,
+                              comp_kernel, comp_weights, comp_bias)
+}
+
+/**Same as `compose_weights2d` but the kernels may be dilated. Run `compose_padded` to obtain `comp_kernel`. Padding has
+no influence on the composed weights.*/
+pub fn compose_weights2d_dilated<T: Copy + Mul<Output=T> + Add<Output=T> + One + AsPrimitive<usize>>(
+    self_in_channels: T,
+    self_stride: &[T; 2], self_kernel: &[T; 2], self_dilation: &[T; 2], self_weights: &[f32], self_bias: &[f32],
+    next_kernel: &[T; 2], next_dilation: &[T; 2], next_weights: &[f32], next_bias: &[f32],
+    comp_kernel: &[T; 2], comp_weights: &mut [f32], comp_bias: &mut [f32]) {
     let self_in_channels = self_in_channels.as_();
     let self_out_channels = self_bias.len();
     let next_in_channels = self_out_channels;
@@ -189,8 +307,8 @@ pub fn compose_weights2d<T: Copy + Mul<Output=T> + Add<Output=T> + One + AsPrimi
                     let b_self = self_bias[self_out_channel];
                     comp_bias[next_out_channel] += b_self * w_next;
                     /*(hidden_x,hidden_y) is like the index of y*/
-                    let hidden_x = next_x * self_stride[0].as_();
-                    let hidden_y = next_y * self_stride[1].as_();
+                    let hidden_x = next_x * next_dilation[0].as_() * self_stride[0].as_();
+                    let hidden_y = next_y * next_dilation[1].as_() * self_stride[1].as_();
                     /*(self_x,self_y) is like the index of lowercase w*/
                     for self_in_channel in 0..self_in_channels {
                         let w_comp_offset = (next_out_channel * self_in_channels + self_in_channel) * comp_ker_area;
@@ -201,8 +319,8 @@ pub fn compose_weights2d<T: Copy + Mul<Output=T> + Add<Output=T> + One + AsPrimi
                                 let w_self = self_weights[w_self_offset + self_x * self_kernel[1].as_() + self_y];
                                 let w_comp = w_self * w_next;
                                 /* (comp_x,comp_y) is like the index of x*/
-                                let comp_x = hidden_x + self_x;
-                                let comp_y = hidden_y + self_y;
+                                let comp_x = hidden_x + self_x * self_dilation[0].as_();
+                                let comp_y = hidden_y + self_y * self_dilation[1].as_();
                                 comp_weights[w_comp_offset + comp_x * comp_kernel[1].as_() + comp_y] += w_comp;
                             }
                         }
@@ -280,4 +398,119 @@ mod tests {
             }
         }
     }
+    #[test]
+    fn test8() {
+        // PyTorch: out = floor((in + 2*padding - dilation*(kernel-1) - 1)/stride) + 1
+        for input in 1..12 {
+            for kernel in 1..4 {
+                for stride in 1..4 {
+                    for padding in 0..3 {
+                        for dilation in 1..3 {
+                            let span = dilation * (kernel - 1) + 1;
+                            if span > input + 2 * padding {
+                                continue;
+                            }
+                            let out = out_size_padded(&[input], &[stride], &[kernel], &[padding], &[padding], &[dilation]);
+                            assert_eq!(out, [(input + 2 * padding - span) / stride + 1]);
+                            let min_in = in_size_padded(&out, &[stride], &[kernel], &[padding], &[padding], &[dilation]);
+                            assert!(min_in[0] <= input);
+                            let min_out = out_size_padded(&min_in, &[stride], &[kernel], &[padding], &[padding], &[dilation]);
+                            if min_in[0] > 1 {
+                                assert_eq!(out, min_out);
+                            } else {
+                                assert!(out[0] <= min_out[0]);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test9() {
+        for input in 1..10 {
+            for kernel in 1..5 {
+                for dilation in 1..4 {
+                    let (b, e) = Padding::Same.begin_end(&[kernel], &[dilation]);
+                    assert!(b[0] <= e[0]);
+                    assert_eq!([input], out_size_padded(&[input], &[1], &[kernel], &b, &e, &[dilation]));
+                }
+            }
+        }
+        assert_eq!(Padding::Same.begin_end(&[4u32], &[1]), ([1], [2]));
+        assert_eq!(Padding::Zero([3u32]).begin_end(&[4], &[1]), ([3], [3]));
+    }
+
+    #[test]
+    fn test10() {
+        let input = 9;
+        for kernel in 1..4 {
+            for stride in 1..4 {
+                for padding in 0..3 {
+                    for dilation in 1..3 {
+                        let out = out_size_padded(&[input], &[stride], &[kernel], &[padding], &[padding], &[dilation]);
+                        for o in 0..out[0] {
+                            let r = in_range_clipped_padded(&[o], &[stride], &[kernel], &[padding], &[dilation], &[input]);
+                            for i in 0..input {
+                                let k = kernel_pos(&[i], &[o], &[stride], &[kernel], &[padding], &[dilation]);
+                                // brute force definition of convolution
+                                let expected = (0..kernel).find(|&k| o * stride + k * dilation == i + padding);
+                                assert_eq!(k, expected.map(|k| [k]));
+                                if k.is_some() {
+                                    assert!(r.start[0] <= i && i < r.end[0]);
+                                    let o_r = out_range_clipped_padded(&[i], &[stride], &[kernel], &[padding], &[dilation], &out);
+                                    assert!(o_r.start[0] <= o && o < o_r.end[0]);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test11() {
+        // composing two dilated convolutions must agree with applying them one after another
+        let self_kernel = [2usize, 3];
+        let self_dilation = [2usize, 1];
+        let self_stride = [1usize, 2];
+        let next_kernel = [2usize, 2];
+        let next_dilation = [1usize, 2];
+        let next_stride = [1usize, 1];
+        let (comp_stride, comp_kernel, comp_padding) = compose_padded(&self_stride, &self_kernel, &[0, 0], &self_dilation, &next_stride, &next_kernel, &[0, 0], &next_dilation);
+        assert_eq!(comp_stride, [1, 2]);
+        assert_eq!(comp_padding, [0, 0]);
+        let self_w: Vec<f32> = (0..6).map(|i| i as f32 - 2.).collect();
+        let next_w: Vec<f32> = (0..4).map(|i| i as f32 * 0.5 + 1.).collect();
+        let mut comp_w = vec![0.; comp_kernel.product()];
+        let mut comp_b = vec![0.];
+        compose_weights2d_dilated(1, &self_stride, &self_kernel, &self_dilation, &self_w, &[1.], &next_kernel, &next_dilation, &next_w, &[2.], &comp_kernel, &mut comp_w, &mut comp_b);
+        let input = [9usize, 13];
+        let x: Vec<f32> = (0..input.product()).map(|i| (i * 7 % 5) as f32).collect();
+        let conv = |x: &[f32], shape: [usize; 2], w: &[f32], b: f32, kernel: [usize; 2], stride: [usize; 2], dilation: [usize; 2]| {
+            let out = out_size_padded(&shape, &stride, &kernel, &[0, 0], &[0, 0], &dilation);
+            let mut y = vec![b; out.product()];
+            for o0 in 0..out[0] {
+                for o1 in 0..out[1] {
+                    for k0 in 0..kernel[0] {
+                        for k1 in 0..kernel[1] {
+                            let i0 = o0 * stride[0] + k0 * dilation[0];
+                            let i1 = o1 * stride[1] + k1 * dilation[1];
+                            y[o0 * out[1] + o1] += w[k0 * kernel[1] + k1] * x[i0 * shape[1] + i1];
+                        }
+                    }
+                }
+            }
+            (y, out)
+        };
+        let (hidden, hidden_shape) = conv(&x, input, &self_w, 1., self_kernel, self_stride, self_dilation);
+        let (expected, expected_shape) = conv(&hidden, hidden_shape, &next_w, 2., next_kernel, next_stride, next_dilation);
+        let (actual, actual_shape) = conv(&x, input, &comp_w, comp_b[0], comp_kernel, comp_stride, [1, 1]);
+        assert_eq!(expected_shape, actual_shape);
+        for (e, a) in expected.iter().zip(actual.iter()) {
+            assert!((e - a).abs() < 0.0001, "{} != {}", e, a);
+        }
+    }
 }
\ No newline at end of file
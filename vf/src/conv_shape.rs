@@ -6,8 +6,10 @@ use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Range, Rem, Sub};
 use std::process::Output;
 use num_traits::{AsPrimitive, MulAdd, Num, One, PrimInt, Zero};
 use crate::shape::Shape;
-use crate::{ArrayCast, conv, vec_range, VecCast, VectorField, VectorFieldAddAssign, VectorFieldOne, VectorFieldPartialOrd, VectorFieldSub};
-use crate::arr_concat::concat;
+use crate::slice_as_arr;
+use crate::conv::Padding;
+use crate::{ArrayCast, conv, vec_range, VecCast, VectorField, VectorFieldAddAssign, VectorFieldAddOwned, VectorFieldMul, VectorFieldOne, VectorFieldPartialOrd, VectorFieldSub};
+use crate::arr_concat::append;
 use crate::xyzw::{xy3, xy_z3, xy_z_w4, xy_zw4, xyz3, z3};
 use crate::from_usize::FromUsize;
 use crate::init::InitEmptyWithCapacity;
@@ -46,70 +48,118 @@ pub fn idx_<Idx: Debug + num_traits::PrimInt>(input_pos: &[Idx; 3], kernel_offse
     idx(output_idx, kernel_column.idx(&position_within_kernel_column), output_volume)
 }
 
+/**[..grid, channels]->grid*/
+pub fn column<X, const DIM: usize>(pos: &[X; DIM + 1]) -> &[X; DIM] {
+    slice_as_arr(&pos[..DIM])
+}
+
+/**[..grid, channels, other_channels]*/
+fn append2<X: Copy, const DIM: usize>(grid: &[X; DIM], channels: X, other_channels: X) -> [X; DIM + 2] {
+    let mut arr = [channels; DIM + 2];
+    arr[..DIM].copy_from_slice(grid);
+    arr[DIM + 1] = other_channels;
+    arr
+}
+
+/**[..grid, channels, ..other_grid, other_channels]*/
+fn columns2<X: Copy, const DIM: usize>(grid: &[X; DIM], channels: X, other_grid: &[X; DIM], other_channels: X) -> [X; 2 * DIM + 2] {
+    let mut arr = [channels; 2 * DIM + 2];
+    arr[..DIM].copy_from_slice(grid);
+    arr[DIM + 1..2 * DIM + 1].copy_from_slice(other_grid);
+    arr[2 * DIM + 1] = other_channels;
+    arr
+}
+
 pub fn sub_kernel_offset<Idx: Debug + Copy + Sub<Output=Idx>>(input_pos: &[Idx; 3], offset: &[Idx; 2]) -> [Idx; 3] {
     xy_z3(xy3(input_pos).sub(offset), *z3(input_pos))
 }
 
 
+/**Shape of convolution over `DIM`-dimensional grid of columns. Every column holds a number of channels, so
+the input and output shapes are `[..grid, channels]`. The default is two dimensional convolution whose shapes are
+`[height, width, channels]`.*/
 #[derive(Clone, Debug)]
-pub struct ConvShape<Idx: Debug + PrimInt> {
+pub struct ConvShape<Idx: Debug + PrimInt, const DIM: usize = 2> where [(); DIM + 1]: {
     /**[in_height, in_width, in_channels]*/
-    input_shape: [Idx; 3],
+    input_shape: [Idx; DIM + 1],
     /**[out_height, out_width, out_channels]*/
-    output_shape: [Idx; 3],
+    output_shape: [Idx; DIM + 1],
     /**[kernel_height, kernel_width]*/
-    kernel: [Idx; 2],
+    kernel: [Idx; DIM],
+    /**[height, width]*/
+    stride: [Idx; DIM],
+    /**[height, width] number of zeros added before the first input*/
+    padding: [Idx; DIM],
+    /**[height, width] number of zeros added after the last input*/
+    padding_end: [Idx; DIM],
     /**[height, width]*/
-    stride: [Idx; 2],
+    dilation: [Idx; DIM],
 }
 
-impl<Idx: Debug + PrimInt> ConvShape<Idx> {
+pub type ConvShape1d<Idx> = ConvShape<Idx, 1>;
+pub type ConvShape2d<Idx> = ConvShape<Idx, 2>;
+pub type ConvShape3d<Idx> = ConvShape<Idx, 3>;
+
+impl<Idx: Debug + PrimInt, const DIM: usize> ConvShape<Idx, DIM> where [(); DIM + 1]:, [(); DIM + 2]:, [(); 2 * DIM + 2]: {
     /**[kernel_height, kernel_width, in_channels, out_height, out_width, out_channels]*/
-    pub fn w_shape(&self) -> [Idx; 6] {
-        let [kernel_height, kernel_width] = self.kernel().clone();
-        let [out_height, out_width, out_channels] = self.output_shape();
-        [kernel_height, kernel_width, self.in_channels(), out_height, out_width, out_channels]
+    pub fn w_shape(&self) -> [Idx; 2 * DIM + 2] {
+        columns2(self.kernel(), self.in_channels(), self.out_grid(), self.out_channels())
     }
     /**[out_height, out_width, out_channels, out_channels]*/
-    pub fn u_shape(&self) -> [Idx; 4] {
-        let [out_height, out_width, out_channels] = self.output_shape();
-        [out_height, out_width, out_channels, out_channels]
+    pub fn u_shape(&self) -> [Idx; DIM + 2] {
+        append2(self.out_grid(), self.out_channels(), self.out_channels())
     }
     /**[out_channels, out_channels]*/
     pub fn minicolumn_u_shape(&self) -> [Idx; 2] {
         [self.out_channels(), self.out_channels()]
     }
     /**[out_height, out_width, out_channels]*/
-    pub fn out_shape(&self) -> &[Idx; 3] {
+    pub fn out_shape(&self) -> &[Idx; DIM + 1] {
         &self.output_shape
     }
     /**[in_height, in_width, in_channels]*/
-    pub fn in_shape(&self) -> &[Idx; 3] {
+    pub fn in_shape(&self) -> &[Idx; DIM + 1] {
         &self.input_shape
     }
     /**[kernel_height, kernel_width]*/
-    pub fn kernel(&self) -> &[Idx; 2] {
+    pub fn kernel(&self) -> &[Idx; DIM] {
         &self.kernel
     }
     /**[height, width]*/
-    pub fn stride(&self) -> &[Idx; 2] {
+    pub fn stride(&self) -> &[Idx; DIM] {
         &self.stride
     }
+    /**[height, width] number of zeros added before the first input*/
+    pub fn padding(&self) -> &[Idx; DIM] {
+        &self.padding
+    }
+    /**[height, width] number of zeros added after the last input. It differs from `padding` only for `Padding::Same` with even kernels*/
+    pub fn padding_end(&self) -> &[Idx; DIM] {
+        &self.padding_end
+    }
+    /**[height, width]*/
+    pub fn dilation(&self) -> &[Idx; DIM] {
+        &self.dilation
+    }
+    /**[height, width] span of the kernel after dilation*/
+    pub fn dilated_kernel(&self) -> [Idx; DIM] {
+        conv::dilated_kernel(self.kernel(), self.dilation())
+    }
     /**[out_height, out_width, out_channels]*/
-    pub fn output_shape(&self) -> [Idx; 3] {
+    pub fn output_shape(&self) -> [Idx; DIM + 1] {
         self.out_shape().clone()
     }
     /**[in_height, in_width, in_channels]*/
-    pub fn input_shape(&self) -> [Idx; 3] {
+    pub fn input_shape(&self) -> [Idx; DIM + 1] {
         self.in_shape().clone()
     }
     /**[kernel_height, kernel_width, in_channels, out_channels]*/
-    pub fn minicolumn_w_shape(&self) -> [Idx; 4] {
-        xy_z_w4(self.kernel().clone(), self.in_channels(), self.out_channels())
+    pub fn minicolumn_w_shape(&self) -> [Idx; DIM + 2] {
+        append2(self.kernel(), self.in_channels(), self.out_channels())
     }
     /**[kernel_height, kernel_width, in_channels]*/
-    pub fn kernel_column_shape(&self) -> [Idx; 3] {
-        xy_z3(self.kernel().clone(), self.in_channels())
+    pub fn kernel_column_shape(&self) -> [Idx; DIM + 1] {
+        append(self.kernel(), &self.in_channels())
     }
     /**kernel_height * kernel_width*/
     pub fn kernel_column_area(&self) -> Idx where Idx: Mul<Output=Idx> + One {
@@ -120,30 +170,18 @@ impl<Idx: Debug + PrimInt> ConvShape<Idx> {
         self.kernel_column_area() * self.in_channels()
     }
     /**[in_height, in_width]*/
-    pub fn in_grid(&self) -> &[Idx; 2] {
-        grid(self.in_shape())
+    pub fn in_grid(&self) -> &[Idx; DIM] {
+        slice_as_arr(&self.input_shape[..DIM])
     }
     /**[out_height, out_width]*/
-    pub fn out_grid(&self) -> &[Idx; 2] {
-        grid(self.out_shape())
-    }
-    pub fn out_width(&self) -> Idx {
-        *width(self.out_shape())
-    }
-    pub fn out_height(&self) -> Idx {
-        *height(self.out_shape())
+    pub fn out_grid(&self) -> &[Idx; DIM] {
+        slice_as_arr(&self.output_shape[..DIM])
     }
     pub fn out_channels(&self) -> Idx {
-        *channels(self.out_shape())
-    }
-    pub fn in_width(&self) -> Idx {
-        *width(self.in_shape())
-    }
-    pub fn in_height(&self) -> Idx {
-        *height(self.in_shape())
+        self.output_shape[DIM]
     }
     pub fn in_channels(&self) -> Idx {
-        *channels(self.in_shape())
+        self.input_shape[DIM]
     }
     /**out_height * out_width*/
     pub fn out_area(&self) -> Idx where Idx: Mul<Output=Idx> + One {
@@ -161,31 +199,35 @@ impl<Idx: Debug + PrimInt> ConvShape<Idx> {
     pub fn in_volume(&self) -> Idx where Idx: Mul<Output=Idx> + One {
         self.in_shape().product()
     }
-    pub fn kernel_offset(&self, output_pos: &[Idx; 3]) -> [Idx; 2] where Idx: Mul<Output=Idx> {
-        conv::in_range_begin(grid(output_pos), self.stride())
+    /**Position of the first kernel weight, expressed in coordinates of padded input*/
+    pub fn kernel_offset(&self, output_pos: &[Idx; DIM + 1]) -> [Idx; DIM] where Idx: Mul<Output=Idx> {
+        conv::in_range_begin(column::<_, DIM>(output_pos), self.stride())
+    }
+    /**Position within the (undilated) kernel, through which input column connects to output column. `None` if they are not connected*/
+    pub fn kernel_pos(&self, input_column_pos: &[Idx; DIM], output_column_pos: &[Idx; DIM]) -> Option<[Idx; DIM]> {
+        conv::kernel_pos(input_column_pos, output_column_pos, self.stride(), self.kernel(), self.padding(), self.dilation())
     }
-    pub fn pos_within_kernel(&self, input_pos: &[Idx; 3], output_pos: &[Idx; 3]) -> [Idx; 3] {
+    pub fn pos_within_kernel(&self, input_pos: &[Idx; DIM + 1], output_pos: &[Idx; DIM + 1]) -> [Idx; DIM + 1] {
         debug_assert!(output_pos.all_lt(self.out_shape()));
         debug_assert!(input_pos.all_lt(self.in_shape()));
-        debug_assert!(vec_range::contains(&conv::in_range(grid(output_pos), self.stride(), self.kernel()), grid(input_pos)));
-        debug_assert!(vec_range::contains(&conv::out_range_clipped(grid(input_pos), self.stride(), self.kernel()), grid(output_pos)));
-        sub_kernel_offset(input_pos, &self.kernel_offset(output_pos))
+        let kernel_pos = self.kernel_pos(column::<_, DIM>(input_pos), column::<_, DIM>(output_pos)).expect("Input is not connected to output");
+        append(&kernel_pos, &input_pos[DIM])
     }
-    pub fn idx_within_kernel(&self, input_pos: &[Idx; 3], output_pos: &[Idx; 3]) -> Idx {
+    pub fn idx_within_kernel(&self, input_pos: &[Idx; DIM + 1], output_pos: &[Idx; DIM + 1]) -> Idx {
         self.kernel_column_shape().idx(&self.pos_within_kernel(input_pos, output_pos))
     }
-    pub fn in_range(&self, output_column_pos: &[Idx; 2]) -> Range<[Idx; 2]> {
+    /**Range of input columns within the receptive field of output column. With padding it's clipped to the input grid.
+    With dilation only some of the columns within the range are connected to output (see `kernel_pos`)*/
+    pub fn in_range(&self, output_column_pos: &[Idx; DIM]) -> Range<[Idx; DIM]> {
         assert!(output_column_pos.all_lt(self.out_grid()));
-        conv::in_range(output_column_pos, self.stride(), self.kernel())
+        conv::in_range_clipped_padded(output_column_pos, self.stride(), self.kernel(), self.padding(), self.dilation(), self.in_grid())
     }
-    pub fn out_range(&self, input_pos: &[Idx; 2]) -> Range<[Idx; 2]> {
-        conv::out_range_clipped_both_sides(input_pos, self.stride(), self.kernel(), self.out_grid())
+    /**Range of output columns, whose receptive fields contain the input column.
+    With dilation only some of the columns within the range are connected to input (see `kernel_pos`)*/
+    pub fn out_range(&self, input_pos: &[Idx; DIM]) -> Range<[Idx; DIM]> {
+        conv::out_range_clipped_padded(input_pos, self.stride(), self.kernel(), self.padding(), self.dilation(), self.out_grid())
     }
-    pub fn idx(&self, input_pos: &[Idx; 3], output_pos: &[Idx; 3]) -> Idx {
-        debug_assert!(output_pos.all_lt(self.out_shape()));
-        debug_assert!(input_pos.all_lt(self.in_shape()));
-        debug_assert!(vec_range::contains(&conv::in_range(grid(output_pos), self.stride(), self.kernel()), grid(input_pos)));
-        debug_assert!(vec_range::contains(&conv::out_range_clipped(grid(input_pos), self.stride(), self.kernel()), grid(output_pos)));
+    pub fn idx(&self, input_pos: &[Idx; DIM + 1], output_pos: &[Idx; DIM + 1]) -> Idx {
         idx(self.out_shape().idx(output_pos), self.idx_within_kernel(input_pos, output_pos), self.out_volume())
     }
     /**conv_tensor is of shape [kernel_height, kernel_width, in_channels, out_channels]*/
@@ -223,14 +265,14 @@ impl<Idx: Debug + PrimInt> ConvShape<Idx> {
         let v = self.out_volume();
         let mut used_w = HashSet::new();
         for &input_idx in lhs_tensor {
-            let input_pos: [Idx; 3] = self.in_shape().pos(input_idx);
-            let r = self.out_range(grid(&input_pos));
-            vec_range::foreach2d(&r, |output_pos| {
-                let kernel_offset = conv::in_range_begin(&output_pos, self.stride());
+            let input_pos: [Idx; DIM + 1] = self.in_shape().pos(input_idx);
+            let r = self.out_range(column::<_, DIM>(&input_pos));
+            vec_range::foreach(&r, |output_pos| if let Some(kernel_pos) = self.kernel_pos(column::<_, DIM>(&input_pos), &output_pos) {
+                let idx_within_kernel_column = kernel_column.idx(&append(&kernel_pos, &input_pos[DIM]));
                 for p2 in Idx::zero()..self.out_channels() {
-                    let output_pos = xy_z3(output_pos.clone(), p2);
+                    let output_pos = append(&output_pos, &p2);
                     let output_idx = self.out_shape().idx(&output_pos);
-                    let w_index = idx_(&input_pos, &kernel_offset, output_idx, &kernel_column, v);
+                    let w_index = idx(output_idx, idx_within_kernel_column, v);
                     debug_assert_eq!(w_index, self.idx(&input_pos, &output_pos));
                     debug_assert!(used_w.insert(w_index), "{:?}", w_index);
                     target(output_idx, w_index);
@@ -253,14 +295,14 @@ impl<Idx: Debug + PrimInt> ConvShape<Idx> {
     pub fn sparse_dot_repeated(&self, lhs_tensor: &[Idx], mut target: impl FnMut(Idx, Idx)) where Idx: Step {
         let kernel_column = self.kernel_column_shape();
         for &input_idx in lhs_tensor {
-            let input_pos: [Idx; 3] = self.in_shape().pos(input_idx);
-            let r = self.out_range(grid(&input_pos));
-            vec_range::foreach2d(&r, |output_pos| {
-                let kernel_offset = conv::in_range_begin(&output_pos, self.stride());
+            let input_pos: [Idx; DIM + 1] = self.in_shape().pos(input_idx);
+            let r = self.out_range(column::<_, DIM>(&input_pos));
+            vec_range::foreach(&r, |output_pos| if let Some(kernel_pos) = self.kernel_pos(column::<_, DIM>(&input_pos), &output_pos) {
+                let idx_within_kernel_column = kernel_column.idx(&append(&kernel_pos, &input_pos[DIM]));
                 for p2 in Idx::zero()..self.out_channels() {
-                    let output_pos = xy_z3(output_pos.clone(), p2);
+                    let output_pos = append(&output_pos, &p2);
                     let output_idx = self.out_shape().idx(&output_pos);
-                    let w_index = idx_(&input_pos, &kernel_offset, p2, &kernel_column, self.out_channels());
+                    let w_index = idx(p2, idx_within_kernel_column, self.out_channels());
                     target(output_idx, w_index);
                 }
             });
@@ -274,17 +316,16 @@ impl<Idx: Debug + PrimInt> ConvShape<Idx> {
                                fold_init: V,
                                mut fold_per_weight_in_kernel_column: impl FnMut(V, Idx) -> V,
                                mut fold_per_kernel_column: impl FnMut(V, Idx) -> V) -> V {
-        let input_pos: Vec<[Idx; 3]> = input.iter().map(|&i| self.in_shape().pos(i)).collect();
+        let input_pos: Vec<[Idx; DIM + 1]> = input.iter().map(|&i| self.in_shape().pos(i)).collect();
         let v = self.out_volume();
         let kernel_column = self.kernel_column_shape();
         let mut value = fold_init;
         for &output_idx in output {
             let output_pos = self.out_shape().pos(output_idx);
-            let kernel_offset = self.kernel_offset(&output_pos);
-            let input_range = self.in_range(grid(&output_pos));
-            for (&input_idx, input_pos) in input.iter().zip(input_pos.iter()) {
-                if vec_range::contains(&input_range, grid(input_pos)) {
-                    let w_index = idx_(&input_pos, &kernel_offset, output_idx, &kernel_column, v);
+            debug_assert!(output_pos.all_lt(self.out_shape()));
+            for input_pos in input_pos.iter() {
+                if let Some(kernel_pos) = self.kernel_pos(column::<_, DIM>(input_pos), column::<_, DIM>(&output_pos)) {
+                    let w_index = idx(output_idx, kernel_column.idx(&append(&kernel_pos, &input_pos[DIM])), v);
                     value = fold_per_weight_in_kernel_column(value, w_index);
                 }
             }
@@ -300,17 +341,16 @@ impl<Idx: Debug + PrimInt> ConvShape<Idx> {
                                         fold_init: V,
                                         mut fold_per_weight_in_kernel_column: impl FnMut(V, Idx) -> V,
                                         mut fold_per_kernel_column: impl FnMut(V, Idx) -> V) -> V {
-        let input_pos: Vec<[Idx; 3]> = input.iter().map(|&i| self.in_shape().pos(i)).collect();
+        let input_pos: Vec<[Idx; DIM + 1]> = input.iter().map(|&i| self.in_shape().pos(i)).collect();
         let v = self.out_channels();
         let kernel_column = self.kernel_column_shape();
         let mut value = fold_init;
         for &output_idx in output {
             let output_pos = self.out_shape().pos(output_idx);
-            let kernel_offset = self.kernel_offset(&output_pos);
-            let input_range = self.in_range(grid(&output_pos));
-            for (&input_idx, input_pos) in input.iter().zip(input_pos.iter()) {
-                if vec_range::contains(&input_range, grid(input_pos)) {
-                    let w_index = idx_(&input_pos, &kernel_offset, output_pos[2], &kernel_column, v);
+            debug_assert!(output_pos.all_lt(self.out_shape()));
+            for input_pos in input_pos.iter() {
+                if let Some(kernel_pos) = self.kernel_pos(column::<_, DIM>(input_pos), column::<_, DIM>(&output_pos)) {
+                    let w_index = idx(output_pos[DIM], kernel_column.idx(&append(&kernel_pos, &input_pos[DIM])), v);
                     value = fold_per_weight_in_kernel_column(value, w_index);
                 }
             }
@@ -382,37 +422,148 @@ impl<Idx: Debug + PrimInt> ConvShape<Idx> {
         assert_eq!(w_slice.len(), self.minicolumn_w_shape().product().as_());
         self.sparse_conjugate_repeated(input, output, (), |(), idx| w_slice[idx.as_()] *= epsilon, |_, _| ())
     }
+    /**Visits every connection between input and output neurons. The callback receives input_idx, output_idx and
+    index of the connecting weight in a tensor of shape [kernel_height, kernel_width, in_channels, out_height, out_width, out_channels].
+    Inputs that fall into the padding or between dilated kernel weights are never visited.*/
+    pub fn dense_conjugate(&self, mut f: impl FnMut(Idx, Idx, Idx)) where Idx: Step {
+        let v = self.out_volume();
+        self.dense_conjugate_(|input_idx, output_idx, _, idx_within_kernel_column| f(input_idx, output_idx, idx(output_idx, idx_within_kernel_column, v)))
+    }
+    /**Same as dense_conjugate but weight indices point into a tensor of shape [kernel_height, kernel_width, in_channels, out_channels]*/
+    pub fn dense_conjugate_repeated(&self, mut f: impl FnMut(Idx, Idx, Idx)) where Idx: Step {
+        let c = self.out_channels();
+        self.dense_conjugate_(|input_idx, output_idx, out_channel, idx_within_kernel_column| f(input_idx, output_idx, idx(out_channel, idx_within_kernel_column, c)))
+    }
+    fn dense_conjugate_(&self, mut f: impl FnMut(Idx, Idx, Idx, Idx)) where Idx: Step {
+        let kernel_column = self.kernel_column_shape();
+        vec_range::foreach(&([Idx::zero(); DIM]..*self.out_grid()), |output_column_pos| {
+            let r = self.in_range(&output_column_pos);
+            vec_range::foreach(&r, |input_column_pos| if let Some(kernel_pos) = self.kernel_pos(&input_column_pos, &output_column_pos) {
+                for c in Idx::zero()..self.in_channels() {
+                    let input_idx = self.in_shape().idx(&append(&input_column_pos, &c));
+                    let idx_within_kernel_column = kernel_column.idx(&append(&kernel_pos, &c));
+                    for p in Idx::zero()..self.out_channels() {
+                        let output_idx = self.out_shape().idx(&append(&output_column_pos, &p));
+                        f(input_idx, output_idx, p, idx_within_kernel_column)
+                    }
+                }
+            })
+        })
+    }
+    /**lhs_tensor is a dense input of shape [in_height, in_width, in_channels].
+    rhs_conv_tensor is of shape [kernel_height, kernel_width, in_channels, out_height, out_width, out_channels].
+    Returns output of shape [out_height, out_width, out_channels]*/
+    pub fn dense_dot_slice<D: AddAssign + Mul<Output=D> + Copy + Zero>(&self, lhs_tensor: &[D], rhs_conv_tensor: &[D]) -> Vec<D> where Idx: AsPrimitive<usize> + Step {
+        let mut dot_product_output = vec![D::zero(); self.out_volume().as_()];
+        self.dense_dot_slice_(lhs_tensor, rhs_conv_tensor, &mut dot_product_output);
+        dot_product_output
+    }
+    /**Same as dense_dot_slice but the result is added to dot_product_output*/
+    pub fn dense_dot_slice_<D: AddAssign + Mul<Output=D> + Copy>(&self, lhs_tensor: &[D], rhs_conv_tensor: &[D], dot_product_output: &mut [D]) where Idx: AsPrimitive<usize> + Step {
+        assert_eq!(lhs_tensor.len(), self.in_volume().as_());
+        assert_eq!(rhs_conv_tensor.len(), self.w_shape().product().as_());
+        assert_eq!(dot_product_output.len(), self.out_volume().as_());
+        self.dense_conjugate(|i, o, w| dot_product_output[o.as_()] += lhs_tensor[i.as_()] * rhs_conv_tensor[w.as_()])
+    }
+    /**lhs_tensor is a dense input of shape [in_height, in_width, in_channels].
+    rhs_conv_tensor is of shape [kernel_height, kernel_width, in_channels, out_channels].
+    Returns output of shape [out_height, out_width, out_channels]*/
+    pub fn dense_dot_repeated_slice<D: AddAssign + Mul<Output=D> + Copy + Zero>(&self, lhs_tensor: &[D], rhs_conv_tensor: &[D]) -> Vec<D> where Idx: AsPrimitive<usize> + Step {
+        let mut dot_product_output = vec![D::zero(); self.out_volume().as_()];
+        self.dense_dot_repeated_slice_(lhs_tensor, rhs_conv_tensor, &mut dot_product_output);
+        dot_product_output
+    }
+    /**Same as dense_dot_repeated_slice but the result is added to dot_product_output*/
+    pub fn dense_dot_repeated_slice_<D: AddAssign + Mul<Output=D> + Copy>(&self, lhs_tensor: &[D], rhs_conv_tensor: &[D], dot_product_output: &mut [D]) where Idx: AsPrimitive<usize> + Step {
+        assert_eq!(lhs_tensor.len(), self.in_volume().as_());
+        assert_eq!(rhs_conv_tensor.len(), self.minicolumn_w_shape().product().as_());
+        assert_eq!(dot_product_output.len(), self.out_volume().as_());
+        self.dense_conjugate_repeated(|i, o, w| dot_product_output[o.as_()] += lhs_tensor[i.as_()] * rhs_conv_tensor[w.as_()])
+    }
+    /**Transposed convolution, which reconstructs input from output. output_tensor is of shape [out_height, out_width, out_channels].
+    rhs_conv_tensor is of shape [kernel_height, kernel_width, in_channels, out_height, out_width, out_channels].
+    Returns input of shape [in_height, in_width, in_channels]*/
+    pub fn dense_transpose_dot_slice<D: AddAssign + Mul<Output=D> + Copy + Zero>(&self, output_tensor: &[D], rhs_conv_tensor: &[D]) -> Vec<D> where Idx: AsPrimitive<usize> + Step {
+        let mut input_reconstruction = vec![D::zero(); self.in_volume().as_()];
+        self.dense_transpose_dot_slice_(output_tensor, rhs_conv_tensor, &mut input_reconstruction);
+        input_reconstruction
+    }
+    /**Same as dense_transpose_dot_slice but the result is added to input_reconstruction*/
+    pub fn dense_transpose_dot_slice_<D: AddAssign + Mul<Output=D> + Copy>(&self, output_tensor: &[D], rhs_conv_tensor: &[D], input_reconstruction: &mut [D]) where Idx: AsPrimitive<usize> + Step {
+        assert_eq!(output_tensor.len(), self.out_volume().as_());
+        assert_eq!(rhs_conv_tensor.len(), self.w_shape().product().as_());
+        assert_eq!(input_reconstruction.len(), self.in_volume().as_());
+        self.dense_conjugate(|i, o, w| input_reconstruction[i.as_()] += output_tensor[o.as_()] * rhs_conv_tensor[w.as_()])
+    }
+    /**Transposed convolution, which reconstructs input from output. output_tensor is of shape [out_height, out_width, out_channels].
+    rhs_conv_tensor is of shape [kernel_height, kernel_width, in_channels, out_channels].
+    Returns input of shape [in_height, in_width, in_channels]*/
+    pub fn dense_transpose_dot_repeated_slice<D: AddAssign + Mul<Output=D> + Copy + Zero>(&self, output_tensor: &[D], rhs_conv_tensor: &[D]) -> Vec<D> where Idx: AsPrimitive<usize> + Step {
+        let mut input_reconstruction = vec![D::zero(); self.in_volume().as_()];
+        self.dense_transpose_dot_repeated_slice_(output_tensor, rhs_conv_tensor, &mut input_reconstruction);
+        input_reconstruction
+    }
+    /**Same as dense_transpose_dot_repeated_slice but the result is added to input_reconstruction*/
+    pub fn dense_transpose_dot_repeated_slice_<D: AddAssign + Mul<Output=D> + Copy>(&self, output_tensor: &[D], rhs_conv_tensor: &[D], input_reconstruction: &mut [D]) where Idx: AsPrimitive<usize> + Step {
+        assert_eq!(output_tensor.len(), self.out_volume().as_());
+        assert_eq!(rhs_conv_tensor.len(), self.minicolumn_w_shape().product().as_());
+        assert_eq!(input_reconstruction.len(), self.in_volume().as_());
+        self.dense_conjugate_repeated(|i, o, w| input_reconstruction[i.as_()] += output_tensor[o.as_()] * rhs_conv_tensor[w.as_()])
+    }
+    /**Hebbian rule w += epsilon * x * y applied to every connection. This is the dense counterpart of sparse_biased_increment.
+    input is of shape [in_height, in_width, in_channels], output is of shape [out_height, out_width, out_channels]
+    and w_slice of shape [kernel_height, kernel_width, in_channels, out_height, out_width, out_channels]*/
+    pub fn dense_increment<D: Copy + AddAssign + Mul<Output=D>>(&self, w_slice: &mut [D], epsilon: D, input: &[D], output: &[D]) where Idx: AsPrimitive<usize> + Step {
+        assert_eq!(w_slice.len(), self.w_shape().product().as_());
+        assert_eq!(input.len(), self.in_volume().as_());
+        assert_eq!(output.len(), self.out_volume().as_());
+        self.dense_conjugate(|i, o, w| w_slice[w.as_()] += epsilon * input[i.as_()] * output[o.as_()])
+    }
+    /**Hebbian rule w += epsilon * x * y, where the update of each minicolumn weight is summed over all output columns
+    that share it. w_slice is of shape [kernel_height, kernel_width, in_channels, out_channels]*/
+    pub fn dense_increment_repeated<D: Copy + AddAssign + Mul<Output=D>>(&self, w_slice: &mut [D], epsilon: D, input: &[D], output: &[D]) where Idx: AsPrimitive<usize> + Step {
+        assert_eq!(w_slice.len(), self.minicolumn_w_shape().product().as_());
+        assert_eq!(input.len(), self.in_volume().as_());
+        assert_eq!(output.len(), self.out_volume().as_());
+        self.dense_conjugate_repeated(|i, o, w| w_slice[w.as_()] += epsilon * input[i.as_()] * output[o.as_()])
+    }
 
     pub fn compose(&self, next: &Self) -> Self {
         assert_eq!(self.out_shape(), next.in_shape());
-        let (kernel, stride) = conv::compose(self.stride(), self.kernel(), next.stride(), next.kernel());
+        let (stride, kernel, padding) = conv::compose_padded(self.stride(), self.kernel(), self.padding(), self.dilation(), next.stride(), next.kernel(), next.padding(), next.dilation());
+        let padding_end = next.padding_end().mul(self.stride())._add(self.padding_end());
         Self {
             input_shape: self.input_shape(),
             output_shape: next.output_shape(),
             kernel,
             stride,
+            padding,
+            padding_end,
+            dilation: [Idx::one(); DIM],
         }
     }
 
-    pub fn new_identity(shape: [Idx; 3]) -> Self {
+    pub fn new_identity(shape: [Idx; DIM + 1]) -> Self {
         Self {
             input_shape: shape.clone(),
             output_shape: shape,
-            kernel: [Idx::one(); 2],
-            stride: [Idx::one(); 2],
+            kernel: [Idx::one(); DIM],
+            stride: [Idx::one(); DIM],
+            padding: [Idx::zero(); DIM],
+            padding_end: [Idx::zero(); DIM],
+            dilation: [Idx::one(); DIM],
         }
     }
     /**This convolution is in fact just a dense linear layer with certain number of inputs and outputs.*/
     pub fn new_linear(input: Idx, output: Idx) -> Self {
-        Self {
-            input_shape: [Idx::one(), Idx::one(), input],
-            output_shape: [Idx::one(), Idx::one(), output],
-            kernel: [Idx::one(); 2],
-            stride: [Idx::one(); 2],
-        }
+        Self::new_identity(append(&[Idx::one(); DIM], &input)).with_out_channels(output)
     }
-    pub fn new(output: [Idx; 2], kernel: [Idx; 2], stride: [Idx; 2], in_channels: Idx, out_channels: Idx) -> Self {
-        Self::new_out(in_channels, xy_z3(output, out_channels), kernel, stride)
+    fn with_out_channels(mut self, out_channels: Idx) -> Self {
+        self.output_shape[DIM] = out_channels;
+        self
+    }
+    pub fn new(output: [Idx; DIM], kernel: [Idx; DIM], stride: [Idx; DIM], in_channels: Idx, out_channels: Idx) -> Self {
+        Self::new_out(in_channels, append(&output, &out_channels), kernel, stride)
     }
     pub fn concat(layers: &[Self]) -> Self where Idx: Sum {
         assert_ne!(layers.len(), 0, "No layers provided!");
@@ -422,45 +573,101 @@ impl<Idx: Debug + PrimInt> ConvShape<Idx> {
         let kernel = first_layer.kernel.clone();
         let stride = first_layer.stride.clone();
         assert!(layers.iter().all(|a| a.in_shape().all_eq(&in_shape)), "All concatenated layers must have the same input shape!");
-        assert!(layers.iter().all(|a| a.out_grid().all_eq(grid(&out_shape))), "All concatenated layers must have the same output width and height!");
+        assert!(layers.iter().all(|a| a.out_grid().all_eq(first_layer.out_grid())), "All concatenated layers must have the same output grid!");
         assert!(layers.iter().all(|a| a.stride().all_eq(&stride)), "All concatenated layers must have the same stride!");
         assert!(layers.iter().all(|a| a.kernel().all_eq(&kernel)), "All concatenated layers must have the same kernel!");
+        assert!(layers.iter().all(|a| a.padding().all_eq(first_layer.padding()) && a.padding_end().all_eq(first_layer.padding_end())), "All concatenated layers must have the same padding!");
+        assert!(layers.iter().all(|a| a.dilation().all_eq(first_layer.dilation())), "All concatenated layers must have the same dilation!");
         let concatenated_sum: Idx = layers.iter().map(|a| a.out_channels()).sum();
-        out_shape[2] = concatenated_sum;
+        out_shape[DIM] = concatenated_sum;
         Self {
             input_shape: in_shape,
             output_shape: out_shape,
             kernel,
             stride,
+            padding: first_layer.padding,
+            padding_end: first_layer.padding_end,
+            dilation: first_layer.dilation,
         }
     }
 
-    pub fn new_in(input_shape: [Idx; 3],
+    pub fn new_in(input_shape: [Idx; DIM + 1],
                   out_channels: Idx,
-                  kernel: [Idx; 2],
-                  stride: [Idx; 2]) -> Self {
+                  kernel: [Idx; DIM],
+                  stride: [Idx; DIM]) -> Self {
+        let input_grid: &[Idx; DIM] = slice_as_arr(&input_shape[..DIM]);
+        Self {
+            output_shape: append(&conv::out_size(input_grid, &stride, &kernel), &out_channels),
+            input_shape,
+            kernel,
+            stride,
+            padding: [Idx::zero(); DIM],
+            padding_end: [Idx::zero(); DIM],
+            dilation: [Idx::one(); DIM],
+        }
+    }
+    /**Same as `new_in` but the convolution may be padded and dilated. Just like in PyTorch, inputs that don't fill
+    the last stride are ignored.*/
+    pub fn new_in_padded(input_shape: [Idx; DIM + 1],
+                         out_channels: Idx,
+                         kernel: [Idx; DIM],
+                         stride: [Idx; DIM],
+                         padding: Padding<Idx, DIM>,
+                         dilation: [Idx; DIM]) -> Self {
+        let (padding, padding_end) = Self::padding_begin_end(padding, &kernel, &stride, &dilation);
+        let input_grid: &[Idx; DIM] = slice_as_arr(&input_shape[..DIM]);
         Self {
+            output_shape: append(&conv::out_size_padded(input_grid, &stride, &kernel, &padding, &padding_end, &dilation), &out_channels),
             input_shape,
-            output_shape: xy_z3(conv::out_size(&grid(&input_shape), &stride, &kernel), out_channels),
             kernel,
             stride,
+            padding,
+            padding_end,
+            dilation,
+        }
+    }
+    /**Same as `new_out` but the convolution may be padded and dilated.*/
+    pub fn new_out_padded(in_channels: Idx,
+                          output_shape: [Idx; DIM + 1],
+                          kernel: [Idx; DIM],
+                          stride: [Idx; DIM],
+                          padding: Padding<Idx, DIM>,
+                          dilation: [Idx; DIM]) -> Self {
+        let (padding, padding_end) = Self::padding_begin_end(padding, &kernel, &stride, &dilation);
+        let output_grid: &[Idx; DIM] = slice_as_arr(&output_shape[..DIM]);
+        Self {
+            input_shape: append(&conv::in_size_padded(output_grid, &stride, &kernel, &padding, &padding_end, &dilation), &in_channels),
+            output_shape,
+            kernel,
+            stride,
+            padding,
+            padding_end,
+            dilation,
         }
     }
+    fn padding_begin_end(padding: Padding<Idx, DIM>, kernel: &[Idx; DIM], stride: &[Idx; DIM], dilation: &[Idx; DIM]) -> ([Idx; DIM], [Idx; DIM]) {
+        assert!(dilation.all_gt_scalar(Idx::zero()), "Dilation must be positive");
+        assert!(padding != Padding::Same || stride.all_eq_scalar(Idx::one()), "Padding::Same requires stride 1 but got {:?}", stride);
+        padding.begin_end(kernel, dilation)
+    }
     pub fn new_out(in_channels: Idx,
-                   output_shape: [Idx; 3],
-                   kernel: [Idx; 2],
-                   stride: [Idx; 2]) -> Self {
+                   output_shape: [Idx; DIM + 1],
+                   kernel: [Idx; DIM],
+                   stride: [Idx; DIM]) -> Self {
+        let output_grid: &[Idx; DIM] = slice_as_arr(&output_shape[..DIM]);
         Self {
-            input_shape: xy_z3(conv::in_size(&grid(&output_shape), &stride, &kernel), in_channels),
+            input_shape: append(&conv::in_size(output_grid, &stride, &kernel), &in_channels),
             output_shape,
             kernel,
             stride,
+            padding: [Idx::zero(); DIM],
+            padding_end: [Idx::zero(); DIM],
+            dilation: [Idx::one(); DIM],
         }
     }
-    pub fn set_stride(&mut self, new_stride: [Idx; 2]) {
-        let input = conv::in_size(self.out_grid(), &new_stride, self.kernel());
-        let input = xy_z3(input, self.in_channels());
-        self.input_shape = input;
+    pub fn set_stride(&mut self, new_stride: [Idx; DIM]) {
+        let input = conv::in_size_padded(self.out_grid(), &new_stride, self.kernel(), self.padding(), self.padding_end(), self.dilation());
+        self.input_shape = append(&input, &self.in_channels());
         self.stride = new_stride;
     }
     /**Input weights are of shape [kernel_height, kernel_width, in_channels, out_channels]. Output is
@@ -498,7 +705,7 @@ impl<Idx: Debug + PrimInt> ConvShape<Idx> {
                 let j = k_yx*channels+c;
                 let yj = y_dense(j);
                 let sj = s[j];
-                u_weights[k*channels + j] = formula(sk,sj,yj,u_weights[k*channels + j]);
+                u_weights[k*channels + c] = formula(sk,sj,yj,u_weights[k*channels + c]);
             }
         }
     }
@@ -526,22 +733,24 @@ impl<Idx: Debug + PrimInt> ConvShape<Idx> {
         let mut u_updates = vec![D::zero();c2];
         let mut win_count = vec![0usize; channels];
         for k in y_sparse.iter().map(|k|k.as_()){
-            win_count[k] += 1;
             let k_yx = k / channels;
             let k_c = k % channels;
+            win_count[k_c] += 1;
             let sk = s[k];
             for c in 0..channels{
                 let j = k_yx*channels+c;
                 let yj = y_dense(j);
                 let sj = s[j];
-                u_updates[k_c*channels + j] += formula(sk,sj,yj,u_weights[k_c*channels + j])
+                u_updates[k_c*channels + c] += formula(sk,sj,yj,u_weights[k_c*channels + c])
             }
         }
         for k in 0..channels{
             let wins = win_count[k];
-            for j in 0..channels {
-                let j = k*channels+j;
-                u_weights[j] = accumulate(u_updates[j], u_weights[j], wins);
+            if wins > 0 {
+                for j in 0..channels {
+                    let j = k*channels+j;
+                    u_weights[j] = accumulate(u_updates[j], u_weights[j], wins);
+                }
             }
         }
 
@@ -554,15 +763,15 @@ impl<Idx: Debug + PrimInt> ConvShape<Idx> {
                       |new_ukj_sum,old_ukj,k_win_count|old_ukj*one_minus_eps+(new_ukj_sum/D::from_usize(k_win_count))*epsilon)
     }
     /**[out_height,out_width,out_channels,kernel_height, kernel_width, in_channels]*/
-    pub fn receptive_field_shape(&self)->[Idx;6]{
-        let [oh,ow,oc] = self.output_shape;
-        let [kh,kw] = self.kernel;
-        [oh,ow,oc,kh,kw,self.in_channels()]
+    pub fn receptive_field_shape(&self)->[Idx;2 * DIM + 2]{
+        columns2(self.out_grid(),self.out_channels(),self.kernel(),self.in_channels())
     }
     /**[out_channels,kernel_height, kernel_width, in_channels]*/
-    pub fn minicolumn_receptive_field_shape(&self)->[Idx;4]{
-        let [kh,kw] = self.kernel;
-        [self.out_channels(),kh,kw,self.in_channels()]
+    pub fn minicolumn_receptive_field_shape(&self)->[Idx;DIM + 2]{
+        let mut shape = append2(self.kernel(),self.in_channels(),self.in_channels());
+        shape.rotate_right(1);
+        shape[0] = self.out_channels();
+        shape
     }
     /**minicolumn_receptive_field is of shape [out_channels,kernel_height, kernel_width, in_channels]*/
     pub fn add_to_receptive_field_repeated<D:AddAssign+One>(&self, minicolumn_receptive_field:&mut [D], x:&[Idx], y: &[Idx]) where Idx: AsPrimitive<usize> {
@@ -574,17 +783,16 @@ impl<Idx: Debug + PrimInt> ConvShape<Idx> {
         let os = self.out_shape().as_scalar::<usize>();
         let stride = self.stride().as_scalar::<usize>();
         let kernel = self.kernel().as_scalar::<usize>();
-        let x_pos:Vec<[usize;3]> = x.iter().map(|i|is.pos(i.as_())).collect();
+        let padding = self.padding().as_scalar::<usize>();
+        let dilation = self.dilation().as_scalar::<usize>();
+        let x_pos:Vec<[usize;DIM + 1]> = x.iter().map(|i|is.pos(i.as_())).collect();
         for j in y{
             debug_assert!(j.as_()<self.out_volume().as_());
             let j_pos = os.pos(j.as_());
-            let i_range = conv::in_range(xy3(&j_pos),&stride,&kernel);
             for i_pos in &x_pos{
-                debug_assert!(j.as_()<self.in_volume().as_());
-                if vec_range::contains(&i_range,xy3(i_pos)){
-                    let i_pos_within_kernel_column = sub_kernel_offset(i_pos,&i_range.start);
-                    let i_within_kernel_column = kc.idx(&i_pos_within_kernel_column);
-                    minicolumn_receptive_field[j_pos[2]*kcv + i_within_kernel_column] += D::one();
+                if let Some(kernel_pos) = conv::kernel_pos(column::<_, DIM>(i_pos),column::<_, DIM>(&j_pos),&stride,&kernel,&padding,&dilation){
+                    let i_within_kernel_column = kc.idx(&append(&kernel_pos,&i_pos[DIM]));
+                    minicolumn_receptive_field[j_pos[DIM]*kcv + i_within_kernel_column] += D::one();
                 }
             }
         }
@@ -608,11 +816,92 @@ impl<Idx: Debug + PrimInt> ConvShape<Idx> {
     // }
 }
 
+
+impl<Idx: Debug + PrimInt> ConvShape<Idx, 2> {
+    pub fn out_width(&self) -> Idx {
+        *width(self.out_shape())
+    }
+    pub fn out_height(&self) -> Idx {
+        *height(self.out_shape())
+    }
+    pub fn in_width(&self) -> Idx {
+        *width(self.in_shape())
+    }
+    pub fn in_height(&self) -> Idx {
+        *height(self.in_shape())
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct ConvShapeSer<'a, Idx> {
+    input_shape: &'a [Idx],
+    output_shape: &'a [Idx],
+    kernel: &'a [Idx],
+    stride: &'a [Idx],
+    padding: &'a [Idx],
+    padding_end: &'a [Idx],
+    dilation: &'a [Idx],
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(bound = "Idx: serde::Deserialize<'de>")]
+struct ConvShapeDe<Idx> {
+    input_shape: Vec<Idx>,
+    output_shape: Vec<Idx>,
+    kernel: Vec<Idx>,
+    stride: Vec<Idx>,
+    #[serde(default)]
+    padding: Option<Vec<Idx>>,
+    #[serde(default)]
+    padding_end: Option<Vec<Idx>>,
+    #[serde(default)]
+    dilation: Option<Vec<Idx>>,
+}
+
+/**Serde can't derive arrays whose length depends on const generics. The serialized layout is the same as
+the one derived for two dimensional convolutions, so old files can still be loaded (missing padding and dilation default to none).*/
+#[cfg(feature = "serde")]
+impl<Idx: Debug + PrimInt + serde::Serialize, const DIM: usize> serde::Serialize for ConvShape<Idx, DIM> where [(); DIM + 1]: {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ConvShapeSer {
+            input_shape: &self.input_shape,
+            output_shape: &self.output_shape,
+            kernel: &self.kernel,
+            stride: &self.stride,
+            padding: &self.padding,
+            padding_end: &self.padding_end,
+            dilation: &self.dilation,
+        }.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, Idx: Debug + PrimInt + serde::Deserialize<'de>, const DIM: usize> serde::Deserialize<'de> for ConvShape<Idx, DIM> where [(); DIM + 1]: {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        fn arr<Idx: Copy, E: serde::de::Error, const L: usize>(field: &str, v: Vec<Idx>) -> Result<[Idx; L], E> {
+            let len = v.len();
+            v.try_into().map_err(|_| E::custom(format!("Field {} has length {} but expected {}", field, len, L)))
+        }
+        let ConvShapeDe { input_shape, output_shape, kernel, stride, padding, padding_end, dilation } = ConvShapeDe::deserialize(deserializer)?;
+        Ok(Self {
+            input_shape: arr("input_shape", input_shape)?,
+            output_shape: arr("output_shape", output_shape)?,
+            kernel: arr("kernel", kernel)?,
+            stride: arr("stride", stride)?,
+            padding: padding.map(|p| arr("padding", p)).transpose()?.unwrap_or([Idx::zero(); DIM]),
+            padding_end: padding_end.map(|p| arr("padding_end", p)).transpose()?.unwrap_or([Idx::zero(); DIM]),
+            dilation: dilation.map(|d| arr("dilation", d)).transpose()?.unwrap_or([Idx::one(); DIM]),
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use rand::rngs::StdRng;
     use super::*;
-    use rand::{random, SeedableRng};
+    use rand::{random, Rng, SeedableRng};
     use crate::init_rand::InitRandWithCapacity;
     use crate::{rand_set, VectorFieldAddOwned, VectorFieldRemOwned};
     #[test]
@@ -742,4 +1031,250 @@ mod tests {
             assert_eq!(o0, o1);
         }
     }
-}
\ No newline at end of file
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test7() {
+        let shape = ConvShape::new_in([27, 27, 3], 8, [5, 5], [2, 2]);
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(&shape, &mut bytes).unwrap();
+        let shape2: ConvShape<u32> = ciborium::de::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(shape.in_shape(), shape2.in_shape());
+        assert_eq!(shape.out_shape(), shape2.out_shape());
+        assert_eq!(shape.kernel(), shape2.kernel());
+        assert_eq!(shape.stride(), shape2.stride());
+        assert_eq!(shape.padding(), shape2.padding());
+        assert_eq!(shape.dilation(), shape2.dilation());
+        // files saved before padding and dilation were introduced
+        #[derive(serde::Serialize)]
+        struct Old {
+            input_shape: [u32; 3],
+            output_shape: [u32; 3],
+            kernel: [u32; 2],
+            stride: [u32; 2],
+        }
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(&Old { input_shape: [27, 27, 3], output_shape: [12, 12, 8], kernel: [5, 5], stride: [2, 2] }, &mut bytes).unwrap();
+        let shape3: ConvShape<u32> = ciborium::de::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(shape.out_shape(), shape3.out_shape());
+        assert_eq!(shape3.padding(), &[0, 0]);
+        assert_eq!(shape3.dilation(), &[1, 1]);
+        let shape4: Result<ConvShape3d<u32>, _> = ciborium::de::from_reader(bytes.as_slice());
+        assert!(shape4.is_err());
+        let shape5 = ConvShape3d::new_in_padded([5, 6, 7, 2], 3, [2, 3, 2], [1, 2, 1], Padding::Zero([1, 0, 2]), [2, 1, 1]);
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(&shape5, &mut bytes).unwrap();
+        let shape6: ConvShape3d<u32> = ciborium::de::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(shape5.w_shape(), shape6.w_shape());
+        assert_eq!(shape5.padding(), shape6.padding());
+    }
+
+    #[test]
+    fn test8() {
+        // compares against the PyTorch definition of padded and dilated convolution
+        for (padding, stride, dilation) in [(Padding::Zero([1, 2]), [2, 1], [1, 2]), (Padding::Same, [1, 1], [2, 3]), (Padding::Zero([0, 0]), [3, 2], [2, 1])] {
+            let shape = ConvShape::new_in_padded([9, 10, 2], 3, [3, 2], stride, padding, dilation);
+            let [ih, iw, ic] = *shape.in_shape();
+            let [oh, ow, oc] = *shape.out_shape();
+            let mini_w: Vec<f32> = Vec::rand(shape.minicolumn_w_shape().product());
+            let x = rand_set(20, 0..shape.in_volume());
+            let mut x_dense = vec![0.; shape.in_volume()];
+            x.iter().for_each(|&i| x_dense[i] = 1.);
+            let mut expected = vec![0f32; shape.out_volume()];
+            for o0 in 0..oh {
+                for o1 in 0..ow {
+                    for k0 in 0..shape.kernel()[0] {
+                        for k1 in 0..shape.kernel()[1] {
+                            let i0 = (o0 * stride[0] + k0 * dilation[0]) as isize - shape.padding()[0] as isize;
+                            let i1 = (o1 * stride[1] + k1 * dilation[1]) as isize - shape.padding()[1] as isize;
+                            if i0 < 0 || i1 < 0 || i0 >= ih as isize || i1 >= iw as isize {
+                                continue;
+                            }
+                            for c_in in 0..ic {
+                                let xi = x_dense[shape.in_shape().idx(&[i0 as usize, i1 as usize, c_in])];
+                                for c_out in 0..oc {
+                                    let w = mini_w[shape.minicolumn_w_shape().idx(&[k0, k1, c_in, c_out])];
+                                    expected[shape.out_shape().idx(&[o0, o1, c_out])] += xi * w;
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            let o0 = shape.sparse_dot_repeated_slice(&x, &mini_w);
+            let o1 = shape.sparse_dot_slice(&x, &shape.repeat_minicolumn(&mini_w));
+            for ((e, a0), a1) in expected.iter().zip(o0.iter()).zip(o1.iter()) {
+                assert!((e - a0).abs() < 0.0001 && (e - a1).abs() < 0.0001, "{} {} {}", e, a0, a1);
+            }
+            if padding == Padding::Same {
+                assert_eq!(shape.in_grid(), shape.out_grid());
+            }
+            let y = rand_set(5, 0..shape.out_volume());
+            let mut mini_w = vec![0.; shape.minicolumn_w_shape().product()];
+            let mut w = vec![0.; shape.w_shape().product()];
+            shape.sparse_biased_increment_repeated(&mut mini_w, 1., &x, &y);
+            shape.sparse_biased_increment(&mut w, 1., &x, &y);
+            let mut rf = vec![0.; shape.minicolumn_receptive_field_shape().product()];
+            shape.add_to_receptive_field_repeated(&mut rf, &x, &y);
+            assert_eq!(mini_w.iter().sum::<f32>(), w.iter().sum::<f32>());
+            assert_eq!(mini_w.iter().sum::<f32>(), rf.iter().sum::<f32>());
+        }
+    }
+
+    #[test]
+    fn test9() {
+        let a = ConvShape::new_in_padded([16, 16, 1], 2, [3, 3], [2, 2], Padding::Zero([1, 1]), [1, 1]);
+        assert_eq!(a.out_shape(), &[8, 8, 2]);
+        let b = ConvShape::new_in_padded([8, 8, 2], 4, [3, 3], [1, 1], Padding::Same, [2, 2]);
+        assert_eq!(b.out_shape(), &[8, 8, 4]);
+        let c = a.compose(&b);
+        assert_eq!(c.stride(), &[2, 2]);
+        assert_eq!(c.kernel(), &[11, 11]);
+        assert_eq!(c.padding(), &[5, 5]);
+        assert_eq!(c.dilation(), &[1, 1]);
+        let d = ConvShape::new_in([9, 9, 1], 1, [3, 3], [2, 2]).compose(&ConvShape::new_in([4, 4, 1], 1, [2, 2], [1, 1]));
+        assert_eq!(d.stride(), &[2, 2]);
+        assert_eq!(d.kernel(), &[5, 5]);
+        let e = ConvShape::new_out_padded(3, [5, 5, 1], [4, 4], [2, 2], Padding::Zero([1, 1]), [1, 1]);
+        assert_eq!(e.in_shape(), &[10, 10, 3]);
+    }
+
+    #[test]
+    fn test10() {
+        // 1D convolution compared against PyTorch definition
+        let shape = ConvShape1d::new_in_padded([20, 2], 3, [3], [2], Padding::Zero([2]), [2]);
+        assert_eq!(shape.out_shape(), &[(20 + 4 - 5) / 2 + 1, 3]);
+        assert_eq!(shape.w_shape(), [3, 2, 10, 3]);
+        assert_eq!(shape.minicolumn_w_shape(), [3, 2, 3]);
+        assert_eq!(shape.receptive_field_shape(), [10, 3, 3, 2]);
+        assert_eq!(shape.minicolumn_receptive_field_shape(), [3, 3, 2]);
+        let mini_w: Vec<f32> = Vec::rand(shape.minicolumn_w_shape().product());
+        for _ in 0..20 {
+            let x = rand_set(6, 0..shape.in_volume());
+            let mut expected = vec![0f32; shape.out_volume()];
+            for &i in &x {
+                let [i_pos, i_c] = shape.in_shape().pos(i);
+                for o in 0..shape.out_grid()[0] {
+                    for k in 0..3 {
+                        if o * 2 + k * 2 == i_pos + 2 {
+                            for c in 0..3 {
+                                expected[shape.out_shape().idx(&[o, c])] += mini_w[shape.minicolumn_w_shape().idx(&[k, i_c, c])];
+                            }
+                        }
+                    }
+                }
+            }
+            assert_eq!(shape.sparse_dot_repeated_slice(&x, &mini_w), expected);
+            assert_eq!(shape.sparse_dot_slice(&x, &shape.repeat_minicolumn(&mini_w)), expected);
+        }
+    }
+
+    #[test]
+    fn test11() {
+        let shape = ConvShape3d::new_in([6, 5, 7, 2], 4, [2, 3, 3], [2, 1, 2]);
+        assert_eq!(shape.out_shape(), &[3, 3, 3, 4]);
+        assert_eq!(shape.kernel_column_shape(), [2, 3, 3, 2]);
+        assert_eq!(shape.in_range(&[1, 2, 1]), [2, 2, 2]..[4, 5, 5]);
+        assert_eq!(shape.out_range(&[3, 2, 4]), [1, 0, 1]..[2, 3, 3]);
+        let mini_w: Vec<f32> = Vec::rand(shape.minicolumn_w_shape().product());
+        let w = shape.repeat_minicolumn(&mini_w);
+        for _ in 0..20 {
+            let x = rand_set(10, 0..shape.in_volume());
+            let y = rand_set(5, 0..shape.out_volume());
+            let o0 = shape.sparse_dot_repeated_slice(&x, &mini_w);
+            let o1 = shape.sparse_dot_slice(&x, &w);
+            assert_eq!(o0, o1);
+            let mut mini_w_inc = vec![0.; shape.minicolumn_w_shape().product()];
+            let mut w_inc = vec![0.; shape.w_shape().product()];
+            shape.sparse_biased_increment_repeated(&mut mini_w_inc, 1., &x, &y);
+            shape.sparse_biased_increment(&mut w_inc, 1., &x, &y);
+            let mut rf = vec![0.; shape.minicolumn_receptive_field_shape().product()];
+            shape.add_to_receptive_field_repeated(&mut rf, &x, &y);
+            assert_eq!(mini_w_inc.iter().sum::<f32>(), w_inc.iter().sum::<f32>());
+            assert_eq!(mini_w_inc.iter().sum::<f32>(), rf.iter().sum::<f32>());
+        }
+        let composed = shape.compose(&ConvShape3d::new_in([3, 3, 3, 4], 1, [2, 2, 2], [1, 1, 1]));
+        assert_eq!(composed.kernel(), &[4, 4, 5]);
+        assert_eq!(composed.stride(), &[2, 1, 2]);
+    }
+
+    #[test]
+    fn test12() {
+        for shape in [ConvShape::new_in([7, 8, 3], 4, [3, 2], [2, 1]),
+            ConvShape::new_in_padded([9, 10, 2], 3, [3, 2], [1, 1], Padding::Same, [2, 3]),
+            ConvShape::new_in_padded([9, 10, 2], 3, [2, 3], [2, 3], Padding::Zero([1, 2]), [2, 1])] {
+            let mini_w: Vec<f32> = Vec::rand(shape.minicolumn_w_shape().product());
+            let w = shape.repeat_minicolumn(&mini_w);
+            let x = rand_set(12, 0..shape.in_volume());
+            let mut x_dense = vec![0f32; shape.in_volume()];
+            x.iter().for_each(|&i| x_dense[i] = 1.);
+            let y = rand_set(6, 0..shape.out_volume());
+            let mut y_dense = vec![0f32; shape.out_volume()];
+            y.iter().for_each(|&i| y_dense[i] = 1.);
+            let assert_close = |a: &[f32], b: &[f32]| for (a, b) in a.iter().zip(b.iter()) {
+                assert!((a - b).abs() < 0.0001, "{} {}", a, b);
+            };
+            // binary dense input must agree with sparse input
+            assert_close(&shape.dense_dot_slice(&x_dense, &w), &shape.sparse_dot_slice(&x, &w));
+            assert_close(&shape.dense_dot_repeated_slice(&x_dense, &mini_w), &shape.sparse_dot_repeated_slice(&x, &mini_w));
+            let mut w_inc = vec![0f32; w.len()];
+            let mut w_inc_sparse = vec![0f32; w.len()];
+            shape.dense_increment(&mut w_inc, 0.5, &x_dense, &y_dense);
+            shape.sparse_biased_increment(&mut w_inc_sparse, 0.5, &x, &y);
+            assert_eq!(w_inc, w_inc_sparse);
+            let mut mini_w_inc = vec![0f32; mini_w.len()];
+            let mut mini_w_inc_sparse = vec![0f32; mini_w.len()];
+            shape.dense_increment_repeated(&mut mini_w_inc, 0.5, &x_dense, &y_dense);
+            shape.sparse_biased_increment_repeated(&mut mini_w_inc_sparse, 0.5, &x, &y);
+            assert_eq!(mini_w_inc, mini_w_inc_sparse);
+            // transposed convolution is the adjoint: <y, Wx> = <W^T y, x>
+            let x_real: Vec<f32> = Vec::rand(shape.in_volume());
+            let y_real: Vec<f32> = Vec::rand(shape.out_volume());
+            let wx = shape.dense_dot_slice(&x_real, &w);
+            let wty = shape.dense_transpose_dot_slice(&y_real, &w);
+            let wty_repeated = shape.dense_transpose_dot_repeated_slice(&y_real, &mini_w);
+            let lhs: f32 = wx.iter().zip(y_real.iter()).map(|(a, b)| a * b).sum();
+            let rhs: f32 = wty.iter().zip(x_real.iter()).map(|(a, b)| a * b).sum();
+            assert!((lhs - rhs).abs() < 0.001, "{} {}", lhs, rhs);
+            assert_close(&wty, &wty_repeated);
+        }
+    }
+
+    #[test]
+    fn test13() {
+        // U is indexed by channels of the minicolumn, not by absolute output position
+        let shape: ConvShape<u32> = ConvShape::new_in([6, 6, 1], 3, [2, 2], [2, 2]);
+        let c = 3usize;
+        let v = shape.out_volume() as usize;
+        let mut rng = StdRng::seed_from_u64(12);
+        let s: Vec<f32> = (0..v).map(|_| rng.gen()).collect();
+        // winners in columns 0, 4 and 8, two of which share channel 1
+        let y = vec![1u32, 4 * 3 + 1, 8 * 3 + 2];
+        let u: Vec<f32> = (0..v * c).map(|_| rng.gen()).collect();
+        let mut u_new = u.clone();
+        shape.update_u_as_expected_sk_minus_sj(0.5, &s, &y, &mut u_new);
+        for k in 0..v {
+            for j in 0..c {
+                let i = k * c + j;
+                let expected = if y.contains(&(k as u32)) { u[i] * 0.5 + (s[k] - s[k / c * c + j]) * 0.5 } else { u[i] };
+                assert!((u_new[i] - expected).abs() < 0.0001, "{} {}", k, j);
+            }
+        }
+        let u: Vec<f32> = (0..c * c).map(|_| rng.gen()).collect();
+        let mut u_new = u.clone();
+        shape.update_u_as_expected_sk_minus_sj_repeated(0.5, &s, &y, &mut u_new);
+        for k_c in 0..c {
+            let winners: Vec<usize> = y.iter().map(|&k| k as usize).filter(|k| k % c == k_c).collect();
+            for j in 0..c {
+                let i = k_c * c + j;
+                let expected = if winners.is_empty() {
+                    u[i]
+                } else {
+                    let mean = winners.iter().map(|&k| s[k] - s[k / c * c + j]).sum::<f32>() / winners.len() as f32;
+                    u[i] * 0.5 + mean * 0.5
+                };
+                assert!((u_new[i] - expected).abs() < 0.0001, "{} {}", k_c, j);
+            }
+        }
+    }
+}
@@ -0,0 +1,491 @@
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::iter::Step;
+use num_traits::{AsPrimitive, PrimInt};
+use rand::Rng;
+use crate::ArrayCast;
+use crate::conv_shape::ConvShape;
+use crate::from_usize::FromUsize;
+use crate::init_rand::InitRandWithCapacity;
+use crate::soft_wta::{NULL, top_u_conv_par_, top_v_conv_par_};
+use crate::top_k::argmax;
+
+/**Lateral inhibition between neurons of the same minicolumn*/
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Inhibition {
+    /**Weights of shape `[out_height, out_width, out_channels, out_channels]`. Active neuron `k` inhibits `j`
+    if `s[j] + u[k,j] < s[k]` (see `soft_wta::top_u_`). These weights can be learned.*/
+    U(Vec<f32>),
+    /**Weights of shape `[out_height, out_width, out_channels, out_channels]`. Active neuron `k` inhibits `j`
+    if `v[k,j]` and `s[k] > s[j]` (see `soft_wta::top_v_`)*/
+    V(Vec<bool>),
+}
+
+/**Single layer of Early Competition Circuit. The input `x` is projected through weights `W` (laid out just like
+`ConvShape::w_shape`) and bias `r`, which gives excitation `s = xW + r`. Neurons with `s` above threshold then compete
+within their minicolumn by soft winner-take-all. Dense (fully connected) layers are just convolutions with
+a single output column (see `ConvShape::new_linear`), so weights of shape `[n, m]` are laid out like in numpy.*/
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EccLayer<Idx: Debug + PrimInt> {
+    shape: ConvShape<Idx>,
+    /**weights of shape `[kernel_height, kernel_width, in_channels, out_height, out_width, out_channels]`*/
+    w: Vec<f32>,
+    inhibition: Inhibition,
+    /**bias of shape `[out_height, out_width, out_channels]`*/
+    r: Vec<f32>,
+    threshold: f32,
+}
+
+impl<Idx: Debug + PrimInt + AsPrimitive<usize> + FromUsize + Step + Hash> EccLayer<Idx> {
+    /**Random weights W, normalised with respect to the given norm (1 or 2), and random inhibitory weights U in [0,1)*/
+    pub fn new_u(shape: ConvShape<Idx>, norm: usize, threshold: f32) -> Self {
+        Self::new_u_with_rng(shape, norm, threshold, &mut rand::thread_rng())
+    }
+    /**Same as `new_u` but all random weights are drawn from `rng`, so that they are reproducible*/
+    pub fn new_u_with_rng(shape: ConvShape<Idx>, norm: usize, threshold: f32, rng: &mut impl Rng) -> Self {
+        let u = Vec::rand_with_rng(u_len(&shape), rng);
+        Self::new_with_rng(shape, norm, threshold, Inhibition::U(u), rng)
+    }
+    /**Random weights W, normalised with respect to the given norm (1 or 2), and random binary inhibitory weights V*/
+    pub fn new_v(shape: ConvShape<Idx>, norm: usize, threshold: f32) -> Self {
+        Self::new_v_with_rng(shape, norm, threshold, &mut rand::thread_rng())
+    }
+    /**Same as `new_v` but all random weights are drawn from `rng`*/
+    pub fn new_v_with_rng(shape: ConvShape<Idx>, norm: usize, threshold: f32, rng: &mut impl Rng) -> Self {
+        let v = Vec::rand_with_rng(u_len(&shape), rng);
+        Self::new_with_rng(shape, norm, threshold, Inhibition::V(v), rng)
+    }
+    pub fn new(shape: ConvShape<Idx>, norm: usize, threshold: f32, inhibition: Inhibition) -> Self {
+        Self::new_with_rng(shape, norm, threshold, inhibition, &mut rand::thread_rng())
+    }
+    /**Same as `new` but random weights W are drawn from `rng`*/
+    pub fn new_with_rng(shape: ConvShape<Idx>, norm: usize, threshold: f32, inhibition: Inhibition, rng: &mut impl Rng) -> Self {
+        let u_len = u_len(&shape);
+        match &inhibition {
+            Inhibition::U(u) => assert_eq!(u.len(), u_len, "U has wrong shape"),
+            Inhibition::V(v) => assert_eq!(v.len(), u_len, "V has wrong shape"),
+        }
+        let mut slf = Self {
+            w: Vec::rand_with_rng((shape.kernel_column_volume() * shape.out_volume()).as_(), rng),
+            r: vec![0.; shape.out_volume().as_()],
+            shape,
+            inhibition,
+            threshold,
+        };
+        slf.normalize_w(norm);
+        slf
+    }
+    pub fn shape(&self) -> &ConvShape<Idx> {
+        &self.shape
+    }
+    pub fn w(&self) -> &[f32] {
+        &self.w
+    }
+    pub fn w_mut(&mut self) -> &mut [f32] {
+        &mut self.w
+    }
+    pub fn inhibition(&self) -> &Inhibition {
+        &self.inhibition
+    }
+    pub fn inhibition_mut(&mut self) -> &mut Inhibition {
+        &mut self.inhibition
+    }
+    pub fn r(&self) -> &[f32] {
+        &self.r
+    }
+    pub fn r_mut(&mut self) -> &mut [f32] {
+        &mut self.r
+    }
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold
+    }
+    /**Excitation `s = xW + r` for sparse binary input `x` (list of indices)*/
+    pub fn s_sparse(&self, x: &[Idx]) -> Vec<f32> {
+        let mut s = self.r.clone();
+        self.shape.sparse_dot_slice_(x, &self.w, &mut s);
+        s
+    }
+    /**Excitation `s = xW + r` for dense input `x` of shape `[in_height, in_width, in_channels]`*/
+    pub fn s_dense(&self, x: &[f32]) -> Vec<f32> {
+        let mut s = self.r.clone();
+        self.shape.dense_dot_slice_(x, &self.w, &mut s);
+        s
+    }
+    /**Neurons with `s` not exceeding threshold are ruled out and the rest compete by soft winner-take-all.
+    Returns sparse vector of active neurons.*/
+    pub fn soft_wta(&self, s: &[f32]) -> Vec<Idx> {
+        assert_eq!(s.len(), self.r.len());
+        let y_shape = self.shape.out_shape().as_scalar::<usize>();
+        let mut y: Vec<u8> = s.iter().map(|&s| if s > self.threshold { NULL } else { 0 }).collect();
+        match &self.inhibition {
+            Inhibition::U(u) => top_u_conv_par_(&y_shape, u, s, &mut y),
+            Inhibition::V(v) => top_v_conv_par_(&y_shape, v, s, &mut y),
+        }
+        y.iter().enumerate().filter(|(_, &y)| y == 1).map(|(i, _)| Idx::from_usize(i)).collect()
+    }
+    /**Returns excitation `s` and sparse vector of active neurons*/
+    pub fn infer_sparse(&self, x: &[Idx]) -> (Vec<f32>, Vec<Idx>) {
+        let s = self.s_sparse(x);
+        let y = self.soft_wta(&s);
+        (s, y)
+    }
+    /**Returns excitation `s` and sparse vector of active neurons*/
+    pub fn infer_dense(&self, x: &[f32]) -> (Vec<f32>, Vec<Idx>) {
+        let s = self.s_dense(x);
+        let y = self.soft_wta(&s);
+        (s, y)
+    }
+    /**Normalises incoming weights of every output neuron with respect to the given norm (1 or 2)*/
+    pub fn normalize_w(&mut self, norm: usize) {
+        let v = self.shape.out_volume().as_();
+        for j in 0..v {
+            normalize_column(&mut self.w, j, v, norm)
+        }
+    }
+    /**Normalises incoming weights of the given output neurons with respect to the given norm (1 or 2)*/
+    pub fn normalize_w_columns(&mut self, norm: usize, columns: &[Idx]) {
+        let v = self.shape.out_volume().as_();
+        for j in columns {
+            normalize_column(&mut self.w, j.as_(), v, norm)
+        }
+    }
+    /**Hebbian rule for sparse binary input. Weights between active inputs `x` and active outputs `y` are increased by
+    `epsilon` (or `epsilon/|x|` if not biased) and then the incoming weights of `y` are renormalised with respect to
+    the given norm (1 or 2). In terms of `py/convergence_tests/single_layer.py`, the unbiased rule with norm 2 is
+    `HardWtaL2`, the unbiased rule with norm 1 is `HardWtaZeroOrder` and the biased rule is `SoftWta`.*/
+    pub fn learn_w_sparse(&mut self, epsilon: f32, x: &[Idx], y: &[Idx], biased: bool, norm: usize) {
+        if biased {
+            self.shape.sparse_biased_increment(&mut self.w, epsilon, x, y)
+        } else {
+            self.shape.sparse_unbiased_increment(&mut self.w, epsilon, x, y)
+        }
+        self.normalize_w_columns(norm, y)
+    }
+    /**Hebbian rule for dense input `w += epsilon * x` applied to the incoming weights of active outputs `y`,
+    which are then renormalised with respect to the given norm (1 or 2)*/
+    pub fn learn_w_dense(&mut self, epsilon: f32, x: &[f32], y: &[Idx], norm: usize) {
+        let mut y_dense = vec![0f32; self.r.len()];
+        for k in y {
+            y_dense[k.as_()] = 1.;
+        }
+        self.shape.dense_increment(&mut self.w, epsilon, x, &y_dense);
+        self.normalize_w_columns(norm, y)
+    }
+    /**Moves `U[k,j]` of every active neuron `k` towards `E[s_k-s_j]`. Panics if the inhibition is binary.*/
+    pub fn learn_u(&mut self, epsilon: f32, s: &[f32], y: &[Idx]) {
+        match &mut self.inhibition {
+            Inhibition::U(u) => self.shape.update_u_as_expected_sk_minus_sj(epsilon, s, y, u),
+            Inhibition::V(_) => panic!("Binary inhibition V can't be learned"),
+        }
+    }
+    /**Decreases bias of active neurons, so that all neurons get a chance to win (homeostasis)*/
+    pub fn learn_r(&mut self, epsilon: f32, y: &[Idx]) {
+        for k in y {
+            self.r[k.as_()] -= epsilon
+        }
+    }
+}
+
+/**Port of `HardWtaL1` from `py/convergence_tests/single_layer.py`. Every output neuron keeps real-valued permanences `Q`
+(laid out just like `ConvShape::w_shape`) but it is connected only to the `a` inputs of its kernel column with the
+largest permanence, so the effective weights `W` are binary. Excitation is `s = xW + r` and only the neuron with
+the largest excitation in every minicolumn wins (hard winner-take-all).*/
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HardWtaL1<Idx: Debug + PrimInt> {
+    shape: ConvShape<Idx>,
+    /**permanences of shape `[kernel_height, kernel_width, in_channels, out_height, out_width, out_channels]`*/
+    q: Vec<f32>,
+    /**binary weights (0 or 1) of the same shape as `q`, with exactly `a` ones per output neuron*/
+    w: Vec<f32>,
+    /**bias of shape `[out_height, out_width, out_channels]`*/
+    r: Vec<f32>,
+    a: usize,
+}
+
+impl<Idx: Debug + PrimInt + AsPrimitive<usize> + FromUsize + Step + Hash> HardWtaL1<Idx> {
+    /**Random permanences in [0,1). Every output neuron is connected to `a` inputs of its kernel column.*/
+    pub fn new(shape: ConvShape<Idx>, a: usize) -> Self {
+        Self::new_with_rng(shape, a, &mut rand::thread_rng())
+    }
+    /**Same as `new` but permanences are drawn from `rng`, so that they are reproducible*/
+    pub fn new_with_rng(shape: ConvShape<Idx>, a: usize, rng: &mut impl Rng) -> Self {
+        let q = Vec::rand_with_rng((shape.kernel_column_volume() * shape.out_volume()).as_(), rng);
+        Self::from_q(shape, q, a)
+    }
+    pub fn from_q(shape: ConvShape<Idx>, q: Vec<f32>, a: usize) -> Self {
+        let v: usize = shape.out_volume().as_();
+        assert_eq!(q.len(), shape.kernel_column_volume().as_() * v, "Q has wrong shape");
+        assert!(a <= shape.kernel_column_volume().as_(), "Can't connect {} inputs in kernel column of volume {:?}", a, shape.kernel_column_volume());
+        let mut slf = Self { w: vec![0.; q.len()], r: vec![0.; v], q: vec![0.; q.len()], shape, a };
+        slf.set_q(&q);
+        slf
+    }
+    pub fn shape(&self) -> &ConvShape<Idx> {
+        &self.shape
+    }
+    pub fn a(&self) -> usize {
+        self.a
+    }
+    pub fn q(&self) -> &[f32] {
+        &self.q
+    }
+    /**Overwrites permanences and reconnects every output neuron to the inputs with the largest permanence*/
+    pub fn set_q(&mut self, q: &[f32]) {
+        self.q.copy_from_slice(q);
+        for j in 0..self.r.len() {
+            self.update_w_column(j)
+        }
+    }
+    /**Binary weights derived from `q`*/
+    pub fn w(&self) -> &[f32] {
+        &self.w
+    }
+    pub fn r(&self) -> &[f32] {
+        &self.r
+    }
+    pub fn r_mut(&mut self) -> &mut [f32] {
+        &mut self.r
+    }
+    /**Connects output neuron `j` to the `a` inputs with the largest permanence (just like `argpartition` in numpy)*/
+    fn update_w_column(&mut self, j: usize) {
+        let v = self.shape.out_volume().as_();
+        let kcv = self.shape.kernel_column_volume().as_();
+        let mut order: Vec<usize> = (0..kcv).collect();
+        if self.a > 0 && self.a < kcv {
+            order.select_nth_unstable_by(kcv - self.a, |&a, &b| self.q[a * v + j].partial_cmp(&self.q[b * v + j]).unwrap());
+        }
+        for (rank, &i) in order.iter().enumerate() {
+            self.w[i * v + j] = if rank + self.a >= kcv { 1. } else { 0. };
+        }
+    }
+    /**Excitation `s = xW + r` for sparse binary input `x` (list of indices)*/
+    pub fn s_sparse(&self, x: &[Idx]) -> Vec<f32> {
+        let mut s = self.r.clone();
+        self.shape.sparse_dot_slice_(x, &self.w, &mut s);
+        s
+    }
+    /**Excitation `s = xW + r` for dense input `x` of shape `[in_height, in_width, in_channels]`*/
+    pub fn s_dense(&self, x: &[f32]) -> Vec<f32> {
+        let mut s = self.r.clone();
+        self.shape.dense_dot_slice_(x, &self.w, &mut s);
+        s
+    }
+    /**Returns the first neuron with the largest excitation in every minicolumn*/
+    pub fn hard_wta(&self, s: &[f32]) -> Vec<Idx> {
+        assert_eq!(s.len(), self.r.len());
+        let c = self.shape.out_channels().as_();
+        s.chunks(c).enumerate().map(|(col, s)| Idx::from_usize(col * c + argmax(s))).collect()
+    }
+    /**Returns excitation `s` and sparse vector of winners*/
+    pub fn infer_sparse(&self, x: &[Idx]) -> (Vec<f32>, Vec<Idx>) {
+        let s = self.s_sparse(x);
+        let y = self.hard_wta(&s);
+        (s, y)
+    }
+    /**Returns excitation `s` and sparse vector of winners*/
+    pub fn infer_dense(&self, x: &[f32]) -> (Vec<f32>, Vec<Idx>) {
+        let s = self.s_dense(x);
+        let y = self.hard_wta(&s);
+        (s, y)
+    }
+    /**Multiplies permanences of every winner by `1-epsilon`*/
+    fn decay_q(&mut self, epsilon: f32, y: &[Idx]) {
+        let v = self.shape.out_volume().as_();
+        for k in y {
+            self.q[k.as_()..].iter_mut().step_by(v).for_each(|q| *q *= 1. - epsilon)
+        }
+    }
+    /**Permanences of every winner `k` move towards the sparse binary input `x`, that is `Q[:,k] = (1-epsilon)Q[:,k] + epsilon*x`,
+    and then `k` is reconnected to the inputs with the largest permanence*/
+    pub fn learn_q_sparse(&mut self, epsilon: f32, x: &[Idx], y: &[Idx]) {
+        self.decay_q(epsilon, y);
+        self.shape.sparse_biased_increment(&mut self.q, epsilon, x, y);
+        for k in y {
+            self.update_w_column(k.as_())
+        }
+    }
+    /**Dense counterpart of `learn_q_sparse`, where `x` is of shape `[in_height, in_width, in_channels]`*/
+    pub fn learn_q_dense(&mut self, epsilon: f32, x: &[f32], y: &[Idx]) {
+        let mut y_dense = vec![0f32; self.r.len()];
+        for k in y {
+            y_dense[k.as_()] = 1.;
+        }
+        self.decay_q(epsilon, y);
+        self.shape.dense_increment(&mut self.q, epsilon, x, &y_dense);
+        for k in y {
+            self.update_w_column(k.as_())
+        }
+    }
+    /**Decreases bias of winners, so that all neurons get a chance to win (homeostasis)*/
+    pub fn learn_r(&mut self, epsilon: f32, y: &[Idx]) {
+        for k in y {
+            self.r[k.as_()] -= epsilon
+        }
+    }
+}
+
+/**Number of elements in tensor of shape `ConvShape::u_shape`*/
+fn u_len<Idx: Debug + PrimInt + AsPrimitive<usize>>(shape: &ConvShape<Idx>) -> usize {
+    (shape.out_volume() * shape.out_channels()).as_()
+}
+
+/**Divides every `stride`-th element starting at `column` by its norm (1 or 2)*/
+pub(crate) fn normalize_column(w: &mut [f32], column: usize, stride: usize, norm: usize) {
+    let column_iter = || w[column..].iter().step_by(stride);
+    let n = match norm {
+        1 => column_iter().map(|w| w.abs()).sum::<f32>(),
+        2 => column_iter().map(|w| w * w).sum::<f32>().sqrt(),
+        _ => panic!("Unknown norm {}", norm)
+    };
+    if n > 0. {
+        w[column..].iter_mut().step_by(stride).for_each(|w| *w /= n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use rand::seq::IteratorRandom;
+    use super::*;
+
+    /**Sorted sparse vector of `n` distinct indices below `volume`*/
+    fn rand_sparse(rng: &mut StdRng, n: usize, volume: u32) -> Vec<u32> {
+        let mut x = (0..volume).choose_multiple(rng, n);
+        x.sort();
+        x
+    }
+
+    #[test]
+    fn test1() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut layer = EccLayer::<u32>::new_u_with_rng(ConvShape::new_linear(25, 16), 2, 0.1, &mut rng);
+        let n = layer.shape().out_volume() as usize;
+        let norm = |layer: &EccLayer<u32>, j: usize| layer.w().iter().skip(j).step_by(n).map(|w| w * w).sum::<f32>();
+        assert!((0..n).all(|j| (norm(&layer, j) - 1.).abs() < 0.0001));
+        for _ in 0..20 {
+            let x = rand_sparse(&mut rng, 8, 25);
+            let (s, y) = layer.infer_sparse(&x);
+            for &k in &y {
+                assert!(s[k as usize] > 0.1);
+            }
+            let u_before = layer.inhibition().clone();
+            layer.learn_w_sparse(0.01, &x, &y, true, 2);
+            layer.learn_u(0.01, &s, &y);
+            layer.learn_r(0.01, &y);
+            assert!((0..n).all(|j| (norm(&layer, j) - 1.).abs() < 0.0001));
+            let (Inhibition::U(before), Inhibition::U(after)) = (u_before, layer.inhibition()) else { panic!("Expected U inhibition") };
+            for k in 0..n {
+                let changed = (0..n).any(|j| before[k * n + j] != after[k * n + j]);
+                assert_eq!(changed, y.contains(&(k as u32)), "{}", k);
+            }
+        }
+    }
+
+    #[test]
+    fn test2() {
+        let shape = ConvShape::new_in([6, 6, 2], 4, [3, 3], [1, 1]);
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut layer = EccLayer::<u32>::new_v_with_rng(shape.clone(), 1, 0., &mut rng);
+        let x: Vec<f32> = Vec::rand_with_rng(shape.in_volume() as usize, &mut rng);
+        let (s, y) = layer.infer_dense(&x);
+        assert_eq!(s.len(), shape.out_volume() as usize);
+        // every minicolumn has at least one winner, because all s are positive
+        let c = shape.out_channels();
+        for column in 0..shape.out_area() {
+            assert!(y.iter().any(|&k| k / c == column));
+        }
+        layer.learn_w_dense(0.1, &x, &y, 1);
+        let v = shape.out_volume() as usize;
+        for j in 0..v {
+            let sum: f32 = layer.w().iter().skip(j).step_by(v).sum();
+            assert!((sum - 1.).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    fn test3() {
+        // U is updated only within the minicolumns of winners
+        let shape = ConvShape::new_in([4, 4, 1], 3, [2, 2], [2, 2]);
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut layer = EccLayer::<u32>::new_u_with_rng(shape.clone(), 2, 0., &mut rng);
+        let s: Vec<f32> = Vec::rand_with_rng(shape.out_volume() as usize, &mut rng);
+        let y = vec![4u32, 11];
+        let before = layer.inhibition().clone();
+        layer.learn_u(0.5, &s, &y);
+        let (Inhibition::U(before), Inhibition::U(after)) = (before, layer.inhibition()) else { panic!("Expected U inhibition") };
+        for k in 0..12usize {
+            for c in 0..3 {
+                let i = k * 3 + c;
+                if y.contains(&(k as u32)) {
+                    let j = k / 3 * 3 + c;
+                    assert!((after[i] - (before[i] * 0.5 + (s[k] - s[j]) * 0.5)).abs() < 0.0001);
+                } else {
+                    assert_eq!(after[i], before[i]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test4() {
+        let mut layer = HardWtaL1::<u32>::new_with_rng(ConvShape::new_linear(10, 3), 2, &mut StdRng::seed_from_u64(4));
+        let n = 3;
+        let connections = |layer: &HardWtaL1<u32>, j: usize| layer.w().iter().skip(j).step_by(n).filter(|&&w| w == 1.).count();
+        assert!((0..n).all(|j| connections(&layer, j) == 2));
+        let x = vec![3u32, 7];
+        let (s, y) = layer.infer_sparse(&x);
+        assert_eq!(y.len(), 1);
+        let k = y[0] as usize;
+        assert!(s.iter().all(|&v| v <= s[k]));
+        let q_before = layer.q().to_vec();
+        layer.learn_q_sparse(0.1, &x, &y);
+        layer.learn_r(0.1, &y);
+        assert_eq!(layer.r()[k], -0.1);
+        for i in 0..10 {
+            let x_i = if x.contains(&(i as u32)) { 1. } else { 0. };
+            for j in 0..n {
+                let expected = if j == k { 0.9 * q_before[i * n + j] + 0.1 * x_i } else { q_before[i * n + j] };
+                assert!((layer.q()[i * n + j] - expected).abs() < 0.0001);
+            }
+        }
+        // after enough steps the winner is connected exactly to the input
+        for _ in 0..100 {
+            layer.learn_q_sparse(0.1, &x, &y);
+        }
+        assert_eq!(connections(&layer, k), 2);
+        assert_eq!(layer.w()[3 * n + k], 1.);
+        assert_eq!(layer.w()[7 * n + k], 1.);
+        assert!((layer.s_sparse(&x)[k] - 1.9).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test5() {
+        let shape = ConvShape::new_in([5, 5, 2], 3, [2, 2], [1, 1]);
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut sparse = HardWtaL1::<u32>::new_with_rng(shape.clone(), 4, &mut rng);
+        let mut dense = sparse.clone();
+        for _ in 0..10 {
+            let x = rand_sparse(&mut rng, 10, shape.in_volume());
+            let mut x_dense = vec![0f32; shape.in_volume() as usize];
+            x.iter().for_each(|&i| x_dense[i as usize] = 1.);
+            let (s, y) = sparse.infer_sparse(&x);
+            assert_eq!((s.clone(), y.clone()), dense.infer_dense(&x_dense));
+            // exactly one winner per minicolumn
+            assert_eq!(y.len(), shape.out_area() as usize);
+            for (column, &k) in y.iter().enumerate() {
+                assert_eq!(k / 3, column as u32);
+            }
+            sparse.learn_q_sparse(0.2, &x, &y);
+            dense.learn_q_dense(0.2, &x_dense, &y);
+            assert!(sparse.q().iter().zip(dense.q()).all(|(a, b)| (a - b).abs() < 0.0001));
+            assert_eq!(sparse.w(), dense.w());
+        }
+    }
+}
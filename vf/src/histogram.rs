@@ -2,78 +2,141 @@ use std::ops::{Add, Div, Mul, Range, Sub};
 use num_traits::{Num, Zero};
 use crate::*;
 use crate::from_usize::FromUsize;
+use crate::color_space::ColorSpace;
+
+/**Types of pixel values that can be sorted into histogram bins*/
+pub trait Pixel: Copy {
+    /**Range of values covered by the histogram bins by default. It's `0..256` for `u8`, `0..65536` for `u16` and `0..1` for `f32`*/
+    const RANGE: Range<f32>;
+    /**Value of full intensity. It's `255` for `u8`, `65535` for `u16` and `1` for `f32`*/
+    const MAX: f32;
+    fn to_f32(self) -> f32;
+    /**Rounds and saturates the value if necessary*/
+    fn from_f32(v: f32) -> Self;
+    /**Intensity scaled to `0..=1`*/
+    fn to_unit(self) -> f32 {
+        self.to_f32() / Self::MAX
+    }
+    /**Inverse of `to_unit`*/
+    fn from_unit(v: f32) -> Self {
+        Self::from_f32(v * Self::MAX)
+    }
+}
 
+impl Pixel for u8 {
+    const RANGE: Range<f32> = 0. ..256.;
+    const MAX: f32 = 255.;
+    fn to_f32(self) -> f32 { self as f32 }
+    fn from_f32(v: f32) -> Self { v.round() as u8 }
+}
+
+impl Pixel for u16 {
+    const RANGE: Range<f32> = 0. ..65536.;
+    const MAX: f32 = 65535.;
+    fn to_f32(self) -> f32 { self as f32 }
+    fn from_f32(v: f32) -> Self { v.round() as u16 }
+}
+
+impl Pixel for f32 {
+    const RANGE: Range<f32> = 0. ..1.;
+    const MAX: f32 = 1.;
+    fn to_f32(self) -> f32 { self }
+    fn from_f32(v: f32) -> Self { v }
+}
+
+/**Splits range of values `low..high` into `count` bins of equal width. Values outside of the range fall into the first or last bin.*/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bins {
+    count: usize,
+    low: f32,
+    high: f32,
+}
+
+impl Bins {
+    pub fn new(count: usize, range: Range<f32>) -> Self {
+        assert!(count > 0, "There must be at least one bin");
+        assert!(range.start < range.end, "Range {:?} is empty", range);
+        Self { count, low: range.start, high: range.end }
+    }
+    /**`count` bins spanning the default range of pixel type `P`*/
+    pub fn of<P: Pixel>(count: usize) -> Self {
+        Self::new(count, P::RANGE)
+    }
+    /**256 bins spanning `0..256`. This is what all the functions without `_binned` suffix use*/
+    pub fn u8() -> Self {
+        Self::of::<u8>(256)
+    }
+    pub fn count(&self) -> usize {
+        self.count
+    }
+    pub fn range(&self) -> Range<f32> {
+        self.low..self.high
+    }
+    pub fn width(&self) -> f32 {
+        (self.high - self.low) / self.count as f32
+    }
+    pub fn bin(&self, value: f32) -> usize {
+        // negative values and NaN saturate to 0
+        (((value - self.low) / self.width()) as usize).min(self.count - 1)
+    }
+    /**Smallest value that falls into the bin*/
+    pub fn bin_start(&self, bin: usize) -> f32 {
+        self.low + bin as f32 * self.width()
+    }
+    /**Moves value from one bin to another, preserving its position within the bin*/
+    pub fn rebin<P: Pixel>(&self, value: P, from_bin: usize, to_bin: usize) -> P {
+        if from_bin == to_bin {
+            value
+        } else {
+            P::from_f32(self.bin_start(to_bin) + (value.to_f32() - self.bin_start(from_bin)))
+        }
+    }
+}
 
 pub fn interpolate_gaps(histogram: &mut [f32]) {
-    assert_eq!(histogram.len() % 256, 0);
-    let mut offset = 0;
+    interpolate_gaps_binned(histogram, 256)
+}
 
-    while offset < histogram.len() {
+/**`histogram` shape is `[channels, bins]`. Every run of zeros is replaced by values linearly interpolated between the
+last non-zero value before the run (or 0 if there is none) and the first non-zero value after it (or 0 if there is none).*/
+pub fn interpolate_gaps_binned(histogram: &mut [f32], bins: usize) {
+    assert_eq!(histogram.len() % bins, 0);
+    for histogram in histogram.chunks_mut(bins) {
         let mut prev_non_zero = 0.;
         let mut i = 0;
-        while i < 256 {
-            debug_assert!(i < 256);
-            debug_assert!((i>0 && histogram[i - 1] == prev_non_zero) || (i == 0 && prev_non_zero == 0.));
-            let hist_i = histogram[offset + i];
+        while i < bins {
+            let hist_i = histogram[i];
             if hist_i == 0. {
-                let mut j = i;
-                debug_assert_eq!(histogram[offset + i], hist_i);
-                debug_assert_eq!(hist_i, 0.);
-                debug_assert!(j < 256);
-                let mut hist_j = 0.;
-                while j < 256 {
-                    hist_j = histogram[offset + j];
-                    if hist_j == 0. {
-                        j += 1;
-                    } else {
-                        debug_assert_eq!(histogram[offset + j], hist_j);
-                        debug_assert_ne!(hist_j, 0.);
-                        break;
-                    }
+                let mut j = i + 1;
+                while j < bins && histogram[j] == 0. {
+                    j += 1;
                 }
-                debug_assert!((histogram[offset + j] == hist_j && hist_j != 0.) || (hist_j == 0. && j == 256));
-                debug_assert!(j >= i);
-                let delta_x = (j+1-i) as f32;
-                debug_assert!((i>0 && histogram[offset + i - 1] == prev_non_zero) || (i == 0 && prev_non_zero == 0.));
-                let delta_y = hist_j - prev_non_zero;
-                let b = delta_y / delta_x;
+                let hist_j = if j < bins { histogram[j] } else { 0. };
+                let delta_x = (j + 1 - i) as f32;
+                let b = (hist_j - prev_non_zero) / delta_x;
                 for k in i..j {
-                    // i =< k < j
-                    // i-1 < k < j
-                    // i-1 - k < 0 < j - k
-                    // 0 < k - i+1
-                    // 0 < k - i+1 < delta_x
-                    // prev_non_zero < prev_non_zero + (k - i+1)*b < prev_non_zero + delta_x*b == prev_non_zero + delta_y == hist_j
-                    debug_assert!(if b >= 0. {prev_non_zero + (k+1 - i) as f32 *b < hist_j} else{hist_j < prev_non_zero + (k+1 - i) as f32 *b});
-                    debug_assert!(if b >= 0. {prev_non_zero < prev_non_zero + (k - i+1) as f32 *b}else{prev_non_zero + (k - i+1) as f32 *b < prev_non_zero});
-                    // by induction: prev_non_zero != 0 or (i == 0 and prev_non_zero == 0)
-                    histogram[offset+k] = prev_non_zero + (k - i + 1) as f32 * b;
-                    debug_assert!(if b >=0. {prev_non_zero < histogram[offset+k]}else{histogram[offset+k] < prev_non_zero});
-                    debug_assert!(if b >= 0. {histogram[offset+k] < hist_j}else{hist_j < histogram[offset+k]});
+                    histogram[k] = prev_non_zero + (k - i + 1) as f32 * b;
                 }
+                prev_non_zero = hist_j;
                 i = j + 1;
-                debug_assert_ne!(histogram[offset+i-1],0.);
-                debug_assert_ne!(hist_j, 0.);
-                prev_non_zero = hist_j ;
-
-            }else{
-                i += 1;
-                debug_assert_ne!(histogram[offset+i-1], 0.);
-                debug_assert_eq!(hist_i, histogram[offset+i-1]);
+            } else {
                 prev_non_zero = hist_i;
+                i += 1;
             }
         }
-        debug_assert_eq!(i,256);
-        offset = i;
     }
 }
 
 
 pub fn histogram(image: &[u8], stride: usize, source_mask: impl Fn(usize) -> bool) -> Box<[u32; 256]> {
-    let mut slice = Box::new([0; 256]);
+    histogram_binned(image, stride, &Bins::u8(), source_mask).into_boxed_slice().try_into().unwrap()
+}
+
+pub fn histogram_binned<P: Pixel>(image: &[P], stride: usize, bins: &Bins, source_mask: impl Fn(usize) -> bool) -> Vec<u32> {
+    let mut slice = vec![0; bins.count()];
     for (idx, pixel) in image.iter().step_by(stride).cloned().enumerate() {
         if source_mask(idx) {
-            slice[pixel as usize] += 1
+            slice[bins.bin(pixel.to_f32())] += 1
         }
     }
     slice
@@ -81,16 +144,16 @@ pub fn histogram(image: &[u8], stride: usize, source_mask: impl Fn(usize) -> boo
 
 /**`image` shape is `[height,width,channels]`*/
 pub fn histograms(image: &[u8], channels: usize, source_mask: impl Fn(usize) -> bool) -> Box<[[u32; 256]]> {
-    let mut slice = vec![[0u32; 256]; channels];
+    histograms_binned(image, channels, &Bins::u8(), source_mask).chunks_exact(256).map(|h| h.try_into().unwrap()).collect()
+}
+
+/**`image` shape is `[height,width,channels]`, returned histograms are of shape `[channels, bins]`*/
+pub fn histograms_binned<P: Pixel>(image: &[P], channels: usize, bins: &Bins, source_mask: impl Fn(usize) -> bool) -> Vec<u32> {
+    let mut slice = Vec::with_capacity(channels * bins.count());
     for channel in 0..channels {
-        let subslice = &mut slice[channel];
-        for (idx, pixel) in image[channel..].iter().step_by(channels).cloned().enumerate() {
-            if source_mask(idx) {
-                subslice[pixel as usize] += 1;
-            }
-        }
+        slice.extend(histogram_binned(&image[channel..], channels, bins, &source_mask));
     }
-    slice.into_boxed_slice()
+    slice
 }
 
 pub fn _normalize_histogram(histogram: Box<[u32; 256]>) -> Box<[f32; 256]> {
@@ -113,13 +176,18 @@ pub fn normalize_histogram(histogram: &[u32; 256]) -> Box<[f32; 256]> {
 
 /**Histograms with multiple channels*/
 pub fn normalize_histograms(histograms: &[u32]) -> Box<[f32]> {
-    assert_eq!(histograms.len() % 256, 0);
-    let channels: usize = histograms.len() / 256;
+    normalize_histograms_binned(histograms, 256)
+}
+
+/**Histograms of shape `[channels, bins]`*/
+pub fn normalize_histograms_binned(histograms: &[u32], bins: usize) -> Box<[f32]> {
+    assert_eq!(histograms.len() % bins, 0);
+    let channels: usize = histograms.len() / bins;
     let mut o = Vec::with_capacity(histograms.len());
     for channel in 0..channels {
-        let offset = channel * 256;
-        let sum_inv = 1. / histograms[offset..offset + 256].iter().sum::<u32>() as f32;
-        for i in offset..offset + 256 {
+        let offset = channel * bins;
+        let sum_inv = 1. / histograms[offset..offset + bins].iter().sum::<u32>() as f32;
+        for i in offset..offset + bins {
             debug_assert_eq!(o.len(), i);
             o.push(histograms[i] as f32 * sum_inv);
         }
@@ -135,8 +203,18 @@ pub fn match_histogram(source: &[u8], src_stride: usize, reference: &[u8], ref_s
 }
 
 pub fn match_histogram_(source: &[u8], src_stride: usize, reference: &[u8], ref_stride: usize, output: &mut [u8], out_stride: usize, source_mask: impl Fn(usize) -> bool) {
-    let hist_ref = _normalize_histogram(histogram(reference, ref_stride, &source_mask));
-    match_precomputed_histogram_(source, src_stride, hist_ref.as_slice(), output, out_stride, source_mask)
+    match_histogram_binned_(source, src_stride, reference, ref_stride, output, out_stride, &Bins::u8(), source_mask)
+}
+
+pub fn match_histogram_binned<P: Pixel>(source: &[P], src_stride: usize, reference: &[P], ref_stride: usize, bins: &Bins, source_mask: impl Fn(usize) -> bool) -> Vec<P> {
+    let mut o = source.to_vec();
+    match_histogram_binned_(source, src_stride, reference, ref_stride, &mut o, src_stride, bins, source_mask);
+    o
+}
+
+pub fn match_histogram_binned_<P: Pixel>(source: &[P], src_stride: usize, reference: &[P], ref_stride: usize, output: &mut [P], out_stride: usize, bins: &Bins, source_mask: impl Fn(usize) -> bool) {
+    let hist_ref = normalize_histograms_binned(&histogram_binned(reference, ref_stride, bins, &source_mask), bins.count());
+    match_precomputed_histogram_binned_(source, src_stride, &hist_ref, output, out_stride, bins, source_mask)
 }
 
 pub fn match_precomputed_histogram(source: &[u8], src_stride: usize, hist_ref: &[f32], source_mask: impl Fn(usize) -> bool) -> Vec<u8> {
@@ -147,8 +225,12 @@ pub fn match_precomputed_histogram(source: &[u8], src_stride: usize, hist_ref: &
 }
 
 pub fn match_precomputed_histogram_(source: &[u8], src_stride: usize, hist_ref: &[f32], output: &mut [u8], out_stride: usize, source_mask: impl Fn(usize) -> bool) {
-    let hist_src = histogram(source, src_stride, &source_mask);
-    match_2precomputed_histogram_(source, src_stride, hist_src.as_slice(), &hist_ref, output, out_stride, source_mask)
+    match_precomputed_histogram_binned_(source, src_stride, hist_ref, output, out_stride, &Bins::u8(), source_mask)
+}
+
+pub fn match_precomputed_histogram_binned_<P: Pixel>(source: &[P], src_stride: usize, hist_ref: &[f32], output: &mut [P], out_stride: usize, bins: &Bins, source_mask: impl Fn(usize) -> bool) {
+    let hist_src = histogram_binned(source, src_stride, bins, &source_mask);
+    match_2precomputed_histogram_binned_(source, src_stride, &hist_src, hist_ref, output, out_stride, bins, source_mask)
 }
 
 /**You can use source_mask to apply histogram matching partially only to a masked region of image. `source_mask(idx)` takes as input `idx=y*img_width+x`.
@@ -159,29 +241,36 @@ hist_src.iter().sum()==source.iter().step_by(src_stride).enumerate().filter(|(id
 so it must be recomputed specifically for every given mask
  */
 pub fn match_2precomputed_histogram_(source: &[u8], src_stride: usize, hist_src: &[u32], hist_ref: &[f32], output: &mut [u8], out_stride: usize, source_mask: impl Fn(usize) -> bool) {
-    debug_assert_eq!(hist_ref.len(), 256);
-    debug_assert_eq!(hist_src.len(), 256);
+    match_2precomputed_histogram_binned_(source, src_stride, hist_src, hist_ref, output, out_stride, &Bins::u8(), source_mask)
+}
+
+/**Same as `match_2precomputed_histogram_` but pixels are sorted into arbitrary bins.
+ Matched pixels keep their position within the bin, so precision finer than bin width is preserved.*/
+pub fn match_2precomputed_histogram_binned_<P: Pixel>(source: &[P], src_stride: usize, hist_src: &[u32], hist_ref: &[f32], output: &mut [P], out_stride: usize, bins: &Bins, source_mask: impl Fn(usize) -> bool) {
+    let n = bins.count();
+    assert_eq!(hist_ref.len(), n);
+    assert_eq!(hist_src.len(), n);
 
     let sum_src: u32 = hist_src.iter().sum();
 
     let mut i_ref = 0;
-    let mut stack: Vec<(/*reference value to replace source value*/u8, /*how much source value to replace*/usize)> = Vec::new();
-    /**for each source pixel value stores a slice (offset,end) into stack that tells us how to replace that value*/
-    let mut stack_offsets = Box::new([(0usize, 0usize); 256]);
+    let mut stack: Vec<(/*reference bin to replace source bin*/usize, /*how much source value to replace*/usize)> = Vec::new();
+    /**for each source bin stores a slice (offset,end) into stack that tells us how to replace that value*/
+    let mut stack_offsets = vec![(0usize, 0usize); n];
     let mut stack_offset = 0;
     let mut popped_src = 0i32;
-    for i_src in 0..256 {
+    for i_src in 0..n {
         if popped_src <= 0 {
             let to_pop = hist_src[i_src] as i32;
             let to_replace = to_pop.min(-popped_src);
             if to_replace > 0 {
-                stack.push(((i_ref - 1) as u8, to_replace as usize));//we should replace `to_replace` pixels of value `i_src` with value `i_ref`
+                stack.push((i_ref - 1, to_replace as usize));//we should replace `to_replace` pixels of value `i_src` with value `i_ref`
             }
             popped_src += to_pop;
         }
         while popped_src > 0 {
-            if i_ref >= 256 {
-                stack.push((255u8, popped_src as usize));
+            if i_ref >= n {
+                stack.push((n - 1, popped_src as usize));
                 popped_src = 0;
                 break;
             }
@@ -192,7 +281,7 @@ pub fn match_2precomputed_histogram_(source: &[u8], src_stride: usize, hist_src:
             let replaced_src = corresponding_src.min(popped_src);
             popped_src -= corresponding_src;
             if replaced_src > 0 {
-                stack.push((i_ref as u8, replaced_src as usize));//we should replace `replaced_src` pixels of value `i_src` with value `i_ref`
+                stack.push((i_ref, replaced_src as usize));//we should replace `replaced_src` pixels of value `i_src` with value `i_ref`
             }
             i_ref += 1;
         }
@@ -202,7 +291,7 @@ pub fn match_2precomputed_histogram_(source: &[u8], src_stride: usize, hist_src:
         stack_offset = stack.len();
     }
     // debug_assert!(true);
-    // If at this point i_ref < 256, that can only be due to floating-point imprecision. (which is unlikely as we use f64)
+    // If at this point i_ref < n, that can only be due to floating-point imprecision. (which is unlikely as we use f64)
     // A few pixels might be improperly replaced but that's fine. Nobody will notice.
 
     'outer: for ((idx, src_i), out_i) in source
@@ -210,16 +299,17 @@ pub fn match_2precomputed_histogram_(source: &[u8], src_stride: usize, hist_src:
         .enumerate()
         .zip(output.iter_mut().step_by(out_stride)) {
         if source_mask(idx) { // we can partially apply histogram matching only to a masked region of an image
-            let (mut offset, end) = stack_offsets[src_i as usize];
+            let src_bin = bins.bin(src_i.to_f32());
+            let (mut offset, end) = stack_offsets[src_bin];
             while offset < end {
-                let (ref_i, to_replace) = stack[offset];
+                let (ref_bin, to_replace) = stack[offset];
                 if to_replace > 0 {
-                    *out_i = ref_i;
+                    *out_i = bins.rebin(src_i, src_bin, ref_bin);
                     stack[offset].1 = to_replace - 1;
                     continue 'outer;
                 } else {
                     offset += 1;
-                    stack_offsets[src_i as usize].0 = offset;
+                    stack_offsets[src_bin].0 = offset;
                 }
             }
             // coming here should rarely happen. Only possible due to FP imprecision. Usually we hit continue statement
@@ -253,112 +343,361 @@ pub fn blend_(scalar1: f32, histogram1: &[f32], scalar2: f32, histogram2: &[f32]
 
 /**shape == [height, width, channels]*/
 pub fn match_images(source: &[u8], src_shape: &[usize; 3], reference: &[u8], ref_shape: &[usize; 3], source_mask: impl Fn(usize) -> bool) -> Box<[u8]> {
+    match_images_binned(source, src_shape, reference, ref_shape, &Bins::u8(), source_mask)
+}
+
+/**shape == [height, width, channels]*/
+pub fn match_images_binned<P: Pixel>(source: &[P], src_shape: &[usize; 3], reference: &[P], ref_shape: &[usize; 3], bins: &Bins, source_mask: impl Fn(usize) -> bool) -> Box<[P]> {
     assert_eq!(src_shape[2], ref_shape[2]);
     let channels = src_shape[2];
-    let len = src_shape[0] * src_shape[1];
-    let mut out = Vec::<u8>::with_capacity(len * channels);
-    unsafe { out.set_len(out.capacity()) }
+    let mut out = source.to_vec();
     for channel in 0..channels {
-        match_histogram_(&source[channel..], channels, &reference[channel..], channels, &mut out[channel..], channels, &source_mask);
+        match_histogram_binned_(&source[channel..], channels, &reference[channel..], channels, &mut out[channel..], channels, bins, &source_mask);
     }
     out.into_boxed_slice()
 }
 
 /**shape == [height, width, channels],  hist_ref:[channels, 256]*/
 pub fn match_precomputed_images(source: &[u8], src_shape: &[usize; 3], hist_ref: &[f32], source_mask: impl Fn(usize) -> bool) -> Box<[u8]> {
-    let hist_src = histograms(source, src_shape[2], &source_mask);
-    match_2precomputed_images(source, src_shape, hist_src.flatten(), hist_ref, source_mask)
+    match_precomputed_images_binned(source, src_shape, hist_ref, &Bins::u8(), source_mask)
+}
+
+/**shape == [height, width, channels],  hist_ref:[channels, bins]*/
+pub fn match_precomputed_images_binned<P: Pixel>(source: &[P], src_shape: &[usize; 3], hist_ref: &[f32], bins: &Bins, source_mask: impl Fn(usize) -> bool) -> Box<[P]> {
+    let hist_src = histograms_binned(source, src_shape[2], bins, &source_mask);
+    match_2precomputed_images_binned(source, src_shape, &hist_src, hist_ref, bins, source_mask)
 }
 
 /**shape == [height, width, channels], hist_src:[channels,256], hist_ref:[channels, 256]*/
 pub fn match_2precomputed_images(source: &[u8], src_shape: &[usize; 3], hist_src: &[u32], hist_ref: &[f32], source_mask: impl Fn(usize) -> bool) -> Box<[u8]> {
+    match_2precomputed_images_binned(source, src_shape, hist_src, hist_ref, &Bins::u8(), source_mask)
+}
+
+/**shape == [height, width, channels], hist_src:[channels,bins], hist_ref:[channels, bins]*/
+pub fn match_2precomputed_images_binned<P: Pixel>(source: &[P], src_shape: &[usize; 3], hist_src: &[u32], hist_ref: &[f32], bins: &Bins, source_mask: impl Fn(usize) -> bool) -> Box<[P]> {
     assert_eq!(hist_src.len(), hist_ref.len());
+    let n = bins.count();
     let channels = src_shape[2];
-    let len = src_shape[0] * src_shape[1];
-    let mut out = Vec::<u8>::with_capacity(len * channels);
-    unsafe { out.set_len(out.capacity()) }
+    assert_eq!(hist_src.len(), channels * n);
+    let mut out = source.to_vec();
     for channel in 0..channels {
-        let hist_offset = channel * 256;
-        match_2precomputed_histogram_(&source[channel..], channels, &hist_src[hist_offset..hist_offset + 256], &hist_ref[hist_offset..hist_offset + 256], &mut out[channel..], channels, &source_mask);
+        let hist_offset = channel * n;
+        match_2precomputed_histogram_binned_(&source[channel..], channels, &hist_src[hist_offset..hist_offset + n], &hist_ref[hist_offset..hist_offset + n], &mut out[channel..], channels, bins, &source_mask);
     }
     out.into_boxed_slice()
 }
 
-/**Finds reference histogram that is closest to the source histogram. The distance is mean square error.
- Returned distance is between 0 and the number of channels (because it's between 0 and 1 for each channel and then summed up)*/
-pub fn find_closest(hist_src: &[[u32; 256]], batch: usize, references: &[f32]) -> (usize, f32) {
-    let channels = hist_src.len();
-    let channels256 = channels * 256;
-    assert_eq!(references.len(), batch * channels * 256);
-    let mut min_square_diff = f32::INFINITY;
-    let mut best_ref_idx = 0;
-    for ref_idx in 0..batch {
-        let ref_offset = ref_idx * channels256;
-        /**`ref_hists` shape is `[channels, 256]`*/
-        let ref_hists = &references[ref_offset..ref_offset + channels256];
-        let mut square_diff = 0.;
-        for channel in 0..channels {
-            let offset = channel * 256;
-            let hist_src = &hist_src[channel];
-            let hist_ref = &ref_hists[offset..offset + 256];
-            let s_inv = 1. / hist_src.iter().sum::<u32>() as f32;
-            fn sq(a: f32) -> f32 {
-                a * a
+/**Converts `image` of shape `[height, width, 3]` from RGB to `color_space` and returns histograms of all three channels,
+ of shape `[3, bins]`. Converted channels are in `0..=1`, so that's the range `bins` should span.*/
+pub fn histograms_in_space<P: Pixel>(image: &[P], color_space: ColorSpace, bins: &Bins, source_mask: impl Fn(usize) -> bool) -> Vec<u32> {
+    histograms_binned(&color_space.image_from_rgb(image), 3, bins, source_mask)
+}
+
+/**Converts converted image back to RGB. Pixels outside of the mask are copied from the source, so they stay intact despite rounding.*/
+fn image_from_space<P: Pixel>(source: &[P], converted: &[f32], color_space: ColorSpace, source_mask: impl Fn(usize) -> bool) -> Box<[P]> {
+    let mut out = color_space.image_to_rgb(converted);
+    for (idx, (out, src)) in out.chunks_exact_mut(3).zip(source.chunks_exact(3)).enumerate() {
+        if !source_mask(idx) {
+            out.copy_from_slice(src)
+        }
+    }
+    out.into_boxed_slice()
+}
+
+/**Images of shape `[height, width, 3]` in RGB are converted to `color_space` and only the given `channels` of it are matched
+ (for instance `&[color_space.luminance().unwrap()]` matches brightness without shifting hues). Converted channels are in `0..=1`,
+ so that's the range `bins` should span.*/
+pub fn match_images_in_space<P: Pixel>(source: &[P], src_shape: &[usize; 3], reference: &[P], ref_shape: &[usize; 3], bins: &Bins, color_space: ColorSpace, channels: &[usize], source_mask: impl Fn(usize) -> bool) -> Box<[P]> {
+    assert_eq!(src_shape[2], 3, "Source must be an RGB image");
+    assert_eq!(ref_shape[2], 3, "Reference must be an RGB image");
+    assert!(channels.iter().all(|&c| c < 3), "Channels {:?} out of bounds", channels);
+    let src = color_space.image_from_rgb(source);
+    let rfc = color_space.image_from_rgb(reference);
+    let mut out = src.clone();
+    for &c in channels {
+        match_histogram_binned_(&src[c..], 3, &rfc[c..], 3, &mut out[c..], 3, bins, &source_mask);
+    }
+    image_from_space(source, &out, color_space, source_mask)
+}
+
+/**Same as `match_best_images_binned` but source image of shape `[height, width, 3]` in RGB is converted to `color_space` and only
+ the given `channels` are compared and matched. `references` are of shape `[batch, 3, bins]` and hold histograms of all
+ three channels of `color_space` (as computed by `histograms_in_space`).*/
+pub fn match_best_images_in_space<P: Pixel>(source: &[P], src_shape: &[usize; 3], batch: usize, references: &[f32], bins: &Bins, metric: HistogramDistance, color_space: ColorSpace, channels: &[usize], source_mask: impl Fn(usize) -> bool) -> (Box<[P]>, usize, f32) {
+    assert_eq!(src_shape[2], 3, "Source must be an RGB image");
+    assert!(channels.iter().all(|&c| c < 3), "Channels {:?} out of bounds", channels);
+    let n = bins.count();
+    assert_eq!(references.len(), batch * 3 * n);
+    let src = color_space.image_from_rgb(source);
+    let hist_src = histograms_binned(&src, 3, bins, &source_mask);
+    let select_channels = |hists: &[u32]| -> Vec<u32> { channels.iter().flat_map(|&c| hists[c * n..(c + 1) * n].iter().cloned()).collect() };
+    let selected_references: Vec<f32> = references.chunks_exact(3 * n)
+        .flat_map(|r| channels.iter().flat_map(move |&c| r[c * n..(c + 1) * n].iter().cloned()))
+        .collect();
+    let (best_ref_idx, min_dist) = find_closest_binned(&select_channels(&hist_src), n, batch, &selected_references, metric);
+    let best_ref_hists = &references[best_ref_idx * 3 * n..(best_ref_idx + 1) * 3 * n];
+    let mut out = src.clone();
+    for &c in channels {
+        match_2precomputed_histogram_binned_(&src[c..], 3, &hist_src[c * n..(c + 1) * n], &best_ref_hists[c * n..(c + 1) * n], &mut out[c..], 3, bins, &source_mask);
+    }
+    (image_from_space(source, &out, color_space, source_mask), best_ref_idx, min_dist)
+}
+
+/**Maps every bin to the value that equalises `histogram` (of shape `[bins]`). Equalised values are spread over the whole
+ range of `bins` (capped at `P::MAX`), so for `u8` with 256 bins they span the usual `0..=255` and for `f32` they reach 1.
+ Returns `None` if all values fall into a single bin (or there are none), because then there is nothing to equalise.*/
+pub fn equalization_lut<P: Pixel>(histogram: &[f32], bins: &Bins) -> Option<Vec<f32>> {
+    assert_eq!(histogram.len(), bins.count());
+    let total: f32 = histogram.iter().sum();
+    let cdf_min = histogram.iter().cloned().find(|&h| h > 0.)?;
+    if total - cdf_min <= 0. {
+        return None;
+    }
+    let range = bins.range();
+    let low = range.start;
+    let span = range.end.min(P::MAX) - low;
+    let mut cdf = 0.;
+    Some(histogram.iter().map(|&h| {
+        cdf += h;
+        low + span * ((cdf - cdf_min) / (total - cdf_min)).max(0.)
+    }).collect())
+}
+
+/**Clips every bin at `limit` and redistributes the clipped excess evenly among all bins, so the sum stays the same*/
+pub fn clip_histogram(histogram: &mut [f32], limit: f32) {
+    let mut excess = 0.;
+    for h in histogram.iter_mut() {
+        if *h > limit {
+            excess += *h - limit;
+            *h = limit;
+        }
+    }
+    let share = excess / histogram.len() as f32;
+    histogram.iter_mut().for_each(|h| *h += share);
+}
+
+/**Global histogram equalization. Only pixels selected by `source_mask(idx)` are counted and changed, the rest is copied as is.*/
+pub fn equalize_histogram_binned_<P: Pixel>(source: &[P], src_stride: usize, output: &mut [P], out_stride: usize, bins: &Bins, source_mask: impl Fn(usize) -> bool) {
+    let hist: Vec<f32> = histogram_binned(source, src_stride, bins, &source_mask).into_iter().map(|h| h as f32).collect();
+    let lut = equalization_lut::<P>(&hist, bins);
+    for ((idx, src_i), out_i) in source
+        .iter().step_by(src_stride).cloned()
+        .enumerate()
+        .zip(output.iter_mut().step_by(out_stride)) {
+        *out_i = match &lut {
+            Some(lut) if source_mask(idx) => P::from_f32(lut[bins.bin(src_i.to_f32())]),
+            _ => src_i
+        };
+    }
+}
+
+/**shape == [height, width, channels]. Every channel is equalised separately*/
+pub fn equalize_images(source: &[u8], src_shape: &[usize; 3], source_mask: impl Fn(usize) -> bool) -> Box<[u8]> {
+    equalize_images_binned(source, src_shape, &Bins::u8(), source_mask)
+}
+
+/**shape == [height, width, channels]. Every channel is equalised separately*/
+pub fn equalize_images_binned<P: Pixel>(source: &[P], src_shape: &[usize; 3], bins: &Bins, source_mask: impl Fn(usize) -> bool) -> Box<[P]> {
+    let channels = src_shape[2];
+    let mut out = source.to_vec();
+    for channel in 0..channels {
+        equalize_histogram_binned_(&source[channel..], channels, &mut out[channel..], channels, bins, &source_mask);
+    }
+    out.into_boxed_slice()
+}
+
+/**Axis of length `len` is split into `tiles` tiles, where tile `t` spans `t*len/tiles..(t+1)*len/tiles`. For every coordinate
+ returns the two tiles whose centres surround it and the weight of the second one. Coordinates before the first centre
+ or after the last one only use the nearest tile.*/
+fn tile_interpolation(len: usize, tiles: usize) -> Vec<(usize, usize, f32)> {
+    let centre = |t: usize| (t * len / tiles + (t + 1) * len / tiles - 1) as f32 / 2.;
+    let mut t = 0;
+    (0..len).map(|i| {
+        let i = i as f32;
+        while t + 1 < tiles && centre(t + 1) <= i {
+            t += 1
+        }
+        if i <= centre(t) || t + 1 == tiles {
+            (t, t, 0.)
+        } else {
+            (t, t + 1, (i - centre(t)) / (centre(t + 1) - centre(t)))
+        }
+    }).collect()
+}
+
+/**shape == [height, width, channels], tiles == [tiles_y, tiles_x]. Contrast limited adaptive histogram equalization (CLAHE) with 256 bins*/
+pub fn clahe_images(source: &[u8], src_shape: &[usize; 3], tiles: [usize; 2], clip_limit: f32, source_mask: impl Fn(usize) -> bool) -> Box<[u8]> {
+    clahe_images_binned(source, src_shape, tiles, clip_limit, &Bins::u8(), source_mask)
+}
+
+/**Contrast limited adaptive histogram equalization (CLAHE). Image of shape `[height, width, channels]` is split into
+ `tiles == [tiles_y, tiles_x]` rectangles and every channel of every tile gets its own equalization lookup table. Before that,
+ tile histograms are clipped at `clip_limit` times the mean bin count (but at least 1) to limit amplification of noise.
+ Use `f32::INFINITY` to disable clipping. Every pixel is then mapped by the four tiles whose centres are nearest to it and the
+ results are blended bilinearly, so there are no seams between tiles. As in `equalize_images_binned`, only pixels selected by
+ `source_mask(y*width+x)` are counted and changed.*/
+pub fn clahe_images_binned<P: Pixel>(source: &[P], src_shape: &[usize; 3], tiles: [usize; 2], clip_limit: f32, bins: &Bins, source_mask: impl Fn(usize) -> bool) -> Box<[P]> {
+    let [height, width, channels] = *src_shape;
+    let [tiles_y, tiles_x] = tiles;
+    assert_eq!(source.len(), height * width * channels);
+    assert!(0 < tiles_y && tiles_y <= height, "Can't split {} rows into {} tiles", height, tiles_y);
+    assert!(0 < tiles_x && tiles_x <= width, "Can't split {} columns into {} tiles", width, tiles_x);
+    assert!(clip_limit > 0., "Clip limit {} must be positive", clip_limit);
+    let n = bins.count();
+    let rows = tile_interpolation(height, tiles_y);
+    let columns = tile_interpolation(width, tiles_x);
+    let mut out = source.to_vec();
+    for channel in 0..channels {
+        let mut hists = vec![0f32; tiles_y * tiles_x * n];
+        for y in 0..height {
+            let ty = ((y + 1) * tiles_y - 1) / height;
+            for x in 0..width {
+                let idx = y * width + x;
+                if source_mask(idx) {
+                    let tx = ((x + 1) * tiles_x - 1) / width;
+                    hists[(ty * tiles_x + tx) * n + bins.bin(source[idx * channels + channel].to_f32())] += 1.;
+                }
             }
-            fn f(a: &u32) -> f32 { *a as f32 }
-            let channel_square_diff = hist_src.iter().map(f).zip(hist_ref.iter()).map(|(s, r)| sq(s * s_inv - r)).sum::<f32>();
-            square_diff += channel_square_diff;
         }
-        if square_diff < min_square_diff {
-            min_square_diff = square_diff;
-            best_ref_idx = ref_idx;
+        let luts: Vec<Option<Vec<f32>>> = hists.chunks_mut(n).map(|h| {
+            let limit = (clip_limit * h.iter().sum::<f32>() / n as f32).max(1.);
+            clip_histogram(h, limit);
+            equalization_lut::<P>(h, bins)
+        }).collect();
+        for (y, &(ty0, ty1, wy)) in rows.iter().enumerate() {
+            for (x, &(tx0, tx1, wx)) in columns.iter().enumerate() {
+                let idx = y * width + x;
+                if source_mask(idx) {
+                    let v = source[idx * channels + channel].to_f32();
+                    let b = bins.bin(v);
+                    // tiles with nothing to equalise leave pixels as they are
+                    let map = |ty: usize, tx: usize| luts[ty * tiles_x + tx].as_ref().map(|lut| lut[b]).unwrap_or(v);
+                    let top = map(ty0, tx0) * (1. - wx) + map(ty0, tx1) * wx;
+                    let bottom = map(ty1, tx0) * (1. - wx) + map(ty1, tx1) * wx;
+                    out[idx * channels + channel] = P::from_f32(top * (1. - wy) + bottom * wy);
+                }
+            }
         }
     }
-    (best_ref_idx, min_square_diff)
+    out.into_boxed_slice()
 }
 
-/**Finds reference histogram that is closest to the source histogram. The distance is mean square error.*/
-pub fn find_closest_n(hist_src: &[f32], batch: usize, references: &[f32]) -> (usize, f32) {
-    assert_eq!(hist_src.len() % 256, 0);
-    let channels = hist_src.len() / 256;
-    ;
-    let channels256 = hist_src.len();
-    assert_eq!(references.len(), batch * channels * 256);
-    let mut min_square_diff = f32::INFINITY;
-    let mut best_ref_idx = 0;
-    for ref_idx in 0..batch {
-        let ref_offset = ref_idx * channels256;
-        /**`ref_hists` shape is `[channels, 256]`*/
-        let ref_hists = &references[ref_offset..ref_offset + channels256];
-        let mut square_diff = 0.;
-        for channel in 0..channels {
-            let offset = channel * 256;
-            let hist_src = &hist_src[offset..offset + 256];
-            let hist_ref = &ref_hists[offset..offset + 256];
-            fn sq(a: f32) -> f32 {
-                a * a
+/**Distance between two normalised histograms. Every metric is computed per channel and returns values between 0 (identical
+ histograms) and 1, except for `SquaredError`, which reaches 2 (for two histograms concentrated in different bins), and
+ `KullbackLeibler`, which is unbounded. Distances of histograms with many channels are summed up.*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HistogramDistance {
+    /**`sum((p-q)^2)`, between 0 and 2*/
+    SquaredError,
+    /**Symmetric chi-square `sum((p-q)^2/(p+q))/2`*/
+    ChiSquare,
+    /**`sqrt(1-sum(sqrt(p*q)))`, also known as Hellinger distance*/
+    Bhattacharyya,
+    /**`sum(p*ln(p/q))` where `p` is the source and `q` the reference. Empty reference bins are treated as if they had
+     `f32::EPSILON` in them, so that the divergence stays finite.*/
+    KullbackLeibler,
+    /**Jensen-Shannon divergence `(KL(p|m)+KL(q|m))/2` with `m=(p+q)/2` and base 2 logarithm*/
+    JensenShannon,
+    /**`1-sum(min(p,q))`, that is, the area that is not shared by both histograms*/
+    Intersection,
+    /**1D Earth Mover's (Wasserstein) distance `sum(|cdf(p)-cdf(q)|)`, measured in bins and divided by `bins-1`*/
+    EarthMovers,
+}
+
+impl Default for HistogramDistance {
+    fn default() -> Self {
+        Self::SquaredError
+    }
+}
+
+impl HistogramDistance {
+    /**Distance between two histograms of a single channel*/
+    pub fn distance(&self, p: &[f32], q: &[f32]) -> f32 {
+        assert_eq!(p.len(), q.len());
+        let pq = p.iter().cloned().zip(q.iter().cloned());
+        fn kl(p: f32, q: f32) -> f32 {
+            if p > 0. { p * (p / q.max(f32::EPSILON)).ln() } else { 0. }
+        }
+        match self {
+            Self::SquaredError => pq.map(|(p, q)| (p - q) * (p - q)).sum(),
+            Self::ChiSquare => pq.filter(|&(p, q)| p + q > 0.).map(|(p, q)| (p - q) * (p - q) / (p + q)).sum::<f32>() / 2.,
+            Self::Bhattacharyya => (1. - pq.map(|(p, q)| (p * q).sqrt()).sum::<f32>()).max(0.).sqrt(),
+            Self::KullbackLeibler => pq.map(|(p, q)| kl(p, q)).sum(),
+            Self::JensenShannon => pq.map(|(p, q)| {
+                let m = (p + q) / 2.;
+                kl(p, m) + kl(q, m)
+            }).sum::<f32>() / (2. * std::f32::consts::LN_2),
+            Self::Intersection => 1. - pq.map(|(p, q)| p.min(q)).sum::<f32>(),
+            Self::EarthMovers => {
+                let mut cdf_diff = 0.;
+                pq.map(|(p, q)| {
+                    cdf_diff += p - q;
+                    cdf_diff.abs()
+                }).sum::<f32>() / (p.len() - 1).max(1) as f32
             }
-            let channel_square_diff = hist_src.iter().zip(hist_ref.iter()).map(|(s, r)| sq(s - r)).sum::<f32>();
-            square_diff += channel_square_diff;
         }
-        if square_diff < min_square_diff {
-            min_square_diff = square_diff;
+    }
+    /**Sum of distances of all channels. Histograms are of shape `[channels, bins]`*/
+    pub fn distance_n(&self, p: &[f32], q: &[f32], bins: usize) -> f32 {
+        assert_eq!(p.len(), q.len());
+        assert_eq!(p.len() % bins, 0);
+        p.chunks_exact(bins).zip(q.chunks_exact(bins)).map(|(p, q)| self.distance(p, q)).sum()
+    }
+}
+
+/**Finds reference histogram that is closest to the source histogram with respect to the given metric.
+ Returned distance is the sum of distances of all channels (so for most metrics it's between 0 and the number of channels)*/
+pub fn find_closest(hist_src: &[[u32; 256]], batch: usize, references: &[f32], metric: HistogramDistance) -> (usize, f32) {
+    find_closest_binned(hist_src.flatten(), 256, batch, references, metric)
+}
+
+/**Same as `find_closest` but `hist_src` is of shape `[channels, bins]` and `references` of shape `[batch, channels, bins]`*/
+pub fn find_closest_binned(hist_src: &[u32], bins: usize, batch: usize, references: &[f32], metric: HistogramDistance) -> (usize, f32) {
+    let hist_src = normalize_histograms_binned(hist_src, bins);
+    find_closest_n_binned(&hist_src, bins, batch, references, metric)
+}
+
+/**Finds reference histogram that is closest to the (already normalised) source histogram with respect to the given metric.*/
+pub fn find_closest_n(hist_src: &[f32], batch: usize, references: &[f32], metric: HistogramDistance) -> (usize, f32) {
+    find_closest_n_binned(hist_src, 256, batch, references, metric)
+}
+
+/**Same as `find_closest_n` but `hist_src` is of shape `[channels, bins]` and `references` of shape `[batch, channels, bins]`*/
+pub fn find_closest_n_binned(hist_src: &[f32], bins: usize, batch: usize, references: &[f32], metric: HistogramDistance) -> (usize, f32) {
+    assert_eq!(hist_src.len() % bins, 0);
+    let channels_bins = hist_src.len();
+    assert_eq!(references.len(), batch * channels_bins);
+    let mut min_dist = f32::INFINITY;
+    let mut best_ref_idx = 0;
+    for ref_idx in 0..batch {
+        let ref_offset = ref_idx * channels_bins;
+        // `ref_hists` shape is `[channels, bins]`
+        let ref_hists = &references[ref_offset..ref_offset + channels_bins];
+        let dist = metric.distance_n(hist_src, ref_hists, bins);
+        if dist < min_dist {
+            min_dist = dist;
             best_ref_idx = ref_idx;
         }
     }
-    (best_ref_idx, min_square_diff)
+    (best_ref_idx, min_dist)
 }
 
 /**shape == [height, width, channels], references:[batch,channels,256]*/
-pub fn match_best_images(source: &[u8], src_shape: &[usize; 3], batch: usize, references: &[f32], source_mask: impl Fn(usize) -> bool) -> (Box<[u8]>, usize, f32) {
+pub fn match_best_images(source: &[u8], src_shape: &[usize; 3], batch: usize, references: &[f32], metric: HistogramDistance, source_mask: impl Fn(usize) -> bool) -> (Box<[u8]>, usize, f32) {
+    match_best_images_binned(source, src_shape, batch, references, &Bins::u8(), metric, source_mask)
+}
+
+/**shape == [height, width, channels], references:[batch,channels,bins]*/
+pub fn match_best_images_binned<P: Pixel>(source: &[P], src_shape: &[usize; 3], batch: usize, references: &[f32], bins: &Bins, metric: HistogramDistance, source_mask: impl Fn(usize) -> bool) -> (Box<[P]>, usize, f32) {
     assert_eq!(source.len(), src_shape.iter().product());
     let channels = src_shape[2];
-    let hist_src = histograms(source, channels, &source_mask);
-    let channels256 = channels * 256;
-    let (best_ref_idx, min_square_diff) = find_closest(&hist_src, batch, references);
-    let ref_offset = best_ref_idx * channels256;
-    let best_ref_hists = &references[ref_offset..ref_offset + channels256];
-    (match_2precomputed_images(source, src_shape, hist_src.flatten(), best_ref_hists, source_mask), best_ref_idx, min_square_diff)
+    let hist_src = histograms_binned(source, channels, bins, &source_mask);
+    let channels_bins = channels * bins.count();
+    let (best_ref_idx, min_dist) = find_closest_binned(&hist_src, bins.count(), batch, references, metric);
+    let ref_offset = best_ref_idx * channels_bins;
+    let best_ref_hists = &references[ref_offset..ref_offset + channels_bins];
+    (match_2precomputed_images_binned(source, src_shape, &hist_src, best_ref_hists, bins, source_mask), best_ref_idx, min_dist)
 }
 
 /**Find if there exists `x1` and `x2` such that
@@ -436,20 +775,30 @@ pub fn find_histogram_anomaly<T: Copy + Zero + PartialOrd + Sub<Output=T> + Mul<
 }
 
 pub fn find_histograms_anomaly<T: Copy + Zero + PartialOrd + Sub<Output=T> + Mul<Output=T> + FromUsize>(histograms: &[T], ratio: T) -> Vec<(usize, Range<isize>)> {
+    find_histograms_anomaly_binned(histograms, 256, ratio)
+}
+
+/**`histograms` shape is `[channels, bins]`*/
+pub fn find_histograms_anomaly_binned<T: Copy + Zero + PartialOrd + Sub<Output=T> + Mul<Output=T> + FromUsize>(histograms: &[T], bins: usize, ratio: T) -> Vec<(usize, Range<isize>)> {
     let mut out = Vec::new();
-    assert_eq!(histograms.len() % 256, 0);
-    let channels = histograms.len() / 256;
+    assert_eq!(histograms.len() % bins, 0);
+    let channels = histograms.len() / bins;
     for channel in 0..channels {
-        let offset = channel * 256;
-        let a = find_histogram_anomaly(&histograms[offset..offset + 256], ratio);
+        let offset = channel * bins;
+        let a = find_histogram_anomaly(&histograms[offset..offset + bins], ratio);
         out.extend(a.into_iter().map(|r| (channel, r)));
     }
     out
 }
 
 pub fn find_n_histograms_anomaly<T: Copy + Zero + PartialOrd + Sub<Output=T> + Mul<Output=T> + Div<Output=T> + FromUsize>(histograms: &[T], ratio: T) -> Vec<(usize, Range<isize>)> {
+    find_n_histograms_anomaly_binned(histograms, 256, ratio)
+}
+
+/**`histograms` shape is `[channels, bins]`. The ratio is normalised by the total length of `histograms`*/
+pub fn find_n_histograms_anomaly_binned<T: Copy + Zero + PartialOrd + Sub<Output=T> + Mul<Output=T> + Div<Output=T> + FromUsize>(histograms: &[T], bins: usize, ratio: T) -> Vec<(usize, Range<isize>)> {
     let ratio = ratio / T::from_usize(histograms.len());
-    find_histograms_anomaly(histograms, ratio)
+    find_histograms_anomaly_binned(histograms, bins, ratio)
 }
 
 #[cfg(test)]
@@ -504,4 +853,167 @@ mod tests {
         interpolate_gaps(&mut b);
         assert_eq!(&b[..a.len()], &[0.5,1.,2.,3.,8.,6.0,4.0,2.]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test7() {
+        // every channel is interpolated separately, including runs of zeros at the very end
+        let mut h = vec![0., 2., 0., 0., 4., 0., 2., 0.];
+        interpolate_gaps_binned(&mut h, 4);
+        let expected = [1., 2., 4. / 3., 2. / 3., 4., 3., 2., 1.];
+        assert!(h.iter().zip(expected).all(|(&h, e)| (h - e).abs() < 1e-6), "{:?}", h);
+    }
+
+    #[test]
+    fn test8() {
+        let bins = Bins::of::<u16>(4);
+        assert_eq!(bins.width(), 16384.);
+        assert_eq!(bins.bin(0.), 0);
+        assert_eq!(bins.bin(16384.), 1);
+        assert_eq!(bins.bin(65535.), 3);
+        assert_eq!(bins.bin(-5.), 0);
+        assert_eq!(bins.bin(1e9), 3);
+        assert_eq!(bins.rebin(16390u16, 1, 3), 49158);
+        let s: Vec<u16> = vec![0, 1, 2, 2, 5, 7, 3, 4, 6];
+        let r: Vec<u16> = vec![3, 4, 5];
+        let bins = Bins::new(8, 0. ..8.);
+        let o = match_images_binned(&s, &[s.len(), 1, 1], &r, &[r.len(), 1, 1], &bins, |_| true);
+        assert_eq!(o.as_ref(), &[3, 3, 3, 4, 5, 5, 4, 4, 5]);
+        assert_eq!(histogram_binned(&s, 1, &bins, |_| true), vec![1, 1, 2, 1, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test9() {
+        // 256 bins over u8 behave exactly like the specialised API
+        let mut rng = rand::rngs::StdRng::seed_from_u64(43);
+        let s: Vec<u8> = (0..300).map(|_| rand::Rng::gen(&mut rng)).collect();
+        let r: Vec<u8> = (0..200).map(|_| rand::Rng::gen_range(&mut rng, 50..150)).collect();
+        let mask = |i: usize| i % 3 != 0;
+        let o = match_images(&s, &[100, 1, 3], &r[..198], &[66, 1, 3], mask);
+        let o_binned = match_images_binned(&s, &[100, 1, 3], &r[..198], &[66, 1, 3], &Bins::u8(), mask);
+        assert_eq!(o, o_binned);
+        assert_eq!(flatten_box(histograms(&s, 3, mask)).as_ref(), histograms_binned(&s, 3, &Bins::u8(), mask).as_slice());
+    }
+
+    #[test]
+    fn test10() {
+        // float images keep their precision within bins
+        let s: Vec<f32> = vec![0.01, 0.02, 0.26, 0.27];
+        let r: Vec<f32> = vec![0.5, 0.5, 0.75, 0.75];
+        let bins = Bins::of::<f32>(4);
+        let o = match_images_binned(&s, &[4, 1, 1], &r, &[4, 1, 1], &bins, |_| true);
+        let expected = [0.51, 0.52, 0.76, 0.77];
+        assert!(o.iter().zip(expected).all(|(&o, e)| (o - e).abs() < 1e-6), "{:?}", o);
+        let h = normalize_histograms_binned(&histogram_binned(&r, 1, &bins, |_| true), 4);
+        assert_eq!(find_closest_binned(&histogram_binned(&s, 1, &bins, |_| true), 4, 2, &[&[0.5, 0.5, 0., 0.][..], &h].concat(), HistogramDistance::SquaredError), (0, 0.));
+    }
+
+    #[test]
+    fn test11() {
+        let s: Vec<u8> = vec![0, 0, 1, 1, 2, 2, 3, 3, 9];
+        let o = equalize_images(&s, &[3, 3, 1], |i| i < 8);
+        assert_eq!(o.as_ref(), &[0, 0, 85, 85, 170, 170, 255, 255, 9]);
+        let f: Vec<f32> = s.iter().map(|&v| v as f32 / 4.).collect();
+        let o = equalize_images_binned(&f, &[3, 3, 1], &Bins::of::<f32>(4), |i| i < 8);
+        let expected = [0., 0., 1. / 3., 1. / 3., 2. / 3., 2. / 3., 1., 1., 2.25];
+        assert!(o.iter().zip(&expected).all(|(o, e)| (o - e).abs() < 1e-6), "{:?}", o);
+        let w: Vec<u16> = s.iter().map(|&v| v as u16 * 1000).collect();
+        let o = equalize_images_binned(&w, &[3, 3, 1], &Bins::of::<u16>(256), |i| i < 8);
+        assert_eq!(o[6], 65535);
+        let c = [7u8; 6];
+        assert_eq!(equalize_images(&c, &[2, 1, 3], |_| true).as_ref(), &c);
+        let mut h = vec![0., 6., 2., 0.];
+        clip_histogram(&mut h, 3.);
+        assert_eq!(h, vec![0.75, 3.75, 2.75, 0.75]);
+    }
+
+    #[test]
+    fn test12() {
+        assert_eq!(tile_interpolation(4, 2), vec![(0, 0, 0.), (0, 1, 0.25), (0, 1, 0.75), (1, 1, 0.)]);
+        assert_eq!(tile_interpolation(3, 1), vec![(0, 0, 0.); 3]);
+        let mut rng = rand::rngs::StdRng::seed_from_u64(44);
+        let s: Vec<u8> = (0..20 * 30 * 3).map(|_| rand::Rng::gen_range(&mut rng, 40..120)).collect();
+        let mask = |i: usize| i % 7 != 0;
+        // a single tile without clipping is plain equalization
+        let o = clahe_images(&s, &[20, 30, 3], [1, 1], f32::INFINITY, mask);
+        assert_eq!(o, equalize_images(&s, &[20, 30, 3], mask));
+        let o = clahe_images(&s, &[20, 30, 3], [3, 4], 2., mask);
+        for (i, (&o, &s)) in o.iter().zip(&s).enumerate() {
+            if !mask(i / 3) {
+                assert_eq!(o, s);
+            }
+        }
+        // clipping limits how much contrast gets stretched
+        let spread = |o: &[u8]| o.iter().max().unwrap() - o.iter().min().unwrap();
+        let o_clipped = clahe_images(&s, &[20, 30, 3], [1, 1], 1.5, |_| true);
+        let o_full = clahe_images(&s, &[20, 30, 3], [1, 1], f32::INFINITY, |_| true);
+        assert!(spread(&o_clipped) < spread(&o_full));
+    }
+
+    #[test]
+    fn test13() {
+        let p = [0.5, 0.5, 0., 0.];
+        let q = [0., 0., 0.5, 0.5];
+        let all = [HistogramDistance::SquaredError, HistogramDistance::ChiSquare, HistogramDistance::Bhattacharyya,
+            HistogramDistance::KullbackLeibler, HistogramDistance::JensenShannon, HistogramDistance::Intersection, HistogramDistance::EarthMovers];
+        for metric in all {
+            assert!(metric.distance(&p, &p).abs() < 1e-6, "{:?}", metric);
+        }
+        // disjoint histograms are as far apart as possible
+        for metric in [HistogramDistance::ChiSquare, HistogramDistance::Bhattacharyya, HistogramDistance::JensenShannon, HistogramDistance::Intersection] {
+            assert!((metric.distance(&p, &q) - 1.).abs() < 1e-6, "{:?}", metric);
+        }
+        assert_eq!(HistogramDistance::SquaredError.distance(&p, &q), 1.);
+        // squared error peaks when all the mass is in a single bin
+        assert_eq!(HistogramDistance::SquaredError.distance(&[1., 0.], &[0., 1.]), 2.);
+        assert!(HistogramDistance::KullbackLeibler.distance(&p, &q) > 10.);
+        assert!((HistogramDistance::EarthMovers.distance(&p, &q) - 2. / 3.).abs() < 1e-6);
+        // earth mover's distance grows with how far the mass has to travel, even if histograms don't overlap
+        let r = [0., 0.5, 0.5, 0.];
+        assert!(HistogramDistance::EarthMovers.distance(&p, &r) < HistogramDistance::EarthMovers.distance(&p, &q));
+        assert_eq!(HistogramDistance::Intersection.distance(&p, &r), HistogramDistance::Intersection.distance(&p, &[0., 0.5, 0., 0.5]));
+        let references = [q, r].concat();
+        assert_eq!(find_closest_n_binned(&p, 4, 2, &references, HistogramDistance::EarthMovers).0, 1);
+        assert_eq!(HistogramDistance::Bhattacharyya.distance_n(&[p, q].concat(), &[p, p].concat(), 4), 1.);
+    }
+
+    #[test]
+    fn test14() {
+        let s: Vec<u8> = vec![200, 10, 10, 10, 200, 10, 100, 100, 180, 30, 30, 30];
+        let r: Vec<u8> = vec![250, 250, 250, 240, 240, 240, 230, 230, 230, 5, 5, 5];
+        let src_hsv = ColorSpace::Hsv.image_from_rgb(&s);
+        let o = match_images_in_space(&s, &[2, 2, 3], &r, &[2, 2, 3], &Bins::of::<f32>(64), ColorSpace::Hsv, &[2], |_| true);
+        let o_hsv = ColorSpace::Hsv.image_from_rgb(&o);
+        // hues stay where they were while brightness follows the reference
+        for (s, o) in src_hsv.chunks(3).zip(o_hsv.chunks(3)) {
+            assert!((s[0] - o[0]).abs() < 0.02, "{:?} {:?}", s, o);
+        }
+        assert!(o_hsv[2] > src_hsv[2] && o_hsv[11] < src_hsv[11]);
+        // matching all RGB channels is the same as plain matching, up to rounding within bins
+        let o = match_images_in_space(&s, &[2, 2, 3], &r, &[2, 2, 3], &Bins::of::<f32>(256), ColorSpace::Rgb, &[0, 1, 2], |_| true);
+        let o_plain = match_images(&s, &[2, 2, 3], &r, &[2, 2, 3], |_| true);
+        assert!(o.iter().zip(o_plain.iter()).all(|(&a, &b)| (a as i32 - b as i32).abs() <= 1), "{:?} {:?}", o, o_plain);
+        let bins = Bins::of::<f32>(16);
+        let refs: Vec<f32> = [&r[..], &s[..]].iter()
+            .flat_map(|i| normalize_histograms_binned(&histograms_in_space(i, ColorSpace::Lab, &bins, |_| true), 16).into_vec())
+            .collect();
+        let (o, best, dist) = match_best_images_in_space(&s, &[2, 2, 3], 2, &refs, &bins, HistogramDistance::SquaredError, ColorSpace::Lab, &[0], |_| true);
+        assert_eq!((best, dist), (1, 0.));
+        assert_eq!(o.as_ref(), s.as_slice());
+        let (o, best, _) = match_best_images_in_space(&s, &[2, 2, 3], 1, &refs[..48], &bins, HistogramDistance::SquaredError, ColorSpace::Lab, &[0], |i| i != 3);
+        assert_eq!(best, 0);
+        assert_eq!(&o[9..], &s[9..]);
+        assert_ne!(&o[..9], &s[..9]);
+    }
+
+    #[test]
+    fn test15() {
+        // two channels of 8 bins, only the second one has a peak
+        let h = [0., 0., 0.1, 0.1, 0.1, 0.1, 0.1, 0.1, 0., 0., 0.1, 0.1, 0.4, 0.1, 0.1, 0.1];
+        assert_eq!(find_n_histograms_anomaly_binned(&h, 8, 4.), vec![(1, 3..5)]);
+        // the 256-bin variant normalises the ratio in the same way
+        let mut h256 = vec![0.; 512];
+        h256[256 + 100] = 0.5;
+        assert_eq!(find_n_histograms_anomaly(&h256, 100.), find_n_histograms_anomaly_binned(&h256, 256, 100.));
+        assert_eq!(find_n_histograms_anomaly(&h256, 100.), find_histograms_anomaly(&h256, 100. / 512.));
+    }
+}
@@ -1,16 +1,21 @@
 use rand::distributions::Standard;
 use rand::prelude::Distribution;
-use rand::random;
+use rand::{random, Rng};
 use crate::init::{filled2, filled3, InitWithCapacity};
 
 pub  trait InitRandWithCapacity{
     fn rand(capacity:usize)->Self;
+    /**Same as `rand` but reproducible, because values are drawn from the given `rng`*/
+    fn rand_with_rng(capacity:usize, rng:&mut impl Rng)->Self;
 }
 
 impl <T> InitRandWithCapacity for Vec<T> where Standard: Distribution<T>{
     fn rand(capacity: usize) -> Self {
         Vec::init_with(capacity, |_|random())
     }
+    fn rand_with_rng(capacity: usize, rng: &mut impl Rng) -> Self {
+        Vec::init_with(capacity, |_|rng.gen())
+    }
 }
 
 pub fn rand2<A:Copy, const W:usize, const H:usize>()->[[A;W];H] where Standard: Distribution<A>{
@@ -0,0 +1,478 @@
+use std::fmt::{Display, Formatter};
+use crate::blas_safe::{Matrix, Order, Symmetry, Transpose};
+
+/**LAPACK works in column-major order. A row-major matrix is therefore seen by LAPACK as its own transpose.*/
+#[derive(Clone, Debug, PartialEq)]
+pub enum LapackError {
+    /**The i-th argument (counting from 1) had an illegal value*/
+    IllegalArgument(i32),
+    /**The i-th (counting from 1) diagonal element of U in LU factorisation is exactly zero*/
+    Singular(i32),
+    /**The leading minor of order i is not positive definite*/
+    NotPositiveDefinite(i32),
+    /**Estimated reciprocal condition number is below machine precision*/
+    IllConditioned(f64),
+    /**Iterative algorithm failed to converge. The value is `info` returned by LAPACK*/
+    NoConvergence(i32),
+    /**Matrix must be square or shapes of operands do not match*/
+    ShapeMismatch(String),
+}
+
+impl Display for LapackError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LapackError::IllegalArgument(i) => write!(f, "argument {} had an illegal value", i),
+            LapackError::Singular(i) => write!(f, "matrix is singular, U({},{}) is exactly zero", i, i),
+            LapackError::NotPositiveDefinite(i) => write!(f, "leading minor of order {} is not positive definite", i),
+            LapackError::IllConditioned(rcond) => write!(f, "matrix is ill-conditioned, reciprocal condition number is {}", rcond),
+            LapackError::NoConvergence(i) => write!(f, "algorithm failed to converge (info={})", i),
+            LapackError::ShapeMismatch(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for LapackError {}
+
+fn check_info(info: i32, on_positive: impl FnOnce(i32) -> LapackError) -> Result<(), LapackError> {
+    if info < 0 {
+        Err(LapackError::IllegalArgument(-info))
+    } else if info > 0 {
+        Err(on_positive(info))
+    } else {
+        Ok(())
+    }
+}
+
+/**Returns (rows, cols, leading dimension) of the matrix, as seen by LAPACK in column-major order*/
+fn col_major<T>(a: &(impl ?Sized + Matrix<T>)) -> (i32, i32, i32) {
+    match a.order() {
+        Order::RowMajor => (a.cols() as i32, a.rows() as i32, a.lead_dim() as i32),
+        Order::ColMajor => (a.rows() as i32, a.cols() as i32, a.lead_dim() as i32),
+    }
+}
+
+fn uplo(s: Symmetry) -> u8 {
+    match s {
+        Symmetry::Upper => b'U',
+        Symmetry::Lower => b'L',
+    }
+}
+
+fn trans(t: Transpose) -> u8 {
+    match t {
+        Transpose::NoTrans => b'N',
+        Transpose::Trans => b'T',
+        Transpose::ConjTrans => b'C',
+    }
+}
+
+/// Computes LU factorisation `A = P·L·U` with partial pivoting and stores `L` and `U` in `a`.
+/// Pivot indices are 1-based. On `Singular` error the factorisation is still complete and can be used
+/// to compute determinant.
+pub trait Getrf: Sized {
+    fn getrf(a: &mut (impl ?Sized + Matrix<Self>), ipiv: &mut [i32]) -> Result<(), LapackError>;
+}
+
+impl Getrf for f32 {
+    fn getrf(a: &mut (impl ?Sized + Matrix<Self>), ipiv: &mut [i32]) -> Result<(), LapackError> {
+        let (m, n, lda) = col_major(a);
+        assert!(ipiv.len() >= m.min(n) as usize);
+        let mut info = 0;
+        unsafe {
+            lapack::sgetrf(m, n, a.as_mut_slice(), lda, ipiv, &mut info);
+        }
+        check_info(info, LapackError::Singular)
+    }
+}
+
+impl Getrf for f64 {
+    fn getrf(a: &mut (impl ?Sized + Matrix<Self>), ipiv: &mut [i32]) -> Result<(), LapackError> {
+        let (m, n, lda) = col_major(a);
+        assert!(ipiv.len() >= m.min(n) as usize);
+        let mut info = 0;
+        unsafe {
+            lapack::dgetrf(m, n, a.as_mut_slice(), lda, ipiv, &mut info);
+        }
+        check_info(info, LapackError::Singular)
+    }
+}
+
+/// Computes inverse of a matrix, given its LU factorisation produced by `getrf`.
+pub trait Getri: Sized {
+    fn getri(a: &mut (impl ?Sized + Matrix<Self>), ipiv: &[i32]) -> Result<(), LapackError>;
+}
+
+impl Getri for f32 {
+    fn getri(a: &mut (impl ?Sized + Matrix<Self>), ipiv: &[i32]) -> Result<(), LapackError> {
+        let (_, n, lda) = col_major(a);
+        let mut info = 0;
+        let mut lwork = [0f32];
+        unsafe {
+            lapack::sgetri(n, a.as_mut_slice(), lda, ipiv, &mut lwork, -1, &mut info);
+        }
+        check_info(info, LapackError::Singular)?;
+        let lwork = (lwork[0] as usize).max(1);
+        let mut work = vec![0f32; lwork];
+        unsafe {
+            lapack::sgetri(n, a.as_mut_slice(), lda, ipiv, &mut work, lwork as i32, &mut info);
+        }
+        check_info(info, LapackError::Singular)
+    }
+}
+
+impl Getri for f64 {
+    fn getri(a: &mut (impl ?Sized + Matrix<Self>), ipiv: &[i32]) -> Result<(), LapackError> {
+        let (_, n, lda) = col_major(a);
+        let mut info = 0;
+        let mut lwork = [0f64];
+        unsafe {
+            lapack::dgetri(n, a.as_mut_slice(), lda, ipiv, &mut lwork, -1, &mut info);
+        }
+        check_info(info, LapackError::Singular)?;
+        let lwork = (lwork[0] as usize).max(1);
+        let mut work = vec![0f64; lwork];
+        unsafe {
+            lapack::dgetri(n, a.as_mut_slice(), lda, ipiv, &mut work, lwork as i32, &mut info);
+        }
+        check_info(info, LapackError::Singular)
+    }
+}
+
+/// Solves `op(A)·X = B` given LU factorisation of `A` produced by `getrf`. Matrix `b` holds `nrhs` right hand sides
+/// in column-major order (with leading dimension equal to the size of `A`) and is overwritten with the solution `X`.
+pub trait Getrs: Sized {
+    fn getrs(t: Transpose, a: &(impl ?Sized + Matrix<Self>), ipiv: &[i32], b: &mut [Self], nrhs: i32) -> Result<(), LapackError>;
+}
+
+impl Getrs for f32 {
+    fn getrs(t: Transpose, a: &(impl ?Sized + Matrix<Self>), ipiv: &[i32], b: &mut [Self], nrhs: i32) -> Result<(), LapackError> {
+        let (_, n, lda) = col_major(a);
+        assert_eq!(b.len(), (n * nrhs) as usize);
+        let mut info = 0;
+        unsafe {
+            lapack::sgetrs(trans(t), n, nrhs, a.as_slice(), lda, ipiv, b, n.max(1), &mut info);
+        }
+        check_info(info, LapackError::Singular)
+    }
+}
+
+impl Getrs for f64 {
+    fn getrs(t: Transpose, a: &(impl ?Sized + Matrix<Self>), ipiv: &[i32], b: &mut [Self], nrhs: i32) -> Result<(), LapackError> {
+        let (_, n, lda) = col_major(a);
+        assert_eq!(b.len(), (n * nrhs) as usize);
+        let mut info = 0;
+        unsafe {
+            lapack::dgetrs(trans(t), n, nrhs, a.as_slice(), lda, ipiv, b, n.max(1), &mut info);
+        }
+        check_info(info, LapackError::Singular)
+    }
+}
+
+/// Computes Cholesky factorisation of a symmetric positive definite matrix. Only the chosen triangle
+/// (in column-major sense) is referenced and overwritten, the other one is left untouched.
+pub trait Potrf: Sized {
+    fn potrf(s: Symmetry, a: &mut (impl ?Sized + Matrix<Self>)) -> Result<(), LapackError>;
+}
+
+impl Potrf for f32 {
+    fn potrf(s: Symmetry, a: &mut (impl ?Sized + Matrix<Self>)) -> Result<(), LapackError> {
+        let (_, n, lda) = col_major(a);
+        let mut info = 0;
+        unsafe {
+            lapack::spotrf(uplo(s), n, a.as_mut_slice(), lda, &mut info);
+        }
+        check_info(info, LapackError::NotPositiveDefinite)
+    }
+}
+
+impl Potrf for f64 {
+    fn potrf(s: Symmetry, a: &mut (impl ?Sized + Matrix<Self>)) -> Result<(), LapackError> {
+        let (_, n, lda) = col_major(a);
+        let mut info = 0;
+        unsafe {
+            lapack::dpotrf(uplo(s), n, a.as_mut_slice(), lda, &mut info);
+        }
+        check_info(info, LapackError::NotPositiveDefinite)
+    }
+}
+
+/// Computes the 1-norm (maximum absolute column sum, in column-major sense) of a matrix.
+pub trait Lange: Sized {
+    fn lange(a: &(impl ?Sized + Matrix<Self>)) -> Self;
+}
+
+impl Lange for f32 {
+    fn lange(a: &(impl ?Sized + Matrix<Self>)) -> Self {
+        let (m, n, lda) = col_major(a);
+        unsafe {
+            lapack::slange(b'1', m, n, a.as_slice(), lda, &mut [])
+        }
+    }
+}
+
+impl Lange for f64 {
+    fn lange(a: &(impl ?Sized + Matrix<Self>)) -> Self {
+        let (m, n, lda) = col_major(a);
+        unsafe {
+            lapack::dlange(b'1', m, n, a.as_slice(), lda, &mut [])
+        }
+    }
+}
+
+/// Estimates reciprocal of the condition number (in 1-norm) of a matrix, given its LU factorisation
+/// produced by `getrf` and the 1-norm `anorm` of the original matrix computed with `lange`.
+pub trait Gecon: Sized {
+    fn gecon(a: &(impl ?Sized + Matrix<Self>), anorm: Self) -> Result<Self, LapackError>;
+}
+
+impl Gecon for f32 {
+    fn gecon(a: &(impl ?Sized + Matrix<Self>), anorm: Self) -> Result<Self, LapackError> {
+        let (_, n, lda) = col_major(a);
+        let mut rcond = 0.;
+        let mut info = 0;
+        let mut work = vec![0f32; 4 * n as usize];
+        let mut iwork = vec![0i32; n as usize];
+        unsafe {
+            lapack::sgecon(b'1', n, a.as_slice(), lda, anorm, &mut rcond, &mut work, &mut iwork, &mut info);
+        }
+        check_info(info, LapackError::Singular).map(|()| rcond)
+    }
+}
+
+impl Gecon for f64 {
+    fn gecon(a: &(impl ?Sized + Matrix<Self>), anorm: Self) -> Result<Self, LapackError> {
+        let (_, n, lda) = col_major(a);
+        let mut rcond = 0.;
+        let mut info = 0;
+        let mut work = vec![0f64; 4 * n as usize];
+        let mut iwork = vec![0i32; n as usize];
+        unsafe {
+            lapack::dgecon(b'1', n, a.as_slice(), lda, anorm, &mut rcond, &mut work, &mut iwork, &mut info);
+        }
+        check_info(info, LapackError::Singular).map(|()| rcond)
+    }
+}
+
+/// Computes all eigenvalues and eigenvectors of a symmetric matrix using divide and conquer algorithm.
+/// Eigenvalues are stored in `w` in ascending order and `a` is overwritten with orthonormal eigenvectors
+/// (stored as columns, in column-major sense). Only the chosen triangle of `a` is referenced.
+pub trait Syevd: Sized {
+    fn syevd(s: Symmetry, a: &mut (impl ?Sized + Matrix<Self>), w: &mut [Self]) -> Result<(), LapackError>;
+}
+
+impl Syevd for f32 {
+    fn syevd(s: Symmetry, a: &mut (impl ?Sized + Matrix<Self>), w: &mut [Self]) -> Result<(), LapackError> {
+        let (_, n, lda) = col_major(a);
+        assert_eq!(w.len(), n as usize);
+        let mut info = 0;
+        let mut lwork = [0f32];
+        let mut liwork = [0i32];
+        unsafe {
+            lapack::ssyevd(b'V', uplo(s), n, a.as_mut_slice(), lda, w, &mut lwork, -1, &mut liwork, -1, &mut info);
+        }
+        check_info(info, LapackError::NoConvergence)?;
+        let lwork = (lwork[0] as usize).max(1);
+        let liwork = (liwork[0] as usize).max(1);
+        let mut work = vec![0f32; lwork];
+        let mut iwork = vec![0i32; liwork];
+        unsafe {
+            lapack::ssyevd(b'V', uplo(s), n, a.as_mut_slice(), lda, w, &mut work, lwork as i32, &mut iwork, liwork as i32, &mut info);
+        }
+        check_info(info, LapackError::NoConvergence)
+    }
+}
+
+impl Syevd for f64 {
+    fn syevd(s: Symmetry, a: &mut (impl ?Sized + Matrix<Self>), w: &mut [Self]) -> Result<(), LapackError> {
+        let (_, n, lda) = col_major(a);
+        assert_eq!(w.len(), n as usize);
+        let mut info = 0;
+        let mut lwork = [0f64];
+        let mut liwork = [0i32];
+        unsafe {
+            lapack::dsyevd(b'V', uplo(s), n, a.as_mut_slice(), lda, w, &mut lwork, -1, &mut liwork, -1, &mut info);
+        }
+        check_info(info, LapackError::NoConvergence)?;
+        let lwork = (lwork[0] as usize).max(1);
+        let liwork = (liwork[0] as usize).max(1);
+        let mut work = vec![0f64; lwork];
+        let mut iwork = vec![0i32; liwork];
+        unsafe {
+            lapack::dsyevd(b'V', uplo(s), n, a.as_mut_slice(), lda, w, &mut work, lwork as i32, &mut iwork, liwork as i32, &mut info);
+        }
+        check_info(info, LapackError::NoConvergence)
+    }
+}
+
+/// Computes thin singular value decomposition `A = U·diag(s)·V^T` of `m x n` matrix using divide and conquer algorithm.
+/// With `k = min(m, n)`, `u` is `m x k`, `vt` is `k x n` (both in column-major order) and singular values in `s`
+/// are in descending order. Contents of `a` are destroyed.
+pub trait Gesdd: Sized {
+    fn gesdd(a: &mut (impl ?Sized + Matrix<Self>), s: &mut [Self], u: &mut [Self], vt: &mut [Self]) -> Result<(), LapackError>;
+}
+
+impl Gesdd for f32 {
+    fn gesdd(a: &mut (impl ?Sized + Matrix<Self>), s: &mut [Self], u: &mut [Self], vt: &mut [Self]) -> Result<(), LapackError> {
+        let (m, n, lda) = col_major(a);
+        let k = m.min(n);
+        assert_eq!(s.len(), k as usize);
+        assert_eq!(u.len(), (m * k) as usize);
+        assert_eq!(vt.len(), (k * n) as usize);
+        let mut info = 0;
+        let mut lwork = [0f32];
+        let mut iwork = vec![0i32; 8 * k as usize];
+        unsafe {
+            lapack::sgesdd(b'S', m, n, a.as_mut_slice(), lda, s, u, m.max(1), vt, k.max(1), &mut lwork, -1, &mut iwork, &mut info);
+        }
+        check_info(info, LapackError::NoConvergence)?;
+        let lwork = (lwork[0] as usize).max(1);
+        let mut work = vec![0f32; lwork];
+        unsafe {
+            lapack::sgesdd(b'S', m, n, a.as_mut_slice(), lda, s, u, m.max(1), vt, k.max(1), &mut work, lwork as i32, &mut iwork, &mut info);
+        }
+        check_info(info, LapackError::NoConvergence)
+    }
+}
+
+impl Gesdd for f64 {
+    fn gesdd(a: &mut (impl ?Sized + Matrix<Self>), s: &mut [Self], u: &mut [Self], vt: &mut [Self]) -> Result<(), LapackError> {
+        let (m, n, lda) = col_major(a);
+        let k = m.min(n);
+        assert_eq!(s.len(), k as usize);
+        assert_eq!(u.len(), (m * k) as usize);
+        assert_eq!(vt.len(), (k * n) as usize);
+        let mut info = 0;
+        let mut lwork = [0f64];
+        let mut iwork = vec![0i32; 8 * k as usize];
+        unsafe {
+            lapack::dgesdd(b'S', m, n, a.as_mut_slice(), lda, s, u, m.max(1), vt, k.max(1), &mut lwork, -1, &mut iwork, &mut info);
+        }
+        check_info(info, LapackError::NoConvergence)?;
+        let lwork = (lwork[0] as usize).max(1);
+        let mut work = vec![0f64; lwork];
+        unsafe {
+            lapack::dgesdd(b'S', m, n, a.as_mut_slice(), lda, s, u, m.max(1), vt, k.max(1), &mut work, lwork as i32, &mut iwork, &mut info);
+        }
+        check_info(info, LapackError::NoConvergence)
+    }
+}
+
+/// Computes eigenvalues with (1-based, ascending) indices `il..=iu` and their eigenvectors of a symmetric matrix
+/// using relatively robust representations. With `m = iu - il + 1`, the selected eigenvalues are stored in `w[..m]`
+/// in ascending order (`w` must have length `n`) and `z` (of length `n·m`) receives the orthonormal eigenvectors as
+/// columns of a column-major `n x m` matrix. Contents of `a` are destroyed.
+pub trait Syevr: Sized {
+    fn syevr(s: Symmetry, a: &mut (impl ?Sized + Matrix<Self>), il: i32, iu: i32, w: &mut [Self], z: &mut [Self]) -> Result<(), LapackError>;
+}
+
+impl Syevr for f32 {
+    fn syevr(s: Symmetry, a: &mut (impl ?Sized + Matrix<Self>), il: i32, iu: i32, w: &mut [Self], z: &mut [Self]) -> Result<(), LapackError> {
+        let (_, n, lda) = col_major(a);
+        assert!(1 <= il && il <= iu && iu <= n, "Eigenvalue indices {}..={} out of range for matrix of size {}", il, iu, n);
+        assert_eq!(w.len(), n as usize);
+        assert_eq!(z.len(), (n * (iu - il + 1)) as usize);
+        let mut m = 0;
+        let mut isuppz = vec![0i32; 2 * n as usize];
+        let mut info = 0;
+        let mut lwork = [0f32];
+        let mut liwork = [0i32];
+        unsafe {
+            lapack::ssyevr(b'V', b'I', uplo(s), n, a.as_mut_slice(), lda, 0., 0., il, iu, 0., &mut m, w, z, n, &mut isuppz, &mut lwork, -1, &mut liwork, -1, &mut info);
+        }
+        check_info(info, LapackError::NoConvergence)?;
+        let lwork = (lwork[0] as usize).max(1);
+        let liwork = (liwork[0] as usize).max(1);
+        let mut work = vec![0f32; lwork];
+        let mut iwork = vec![0i32; liwork];
+        unsafe {
+            lapack::ssyevr(b'V', b'I', uplo(s), n, a.as_mut_slice(), lda, 0., 0., il, iu, 0., &mut m, w, z, n, &mut isuppz, &mut work, lwork as i32, &mut iwork, liwork as i32, &mut info);
+        }
+        check_info(info, LapackError::NoConvergence)
+    }
+}
+
+impl Syevr for f64 {
+    fn syevr(s: Symmetry, a: &mut (impl ?Sized + Matrix<Self>), il: i32, iu: i32, w: &mut [Self], z: &mut [Self]) -> Result<(), LapackError> {
+        let (_, n, lda) = col_major(a);
+        assert!(1 <= il && il <= iu && iu <= n, "Eigenvalue indices {}..={} out of range for matrix of size {}", il, iu, n);
+        assert_eq!(w.len(), n as usize);
+        assert_eq!(z.len(), (n * (iu - il + 1)) as usize);
+        let mut m = 0;
+        let mut isuppz = vec![0i32; 2 * n as usize];
+        let mut info = 0;
+        let mut lwork = [0f64];
+        let mut liwork = [0i32];
+        unsafe {
+            lapack::dsyevr(b'V', b'I', uplo(s), n, a.as_mut_slice(), lda, 0., 0., il, iu, 0., &mut m, w, z, n, &mut isuppz, &mut lwork, -1, &mut liwork, -1, &mut info);
+        }
+        check_info(info, LapackError::NoConvergence)?;
+        let lwork = (lwork[0] as usize).max(1);
+        let liwork = (liwork[0] as usize).max(1);
+        let mut work = vec![0f64; lwork];
+        let mut iwork = vec![0i32; liwork];
+        unsafe {
+            lapack::dsyevr(b'V', b'I', uplo(s), n, a.as_mut_slice(), lda, 0., 0., il, iu, 0., &mut m, w, z, n, &mut isuppz, &mut work, lwork as i32, &mut iwork, liwork as i32, &mut info);
+        }
+        check_info(info, LapackError::NoConvergence)
+    }
+}
+
+/// Computes partial singular value decomposition of `m x n` matrix, keeping only singular values with (1-based,
+/// descending) indices `il..=iu`. With `k = min(m, n)` and `ns = iu - il + 1`, the selected singular values are
+/// stored in `s[..ns]` in descending order (`s` must have length `k`), `u` is `m x ns` and `vt` is `ns x n`
+/// (both in column-major order). Contents of `a` are destroyed.
+pub trait Gesvdx: Sized {
+    fn gesvdx(a: &mut (impl ?Sized + Matrix<Self>), il: i32, iu: i32, s: &mut [Self], u: &mut [Self], vt: &mut [Self]) -> Result<(), LapackError>;
+}
+
+impl Gesvdx for f32 {
+    fn gesvdx(a: &mut (impl ?Sized + Matrix<Self>), il: i32, iu: i32, s: &mut [Self], u: &mut [Self], vt: &mut [Self]) -> Result<(), LapackError> {
+        let (m, n, lda) = col_major(a);
+        let k = m.min(n);
+        assert!(1 <= il && il <= iu && iu <= k, "Singular value indices {}..={} out of range for matrix of rank at most {}", il, iu, k);
+        let ns = iu - il + 1;
+        assert_eq!(s.len(), k as usize);
+        assert_eq!(u.len(), (m * ns) as usize);
+        assert_eq!(vt.len(), (ns * n) as usize);
+        let mut found = [0i32];
+        let mut info = 0;
+        let mut lwork = [0f32];
+        let mut iwork = vec![0i32; 12 * k as usize];
+        unsafe {
+            lapack::sgesvdx(b'V', b'V', b'I', m, n, a.as_mut_slice(), lda, 0., 0., il, iu, &mut found, s, u, m, vt, ns, &mut lwork, -1, &mut iwork, &mut info);
+        }
+        check_info(info, LapackError::NoConvergence)?;
+        let lwork = (lwork[0] as usize).max(1);
+        let mut work = vec![0f32; lwork];
+        unsafe {
+            lapack::sgesvdx(b'V', b'V', b'I', m, n, a.as_mut_slice(), lda, 0., 0., il, iu, &mut found, s, u, m, vt, ns, &mut work, lwork as i32, &mut iwork, &mut info);
+        }
+        check_info(info, LapackError::NoConvergence)
+    }
+}
+
+impl Gesvdx for f64 {
+    fn gesvdx(a: &mut (impl ?Sized + Matrix<Self>), il: i32, iu: i32, s: &mut [Self], u: &mut [Self], vt: &mut [Self]) -> Result<(), LapackError> {
+        let (m, n, lda) = col_major(a);
+        let k = m.min(n);
+        assert!(1 <= il && il <= iu && iu <= k, "Singular value indices {}..={} out of range for matrix of rank at most {}", il, iu, k);
+        let ns = iu - il + 1;
+        assert_eq!(s.len(), k as usize);
+        assert_eq!(u.len(), (m * ns) as usize);
+        assert_eq!(vt.len(), (ns * n) as usize);
+        let mut found = [0i32];
+        let mut info = 0;
+        let mut lwork = [0f64];
+        let mut iwork = vec![0i32; 12 * k as usize];
+        unsafe {
+            lapack::dgesvdx(b'V', b'V', b'I', m, n, a.as_mut_slice(), lda, 0., 0., il, iu, &mut found, s, u, m, vt, ns, &mut lwork, -1, &mut iwork, &mut info);
+        }
+        check_info(info, LapackError::NoConvergence)?;
+        let lwork = (lwork[0] as usize).max(1);
+        let mut work = vec![0f64; lwork];
+        unsafe {
+            lapack::dgesvdx(b'V', b'V', b'I', m, n, a.as_mut_slice(), lda, 0., 0., il, iu, &mut found, s, u, m, vt, ns, &mut work, lwork as i32, &mut iwork, &mut info);
+        }
+        check_info(info, LapackError::NoConvergence)
+    }
+}
@@ -11,6 +11,9 @@
 
 extern crate core;
 
+#[cfg(not(any(feature = "native", feature = "pure-rust")))]
+compile_error!("Either the native or the pure-rust feature must be enabled to provide BLAS routines");
+
 
 
 pub mod conv;
@@ -25,8 +28,11 @@ pub mod dot_sparse_arr;
 pub mod shape;
 pub mod layout;
 pub mod soft_wta;
+pub mod parallel;
 pub mod init_rand;
 pub mod conv_shape;
+pub mod ecc_layer;
+pub mod stdp;
 pub mod vec_range;
 pub mod from_usize;
 pub mod mat_slice;
@@ -35,6 +41,14 @@ pub mod cayley;
 pub mod bezier;
 pub mod collision;
 pub mod blas_safe;
+pub mod blas_rust;
+#[cfg(not(feature = "pure-rust"))]
+pub mod lapack_safe;
+#[cfg(not(feature = "pure-rust"))]
+pub mod sfa;
+pub mod sdr;
+pub mod sdr_dataset;
+pub mod naive_bayes;
 pub mod mesh_primitives;
 pub mod piecewise_linear;
 pub mod line;
@@ -74,6 +88,7 @@ mod lin_alg;
 pub use lin_alg::*;
 mod quat;
 pub mod histogram;
+pub mod color_space;
 mod num;
 mod vec;
 
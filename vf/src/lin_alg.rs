@@ -1,7 +1,12 @@
 use std::fmt::{Debug, Formatter, Pointer};
 use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Rem, RemAssign, Sub, SubAssign};
-use num_traits::{MulAdd, MulAddAssign, One};
+use num_traits::{MulAdd, MulAddAssign, One, Zero};
+#[cfg(not(feature = "pure-rust"))]
+use num_traits::Float;
 use crate::init::{InitEmptyWithCapacity, InitFilled, InitFilledCapacity, InitRFoldWithCapacity, InitFoldWithCapacity};
+use crate::blas_safe::{BlasError, Diagonal, Gemm, Gemv, Ger, Symmetry, Symv, Transpose, Trmv, Trsv, Syr};
+#[cfg(not(feature = "pure-rust"))]
+use crate::lapack_safe::{Gecon, Gesdd, Gesvdx, Getrf, Getri, Getrs, LapackError, Lange, Potrf, Syevd, Syevr};
 use crate::{blas_safe, VectorFieldAdd, VectorFieldAddAssign, VectorFieldDiv, VectorFieldDivAssign, VectorFieldMul, VectorFieldMulAssign, VectorFieldOne, VectorFieldRem, VectorFieldRemAssign, VectorFieldSub, VectorFieldSubAssign};
 
 /**C contiguous 2D matrix*/
@@ -26,6 +31,33 @@ impl<S:Debug, const DIM: usize> Debug for Mat<S, DIM> {
 }
 impl<S:Eq, const DIM: usize> Eq for Mat<S, DIM> {
 }
+#[cfg(feature = "serde")]
+impl<S: serde::Serialize, const DIM: usize> serde::Serialize for Mat<S, DIM> {
+    fn serialize<Ser: serde::Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        use serde::ser::SerializeStruct;
+        let mut s = serializer.serialize_struct("Mat", 2)?;
+        s.serialize_field("shape", &self.shape[..])?;
+        s.serialize_field("data", &self.data[..])?;
+        s.end()
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de, S: serde::Deserialize<'de>, const DIM: usize> serde::Deserialize<'de> for Mat<S, DIM> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        #[derive(serde::Deserialize)]
+        struct Repr<S> {
+            shape: Vec<u32>,
+            data: Vec<S>,
+        }
+        let Repr { shape, data } = Repr::deserialize(deserializer)?;
+        let shape: [u32; DIM] = shape.try_into().map_err(|shape| D::Error::custom(format!("Expected {}-dimensional shape but got {:?}", DIM, shape)))?;
+        if shape.product() as usize != data.len() {
+            return Err(D::Error::custom(format!("Shape {:?} doesn't match data of length {}", shape, data.len())));
+        }
+        Ok(Self { shape, data: data.into_boxed_slice() })
+    }
+}
 impl<S, const DIM: usize> blas_safe::Vector<S> for Mat<S, DIM> {
     fn len(&self) -> i32 {
         self.data.len() as i32
@@ -60,6 +92,12 @@ impl<S, const DIM: usize> Mat<S, DIM> {
             data,
         }
     }
+    pub fn shape(&self) -> &[u32; DIM] {
+        &self.shape
+    }
+    pub fn into_data(self) -> Box<[S]> {
+        self.data
+    }
 }
 
 impl<S> From<Vec<S>> for Mat<S, 1> {
@@ -311,10 +349,762 @@ impl<S: blas_safe::Nrm2, const DIM: usize> Norm2 for &Mat<S, DIM> {
         S::nrm2(self)
     }
 }
+#[cfg(not(feature = "pure-rust"))]
+impl From<BlasError> for LapackError {
+    fn from(e: BlasError) -> Self {
+        LapackError::ShapeMismatch(e.0)
+    }
+}
+
+/**Row-major matrix borrowed from a slice, used to pass parts of batched `Mat<S,3>` to BLAS*/
+struct MatView<'a, S> {
+    rows: u32,
+    cols: u32,
+    data: &'a [S],
+}
+
+impl<'a, S> blas_safe::Vector<S> for MatView<'a, S> {
+    fn len(&self) -> i32 {
+        self.data.len() as i32
+    }
+    fn as_slice(&self) -> &[S] {
+        self.data
+    }
+    fn as_mut_slice(&mut self) -> &mut [S] {
+        unreachable!("MatView is read-only")
+    }
+}
+
+impl<'a, S> blas_safe::Matrix<S> for MatView<'a, S> {
+    fn rows(&self) -> u32 {
+        self.rows
+    }
+    fn cols(&self) -> u32 {
+        self.cols
+    }
+}
+
+struct MatViewMut<'a, S> {
+    rows: u32,
+    cols: u32,
+    data: &'a mut [S],
+}
+
+impl<'a, S> blas_safe::Vector<S> for MatViewMut<'a, S> {
+    fn len(&self) -> i32 {
+        self.data.len() as i32
+    }
+    fn as_slice(&self) -> &[S] {
+        self.data
+    }
+    fn as_mut_slice(&mut self) -> &mut [S] {
+        self.data
+    }
+}
+
+impl<'a, S> blas_safe::Matrix<S> for MatViewMut<'a, S> {
+    fn rows(&self) -> u32 {
+        self.rows
+    }
+    fn cols(&self) -> u32 {
+        self.cols
+    }
+}
+
+fn op_shape(t: Transpose, shape: [u32; 2]) -> [u32; 2] {
+    let [h, w] = shape;
+    match t {
+        Transpose::NoTrans => [h, w],
+        _ => [w, h],
+    }
+}
+
+fn t_str(t: Transpose) -> &'static str {
+    match t {
+        Transpose::NoTrans => "",
+        Transpose::Trans => "^T",
+        Transpose::ConjTrans => "^H",
+    }
+}
+
+/**Checks shapes of `op(a)·op(b)` and returns the shape of result*/
+fn matmul_shape(at: Transpose, a: [u32; 2], bt: Transpose, b: [u32; 2]) -> Result<[u32; 2], BlasError> {
+    let [m, k] = op_shape(at, a);
+    let [k2, n] = op_shape(bt, b);
+    if k != k2 {
+        Err(BlasError(format!("Can't multiply matrix A{} of shape {:?} by B{} of shape {:?}. Inner dimensions {} and {} don't match", t_str(at), a, t_str(bt), b, k, k2)))
+    } else {
+        Ok([m, n])
+    }
+}
+
+impl<S: Copy + Zero + One + Gemm> Mat<S, 2> {
+    /**Computes `alpha·op(self)·op(rhs) + beta·out` and stores it in `out`*/
+    pub fn gemm_into(&self, alpha: S, at: Transpose, rhs: &Mat<S, 2>, bt: Transpose, beta: S, out: &mut Mat<S, 2>) -> Result<(), BlasError> {
+        let shape = matmul_shape(at, self.shape, bt, rhs.shape)?;
+        if shape != out.shape {
+            return Err(BlasError(format!("Product of A{} of shape {:?} and B{} of shape {:?} has shape {:?} but output has shape {:?}", t_str(at), self.shape, t_str(bt), rhs.shape, shape, out.shape)));
+        }
+        S::gemm(&alpha, at, self, bt, rhs, &beta, out);
+        Ok(())
+    }
+    /**Product `op(self)·op(rhs)`*/
+    pub fn matmul_with(&self, at: Transpose, rhs: &Mat<S, 2>, bt: Transpose) -> Result<Mat<S, 2>, BlasError> {
+        let shape = matmul_shape(at, self.shape, bt, rhs.shape)?;
+        let mut out = Mat::init_filled(shape, S::zero());
+        S::gemm(&S::one(), at, self, bt, rhs, &S::zero(), &mut out);
+        Ok(out)
+    }
+    /**Product `op(self)·op(rhs)` stored in `out`, which must already have the right shape*/
+    pub fn matmul_with_into(&self, at: Transpose, rhs: &Mat<S, 2>, bt: Transpose, out: &mut Mat<S, 2>) -> Result<(), BlasError> {
+        self.gemm_into(S::one(), at, rhs, bt, S::zero(), out)
+    }
+    /**Product `self·rhs`*/
+    pub fn matmul(&self, rhs: &Mat<S, 2>) -> Result<Mat<S, 2>, BlasError> {
+        self.matmul_with(Transpose::NoTrans, rhs, Transpose::NoTrans)
+    }
+    pub fn matmul_into(&self, rhs: &Mat<S, 2>, out: &mut Mat<S, 2>) -> Result<(), BlasError> {
+        self.matmul_with_into(Transpose::NoTrans, rhs, Transpose::NoTrans, out)
+    }
+    /**Product `self·rhs^T`*/
+    pub fn matmul_t(&self, rhs: &Mat<S, 2>) -> Result<Mat<S, 2>, BlasError> {
+        self.matmul_with(Transpose::NoTrans, rhs, Transpose::Trans)
+    }
+    pub fn matmul_t_into(&self, rhs: &Mat<S, 2>, out: &mut Mat<S, 2>) -> Result<(), BlasError> {
+        self.matmul_with_into(Transpose::NoTrans, rhs, Transpose::Trans, out)
+    }
+    /**Product `self^T·rhs`*/
+    pub fn t_matmul(&self, rhs: &Mat<S, 2>) -> Result<Mat<S, 2>, BlasError> {
+        self.matmul_with(Transpose::Trans, rhs, Transpose::NoTrans)
+    }
+    pub fn t_matmul_into(&self, rhs: &Mat<S, 2>, out: &mut Mat<S, 2>) -> Result<(), BlasError> {
+        self.matmul_with_into(Transpose::Trans, rhs, Transpose::NoTrans, out)
+    }
+}
+
+impl<S: Copy + Zero + One + Gemv> Mat<S, 2> {
+    /**Computes `alpha·op(self)·x + beta·out` and stores it in `out`*/
+    pub fn gemv_into(&self, alpha: S, t: Transpose, x: &Mat<S, 1>, beta: S, out: &mut Mat<S, 1>) -> Result<(), BlasError> {
+        let [m, n] = op_shape(t, self.shape);
+        if n != x.shape[0] {
+            return Err(BlasError(format!("Can't multiply matrix A{} of shape {:?} by vector of length {}", t_str(t), self.shape, x.shape[0])));
+        }
+        if m != out.shape[0] {
+            return Err(BlasError(format!("Product of A{} of shape {:?} and vector has length {} but output has length {}", t_str(t), self.shape, m, out.shape[0])));
+        }
+        S::gemv(t, &alpha, self, x, &beta, out)
+    }
+    /**Matrix-vector product `op(self)·x`*/
+    pub fn matvec_with(&self, t: Transpose, x: &Mat<S, 1>) -> Result<Mat<S, 1>, BlasError> {
+        let mut out = Mat::init_filled([op_shape(t, self.shape)[0]], S::zero());
+        self.gemv_into(S::one(), t, x, S::zero(), &mut out)?;
+        Ok(out)
+    }
+    /**Matrix-vector product `self·x`*/
+    pub fn matvec(&self, x: &Mat<S, 1>) -> Result<Mat<S, 1>, BlasError> {
+        self.matvec_with(Transpose::NoTrans, x)
+    }
+    pub fn matvec_into(&self, x: &Mat<S, 1>, out: &mut Mat<S, 1>) -> Result<(), BlasError> {
+        self.gemv_into(S::one(), Transpose::NoTrans, x, S::zero(), out)
+    }
+    /**Matrix-vector product `self^T·x`*/
+    pub fn t_matvec(&self, x: &Mat<S, 1>) -> Result<Mat<S, 1>, BlasError> {
+        self.matvec_with(Transpose::Trans, x)
+    }
+}
+
+impl<S> Mat<S, 2> {
+    fn check_square_len(&self, len: u32) -> Result<(), BlasError> {
+        let [h, w] = self.shape;
+        if h != w {
+            Err(BlasError(format!("Expected square matrix but got shape {:?}", self.shape)))
+        } else if h != len {
+            Err(BlasError(format!("Matrix of shape {:?} doesn't match vector of length {}", self.shape, len)))
+        } else {
+            Ok(())
+        }
+    }
+    /**Rank-1 update `self = alpha·x·y^T + self`. This is the outer-product (Hebbian) update.*/
+    pub fn ger_(&mut self, alpha: S, x: &Mat<S, 1>, y: &Mat<S, 1>) -> Result<(), BlasError> where S: Ger {
+        if self.shape != [x.shape[0], y.shape[0]] {
+            return Err(BlasError(format!("Outer product of vectors of lengths {} and {} can't be added to matrix of shape {:?}", x.shape[0], y.shape[0], self.shape)));
+        }
+        S::ger(&alpha, x, y, self);
+        Ok(())
+    }
+    /**Symmetric rank-1 update `self = alpha·x·x^T + self`. Only the `symmetry` triangle is updated.*/
+    pub fn syr_(&mut self, symmetry: Symmetry, alpha: S, x: &Mat<S, 1>) -> Result<(), BlasError> where S: Syr {
+        self.check_square_len(x.shape[0])?;
+        S::syr(symmetry, &alpha, x, self);
+        Ok(())
+    }
+    /**Product `self·x` of symmetric matrix, of which only the `symmetry` triangle is referenced*/
+    pub fn symv(&self, symmetry: Symmetry, x: &Mat<S, 1>) -> Result<Mat<S, 1>, BlasError> where S: Symv + Clone + Zero + One {
+        self.check_square_len(x.shape[0])?;
+        let mut out = Mat::init_filled(x.shape, S::zero());
+        S::symv(symmetry, &S::one(), self, x, &S::zero(), &mut out);
+        Ok(out)
+    }
+    /**In-place product `x = op(self)·x` of triangular matrix, of which only the `symmetry` triangle is referenced*/
+    pub fn trmv(&self, symmetry: Symmetry, t: Transpose, diag: Diagonal, x: &mut Mat<S, 1>) -> Result<(), BlasError> where S: Trmv {
+        self.check_square_len(x.shape[0])?;
+        S::trmv(symmetry, t, diag, self, x)
+    }
+    /**In-place solution of `op(self)·z = x` for triangular matrix, of which only the `symmetry` triangle is referenced*/
+    pub fn trsv(&self, symmetry: Symmetry, t: Transpose, diag: Diagonal, x: &mut Mat<S, 1>) -> Result<(), BlasError> where S: Trsv {
+        self.check_square_len(x.shape[0])?;
+        S::trsv(symmetry, t, diag, self, x)
+    }
+}
+
+impl<S: Copy + Zero + One + Gemm> Mat<S, 3> {
+    fn matrix(&self, b: usize) -> MatView<'_, S> {
+        let [_, rows, cols] = self.shape;
+        let len = (rows * cols) as usize;
+        MatView { rows, cols, data: &self.data[b * len..(b + 1) * len] }
+    }
+    fn matrix_mut(&mut self, b: usize) -> MatViewMut<'_, S> {
+        let [_, rows, cols] = self.shape;
+        let len = (rows * cols) as usize;
+        MatViewMut { rows, cols, data: &mut self.data[b * len..(b + 1) * len] }
+    }
+    fn batched_shape(&self, at: Transpose, rhs: &Mat<S, 3>, bt: Transpose) -> Result<[u32; 3], BlasError> {
+        let [batch, h, w] = self.shape;
+        let [rhs_batch, rhs_h, rhs_w] = rhs.shape;
+        if batch != rhs_batch {
+            return Err(BlasError(format!("Can't multiply batched matrices of shapes {:?} and {:?}. Batch sizes {} and {} don't match", self.shape, rhs.shape, batch, rhs_batch)));
+        }
+        let [m, n] = matmul_shape(at, [h, w], bt, [rhs_h, rhs_w])?;
+        Ok([batch, m, n])
+    }
+    /**Batched product `op(self[i])·op(rhs[i])` stored in `out[i]` for every `i` in batch*/
+    pub fn matmul_with_into(&self, at: Transpose, rhs: &Mat<S, 3>, bt: Transpose, out: &mut Mat<S, 3>) -> Result<(), BlasError> {
+        let shape = self.batched_shape(at, rhs, bt)?;
+        if shape != out.shape {
+            return Err(BlasError(format!("Batched product of shapes {:?} and {:?} has shape {:?} but output has shape {:?}", self.shape, rhs.shape, shape, out.shape)));
+        }
+        for b in 0..shape[0] as usize {
+            S::gemm(&S::one(), at, &self.matrix(b), bt, &rhs.matrix(b), &S::zero(), &mut out.matrix_mut(b));
+        }
+        Ok(())
+    }
+    pub fn matmul_with(&self, at: Transpose, rhs: &Mat<S, 3>, bt: Transpose) -> Result<Mat<S, 3>, BlasError> {
+        let shape = self.batched_shape(at, rhs, bt)?;
+        let mut out = Mat::init_filled(shape, S::zero());
+        self.matmul_with_into(at, rhs, bt, &mut out)?;
+        Ok(out)
+    }
+    /**Batched product `self[i]·rhs[i]`*/
+    pub fn matmul(&self, rhs: &Mat<S, 3>) -> Result<Mat<S, 3>, BlasError> {
+        self.matmul_with(Transpose::NoTrans, rhs, Transpose::NoTrans)
+    }
+    pub fn matmul_into(&self, rhs: &Mat<S, 3>, out: &mut Mat<S, 3>) -> Result<(), BlasError> {
+        self.matmul_with_into(Transpose::NoTrans, rhs, Transpose::NoTrans, out)
+    }
+    /**Batched product `self[i]·rhs[i]^T`*/
+    pub fn matmul_t(&self, rhs: &Mat<S, 3>) -> Result<Mat<S, 3>, BlasError> {
+        self.matmul_with(Transpose::NoTrans, rhs, Transpose::Trans)
+    }
+    /**Batched product `self[i]^T·rhs[i]`*/
+    pub fn t_matmul(&self, rhs: &Mat<S, 3>) -> Result<Mat<S, 3>, BlasError> {
+        self.matmul_with(Transpose::Trans, rhs, Transpose::NoTrans)
+    }
+    /**Every matrix in the batch is multiplied by the same `rhs`, i.e. `self[i]·rhs`*/
+    pub fn matmul_broadcast(&self, rhs: &Mat<S, 2>) -> Result<Mat<S, 3>, BlasError> {
+        let [batch, h, w] = self.shape;
+        let [m, n] = matmul_shape(Transpose::NoTrans, [h, w], Transpose::NoTrans, rhs.shape)?;
+        let mut out = Mat::init_filled([batch, m, n], S::zero());
+        for b in 0..batch as usize {
+            S::gemm(&S::one(), Transpose::NoTrans, &self.matrix(b), Transpose::NoTrans, rhs, &S::zero(), &mut out.matrix_mut(b));
+        }
+        Ok(out)
+    }
+}
+
 /**Inverse*/
 pub trait Inv{
-    fn inv(self)->Self;
+    type Output;
+    fn inv(self)->Self::Output;
+}
+#[cfg(not(feature = "pure-rust"))]
+impl<S: Float + Getrf + Getri + Gecon + Lange> Inv for &Mat<S, 2> {
+    type Output = Result<Mat<S, 2>, LapackError>;
+
+    fn inv(self) -> Self::Output {
+        self.lu()?.inv()
+    }
+}
+
+#[cfg(not(feature = "pure-rust"))]
+/**LU factorisation of a square matrix. LAPACK is column-major, so it actually holds the factorisation of
+ transposed matrix, but this is transparent to determinant, solve and inverse.*/
+pub struct Lu<S> {
+    lu: Mat<S, 2>,
+    ipiv: Box<[i32]>,
+    anorm: S,
+}
+
+#[cfg(not(feature = "pure-rust"))]
+impl<S: Float> Lu<S> {
+    /**Size of the factorised square matrix*/
+    pub fn n(&self) -> u32 {
+        self.lu.shape[0]
+    }
+    pub fn det(&self) -> S {
+        let n = self.n() as usize;
+        (0..n).fold(S::one(), |d, i| {
+            let u = self.lu.data[i * n + i];
+            if self.ipiv[i] as usize == i + 1 { d * u } else { -d * u }
+        })
+    }
+    /**Estimate of the reciprocal condition number. Returns error if it's below machine precision.*/
+    pub fn rcond(&self) -> Result<S, LapackError> where S: Gecon {
+        let rcond = S::gecon(&self.lu, self.anorm)?;
+        if rcond < S::epsilon() {
+            Err(LapackError::IllConditioned(rcond.to_f64().unwrap()))
+        } else {
+            Ok(rcond)
+        }
+    }
+    /**Solves `A·X = B` where `B` has shape `[n, nrhs]`*/
+    pub fn solve(&self, b: &Mat<S, 2>) -> Result<Mat<S, 2>, LapackError> where S: Getrs + Gecon {
+        let [n, nrhs] = b.shape;
+        if n != self.n() {
+            return Err(LapackError::ShapeMismatch(format!("Matrix of shape {:?} can't be solved against right hand side of shape {:?}", self.lu.shape, b.shape)));
+        }
+        self.rcond()?;
+        // row-major B seen in column-major is B^T, hence we need to transpose it
+        let mut x = b.transpose();
+        S::getrs(Transpose::Trans, &self.lu, &self.ipiv, &mut x.data, nrhs as i32)?;
+        x.shape = [nrhs, n];
+        Ok(x.transpose())
+    }
+    /**Solves `A·x = b` for a single right hand side*/
+    pub fn solve_vec(&self, b: &Mat<S, 1>) -> Result<Mat<S, 1>, LapackError> where S: Getrs + Gecon {
+        let [n] = b.shape;
+        if n != self.n() {
+            return Err(LapackError::ShapeMismatch(format!("Matrix of shape {:?} can't be solved against right hand side of shape {:?}", self.lu.shape, b.shape)));
+        }
+        self.rcond()?;
+        let mut x = b.clone();
+        S::getrs(Transpose::Trans, &self.lu, &self.ipiv, &mut x.data, 1)?;
+        Ok(x)
+    }
+    pub fn inv(&self) -> Result<Mat<S, 2>, LapackError> where S: Getri + Gecon {
+        self.rcond()?;
+        let mut inv = self.lu.clone();
+        // inverse of transposed matrix in column-major is the inverse in row-major
+        S::getri(&mut inv, &self.ipiv)?;
+        Ok(inv)
+    }
+}
+
+impl<S: Copy> Mat<S, 2> {
+    pub fn transpose(&self) -> Self {
+        let [h, w] = self.shape;
+        let (h, w) = (h as usize, w as usize);
+        let data = (0..w).flat_map(|c| (0..h).map(move |r| self.data[r * w + c])).collect();
+        Self { shape: [w as u32, h as u32], data }
+    }
+}
+
+#[cfg(not(feature = "pure-rust"))]
+impl<S> Mat<S, 2> {
+    fn check_square(&self) -> Result<u32, LapackError> {
+        let [h, w] = self.shape;
+        if h == w {
+            Ok(h)
+        } else {
+            Err(LapackError::ShapeMismatch(format!("Expected square matrix but got shape {:?}", self.shape)))
+        }
+    }
+}
+
+#[cfg(not(feature = "pure-rust"))]
+impl<S: Float + Getrf + Lange> Mat<S, 2> {
+    fn lu_unchecked(&self) -> Result<(Lu<S>, Result<(), LapackError>), LapackError> {
+        let n = self.check_square()?;
+        let mut lu = Lu { lu: self.clone(), ipiv: vec![0; n as usize].into_boxed_slice(), anorm: S::lange(self) };
+        let result = S::getrf(&mut lu.lu, &mut lu.ipiv);
+        Ok((lu, result))
+    }
+    /**LU factorisation with partial pivoting. Returns error if matrix is not square or is singular*/
+    pub fn lu(&self) -> Result<Lu<S>, LapackError> {
+        let (lu, result) = self.lu_unchecked()?;
+        result.map(|()| lu)
+    }
+    /**Determinant computed from LU factorisation. Singular matrices yield zero.*/
+    pub fn det(&self) -> Result<S, LapackError> {
+        let (lu, result) = self.lu_unchecked()?;
+        match result {
+            Ok(()) => Ok(lu.det()),
+            Err(LapackError::Singular(_)) => Ok(S::zero()),
+            Err(e) => Err(e)
+        }
+    }
+    /**Solves `A·X = B` where `A` is this square matrix of shape `[n, n]` and `B` has shape `[n, nrhs]`*/
+    pub fn solve(&self, b: &Mat<S, 2>) -> Result<Mat<S, 2>, LapackError> where S: Getrs + Gecon {
+        self.lu()?.solve(b)
+    }
+    /**Solves `A·x = b` where `A` is this square matrix of shape `[n, n]` and `b` has shape `[n]`*/
+    pub fn solve_vec(&self, b: &Mat<S, 1>) -> Result<Mat<S, 1>, LapackError> where S: Getrs + Gecon {
+        self.lu()?.solve_vec(b)
+    }
+}
+
+#[cfg(not(feature = "pure-rust"))]
+impl<S: Float + Potrf> Mat<S, 2> {
+    /**Cholesky factorisation `A = L·L^T` of a symmetric positive definite matrix. Returns lower triangular `L`.
+     Only the lower triangle of `A` is referenced.*/
+    pub fn cholesky(&self) -> Result<Mat<S, 2>, LapackError> {
+        let [h, w] = self.shape;
+        if h != w {
+            return Err(LapackError::ShapeMismatch(format!("Expected square matrix but got shape {:?}", self.shape)));
+        }
+        let mut l = self.clone();
+        // upper triangle in column-major is the lower triangle in row-major
+        S::potrf(Symmetry::Upper, &mut l)?;
+        let n = h as usize;
+        for r in 0..n {
+            for c in r + 1..n {
+                l.data[r * n + c] = S::zero();
+            }
+        }
+        Ok(l)
+    }
+}
+
+#[cfg(not(feature = "pure-rust"))]
+impl<S: Float + Syevd + Syevr> Mat<S, 2> {
+    /**LAPACK sorts eigenvalues in ascending order and stores eigenvectors in columns of column-major `[n, k]` matrix,
+     which are the rows of our row-major buffer. This reverses both, so that the largest eigenvalue comes first.*/
+    fn descending_eigenpairs(n: usize, k: usize, eigenvalues: &[S], z: &[S]) -> (Mat<S, 1>, Mat<S, 2>) {
+        let values: Vec<S> = eigenvalues[..k].iter().rev().cloned().collect();
+        let vectors = (0..n).flat_map(|r| (0..k).map(move |j| (r, j))).map(|(r, j)| z[(k - 1 - j) * n + r]).collect();
+        (Mat::from(values), Mat { shape: [n as u32, k as u32], data: vectors })
+    }
+    /**Eigendecomposition of a symmetric matrix. Returns eigenvalues sorted in descending order and a matrix
+     whose columns are the corresponding orthonormal eigenvectors (just like `numpy.linalg.eigh`, but reversed).
+     Only the lower triangle of `A` is referenced.*/
+    pub fn eigh(&self) -> Result<(Mat<S, 1>, Mat<S, 2>), LapackError> {
+        let n = self.check_square()? as usize;
+        let mut a = self.clone();
+        let mut eigenvalues = vec![S::zero(); n];
+        // upper triangle in column-major is the lower triangle in row-major
+        S::syevd(Symmetry::Upper, &mut a, &mut eigenvalues)?;
+        Ok(Self::descending_eigenpairs(n, n, &eigenvalues, &a.data))
+    }
+    /**Same as `eigh` but computes only `k` largest eigenvalues. Eigenvectors matrix has shape `[n, k]`.*/
+    pub fn eigh_top_k(&self, k: usize) -> Result<(Mat<S, 1>, Mat<S, 2>), LapackError> {
+        let n = self.check_square()? as usize;
+        let k = k.min(n);
+        if k == 0 {
+            return Ok((Mat::from(Vec::new()), Mat { shape: [n as u32, 0], data: Box::new([]) }));
+        }
+        let mut a = self.clone();
+        let mut eigenvalues = vec![S::zero(); n];
+        let mut z = vec![S::zero(); n * k];
+        // only the eigenvalues with (1-based, ascending) indices n-k+1..=n are computed
+        S::syevr(Symmetry::Upper, &mut a, (n - k + 1) as i32, n as i32, &mut eigenvalues, &mut z)?;
+        Ok(Self::descending_eigenpairs(n, k, &eigenvalues, &z))
+    }
+}
+
+#[cfg(not(feature = "pure-rust"))]
+impl<S: Float + Gesdd + Gesvdx> Mat<S, 2> {
+    /**Thin singular value decomposition `A = U·diag(s)·V^T` of matrix of shape `[m, n]`.
+     Returns `U` of shape `[m, k]`, singular values `s` sorted in descending order and `V^T` of shape `[k, n]`
+     where `k = min(m, n)`.*/
+    pub fn svd(&self) -> Result<(Mat<S, 2>, Mat<S, 1>, Mat<S, 2>), LapackError> {
+        let [m, n] = self.shape;
+        let k = m.min(n);
+        let mut a = self.clone();
+        let mut s = vec![S::zero(); k as usize];
+        let mut u = vec![S::zero(); (m * k) as usize].into_boxed_slice();
+        let mut vt = vec![S::zero(); (k * n) as usize].into_boxed_slice();
+        // LAPACK sees the transposed matrix A^T = V·diag(s)·U^T, so the roles of U and V^T are swapped.
+        // Column-major V^T of A^T is row-major U of A, and column-major U of A^T is row-major V^T of A.
+        S::gesdd(&mut a, &mut s, &mut vt, &mut u)?;
+        Ok((Mat { shape: [m, k], data: u }, Mat::from(s), Mat { shape: [k, n], data: vt }))
+    }
+    /**Same as `svd` but computes only `k` largest singular values. Returns `U` of shape `[m, k]`, `s` of shape `[k]`
+     and `V^T` of shape `[k, n]`.*/
+    pub fn svd_top_k(&self, k: usize) -> Result<(Mat<S, 2>, Mat<S, 1>, Mat<S, 2>), LapackError> {
+        let [m, n] = self.shape;
+        let k = k.min(m.min(n) as usize) as u32;
+        if k == 0 {
+            return Ok((Mat { shape: [m, 0], data: Box::new([]) }, Mat::from(Vec::new()), Mat { shape: [0, n], data: Box::new([]) }));
+        }
+        let mut a = self.clone();
+        let mut s = vec![S::zero(); m.min(n) as usize];
+        let mut u = vec![S::zero(); (m * k) as usize].into_boxed_slice();
+        let mut vt = vec![S::zero(); (k * n) as usize].into_boxed_slice();
+        // roles of U and V^T are swapped just like in svd()
+        S::gesvdx(&mut a, 1, k as i32, &mut s, &mut vt, &mut u)?;
+        s.truncate(k as usize);
+        Ok((Mat { shape: [m, k], data: u }, Mat::from(s), Mat { shape: [k, n], data: vt }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_complex::Complex64;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test1() {
+        let m = Mat::new([2, 3], (0..6).map(|_| rand::random::<f32>()).collect());
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(&m, &mut bytes).unwrap();
+        let m2: Mat<f32, 2> = ciborium::de::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(m, m2);
+        assert_eq!(m.shape(), m2.shape());
+        assert!(ciborium::de::from_reader::<Mat<f32, 3>, _>(bytes.as_slice()).is_err());
+    }
+
+    fn rand_mat<const DIM: usize>(rng: &mut StdRng, shape: [u32; DIM]) -> Mat<f64, DIM> {
+        let len = shape.iter().product::<u32>() as usize;
+        Mat::new(shape, (0..len).map(|_| rng.gen::<f64>() * 2. - 1.).collect())
+    }
+
+    /**`op(a)·op(b)` of row-major matrices computed with a triple loop*/
+    fn naive_matmul(at: bool, a: &[f64], [ah, aw]: [u32; 2], bt: bool, b: &[f64], [bh, bw]: [u32; 2]) -> Vec<f64> {
+        let (ah, aw, bh, bw) = (ah as usize, aw as usize, bh as usize, bw as usize);
+        let (m, k) = if at { (aw, ah) } else { (ah, aw) };
+        let n = if bt { bh } else { bw };
+        let mut c = vec![0.; m * n];
+        for i in 0..m {
+            for j in 0..n {
+                for l in 0..k {
+                    let a = if at { a[l * aw + i] } else { a[i * aw + l] };
+                    let b = if bt { b[j * bw + l] } else { b[l * bw + j] };
+                    c[i * n + j] += a * b;
+                }
+            }
+        }
+        c
+    }
+
+    fn assert_close(a: &[f64], b: &[f64]) {
+        assert_eq!(a.len(), b.len());
+        assert!(a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-9), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test2() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let a = rand_mat(&mut rng, [4, 3]);
+        let b = rand_mat(&mut rng, [3, 5]);
+        let c = a.matmul(&b).unwrap();
+        assert_eq!(c.shape, [4, 5]);
+        assert_close(&c.data, &naive_matmul(false, &a.data, a.shape, false, &b.data, b.shape));
+        let mut out = rand_mat(&mut rng, [4, 5]);
+        a.matmul_into(&b, &mut out).unwrap();
+        assert_eq!(out, c);
+        let bt = rand_mat(&mut rng, [5, 3]);
+        let c = a.matmul_t(&bt).unwrap();
+        assert_close(&c.data, &naive_matmul(false, &a.data, a.shape, true, &bt.data, bt.shape));
+        a.matmul_t_into(&bt, &mut out).unwrap();
+        assert_eq!(out, c);
+        let at = rand_mat(&mut rng, [3, 4]);
+        let c = at.t_matmul(&b).unwrap();
+        assert_close(&c.data, &naive_matmul(true, &at.data, at.shape, false, &b.data, b.shape));
+        at.t_matmul_into(&b, &mut out).unwrap();
+        assert_eq!(out, c);
+        let c = at.matmul_with(Transpose::Trans, &bt, Transpose::Trans).unwrap();
+        assert_close(&c.data, &naive_matmul(true, &at.data, at.shape, true, &bt.data, bt.shape));
+        // gemm_into accumulates into the output
+        let c0 = rand_mat(&mut rng, [4, 5]);
+        let mut c = c0.clone();
+        a.gemm_into(2., Transpose::NoTrans, &b, Transpose::NoTrans, 0.5, &mut c).unwrap();
+        let ab = naive_matmul(false, &a.data, a.shape, false, &b.data, b.shape);
+        assert_close(&c.data, &ab.iter().zip(c0.data.iter()).map(|(ab, c0)| 2. * ab + 0.5 * c0).collect::<Vec<f64>>());
+        let x = rand_mat(&mut rng, [3]);
+        assert_close(&a.matvec(&x).unwrap().data, &naive_matmul(false, &a.data, a.shape, false, &x.data, [3, 1]));
+        let y = rand_mat(&mut rng, [4]);
+        assert_close(&a.t_matvec(&y).unwrap().data, &naive_matmul(true, &a.data, a.shape, false, &y.data, [4, 1]));
+    }
+
+    #[test]
+    fn test3() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let a = rand_mat(&mut rng, [3, 4, 2]);
+        let b = rand_mat(&mut rng, [3, 2, 5]);
+        let bt = rand_mat(&mut rng, [3, 5, 2]);
+        let at = rand_mat(&mut rng, [3, 2, 4]);
+        let rhs = rand_mat(&mut rng, [2, 5]);
+        let c = a.matmul(&b).unwrap();
+        let mut out = rand_mat(&mut rng, [3, 4, 5]);
+        a.matmul_into(&b, &mut out).unwrap();
+        assert_eq!(out, c);
+        let c_t = a.matmul_t(&bt).unwrap();
+        let t_c = at.t_matmul(&b).unwrap();
+        let c_b = a.matmul_broadcast(&rhs).unwrap();
+        for (i, c) in [c, c_t, t_c, c_b].iter().enumerate() {
+            assert_eq!(c.shape, [3, 4, 5]);
+            for batch in 0..3 {
+                let expected = match i {
+                    0 => naive_matmul(false, &a.data[batch * 8..][..8], [4, 2], false, &b.data[batch * 10..][..10], [2, 5]),
+                    1 => naive_matmul(false, &a.data[batch * 8..][..8], [4, 2], true, &bt.data[batch * 10..][..10], [5, 2]),
+                    2 => naive_matmul(true, &at.data[batch * 8..][..8], [2, 4], false, &b.data[batch * 10..][..10], [2, 5]),
+                    _ => naive_matmul(false, &a.data[batch * 8..][..8], [4, 2], false, &rhs.data, [2, 5]),
+                };
+                assert_close(&c.data[batch * 20..][..20], &expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test4() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let a = rand_mat(&mut rng, [4, 3]);
+        assert!(a.matmul(&rand_mat(&mut rng, [4, 3])).is_err());
+        assert!(a.matmul_t(&rand_mat(&mut rng, [4, 3])).is_ok());
+        assert!(a.t_matmul(&rand_mat(&mut rng, [3, 4])).is_err());
+        let mut out = rand_mat(&mut rng, [4, 4]);
+        assert!(a.matmul_into(&rand_mat(&mut rng, [3, 5]), &mut out).is_err());
+        assert!(a.matvec(&rand_mat(&mut rng, [4])).is_err());
+        assert!(a.matvec_into(&rand_mat(&mut rng, [3]), &mut rand_mat(&mut rng, [3])).is_err());
+        let batched = rand_mat(&mut rng, [2, 4, 3]);
+        assert!(batched.matmul(&rand_mat(&mut rng, [3, 3, 5])).is_err());
+        assert!(batched.matmul(&rand_mat(&mut rng, [2, 4, 5])).is_err());
+        assert!(batched.matmul_into(&rand_mat(&mut rng, [2, 3, 5]), &mut rand_mat(&mut rng, [2, 5, 4])).is_err());
+        assert!(batched.matmul_broadcast(&rand_mat(&mut rng, [4, 3])).is_err());
+        // BLAS can't conjugate row-major matrix without transposing it
+        let c = Mat::new([2, 2], vec![Complex64::new(1., 2.); 4].into_boxed_slice());
+        let x = Mat::from(vec![Complex64::new(0., 1.); 2]);
+        assert!(c.matvec_with(Transpose::ConjTrans, &x).is_err());
+        assert!(c.matvec_with(Transpose::Trans, &x).is_ok());
+    }
+
+    #[cfg(not(feature = "pure-rust"))]
+    fn eye(n: u32) -> Mat<f64, 2> {
+        Mat::new([n, n], (0..n * n).map(|i| if i % (n + 1) == 0 { 1. } else { 0. }).collect())
+    }
+
+    #[cfg(not(feature = "pure-rust"))]
+    /**Random matrix with dominant diagonal, hence well conditioned*/
+    fn rand_invertible(rng: &mut StdRng, n: u32) -> Mat<f64, 2> {
+        let mut a = rand_mat(rng, [n, n]);
+        a += &(&eye(n) * (n as f64));
+        a
+    }
+
+    #[cfg(not(feature = "pure-rust"))]
+    #[test]
+    fn test5() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let a = rand_invertible(&mut rng, 4);
+        let inv = (&a).inv().unwrap();
+        assert_close(&a.matmul(&inv).unwrap().data, &eye(4).data);
+        assert_close(&inv.matmul(&a).unwrap().data, &eye(4).data);
+        let lu = a.lu().unwrap();
+        assert_eq!(lu.n(), 4);
+        assert_close(&lu.inv().unwrap().data, &inv.data);
+        assert!((&rand_mat(&mut rng, [3, 4])).inv().is_err());
+    }
+
+    #[cfg(not(feature = "pure-rust"))]
+    #[test]
+    fn test6() {
+        let mut rng = StdRng::seed_from_u64(6);
+        let a = rand_invertible(&mut rng, 5);
+        let x = rand_mat(&mut rng, [5, 2]);
+        let b = a.matmul(&x).unwrap();
+        assert_close(&a.solve(&b).unwrap().data, &x.data);
+        let x = rand_mat(&mut rng, [5]);
+        let b = a.matvec(&x).unwrap();
+        assert_close(&a.solve_vec(&b).unwrap().data, &x.data);
+        assert!(a.solve(&rand_mat(&mut rng, [4, 2])).is_err());
+        assert!(a.solve_vec(&rand_mat(&mut rng, [6])).is_err());
+    }
+
+    #[cfg(not(feature = "pure-rust"))]
+    #[test]
+    fn test7() {
+        let upper = Mat::new([3, 3], vec![2., 5., -1., 0., 3., 7., 0., 0., -4.].into_boxed_slice());
+        assert!((upper.det().unwrap() + 24.).abs() < 1e-9);
+        assert!((upper.transpose().det().unwrap() + 24.).abs() < 1e-9);
+        // cyclic permutation of 3 elements is even, while a single swap is odd
+        let cycle = Mat::new([3, 3], vec![0., 1., 0., 0., 0., 1., 1., 0., 0.].into_boxed_slice());
+        assert!((cycle.det().unwrap() - 1.).abs() < 1e-9);
+        let swap = Mat::new([3, 3], vec![0., 1., 0., 1., 0., 0., 0., 0., 1.].into_boxed_slice());
+        assert!((swap.det().unwrap() + 1.).abs() < 1e-9);
+        assert!((eye(4).det().unwrap() - 1.).abs() < 1e-9);
+        let singular = Mat::new([2, 2], vec![1., 2., 2., 4.].into_boxed_slice());
+        assert_eq!(singular.det().unwrap(), 0.);
+        assert!(matches!(singular.lu(), Err(LapackError::Singular(_))));
+        assert!((&singular).inv().is_err());
+        assert!(singular.solve_vec(&Mat::from(vec![1., 2.])).is_err());
+        assert!(rand_mat(&mut StdRng::seed_from_u64(7), [2, 3]).det().is_err());
+    }
+
+    #[cfg(not(feature = "pure-rust"))]
+    #[test]
+    fn test8() {
+        let mut rng = StdRng::seed_from_u64(8);
+        let b = rand_mat(&mut rng, [4, 4]);
+        let a = &b.matmul_t(&b).unwrap() + &eye(4);
+        let l = a.cholesky().unwrap();
+        assert!((0..4).all(|r| (r + 1..4).all(|c| l.data[r * 4 + c] == 0.)));
+        assert_close(&l.matmul_t(&l).unwrap().data, &a.data);
+        let not_spd = Mat::new([2, 2], vec![1., 2., 2., 1.].into_boxed_slice());
+        assert!(matches!(not_spd.cholesky(), Err(LapackError::NotPositiveDefinite(_))));
+        assert!(rand_mat(&mut rng, [2, 3]).cholesky().is_err());
+    }
+
+    #[cfg(not(feature = "pure-rust"))]
+    #[test]
+    fn test9() {
+        let mut rng = StdRng::seed_from_u64(9);
+        let b = rand_mat(&mut rng, [5, 5]);
+        let a = &b + &b.transpose();
+        let (values, vectors) = a.eigh().unwrap();
+        assert_eq!(vectors.shape, [5, 5]);
+        assert!(values.data.windows(2).all(|w| w[0] >= w[1]));
+        // A·V = V·diag(λ) and V is orthonormal
+        let av = a.matmul(&vectors).unwrap();
+        let v_lambda: Vec<f64> = (0..25).map(|i| vectors.data[i] * values.data[i % 5]).collect();
+        assert_close(&av.data, &v_lambda);
+        assert_close(&vectors.transpose().matmul(&vectors).unwrap().data, &eye(5).data);
+        let (top_values, top_vectors) = a.eigh_top_k(2).unwrap();
+        assert_eq!(top_vectors.shape, [5, 2]);
+        assert_close(&top_values.data, &values.data[..2]);
+        for j in 0..2 {
+            let v = Mat::from((0..5).map(|r| top_vectors.data[r * 2 + j]).collect::<Vec<f64>>());
+            let lambda_v: Vec<f64> = v.data.iter().map(|x| x * top_values.data[j]).collect();
+            assert_close(&a.matvec(&v).unwrap().data, &lambda_v);
+        }
+        assert!(rand_mat(&mut rng, [2, 3]).eigh_top_k(1).is_err());
+    }
+
+    #[cfg(not(feature = "pure-rust"))]
+    #[test]
+    fn test10() {
+        let mut rng = StdRng::seed_from_u64(10);
+        for shape in [[4, 6], [6, 4]] {
+            let a = rand_mat(&mut rng, shape);
+            let (u, s, vt) = a.svd().unwrap();
+            assert_eq!(u.shape, [shape[0], 4]);
+            assert_eq!(vt.shape, [4, shape[1]]);
+            assert!(s.data.windows(2).all(|w| w[0] >= w[1]));
+            let reconstruct = |u: &Mat<f64, 2>, s: &Mat<f64, 1>, vt: &Mat<f64, 2>| {
+                let k = s.data.len();
+                let us = Mat::new(u.shape, u.data.iter().enumerate().map(|(i, x)| x * s.data[i % k]).collect());
+                us.matmul(vt).unwrap()
+            };
+            assert_close(&reconstruct(&u, &s, &vt).data, &a.data);
+            let (top_u, top_s, top_vt) = a.svd_top_k(2).unwrap();
+            assert_eq!(top_u.shape, [shape[0], 2]);
+            assert_eq!(top_vt.shape, [2, shape[1]]);
+            assert_close(&top_s.data, &s.data[..2]);
+            // best rank-2 approximation is unique up to signs of singular vectors
+            let u2 = Mat::new([shape[0], 2], (0..shape[0] as usize).flat_map(|r| u.data[r * 4..][..2].to_vec()).collect());
+            let vt2 = Mat::new([2, shape[1]], vt.data[..2 * shape[1] as usize].to_vec().into_boxed_slice());
+            assert_close(&reconstruct(&top_u, &top_s, &top_vt).data, &reconstruct(&u2, &top_s, &vt2).data);
+        }
+    }
 }
-// impl<S: blas_safe::Nrm2, const DIM: usize> Inv for &Mat<S, DIM> {
-//
-// }
\ No newline at end of file
@@ -1,15 +1,19 @@
-use std::ops::{Add, AddAssign, MulAssign, Neg, Sub};
+use std::ops::Neg;
 use std::process::Output;
 use num_traits::{Float, FloatConst, Num};
-use crate::mat_arr::{mat2_add_column, mat3_add_column, mat3x2_add_row, mat4x3_add_row, mul_row_wise_};
-use crate::{mat3_to_mat4, VectorFieldAddAssign, VectorFieldMulAddAssign, VectorFieldMulAdd, xyz4, xyz4_};
+use crate::mat_arr::{inv2, inv3, mat2_add_column, mat3_add_column, mat3x2_add_row, mat4x3_add_row, mul_row_wise_, transpose};
+use crate::{mat3_to_mat4, VectorFieldAddAssign, VectorFieldMulAddAssign, VectorFieldMulAdd, VectorFieldMul, VectorFieldAddOwned, VectorFieldNegOwned, xyz4, xyz4_};
+use crate::dot_mad_arr::{dot0, dot2};
+use crate::quat;
+use crate::quat::Quat;
 
 pub type Translation<S, const DIM: usize> = [S; DIM];
 pub type Scaling<S, const DIM: usize> = [S; DIM];
 /**When DIM=2, it's an array [[S;2];1] holding one normal vector of length 2, which determines where the X axis lies.
-The Y axis can then be obtained by rotating X axis 90 degrees clockwise.
+The Y axis can then be obtained by rotating X axis 90 degrees counter clockwise.
  When DIM=3 it's an array [[S;3];2] holding two normal vector of length 2, which determine where the X and Y axes lie.
-The Z axis can then be obtained by taking cross product of X and Y.
+The Z axis can then be obtained by taking cross product of X and Y (normalised so that its length is the geometric mean
+ of lengths of X and Y).
  And so on for DIM>3*/
 pub type AlignmentAxis<S, const DIM: usize> = [[S; DIM]; { DIM - 1 }];
 pub type EulerRotation<S, const DIM: usize> = [S; DIM];
@@ -46,12 +50,12 @@ pub fn rotate90deg_ccw<S: Neg<Output=S>>(vector: [S; 2]) -> [S; 2] {
     [-y, x]
 }
 
-/**clockwise rotation in radians*/
+/**counter clockwise rotation in radians*/
 pub fn rotate2d(radians: f32, vector: [f32; 2]) -> [f32; 2] {
     let s = radians.sin();
     let c = radians.cos();
     let [x, y] = vector;
-    [c * x - s * x, s * x + c * y]
+    [c * x - s * y, s * x + c * y]
 }
 
 /**cross product of two vectors*/
@@ -62,7 +66,7 @@ pub fn cross(a: &[f32; 3], b: &[f32; 3]) -> [f32; 3] {
 /**Given a normal vector of X axis, produces a 2x2 rotation matrix R such that  `M·[1,0]^T = X_axis`
  and `M·[0, 1]^T = Y_axis`*/
 pub fn rotation2d_from_axis(x_axis: [f32; 2]) -> [[f32; 2]; 2] {
-    let y_axis = rotate90deg_cw(x_axis);
+    let y_axis = rotate90deg_ccw(x_axis);
     [
         [x_axis[0], y_axis[0]],
         [x_axis[1], y_axis[1]],
@@ -320,52 +324,345 @@ pub struct AffTrans<S, const DIM: usize> where [(); { DIM - 1 }]: Sized {
     translation: Translation<S, DIM>,
 }
 
-impl<S: Copy + MulAssign + AddAssign, const DIM: usize> AffineTransformation<S, DIM> for AffTrans<S, DIM> where [(); { DIM - 1 }]: Sized {
+#[cfg(feature = "serde")]
+impl<S: serde::Serialize, const DIM: usize> serde::Serialize for AffTrans<S, DIM> where [(); { DIM - 1 }]: Sized {
+    fn serialize<Ser: serde::Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        use serde::ser::SerializeStruct;
+        let axis: Vec<&[S]> = self.axis.iter().map(|a| &a[..]).collect();
+        let mut s = serializer.serialize_struct("AffTrans", 2)?;
+        s.serialize_field("axis", &axis)?;
+        s.serialize_field("translation", &self.translation[..])?;
+        s.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, S: serde::Deserialize<'de>, const DIM: usize> serde::Deserialize<'de> for AffTrans<S, DIM> where [(); { DIM - 1 }]: Sized {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        #[derive(serde::Deserialize)]
+        struct Repr<S> {
+            axis: Vec<Vec<S>>,
+            translation: Vec<S>,
+        }
+        fn arr<S, E: Error, const N: usize>(v: Vec<S>) -> Result<[S; N], E> {
+            let len = v.len();
+            v.try_into().map_err(|_| E::custom(format!("Expected {} elements but got {}", N, len)))
+        }
+        let Repr { axis, translation } = Repr::deserialize(deserializer)?;
+        let axis: Vec<[S; DIM]> = axis.into_iter().map(arr).collect::<Result<_, D::Error>>()?;
+        Ok(Self { axis: arr(axis)?, translation: arr(translation)? })
+    }
+}
+
+impl<S, const DIM: usize> AffTrans<S, DIM> where [(); { DIM - 1 }]: Sized {
+    pub fn new(axis: AlignmentAxis<S, DIM>, translation: Translation<S, DIM>) -> Self {
+        Self { axis, translation }
+    }
+    pub fn axis(&self) -> &AlignmentAxis<S, DIM> {
+        &self.axis
+    }
+    pub fn translation(&self) -> &Translation<S, DIM> {
+        &self.translation
+    }
+}
+
+fn assert_uniform<const DIM: usize>(scaling: &Scaling<f32, DIM>) {
+    assert!(scaling.iter().all(|&s| (s - scaling[0]).abs() <= 1e-6 * scaling[0].abs()), "Only uniform scaling is supported but got {:?}", scaling);
+}
+
+fn mul_mat_vec<const DIM: usize>(mat: &[[f32; DIM]; DIM], vec: &[f32; DIM]) -> [f32; DIM] {
+    mat.map(|row| dot0(&row, vec))
+}
+
+/**`a.compose(b)` is the transformation that first applies `b` and then `a` (just like matrix product `a·b`).
+ Rotation by Euler angles `[x, y, z]` is applied in order: first about X axis, then Y, then Z. In 2D there is only
+ one angle of counter clockwise rotation `rot[0]`. Rotation and scaling do not affect the translation.
+ Since the last axis is not stored, composition and inverse are exact only for rotations with uniform scaling,
+ so `scale_` panics if the scaling factors differ.*/
+impl AffineTransformation<f32, 2> for AffTrans<f32, 2> {
     fn compose_(&mut self, other: &Self) -> &mut Self {
-        todo!()
+        let rot = self.rot2();
+        self.translation = self.transform_point(&other.translation);
+        self.axis = [mul_mat_vec(&rot, &other.axis[0])];
+        self
     }
 
     fn inverse_(&mut self) -> &mut Self {
-        todo!()
+        let inv = inv2(&self.rot2());
+        let translation = mul_mat_vec(&inv, &self.translation)._neg();
+        *self = Self::new([[inv[0][0], inv[1][0]]], translation);
+        self
     }
 
-    fn inverse(&self) -> Self {
-        todo!()
+    fn scale_(&mut self, scaling: &Scaling<f32, 2>) -> &mut Self {
+        assert_uniform(scaling);
+        mul_row_wise_(&mut self.axis, scaling);
+        self
     }
 
-    fn scale_(&mut self, scaling: &Scaling<S, DIM>) -> &mut Self {
-        mul_row_wise_(&mut self.axis, scaling);
+    fn rotate_(&mut self, rot: &EulerRotation<f32, 2>) -> &mut Self {
+        self.axis = [mul_mat_vec(&rotation2d(rot[0]), &self.axis[0])];
         self
     }
-    fn rotate_(&mut self, rot: &EulerRotation<S, DIM>) -> &mut Self {
-        // self.axis
+
+    fn translate_(&mut self, translation: &Translation<f32, 2>) -> &mut Self {
+        self.translation.add_(translation);
+        self
+    }
+}
+
+impl AffineTransformation<f32, 3> for AffTrans<f32, 3> {
+    fn compose_(&mut self, other: &Self) -> &mut Self {
+        let rot = self.rot3();
+        self.translation = self.transform_point(&other.translation);
+        self.axis = other.axis.map(|a| mul_mat_vec(&rot, &a));
+        self
+    }
+
+    fn inverse_(&mut self) -> &mut Self {
+        let inv = inv3(&self.rot3());
+        let translation = mul_mat_vec(&inv, &self.translation)._neg();
+        let [c0, c1, _] = transpose(&inv);
+        *self = Self::new([c0, c1], translation);
+        self
+    }
+
+    fn scale_(&mut self, scaling: &Scaling<f32, 3>) -> &mut Self {
+        assert_uniform(scaling);
+        mul_row_wise_(&mut self.axis, scaling);
         self
     }
 
-    fn translate_(&mut self, translation: &Translation<S, DIM>) -> &mut Self {
+    fn rotate_(&mut self, rot: &EulerRotation<f32, 3>) -> &mut Self {
+        let &[x, y, z] = rot;
+        let r = dot2(&rotation3d_about_z(z), &dot2(&rotation3d_about_y(y), &rotation3d_about_x(x)));
+        self.rotate_by_mat_(&r)
+    }
+
+    fn translate_(&mut self, translation: &Translation<f32, 3>) -> &mut Self {
         self.translation.add_(translation);
         self
     }
 }
 
 impl AffTrans<f32, 2> {
+    pub fn identity() -> Self {
+        Self::new([[1., 0.]], [0., 0.])
+    }
+    /**Linear part of the transformation. Its columns are the X and Y axes.*/
     pub fn rot2(&self) -> [[f32; 2]; 2] {
         rotation2d_from_axis(self.axis[0])
     }
     pub fn mat3(&self) -> [[f32; 3]; 3] {
-        let mut mat = self.rot2();
+        let mat = self.rot2();
         let mat = mat2_add_column(mat, self.translation);
         mat3x2_add_row(mat, [0., 0., 1.])
     }
+    /**Applies scaling, rotation and translation to a point*/
+    pub fn transform_point(&self, point: &[f32; 2]) -> [f32; 2] {
+        self.transform_vector(point)._add(&self.translation)
+    }
+    /**Applies scaling and rotation to a direction vector. Translation is ignored*/
+    pub fn transform_vector(&self, vector: &[f32; 2]) -> [f32; 2] {
+        mul_mat_vec(&self.rot2(), vector)
+    }
 }
 
 impl AffTrans<f32, 3> {
+    pub fn identity() -> Self {
+        Self::new([[1., 0., 0.], [0., 1., 0.]], [0., 0., 0.])
+    }
+    /**Rotation (and quaternion) representing orientation of the axes, together with translation. There is no scaling.*/
+    pub fn from_quat(q: &Quat<f32>, translation: Translation<f32, 3>) -> Self {
+        let [c0, c1, _] = transpose(&quat::mat3(q));
+        Self::new([c0, c1], translation)
+    }
+    /**Unit quaternion representing the rotation of axes. Scaling is discarded.*/
+    pub fn quat(&self) -> Quat<f32> {
+        let [x, y] = &self.axis;
+        let x = x.mul_scalar(1. / dot0(x, x).sqrt());
+        let y = y.mul_scalar(1. / dot0(y, y).sqrt());
+        quat::from_mat3(&rotation3d_from_axis([x, y]))
+    }
+    /**The Z axis is not stored. It's perpendicular to X and Y and its length is the geometric mean of their lengths.
+     This way uniform scaling applies to all three axes.*/
+    pub fn z_axis(&self) -> [f32; 3] {
+        let [x, y] = &self.axis;
+        let z = cross(x, y);
+        z.mul_scalar(1. / (dot0(x, x) * dot0(y, y)).sqrt().sqrt())
+    }
+    /**Linear part of the transformation. Its columns are the X, Y and Z axes.*/
     pub fn rot3(&self) -> [[f32; 3]; 3] {
-        rotation3d_from_axis(self.axis)
+        let [x, y] = &self.axis;
+        let z = self.z_axis();
+        transpose(&[*x, *y, z])
     }
     pub fn mat4(&self) -> [[f32; 4]; 4] {
-        let mut mat = self.rot3();
+        let mat = self.rot3();
         let mat = mat3_add_column(mat, self.translation);
         mat4x3_add_row(mat, [0., 0., 0., 1.])
     }
-}
\ No newline at end of file
+    /**Applies scaling, rotation and translation to a point*/
+    pub fn transform_point(&self, point: &[f32; 3]) -> [f32; 3] {
+        self.transform_vector(point)._add(&self.translation)
+    }
+    /**Applies scaling and rotation to a direction vector. Translation is ignored*/
+    pub fn transform_vector(&self, vector: &[f32; 3]) -> [f32; 3] {
+        mul_mat_vec(&self.rot3(), vector)
+    }
+    /**Rotates axes (but not translation) by a given rotation matrix*/
+    pub fn rotate_by_mat_(&mut self, rot: &[[f32; 3]; 3]) -> &mut Self {
+        self.axis = self.axis.map(|a| mul_mat_vec(rot, &a));
+        self
+    }
+    /**Rotates axes (but not translation) about a normal vector of given axis*/
+    pub fn rotate_about_(&mut self, angle_in_radians: f32, axis: &[f32; 3]) -> &mut Self {
+        self.rotate_by_mat_(&rotation3d_about(angle_in_radians, axis))
+    }
+    /**Rotates axes (but not translation) by a quaternion*/
+    pub fn rotate_by_quat_(&mut self, q: &Quat<f32>) -> &mut Self {
+        self.rotate_by_mat_(&quat::mat3(q))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    fn rand_angle(rng: &mut StdRng) -> f32 {
+        (rng.gen::<f32>() * 2. - 1.) * f32::PI()
+    }
+
+    fn rand_vec<const DIM: usize>(rng: &mut StdRng) -> [f32; DIM] {
+        [0.; DIM].map(|_| rng.gen::<f32>() * 10. - 5.)
+    }
+
+    fn rand_unit3(rng: &mut StdRng) -> [f32; 3] {
+        let v = rand_vec::<3>(rng);
+        v.mul_scalar(1. / dot0(&v, &v).sqrt())
+    }
+
+    fn rand_aff_trans2(rng: &mut StdRng) -> AffTrans<f32, 2> {
+        let scale = 0.5 + rng.gen::<f32>() * 2.;
+        let mut t = AffTrans::new([[scale, 0.]], rand_vec(rng));
+        t.rotate_(&[rand_angle(rng), 0.]);
+        t
+    }
+
+    fn rand_aff_trans3(rng: &mut StdRng) -> AffTrans<f32, 3> {
+        let scale = 0.5 + rng.gen::<f32>() * 2.;
+        let mut t = AffTrans::new([[scale, 0., 0.], [0., scale, 0.]], rand_vec(rng));
+        t.rotate_(&[rand_angle(rng), rand_angle(rng), rand_angle(rng)]);
+        t
+    }
+
+    fn assert_close<const DIM: usize>(a: &[f32; DIM], b: &[f32; DIM]) {
+        assert!(a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() < 1e-3), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test1() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..100 {
+            let t = rand_aff_trans2(&mut rng);
+            let id = t.compose(&t.inverse());
+            let expected = AffTrans::<f32, 2>::identity();
+            assert_close(&id.axis[0], &expected.axis[0]);
+            assert_close(&id.translation, &expected.translation);
+            let p = rand_vec(&mut rng);
+            assert_close(&t.inverse().transform_point(&t.transform_point(&p)), &p);
+        }
+    }
+
+    #[test]
+    fn test2() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..100 {
+            let t = rand_aff_trans3(&mut rng);
+            let id = t.compose(&t.inverse());
+            let expected = AffTrans::<f32, 3>::identity();
+            assert_close(&id.axis[0], &expected.axis[0]);
+            assert_close(&id.axis[1], &expected.axis[1]);
+            assert_close(&id.translation, &expected.translation);
+            let p = rand_vec(&mut rng);
+            assert_close(&t.transform_point(&t.inverse().transform_point(&p)), &p);
+        }
+    }
+
+    #[test]
+    fn test3() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..100 {
+            let a = rand_aff_trans3(&mut rng);
+            let b = rand_aff_trans3(&mut rng);
+            let p = rand_vec(&mut rng);
+            assert_close(&a.compose(&b).transform_point(&p), &a.transform_point(&b.transform_point(&p)));
+            let a = rand_aff_trans2(&mut rng);
+            let b = rand_aff_trans2(&mut rng);
+            let p = rand_vec(&mut rng);
+            assert_close(&a.compose(&b).transform_point(&p), &a.transform_point(&b.transform_point(&p)));
+        }
+    }
+
+    #[test]
+    fn test4() {
+        let mut rng = StdRng::seed_from_u64(4);
+        for _ in 0..100 {
+            let q = quat::about(rand_unit3(&mut rng), rand_angle(&mut rng));
+            let t = AffTrans::from_quat(&q, rand_vec(&mut rng));
+            let q2 = t.quat();
+            // q and -q represent the same rotation
+            let sign = if dot0(&q, &q2) < 0. { -1. } else { 1. };
+            assert_close(&q2.mul_scalar(sign), &q);
+            let v = rand_vec(&mut rng);
+            assert_close(&t.transform_vector(&v), &mul_mat_vec(&quat::mat3(&q), &v));
+        }
+    }
+
+    #[test]
+    fn test5() {
+        let mut t = AffTrans::<f32, 3>::identity();
+        t.rotate_(&[0., 0., f32::FRAC_PI_2()]);
+        assert_close(&t.transform_vector(&[1., 0., 0.]), &[0., 1., 0.]);
+        let mut t = AffTrans::<f32, 3>::identity();
+        t.rotate_about_(f32::FRAC_PI_2(), &[1., 0., 0.]);
+        assert_close(&t.transform_vector(&[0., 1., 0.]), &[0., 0., 1.]);
+        let mut t = AffTrans::<f32, 2>::identity();
+        t.rotate_(&[f32::FRAC_PI_2(), 0.]);
+        assert_close(&t.transform_vector(&[1., 0.]), &[0., 1.]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test6() {
+        let mut rng = StdRng::seed_from_u64(6);
+        let t = rand_aff_trans3(&mut rng);
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(&t, &mut bytes).unwrap();
+        let t2: AffTrans<f32, 3> = ciborium::de::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(t.axis(), t2.axis());
+        assert_eq!(t.translation(), t2.translation());
+        assert!(ciborium::de::from_reader::<AffTrans<f32, 2>, _>(bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test7() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..100 {
+            let a = rand_aff_trans3(&mut rng).scale(&[2.; 3]);
+            let b = rand_aff_trans3(&mut rng).scale(&[0.5; 3]);
+            let p = rand_vec(&mut rng);
+            assert_close(&a.compose(&b).transform_point(&p), &a.transform_point(&b.transform_point(&p)));
+            assert_close(&a.inverse().transform_point(&a.transform_point(&p)), &p);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Only uniform scaling is supported")]
+    fn test8() {
+        AffTrans::<f32, 3>::identity().scale_(&[1., 2., 1.]);
+    }
+}
@@ -53,4 +53,79 @@ pub fn closest_points<F: Float + MulAdd<Output=F>, const DIM: usize>(a: &Line<F,
 
 pub fn pos<F:Float+Copy+MulAdd<Output=F>,const DIM: usize>(a: &Line<F, DIM>, t:F) -> [F; DIM] {
     a[1].mul_scalar_add(t,&a[0])
-}
\ No newline at end of file
+}
+/**Same as `closest_points` but lines are treated as line segments `P+s*U` with `0<=s<=1`. Returns pair `[s,t]` such that
+ `line::pos(a,s)` and `line::pos(b,t)` are the closest points of both segments. If segments are parallel, then
+ `s` is chosen as close to `0` as possible. Segments of zero length (`U=0`) are treated as single points.*/
+pub fn closest_points_on_segments<F: Float + MulAdd<Output=F>, const DIM: usize>(a: &Line<F, DIM>, b: &Line<F, DIM>) -> [F; 2] {
+    let clamp = |x: F| x.max(F::zero()).min(F::one());
+    let u = &a[1];
+    let v = &b[1];
+    let uu = dot0(u, u);
+    let vv = dot0(v, v);
+    let uv = dot0(u, v);
+    let eps = F::epsilon();
+    if uu <= eps && vv <= eps {
+        return [F::zero(), F::zero()];
+    }
+    if uu <= eps {
+        // a is a single point. Project it onto b
+        return [F::zero(), clamp(dot0(v, &a[0].sub(&b[0])) / vv)];
+    }
+    if vv <= eps {
+        // b is a single point. Project it onto a
+        return [clamp(dot0(u, &b[0].sub(&a[0])) / uu), F::zero()];
+    }
+    let denominator = uu * vv - uv * uv;
+    let s = if denominator > eps * uu * vv {
+        clamp(closest_points(a, b)[0])
+    } else {
+        F::zero()
+    };
+    // point on b closest to pos(a,s)
+    let t = dot0(v, &pos(a, s).sub(&b[0])) / vv;
+    let t_clamped = clamp(t);
+    if t == t_clamped {
+        [s, t]
+    } else {
+        // t fell outside of segment b, so the closest point on a must be recomputed for the clamped t
+        let s = clamp(dot0(u, &pos(b, t_clamped).sub(&a[0])) / uu);
+        [s, t_clamped]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test1() {
+        let a = [[0f32, 0., 0.], [2., 0., 0.]];
+        let b = [[1f32, -1., 1.], [0., 2., 0.]];
+        let [s, t] = closest_points_on_segments(&a, &b);
+        assert!((s - 0.5).abs() < 1e-6, "{}", s);
+        assert!((t - 0.5).abs() < 1e-6, "{}", t);
+    }
+
+    #[test]
+    fn test2() {
+        // b lies beyond the end of a, so s must be clamped to 1
+        let a = [[0f32, 0., 0.], [1., 0., 0.]];
+        let b = [[3f32, -1., 0.], [0., 2., 0.]];
+        let [s, t] = closest_points_on_segments(&a, &b);
+        assert_eq!(s, 1.);
+        assert!((t - 0.5).abs() < 1e-6, "{}", t);
+    }
+
+    #[test]
+    fn test3() {
+        // parallel segments
+        let a = [[0f32, 0.], [1., 0.]];
+        let b = [[0.5f32, 1.], [2., 0.]];
+        let [s, t] = closest_points_on_segments(&a, &b);
+        let pa = pos(&a, s);
+        let pb = pos(&b, t);
+        assert!((pb[1] - pa[1] - 1.).abs() < 1e-6);
+        assert!((pb[0] - pa[0]).abs() < 1e-6, "{:?} {:?}", pa, pb);
+    }
+}
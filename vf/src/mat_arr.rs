@@ -1,5 +1,5 @@
 use std::mem::MaybeUninit;
-use std::ops::{Add, Mul, MulAssign};
+use std::ops::{Add, Div, Mul, MulAssign, Neg, Sub};
 use std::ptr;
 use num_traits::{One, Zero};
 use crate::{_append, _concat, _zip_arr, append, map_arr, mat_slice, shape2, shape3, VectorField, VectorFieldMul, VectorFieldMulAssign, xy_z3, xyz_w4, zip_arr};
@@ -190,11 +190,36 @@ pub fn transpose_<A, const W:usize>(mat:&mut [[A;W];W]){
     }
 }
 
+pub fn det2<A: Copy + Mul<Output=A> + Sub<Output=A>>(mat: &[[A; 2]; 2]) -> A {
+    mat[0][0] * mat[1][1] - mat[0][1] * mat[1][0]
+}
+pub fn det3<A: Copy + Mul<Output=A> + Sub<Output=A> + Add<Output=A>>(mat: &[[A; 3]; 3]) -> A {
+    let [[a, b, c], [d, e, f], [g, h, i]] = *mat;
+    a * (e * i - f * h) - b * (d * i - f * g) + c * (d * h - e * g)
+}
+/**Inverse of 2x2 matrix computed from its adjugate. Matrix is assumed to be non-singular*/
+pub fn inv2<A: Copy + Mul<Output=A> + Sub<Output=A> + Div<Output=A> + Neg<Output=A> + One>(mat: &[[A; 2]; 2]) -> [[A; 2]; 2] {
+    let d = A::one() / det2(mat);
+    let [[a, b], [c, e]] = *mat;
+    [[e * d, -b * d], [-c * d, a * d]]
+}
+/**Inverse of 3x3 matrix computed from its adjugate. Matrix is assumed to be non-singular*/
+pub fn inv3<A: Copy + Mul<Output=A> + Sub<Output=A> + Add<Output=A> + Div<Output=A> + One>(mat: &[[A; 3]; 3]) -> [[A; 3]; 3] {
+    let d = A::one() / det3(mat);
+    let [[a, b, c], [e, f, g], [h, i, j]] = *mat;
+    [
+        [(f * j - g * i) * d, (c * i - b * j) * d, (b * g - c * f) * d],
+        [(g * h - e * j) * d, (a * j - c * h) * d, (c * e - a * g) * d],
+        [(e * i - f * h) * d, (b * h - a * i) * d, (a * f - b * e) * d],
+    ]
+}
+
 
 #[cfg(test)]
 mod tests {
     use crate::init::arange2;
     use crate::init_rand::rand2;
+    use crate::dot_arr::dot2;
     use super::*;
 
     #[test]
@@ -206,4 +231,16 @@ mod tests {
             assert_eq!(arr1, arr2);
         }
     }
+
+    #[test]
+    fn test2() {
+        for _ in 0..5 {
+            let m: [[f64; 3]; 3] = rand2();
+            let i = dot2(&m, &inv3(&m));
+            assert!(i.iter().flatten().zip(id::<f64, 3>().iter().flatten()).all(|(a, b)| (a - b).abs() < 1e-6), "{:?}", i);
+            let m: [[f64; 2]; 2] = rand2();
+            let i = dot2(&m, &inv2(&m));
+            assert!(i.iter().flatten().zip(id::<f64, 2>().iter().flatten()).all(|(a, b)| (a - b).abs() < 1e-6), "{:?}", i);
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,227 @@
+use std::fmt::Debug;
+use num_traits::{AsPrimitive, PrimInt};
+use crate::conv_shape::ConvShape;
+use crate::from_usize::FromUsize;
+use crate::sdr::{conv_subregion_indices, Sdr};
+use crate::sdr_dataset::CpuSdrDataset;
+use crate::shape::Shape;
+use crate::top_k::argmax;
+
+/**Naive Bayes classifier of sparse binary codes. For every input index `i` (of SDRs of shape `[height, width, channels]`)
+and every label `l` it keeps a weight `w[i,l]`. At first the weights are plain co-occurrence counts of `i` and `l`
+(see `count`), then `normalise_wrt_labels` turns them into probabilities `P(i|l)` and `log` turns those into
+log-probabilities. An SDR `x` is classified as `argmax_l sum_{i in x} w[i,l]`. With log-probabilities this is the
+naive Bayes decision under uniform class prior, where only the active inputs are taken into account.*/
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Occurrences<Idx> {
+    /**[height, width, channels]*/
+    shape: [Idx; 3],
+    num_classes: usize,
+    /**weights of shape `[height, width, channels, num_classes]`*/
+    occurrences: Vec<f32>,
+    /**number of samples seen for each label*/
+    class_counts: Vec<f32>,
+}
+
+impl<Idx: PrimInt + Debug + AsPrimitive<usize> + FromUsize> Occurrences<Idx> {
+    pub fn new(shape: [Idx; 3], num_classes: usize) -> Self {
+        Self { shape, num_classes, occurrences: vec![0.; shape.size().as_() * num_classes], class_counts: vec![0.; num_classes] }
+    }
+    pub fn shape(&self) -> &[Idx; 3] {
+        &self.shape
+    }
+    pub fn num_classes(&self) -> usize {
+        self.num_classes
+    }
+    /**Weights of shape `[height, width, channels, num_classes]`*/
+    pub fn occurrences(&self) -> &[f32] {
+        &self.occurrences
+    }
+    pub fn occurrences_for_label(&self, label: usize) -> Vec<f32> {
+        self.occurrences.iter().skip(label).step_by(self.num_classes).cloned().collect()
+    }
+    pub fn prob(&self, input_idx: usize, label: usize) -> f32 {
+        self.occurrences[input_idx * self.num_classes + label]
+    }
+    pub fn class_counts(&self) -> &[f32] {
+        &self.class_counts
+    }
+    /**Frequency of each label among the samples that have been counted so far*/
+    pub fn class_prob(&self) -> Vec<f32> {
+        let total: f32 = self.class_counts.iter().sum();
+        self.class_counts.iter().map(|&c| if total > 0. { c / total } else { 0. }).collect()
+    }
+    /**Adds a single labelled sample. `indices` must be of shape `self.shape()`*/
+    pub fn count(&mut self, indices: &[Idx], label: usize) {
+        assert!(label < self.num_classes, "Label {} is out of range for {} classes", label, self.num_classes);
+        for &i in indices {
+            self.occurrences[i.as_() * self.num_classes + label] += 1.;
+        }
+        self.class_counts[label] += 1.;
+    }
+    /**Turns co-occurrence counts into probabilities `P(i|l)`. Laplace smoothing is applied so that no probability
+    is ever zero (or one), which keeps `log` finite.*/
+    pub fn normalise_wrt_labels(&mut self) {
+        let n = self.num_classes;
+        for (i, w) in self.occurrences.iter_mut().enumerate() {
+            *w = (*w + 1.) / (self.class_counts[i % n] + 2.);
+        }
+    }
+    pub fn log(&mut self) {
+        self.occurrences.iter_mut().for_each(|w| *w = w.ln())
+    }
+    /**Averages weights across all columns, so that the classifier no longer depends on the position of input but
+    only on its channel. The shape becomes `[1, 1, channels]`.*/
+    pub fn aggregate_invariant_to_column(&mut self) {
+        let n = self.num_classes;
+        let column_len = self.shape[2].as_() * n;
+        let area = self.shape[0].as_() * self.shape[1].as_();
+        let mut aggregated = vec![0.; column_len];
+        for column in self.occurrences.chunks(column_len) {
+            aggregated.iter_mut().zip(column).for_each(|(a, &w)| *a += w);
+        }
+        aggregated.iter_mut().for_each(|a| *a /= area as f32);
+        self.occurrences = aggregated;
+        self.shape = [Idx::one(), Idx::one(), self.shape[2]];
+    }
+    /**Sums the weights of active inputs for each label. `indices` must be of shape `self.shape()`*/
+    pub fn collect_votes(&self, indices: &[Idx]) -> Vec<f32> {
+        let n = self.num_classes;
+        let mut votes = vec![0.; n];
+        for &i in indices {
+            let i: usize = i.as_();
+            votes.iter_mut().zip(&self.occurrences[i * n..(i + 1) * n]).for_each(|(v, &w)| *v += w);
+        }
+        votes
+    }
+    pub fn classify_indices(&self, indices: &[Idx]) -> usize {
+        argmax(&self.collect_votes(indices))
+    }
+    pub fn classify(&self, sdr: &Sdr<Idx>) -> usize {
+        assert_eq!(sdr.shape(), &self.shape, "SDR doesn't match the shape of classifier");
+        self.classify_indices(sdr.indices())
+    }
+    pub fn batch_classify(&self, dataset: &CpuSdrDataset<Idx>) -> Vec<u32> {
+        assert_eq!(dataset.shape(), &self.shape, "Dataset doesn't match the shape of classifier");
+        dataset.iter().map(|s| self.classify_indices(s) as u32).collect()
+    }
+    /**Convolution whose kernel column is of `self.shape()` and which slides (with stride 1) over inputs of the given shape.
+    This way a classifier whose weights were aggregated with `aggregate_invariant_to_column` can vote separately in every column.*/
+    pub fn column_conv(&self, in_shape: [Idx; 3]) -> ConvShape<Idx> {
+        ConvShape::new_in(in_shape, Idx::one(), [self.shape[0], self.shape[1]], [Idx::one(), Idx::one()])
+    }
+    fn assert_conv(&self, conv: &ConvShape<Idx>) {
+        assert_eq!(conv.kernel_column_shape(), self.shape, "Kernel column of convolution doesn't match the shape of classifier");
+    }
+    /**Every output column of `conv` classifies the part of `indices` (of shape `conv.in_shape()`) that lies within its
+    receptive field. A column abstains (`None`) if it has no active inputs or if its best label doesn't stand out
+    above the mean vote by at least `min_deviation_from_mean`. Results are laid out according to `conv.out_grid()`.*/
+    pub fn classify_indices_per_column(&self, indices: &[Idx], conv: &ConvShape<Idx>, min_deviation_from_mean: f32) -> Vec<Option<usize>> {
+        self.assert_conv(conv);
+        let out_grid = *conv.out_grid();
+        let mut patch = vec![];
+        (0..out_grid.size().as_()).map(|column| {
+            patch.clear();
+            conv_subregion_indices(indices, conv, &out_grid.pos(Idx::from_usize(column)), &mut patch);
+            if patch.is_empty() {
+                return None;
+            }
+            let votes = self.collect_votes(&patch);
+            let mean = votes.iter().sum::<f32>() / votes.len() as f32;
+            let best = argmax(&votes);
+            if votes[best] - mean >= min_deviation_from_mean { Some(best) } else { None }
+        }).collect()
+    }
+    pub fn classify_per_column(&self, sdr: &Sdr<Idx>, conv: &ConvShape<Idx>, min_deviation_from_mean: f32) -> Vec<Option<usize>> {
+        assert_eq!(sdr.shape(), conv.in_shape(), "SDR doesn't match input shape of convolution");
+        self.classify_indices_per_column(sdr.indices(), conv, min_deviation_from_mean)
+    }
+    /**For each label, the number of columns that voted for it (see `classify_per_column`)*/
+    pub fn count_votes_per_label(&self, indices: &[Idx], conv: &ConvShape<Idx>, min_deviation_from_mean: f32) -> Vec<u32> {
+        let mut counts = vec![0; self.num_classes];
+        self.classify_indices_per_column(indices, conv, min_deviation_from_mean).into_iter().flatten().for_each(|l| counts[l] += 1);
+        counts
+    }
+    /**The label that won in the largest number of columns*/
+    pub fn classify_with_most_votes(&self, sdr: &Sdr<Idx>, conv: &ConvShape<Idx>, min_deviation_from_mean: f32) -> usize {
+        assert_eq!(sdr.shape(), conv.in_shape(), "SDR doesn't match input shape of convolution");
+        argmax(&self.count_votes_per_label(sdr.indices(), conv, min_deviation_from_mean))
+    }
+    pub fn batch_classify_with_most_votes(&self, dataset: &CpuSdrDataset<Idx>, conv: &ConvShape<Idx>, min_deviation_from_mean: f32) -> Vec<u32> {
+        assert_eq!(dataset.shape(), conv.in_shape(), "Dataset doesn't match input shape of convolution");
+        dataset.iter().map(|s| argmax(&self.count_votes_per_label(s, conv, min_deviation_from_mean)) as u32).collect()
+    }
+}
+
+impl<Idx: PrimInt + Debug + AsPrimitive<usize> + FromUsize> CpuSdrDataset<Idx> {
+    /**Co-occurrence counts of every input index and label. `labels[i]` is the label of `i`-th sample*/
+    pub fn count_per_label(&self, labels: &[usize], num_classes: usize) -> Occurrences<Idx> {
+        assert_eq!(labels.len(), self.len(), "Number of labels doesn't match number of samples");
+        let mut o = Occurrences::new(*self.shape(), num_classes);
+        for (sample, &label) in self.iter().zip(labels) {
+            o.count(sample, label);
+        }
+        o
+    }
+    /**Counts co-occurrences and turns them into log-probabilities `ln P(i|l)`. If `invariant_to_column` then the
+    weights are shared by all columns (see `Occurrences::aggregate_invariant_to_column`).*/
+    pub fn fit_naive_bayes(&self, labels: &[usize], num_classes: usize, invariant_to_column: bool) -> Occurrences<Idx> {
+        let mut o = self.count_per_label(labels, num_classes);
+        if invariant_to_column {
+            o.aggregate_invariant_to_column();
+        }
+        o.normalise_wrt_labels();
+        o.log();
+        o
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn test1() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let shape = [2u32, 2, 3];
+        // label l activates channel l in every column, plus one random noisy index
+        let mut d = CpuSdrDataset::new(shape);
+        let mut labels = vec![];
+        for _ in 0..60 {
+            let l = rng.gen_range(0..3);
+            let noise = rng.gen_range(0..12);
+            d.push(&Sdr::from_indices(shape, (0..4).map(|c| c * 3 + l as u32).chain([noise]).collect()));
+            labels.push(l);
+        }
+        let o = d.fit_naive_bayes(&labels, 3, false);
+        assert_eq!(o.batch_classify(&d), labels.iter().map(|&l| l as u32).collect::<Vec<_>>());
+        let inv = d.fit_naive_bayes(&labels, 3, true);
+        assert_eq!(inv.shape(), &[1, 1, 3]);
+        let conv = inv.column_conv(shape);
+        for (s, &l) in d.iter().zip(&labels) {
+            let sdr = Sdr::from_indices(shape, s.to_vec());
+            assert_eq!(inv.classify_with_most_votes(&sdr, &conv, 0.), l);
+            let per_column = inv.classify_per_column(&sdr, &conv, 0.);
+            assert_eq!(per_column.len(), 4);
+            assert!(per_column.iter().filter(|&&c| c == Some(l)).count() >= 3);
+        }
+    }
+
+    #[test]
+    fn test2() {
+        let mut o = Occurrences::new([1u32, 2, 2], 2);
+        o.count(&[0, 3], 0);
+        o.count(&[0], 0);
+        o.count(&[1, 2], 1);
+        assert_eq!(o.class_prob(), vec![2. / 3., 1. / 3.]);
+        assert_eq!(o.occurrences_for_label(0), vec![2., 0., 0., 1.]);
+        o.aggregate_invariant_to_column();
+        assert_eq!(o.occurrences(), &[1., 0.5, 0.5, 0.5]);
+        o.normalise_wrt_labels();
+        assert_eq!(o.prob(0, 0), 0.5);
+        assert_eq!(o.prob(1, 1), 0.5);
+    }
+}
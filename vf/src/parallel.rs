@@ -0,0 +1,68 @@
+use std::thread;
+
+/**Number of threads used by the parallel routines. Falls back to 1 if it cannot be determined.*/
+pub fn num_threads() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+/**Below this many elements of destination, spawning threads costs more than it saves*/
+pub const MIN_CHUNK_LEN: usize = 1 << 12;
+
+/**Splits destination into contiguous chunks and processes each of them on a separate thread.
+The length of every chunk is a multiple of granularity (so that units of work, like minicolumns,
+are never split between threads). f receives the offset of the chunk within destination and the chunk itself.
+Chunks are never shorter than (roughly) MIN_CHUNK_LEN, so small destinations are processed on the calling thread.*/
+pub fn for_each_chunk_par<T: Send>(destination: &mut [T], granularity: usize, f: impl Fn(usize, &mut [T]) + Sync) {
+    for_each_chunk_par_with_min_len(destination, granularity, MIN_CHUNK_LEN, f)
+}
+
+/**Same as for_each_chunk_par but with a custom minimum chunk length. If there is only a single unit of work,
+a single thread or destination is shorter than twice min_chunk_len, everything runs on the calling thread.*/
+pub fn for_each_chunk_par_with_min_len<T: Send>(destination: &mut [T], granularity: usize, min_chunk_len: usize, f: impl Fn(usize, &mut [T]) + Sync) {
+    assert!(granularity > 0, "Granularity must be positive");
+    assert_eq!(destination.len() % granularity, 0, "Length {} is not a multiple of {}", destination.len(), granularity);
+    let units = destination.len() / granularity;
+    let threads = num_threads().min(units).min(destination.len() / min_chunk_len.max(1));
+    if threads <= 1 {
+        f(0, destination);
+        return;
+    }
+    let chunk_len = (units + threads - 1) / threads * granularity;
+    let f = &f;
+    thread::scope(|scope| {
+        for (i, chunk) in destination.chunks_mut(chunk_len).enumerate() {
+            scope.spawn(move || f(i * chunk_len, chunk));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test1() {
+        for (len, granularity) in [(0, 3), (3, 3), (7, 1), (300, 5), (1000, 10)] {
+            let mut v = vec![0usize; len];
+            for_each_chunk_par_with_min_len(&mut v, granularity, 1, |offset, chunk| {
+                assert_eq!(offset % granularity, 0);
+                assert_eq!(chunk.len() % granularity, 0);
+                for (i, e) in chunk.iter_mut().enumerate() {
+                    *e += offset + i;
+                }
+            });
+            assert!(v.iter().cloned().eq(0..len));
+        }
+    }
+
+    #[test]
+    fn test2() {
+        // small destinations never leave the calling thread
+        let caller = thread::current().id();
+        let mut v = vec![0u8; MIN_CHUNK_LEN];
+        for_each_chunk_par(&mut v, 1, |offset, chunk| {
+            assert_eq!(thread::current().id(), caller);
+            assert_eq!((offset, chunk.len()), (0, MIN_CHUNK_LEN));
+        });
+    }
+}
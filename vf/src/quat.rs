@@ -44,6 +44,27 @@ pub fn mat3<F: Float + Copy>(q: &Quat<F>) -> [[F; 3]; 3] {
     ]
 }
 
+/**Rotation matrix to quaternion (Shepperd's method). Assumes the matrix is orthonormal*/
+pub fn from_mat3<F: Float>(m: &[[F; 3]; 3]) -> Quat<F> {
+    let o = F::one();
+    let q = o / (o + o + o + o);
+    let [[m00, m01, m02], [m10, m11, m12], [m20, m21, m22]] = *m;
+    let trace = m00 + m11 + m22;
+    if trace > F::zero() {
+        let s = (trace + o).sqrt() * (o + o);
+        [(m21 - m12) / s, (m02 - m20) / s, (m10 - m01) / s, q * s]
+    } else if m00 > m11 && m00 > m22 {
+        let s = (o + m00 - m11 - m22).sqrt() * (o + o);
+        [q * s, (m01 + m10) / s, (m02 + m20) / s, (m21 - m12) / s]
+    } else if m11 > m22 {
+        let s = (o + m11 - m00 - m22).sqrt() * (o + o);
+        [(m01 + m10) / s, q * s, (m12 + m21) / s, (m02 - m20) / s]
+    } else {
+        let s = (o + m22 - m00 - m11).sqrt() * (o + o);
+        [(m02 + m20) / s, (m12 + m21) / s, q * s, (m10 - m01) / s]
+    }
+}
+
 /**Quaternion to rotation matrix*/
 pub fn mat4<F: Float + Copy>(q: &Quat<F>) -> [[F; 4]; 4] {
     mat3_to_mat4(mat3(q))
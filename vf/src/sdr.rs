@@ -0,0 +1,204 @@
+use std::fmt::Debug;
+use std::ops::Range;
+use num_traits::{AsPrimitive, PrimInt};
+use crate::conv_shape::ConvShape;
+use crate::from_usize::FromUsize;
+use crate::shape::Shape;
+use crate::xyzw::{xy3, xy_z3};
+use crate::{batch_dense_to_sparse, dense_to_sparse, vec_range, SetIntersection, SetOverlap, SetSparseIndexArray, SetSubtract, SetUnion, VectorFieldPartialOrd};
+
+/**Sparse binary tensor of shape `[height, width, channels]`. Only the indices of ones are stored. They are always
+kept sorted and free of duplicates (normalized), so that all set operations run in linear time.*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sdr<Idx> {
+    /**[height, width, channels]*/
+    shape: [Idx; 3],
+    indices: Vec<Idx>,
+}
+
+impl<Idx: PrimInt + Debug + AsPrimitive<usize> + FromUsize> Sdr<Idx> {
+    /**Empty SDR*/
+    pub fn new(shape: [Idx; 3]) -> Self {
+        Self { shape, indices: vec![] }
+    }
+    /**Indices can be given in any order and may contain duplicates. Panics if any index exceeds the volume of the shape*/
+    pub fn from_indices(shape: [Idx; 3], mut indices: Vec<Idx>) -> Self {
+        indices.normalize();
+        if let Some(&last) = indices.last() {
+            assert!(last < shape.size(), "Index {:?} is out of bounds for shape {:?}", last, shape);
+        }
+        Self { shape, indices }
+    }
+    /**`bools` is a dense tensor of the given shape*/
+    pub fn from_dense(shape: [Idx; 3], bools: &[bool]) -> Self {
+        assert_eq!(bools.len(), shape.size().as_(), "Dense tensor doesn't match shape {:?}", shape);
+        Self { shape, indices: dense_to_sparse(bools) }
+    }
+    /**`bools` is a batch of dense tensors, each of the given shape, laid out continuously in memory*/
+    pub fn batch_from_dense(shape: [Idx; 3], bools: &[bool]) -> Vec<Self> {
+        let (indices, offsets) = batch_dense_to_sparse::<Idx>(shape.size().as_(), bools);
+        let mut from = 0;
+        offsets.into_iter().map(|to| {
+            let sdr = Self { shape, indices: indices[from..to].to_vec() };
+            from = to;
+            sdr
+        }).collect()
+    }
+    pub fn shape(&self) -> &[Idx; 3] {
+        &self.shape
+    }
+    pub fn volume(&self) -> Idx {
+        self.shape.size()
+    }
+    pub fn indices(&self) -> &[Idx] {
+        &self.indices
+    }
+    pub fn into_indices(self) -> Vec<Idx> {
+        self.indices
+    }
+    /**Number of ones*/
+    pub fn card(&self) -> usize {
+        self.indices.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+    pub fn contains(&self, idx: Idx) -> bool {
+        self.indices.binary_search(&idx).is_ok()
+    }
+    pub fn to_dense(&self) -> Vec<bool> {
+        let mut bools = vec![false; self.volume().as_()];
+        self.indices.iter().for_each(|&i| bools[i.as_()] = true);
+        bools
+    }
+    fn assert_same_shape(&self, other: &Self) {
+        assert_eq!(self.shape, other.shape, "SDRs have different shapes");
+    }
+    pub fn union(&self, other: &Self) -> Self {
+        self.assert_same_shape(other);
+        Self { shape: self.shape, indices: self.indices.union(&other.indices) }
+    }
+    pub fn intersection(&self, other: &Self) -> Self {
+        self.assert_same_shape(other);
+        Self { shape: self.shape, indices: self.indices.intersection(&other.indices) }
+    }
+    /**Removes all elements that are present in `other`*/
+    pub fn subtract(&mut self, other: &Self) {
+        self.assert_same_shape(other);
+        self.indices.subtract(&other.indices)
+    }
+    /**Number of elements shared by both SDRs*/
+    pub fn overlap(&self, other: &Self) -> usize {
+        self.assert_same_shape(other);
+        self.indices.overlap(&other.indices)
+    }
+    /**Moves every element by `offset` (along height, width and channels respectively).
+    Elements that end up outside of the shape are dropped*/
+    pub fn shift(&self, offset: &[isize; 3]) -> Self {
+        let shape = self.shape.map(|d| d.as_() as isize);
+        // moving all positions by the same offset preserves their order
+        let indices = self.indices.iter().filter_map(|&i| {
+            let pos = self.shape.pos(i);
+            let mut shifted = [0isize; 3];
+            for d in 0..3 {
+                shifted[d] = pos[d].as_() as isize + offset[d];
+            }
+            if shifted.all_ge_scalar(0) && shifted.all_lt(&shape) {
+                Some(Idx::from_usize(shape.idx(&shifted) as usize))
+            } else {
+                None
+            }
+        }).collect();
+        Self { shape: self.shape, indices }
+    }
+    /**Crops the SDR to `range`. The resulting SDR has shape `range.end-range.start`*/
+    pub fn subregion(&self, range: &Range<[Idx; 3]>) -> Self {
+        assert!(range.start.all_le(&range.end) && range.end.all_le(&self.shape), "Subregion {:?} is out of bounds for shape {:?}", range, self.shape);
+        let indices = self.indices.iter().filter_map(|&i| vec_range::translate(range, &self.shape.pos(i))).collect();
+        Self { shape: vec_range::shape(range), indices }
+    }
+    /**Extracts the patch of input seen by the output column at `output_column_pos`. The SDR must be of shape
+    `conv.in_shape()` and the result has shape `conv.kernel_column_shape()`*/
+    pub fn conv_subregion(&self, conv: &ConvShape<Idx>, output_column_pos: &[Idx; 2]) -> Self {
+        assert_eq!(&self.shape, conv.in_shape(), "SDR doesn't match input shape of convolution");
+        let mut indices = vec![];
+        conv_subregion_indices(&self.indices, conv, output_column_pos, &mut indices);
+        Self { shape: conv.kernel_column_shape(), indices }
+    }
+}
+
+/**Appends to `output` those of `indices` (of shape `conv.in_shape()`) that fall within the receptive field of
+the output column at `output_column_pos`. They are translated to the kernel column of shape
+`conv.kernel_column_shape()`. Inputs that fall between the weights of a dilated kernel are skipped. If `indices` are normalized then so are the appended ones.*/
+pub fn conv_subregion_indices<Idx: PrimInt + Debug + AsPrimitive<usize> + FromUsize>(indices: &[Idx], conv: &ConvShape<Idx>, output_column_pos: &[Idx; 2], output: &mut Vec<Idx>) {
+    let in_shape = conv.in_shape();
+    let kernel_column = conv.kernel_column_shape();
+    output.extend(indices.iter().filter_map(|&i| {
+        let pos = in_shape.pos(i);
+        conv.kernel_pos(xy3(&pos), output_column_pos).map(|kernel_pos| kernel_column.idx(&xy_z3(kernel_pos, pos[2])))
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init_rand::InitRandWithCapacity;
+
+    #[test]
+    fn test1() {
+        let a = Sdr::from_indices([2, 3, 4], vec![7u32, 1, 5, 1, 23]);
+        assert_eq!(a.indices(), &[1, 5, 7, 23]);
+        let b = Sdr::from_indices([2, 3, 4], vec![5, 6, 23]);
+        assert_eq!(a.union(&b).indices(), &[1, 5, 6, 7, 23]);
+        assert_eq!(a.intersection(&b).indices(), &[5, 23]);
+        assert_eq!(a.overlap(&b), 2);
+        let mut c = a.clone();
+        c.subtract(&b);
+        assert_eq!(c.indices(), &[1, 7]);
+        assert_eq!(Sdr::from_dense([2, 3, 4], &a.to_dense()), a);
+    }
+
+    #[test]
+    fn test2() {
+        let shape = [4u32, 5, 2];
+        let bools = Vec::<bool>::rand(shape.size() as usize * 3);
+        let batch = Sdr::batch_from_dense(shape, &bools);
+        assert_eq!(batch.len(), 3);
+        for (sdr, dense) in batch.iter().zip(bools.chunks(shape.size() as usize)) {
+            assert_eq!(sdr.to_dense(), dense);
+        }
+    }
+
+    #[test]
+    fn test3() {
+        let shape = [4u32, 5, 2];
+        let a = Sdr::from_dense(shape, &Vec::<bool>::rand(shape.size() as usize));
+        let shifted = a.shift(&[1, -2, 0]);
+        for i in 0..shape.size() {
+            let [y, x, c] = shape.pos(i);
+            let src = [y as isize - 1, x as isize + 2, c as isize];
+            let expected = src[0] >= 0 && src[1] < 5 && a.contains(shape.idx(&src.map(|v| v as u32)));
+            assert_eq!(shifted.contains(i), expected);
+        }
+        let sub = a.subregion(&([1, 2, 0]..[3, 5, 2]));
+        assert_eq!(sub.shape(), &[2, 3, 2]);
+        for i in 0..sub.volume() {
+            let [y, x, c] = sub.shape().pos(i);
+            assert_eq!(sub.contains(i), a.contains(shape.idx(&[y + 1, x + 2, c])));
+        }
+    }
+
+    #[test]
+    fn test4() {
+        let conv = ConvShape::new_in([7u32, 9, 2], 4, [3, 2], [2, 1]);
+        let a = Sdr::from_dense(*conv.in_shape(), &Vec::<bool>::rand(conv.in_volume() as usize));
+        for y in 0..conv.out_height() {
+            for x in 0..conv.out_width() {
+                let r = conv.in_range(&[y, x]);
+                let expected = a.subregion(&([r.start[0], r.start[1], 0]..[r.end[0], r.end[1], 2]));
+                assert_eq!(a.conv_subregion(&conv, &[y, x]), expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use std::fmt::Debug;
+use std::ops::{Index, Range};
+use num_traits::{AsPrimitive, PrimInt};
+use rand::Rng;
+use crate::conv_shape::{ConvShape, grid};
+use crate::from_usize::FromUsize;
+use crate::sdr::{conv_subregion_indices, Sdr};
+use crate::shape::Shape;
+use crate::{batch_dense_to_sparse, conv, vec_range, VectorFieldAdd, VectorFieldPartialOrd, VectorFieldSub};
+
+/**A collection of SDRs, all of the same shape `[height, width, channels]`. Instead of keeping a separate vector
+for every sample, all indices are stored back to back in one buffer (much like the CSR sparse matrix layout).
+`offsets[i]` marks the end of `i`-th sample, which begins where the previous one ended.*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CpuSdrDataset<Idx> {
+    /**[height, width, channels]*/
+    shape: [Idx; 3],
+    indices: Vec<Idx>,
+    offsets: Vec<usize>,
+}
+
+/**Randomly chosen output columns of some convolution, applied to randomly chosen samples of a dataset.
+Every entry is of the form `[y, x, sample_idx]`.*/
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubregionIndices<Idx> {
+    /**shape of the dataset from which samples were drawn*/
+    shape: [Idx; 3],
+    dataset_len: usize,
+    indices: Vec<[Idx; 3]>,
+}
+
+impl<Idx: Copy> SubregionIndices<Idx> {
+    pub fn shape(&self) -> &[Idx; 3] {
+        &self.shape
+    }
+    pub fn dataset_len(&self) -> usize {
+        self.dataset_len
+    }
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+    pub fn iter(&self) -> std::slice::Iter<'_, [Idx; 3]> {
+        self.indices.iter()
+    }
+    pub fn as_slice(&self) -> &[[Idx; 3]] {
+        &self.indices
+    }
+}
+
+impl<Idx> Index<usize> for SubregionIndices<Idx> {
+    type Output = [Idx; 3];
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.indices[index]
+    }
+}
+
+impl<Idx: PrimInt + Debug + AsPrimitive<usize> + FromUsize> CpuSdrDataset<Idx> {
+    pub fn new(shape: [Idx; 3]) -> Self {
+        Self { shape, indices: vec![], offsets: vec![] }
+    }
+    pub fn with_capacity(samples: usize, shape: [Idx; 3]) -> Self {
+        Self { shape, indices: vec![], offsets: Vec::with_capacity(samples) }
+    }
+    /**`bools` is a batch of dense tensors, each of the given shape, laid out continuously in memory*/
+    pub fn from_dense(shape: [Idx; 3], bools: &[bool]) -> Self {
+        let (indices, offsets) = batch_dense_to_sparse(shape.size().as_(), bools);
+        Self { shape, indices, offsets }
+    }
+    pub fn shape(&self) -> &[Idx; 3] {
+        &self.shape
+    }
+    pub fn volume(&self) -> Idx {
+        self.shape.size()
+    }
+    /**Number of samples*/
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+    /**Total number of ones across all samples*/
+    pub fn total_card(&self) -> usize {
+        self.indices.len()
+    }
+    fn range(&self, idx: usize) -> Range<usize> {
+        let from = if idx == 0 { 0 } else { self.offsets[idx - 1] };
+        from..self.offsets[idx]
+    }
+    /**Sorted indices of ones in `idx`-th sample*/
+    pub fn get(&self, idx: usize) -> &[Idx] {
+        &self.indices[self.range(idx)]
+    }
+    pub fn get_sdr(&self, idx: usize) -> Sdr<Idx> {
+        Sdr::from_indices(self.shape, self.get(idx).to_vec())
+    }
+    pub fn iter(&self) -> impl Iterator<Item=&[Idx]> + '_ {
+        (0..self.len()).map(move |i| self.get(i))
+    }
+    /**`indices` must be normalized (sorted and unique) and lie within the volume of `shape`*/
+    pub fn push_indices(&mut self, indices: &[Idx]) {
+        assert!(indices.windows(2).all(|w| w[0] < w[1]), "Indices {:?} are not normalized", indices);
+        assert!(indices.last().map(|&l| l < self.volume()).unwrap_or(true), "Index {:?} is out of bounds for shape {:?}", indices.last(), self.shape);
+        self.indices.extend_from_slice(indices);
+        self.offsets.push(self.indices.len());
+    }
+    pub fn push(&mut self, sdr: &Sdr<Idx>) {
+        assert_eq!(sdr.shape(), &self.shape, "SDR doesn't match the shape of dataset");
+        self.push_indices(sdr.indices())
+    }
+    pub fn extend<'a>(&mut self, sdrs: impl IntoIterator<Item=&'a Sdr<Idx>>) where Idx: 'a {
+        sdrs.into_iter().for_each(|sdr| self.push(sdr))
+    }
+    pub fn pop(&mut self) -> Option<Sdr<Idx>> {
+        self.offsets.pop()?;
+        let from = self.offsets.last().cloned().unwrap_or(0);
+        let indices = self.indices.split_off(from);
+        Some(Sdr::from_indices(self.shape, indices))
+    }
+    /**Replaces `idx`-th sample. All subsequent samples need to be moved, so this runs in linear time*/
+    pub fn set(&mut self, idx: usize, sdr: &Sdr<Idx>) {
+        assert_eq!(sdr.shape(), &self.shape, "SDR doesn't match the shape of dataset");
+        let r = self.range(idx);
+        let diff = sdr.card() as isize - r.len() as isize;
+        self.indices.splice(r, sdr.indices().iter().cloned());
+        self.offsets[idx..].iter_mut().for_each(|o| *o = (*o as isize + diff) as usize);
+    }
+    pub fn clear(&mut self) {
+        self.indices.clear();
+        self.offsets.clear();
+    }
+    /**Random sample or `None` if dataset is empty*/
+    pub fn rand(&self, rng: &mut impl Rng) -> Option<&[Idx]> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.get(rng.gen_range(0..self.len())))
+        }
+    }
+    /**Copy of samples within the given range. Panics if the range is out of bounds.*/
+    pub fn subdataset(&self, range: Range<usize>) -> Self {
+        assert!(range.start <= range.end && range.end <= self.len(), "Range {:?} is out of bounds for dataset of length {}", range, self.len());
+        let from = if range.start == 0 { 0 } else { self.offsets[range.start - 1] };
+        let to = if range.end == 0 { 0 } else { self.offsets[range.end - 1] };
+        let offsets = self.offsets[range].iter().map(|&o| o - from).collect();
+        Self { shape: self.shape, indices: self.indices[from..to].to_vec(), offsets }
+    }
+    /**Removes (in-place) all samples that have fewer than `min_cardinality` ones*/
+    pub fn filter_by_cardinality_threshold(&mut self, min_cardinality: usize) {
+        let mut indices = Vec::with_capacity(self.indices.len());
+        let mut offsets = Vec::with_capacity(self.offsets.len());
+        for sample in self.iter() {
+            if sample.len() >= min_cardinality {
+                indices.extend_from_slice(sample);
+                offsets.push(indices.len());
+            }
+        }
+        self.indices = indices;
+        self.offsets = offsets;
+    }
+    /**For every input index, the number of samples in which it is active. The result has volume of `shape`.*/
+    pub fn count(&self) -> Vec<u32> {
+        let mut counts = vec![0; self.volume().as_()];
+        self.indices.iter().for_each(|&i| counts[i.as_()] += 1);
+        counts
+    }
+    /**Dense tensor of `idx`-th sample. All ones are replaced with `one`*/
+    pub fn to_dense<T: Copy + Default>(&self, idx: usize, one: T) -> Vec<T> {
+        let mut dense = vec![T::default(); self.volume().as_()];
+        self.get(idx).iter().for_each(|&i| dense[i.as_()] = one);
+        dense
+    }
+    /**Dense tensor of shape `[len, height, width, channels]`*/
+    pub fn batch_to_dense<T: Copy + Default>(&self, one: T) -> Vec<T> {
+        let v: usize = self.volume().as_();
+        let mut dense = vec![T::default(); v * self.len()];
+        for (sample, out) in self.iter().zip(dense.chunks_mut(v)) {
+            sample.iter().for_each(|&i| out[i.as_()] = one);
+        }
+        dense
+    }
+    /**Picks `number_of_samples` random output columns (within `out_grid`) of random samples. If there is nothing
+    to pick from (the dataset is empty or `out_grid` has no columns), the result is empty.*/
+    pub fn gen_rand_conv_subregion_indices(&self, out_grid: [Idx; 2], number_of_samples: usize, rng: &mut impl Rng) -> SubregionIndices<Idx> {
+        let number_of_samples = if self.is_empty() || out_grid.iter().any(|d| d.is_zero()) { 0 } else { number_of_samples };
+        let indices = (0..number_of_samples).map(|_| {
+            let y = rng.gen_range(0..out_grid[0].as_());
+            let x = rng.gen_range(0..out_grid[1].as_());
+            let s = rng.gen_range(0..self.len());
+            [y, x, s].map(Idx::from_usize)
+        }).collect();
+        SubregionIndices { shape: self.shape, dataset_len: self.len(), indices }
+    }
+    /**Same as `gen_rand_conv_subregion_indices` but the output grid is derived from convolution of the dataset with
+    given kernel and stride*/
+    pub fn gen_rand_conv_subregion_indices_with_ker(&self, kernel: &[Idx; 2], stride: &[Idx; 2], number_of_samples: usize, rng: &mut impl Rng) -> SubregionIndices<Idx> {
+        let out_grid = conv::out_size(grid(&self.shape), stride, kernel);
+        self.gen_rand_conv_subregion_indices(out_grid, number_of_samples, rng)
+    }
+    pub fn gen_rand_conv_subregion_indices_with_conv(&self, conv: &ConvShape<Idx>, number_of_samples: usize, rng: &mut impl Rng) -> SubregionIndices<Idx> {
+        assert_eq!(conv.in_shape(), &self.shape, "Dataset doesn't match input shape of convolution");
+        self.gen_rand_conv_subregion_indices(*conv.out_grid(), number_of_samples, rng)
+    }
+    /**For every entry of `indices`, appends the input patch (of shape `conv.kernel_column_shape()`, which must be the
+    shape of this dataset) seen by the chosen output column in the chosen sample of `original`.*/
+    pub fn extend_from_conv_subregion_indices(&mut self, conv: &ConvShape<Idx>, indices: &SubregionIndices<Idx>, original: &Self) {
+        assert_eq!(conv.kernel_column_shape(), self.shape, "Dataset doesn't match kernel column shape of convolution");
+        assert_eq!(conv.in_shape(), &original.shape, "Original dataset doesn't match input shape of convolution");
+        assert_eq!(indices.dataset_len(), original.len(), "Subregion indices were generated for a different dataset");
+        self.offsets.reserve(indices.len());
+        for &[y, x, s] in indices.iter() {
+            conv_subregion_indices(original.get(s.as_()), conv, &[y, x], &mut self.indices);
+            self.offsets.push(self.indices.len());
+        }
+    }
+    /**New dataset of input patches. See `extend_from_conv_subregion_indices`*/
+    pub fn conv_subregion_indices(&self, conv: &ConvShape<Idx>, indices: &SubregionIndices<Idx>) -> Self {
+        let mut d = Self::with_capacity(indices.len(), conv.kernel_column_shape());
+        d.extend_from_conv_subregion_indices(conv, indices, self);
+        d
+    }
+    pub fn conv_subregion_indices_with_ker(&self, kernel: [Idx; 2], stride: [Idx; 2], indices: &SubregionIndices<Idx>) -> Self {
+        let conv = ConvShape::new_in(self.shape, Idx::one(), kernel, stride);
+        self.conv_subregion_indices(&conv, indices)
+    }
+    /**Appends `number_of_samples` input patches, seen by random output columns in random samples of `original`*/
+    pub fn extend_from_conv_rand_subregion(&mut self, conv: &ConvShape<Idx>, number_of_samples: usize, original: &Self, rng: &mut impl Rng) {
+        let indices = original.gen_rand_conv_subregion_indices_with_conv(conv, number_of_samples, rng);
+        self.extend_from_conv_subregion_indices(conv, &indices, original)
+    }
+    /**Appends `number_of_samples` random crops (of shape of this dataset) taken from random samples of `original`*/
+    pub fn extend_from_rand_subregions(&mut self, number_of_samples: usize, original: &Self, rng: &mut impl Rng) {
+        assert!(self.shape.all_le(&original.shape), "Subregion {:?} is larger than the original shape {:?}", self.shape, original.shape);
+        assert!(!original.is_empty(), "Dataset is empty");
+        let margin = original.shape.sub(&self.shape).add_scalar(Idx::one());
+        self.offsets.reserve(number_of_samples);
+        for _ in 0..number_of_samples {
+            let sample = original.rand(rng).unwrap();
+            let start = margin.map(|m| Idx::from_usize(rng.gen_range(0..m.as_())));
+            let r = start..start.add(&self.shape);
+            self.indices.extend(sample.iter().filter_map(|&i| vec_range::translate(&r, &original.shape.pos(i))));
+            self.offsets.push(self.indices.len());
+        }
+    }
+    /**New dataset of `number_of_samples` random crops of the given shape. See `extend_from_rand_subregions`*/
+    pub fn gen_rand_subregions(&self, subregion: [Idx; 3], number_of_samples: usize, rng: &mut impl Rng) -> Self {
+        let mut d = Self::with_capacity(number_of_samples, subregion);
+        d.extend_from_rand_subregions(number_of_samples, self, rng);
+        d
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init_rand::InitRandWithCapacity;
+
+    #[test]
+    fn test1() {
+        let shape = [3u32, 4, 2];
+        let bools = Vec::<bool>::rand(shape.size() as usize * 5);
+        let mut d = CpuSdrDataset::from_dense(shape, &bools);
+        assert_eq!(d.len(), 5);
+        assert_eq!(d.batch_to_dense(true), bools);
+        let sdrs: Vec<Sdr<u32>> = (0..d.len()).map(|i| d.get_sdr(i)).collect();
+        let mut e = CpuSdrDataset::new(shape);
+        e.extend(&sdrs);
+        assert_eq!(d, e);
+        for (i, sample) in d.iter().enumerate() {
+            assert_eq!(d.to_dense(i, true), &bools[i * 24..(i + 1) * 24]);
+            assert_eq!(sample, sdrs[i].indices());
+        }
+        let sub = d.subdataset(1..4);
+        assert_eq!(sub.len(), 3);
+        assert!((0..3).all(|i| sub.get(i) == d.get(i + 1)));
+        let count = d.count();
+        assert!((0..24).all(|i| count[i] == (0..5).filter(|&s| bools[s * 24 + i]).count() as u32));
+        d.set(2, &Sdr::from_indices(shape, vec![0, 1, 2, 3, 4, 5, 6]));
+        assert_eq!(d.get(2), &[0, 1, 2, 3, 4, 5, 6]);
+        assert_eq!(d.get(4), sdrs[4].indices());
+        d.filter_by_cardinality_threshold(7);
+        assert!(d.iter().all(|s| s.len() >= 7));
+        assert!(d.iter().any(|s| s == &[0, 1, 2, 3, 4, 5, 6]));
+        let len = d.len();
+        let last = d.get(len - 1).to_vec();
+        assert_eq!(d.pop().map(|s| s.into_indices()), Some(last));
+        assert_eq!(d.len(), len - 1);
+    }
+
+    #[test]
+    fn test2() {
+        let conv = ConvShape::new_in([9u32, 8, 3], 2, [3, 2], [2, 2]);
+        let original = CpuSdrDataset::from_dense(*conv.in_shape(), &Vec::<bool>::rand(conv.in_volume() as usize * 6));
+        let mut rng = rand::thread_rng();
+        let indices = original.gen_rand_conv_subregion_indices_with_ker(conv.kernel(), conv.stride(), 32, &mut rng);
+        assert_eq!(indices.len(), 32);
+        assert!(indices.iter().all(|&[y, x, s]| [y, x].all_lt(conv.out_grid()) && (s as usize) < original.len()));
+        let patches = original.conv_subregion_indices(&conv, &indices);
+        assert_eq!(patches.shape(), &conv.kernel_column_shape());
+        for (patch, &[y, x, s]) in patches.iter().zip(indices.iter()) {
+            assert_eq!(patch, original.get_sdr(s as usize).conv_subregion(&conv, &[y, x]).indices());
+        }
+        assert_eq!(patches, original.conv_subregion_indices_with_ker(*conv.kernel(), *conv.stride(), &indices));
+    }
+
+    #[test]
+    fn test3() {
+        let shape = [6u32, 5, 2];
+        let original = CpuSdrDataset::from_dense(shape, &Vec::<bool>::rand(shape.size() as usize * 4));
+        let crops = original.gen_rand_subregions([3, 5, 1], 20, &mut rand::thread_rng());
+        assert_eq!(crops.len(), 20);
+        assert!(crops.iter().all(|s| s.iter().all(|&i| i < 15)));
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds for dataset of length 2")]
+    fn test4() {
+        let mut d = CpuSdrDataset::<u32>::new([2, 2, 1]);
+        d.push_indices(&[0, 3]);
+        d.push_indices(&[]);
+        assert_eq!(d.subdataset(2..2).len(), 0);
+        d.subdataset(1..3);
+    }
+
+    #[test]
+    #[should_panic(expected = "are not normalized")]
+    fn test5() {
+        CpuSdrDataset::<u32>::new([2, 2, 1]).push_indices(&[2, 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds for shape")]
+    fn test6() {
+        CpuSdrDataset::<u32>::new([2, 2, 1]).push_indices(&[1, 4]);
+    }
+
+    #[test]
+    fn test7() {
+        let mut rng = rand::thread_rng();
+        let mut d = CpuSdrDataset::<u32>::new([4, 4, 1]);
+        assert!(d.gen_rand_conv_subregion_indices([2, 2], 5, &mut rng).is_empty());
+        d.push_indices(&[1, 5]);
+        assert!(d.gen_rand_conv_subregion_indices([0, 2], 5, &mut rng).is_empty());
+        assert!(d.gen_rand_conv_subregion_indices([2, 0], 5, &mut rng).is_empty());
+        assert_eq!(d.gen_rand_conv_subregion_indices([2, 2], 5, &mut rng).len(), 5);
+    }
+}
@@ -6,6 +6,7 @@ use num_traits::{AsPrimitive, NumAssignOps, One, PrimInt, Zero};
 use std::ops::{Add, AddAssign, Range, Rem, Sub};
 use rand::distributions::{Distribution, Standard};
 use crate::from_usize::FromUsize;
+use crate::parallel::for_each_chunk_par;
 
 pub trait SetCardinality {
     /**Set cardinality is the number of its member elements*/
@@ -44,7 +45,7 @@ pub trait SetSparseMask {
 }
 
 pub trait SetSparseParallelMask {
-    fn mask_par<T>(&self, destination: &mut [T], f: impl Fn(&mut T) + Send + Sync);
+    fn mask_par<T: Send>(&self, destination: &mut [T], f: impl Fn(&mut T) + Send + Sync);
 }
 
 // pub trait SetSparseRand<T> {
@@ -197,6 +198,21 @@ impl<N: AsPrimitive<usize> + Copy> SetSparseMask for [N] {
     }
 }
 
+impl<N: AsPrimitive<usize> + Copy + Sync> SetSparseParallelMask for [N] {
+    /**Same as mask, but destination is split between threads. Indices must be sorted, so that every thread
+    can binary search for the indices that fall into its own chunk (panics otherwise, because unsorted indices would be silently skipped).*/
+    fn mask_par<T: Send>(&self, destination: &mut [T], f: impl Fn(&mut T) + Send + Sync) {
+        assert!(self.windows(2).all(|w| w[0].as_() <= w[1].as_()), "Indices must be sorted");
+        for_each_chunk_par(destination, 1, |offset, chunk| {
+            let from = self.partition_point(|i| i.as_() < offset);
+            let to = self.partition_point(|i| i.as_() < offset + chunk.len());
+            for i in &self[from..to] {
+                f(&mut chunk[i.as_() - offset])
+            }
+        })
+    }
+}
+
 impl<N: AsPrimitive<usize> + Copy> SetSparseIndexArray for Vec<N> {
     fn normalize(&mut self) -> &mut Self {
         self.sort_by_key(|a| a.as_());
@@ -274,8 +290,9 @@ pub fn rand_set<N:PrimInt+AsPrimitive<usize>+Step+Hash+NumAssignOps>(cardinality
 //
 // }
 
-/**Returns a single vector containing indices of all true boolean values.
-The second vector contains offsets to the first one. It works just like Vec<Vec<Idx>> but is flattened.*/
+/**Returns a single vector containing indices (relative to the start of their batch) of all true boolean values.
+The second vector contains end offsets into the first one, that is, indices of `i`-th batch are
+`indices[offsets[i-1]..offsets[i]]` (with `offsets[-1]` being 0). It works just like Vec<Vec<Idx>> but is flattened.*/
 pub fn batch_dense_to_sparse<Idx: FromUsize>(batch_size: usize, bools: &[bool]) -> (Vec<Idx>, Vec<usize>) {
     assert_eq!(bools.len() % batch_size, 0);
     let mut from = 0;
@@ -284,7 +301,7 @@ pub fn batch_dense_to_sparse<Idx: FromUsize>(batch_size: usize, bools: &[bool])
     while from < bools.len() {
         let to = from + batch_size;
         dense_to_sparse_(&bools[from..to], &mut indices);
-        offsets.push(to);
+        offsets.push(indices.len());
         from = to;
     }
     (indices, offsets)
@@ -432,4 +449,37 @@ mod tests {
         rle_to_mat(&rle, &mut mat2);
         assert_eq!(&mat,&mat2);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test9() {
+        for len in [2000, 1 << 16] {
+            let mut indices: Vec<u32> = rand_set(300, 0..len);
+            indices.sort();
+            let mut a = vec![0u8; len as usize];
+            let mut b = vec![0u8; len as usize];
+            indices.mask(&mut a, |e| *e += 1);
+            indices.mask_par(&mut b, |e| *e += 1);
+            assert_eq!(a, b);
+            assert_eq!(b.iter().map(|&e| e as usize).sum::<usize>(), 300);
+        }
+    }
+
+    #[test]
+    fn test10() {
+        let bools = [true, false, true, false, false, false, false, true, true, true, true, false];
+        let (indices, offsets) = batch_dense_to_sparse::<u32>(4, &bools);
+        assert_eq!(indices, vec![0, 2, 3, 0, 1, 2]);
+        // offsets point to the end of every batch within indices (not within bools)
+        assert_eq!(offsets, vec![2, 3, 6]);
+        let (indices, offsets) = batch_dense_to_sparse::<u32>(3, &[false; 6]);
+        assert!(indices.is_empty());
+        assert_eq!(offsets, vec![0, 0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Indices must be sorted")]
+    fn test11() {
+        let mut b = vec![0u8; 1 << 16];
+        [60000u32, 5].mask_par(&mut b, |e| *e += 1);
+    }
+}
@@ -0,0 +1,576 @@
+use num_traits::Float;
+use rand::Rng;
+use crate::blas_safe::Vector;
+use crate::lapack_safe::{LapackError, Syevd, Syevr};
+use crate::Mat;
+
+fn dot<S: Float>(a: &[S], b: &[S]) -> S {
+    a.iter().zip(b.iter()).fold(S::zero(), |s, (&a, &b)| s + a * b)
+}
+
+fn normalise_<S: Float>(v: &mut [S]) -> S {
+    let d = dot(v, v).sqrt();
+    v.iter_mut().for_each(|e| *e = *e / d);
+    d
+}
+
+/**Number of features produced by `quadratic_expansion` for input of given dimensionality*/
+pub fn quadratic_expansion_dim(dim: usize) -> usize {
+    dim * dim + dim
+}
+
+/**Each row `x` of shape `[dim]` is expanded into all products `x[i]*x[j]` followed by `x` itself.
+ Output has shape `[batch, dim*dim+dim]`*/
+pub fn quadratic_expansion<S: Float>(x: &[S], dim: usize) -> Vec<S> {
+    assert_eq!(x.len() % dim, 0);
+    let mut out = Vec::with_capacity(x.len() / dim * quadratic_expansion_dim(dim));
+    for row in x.chunks_exact(dim) {
+        for &a in row {
+            out.extend(row.iter().map(|&b| a * b));
+        }
+        out.extend_from_slice(row);
+    }
+    out
+}
+
+/**Difference between consecutive rows. Output has shape `[batch-1, dim]`*/
+pub fn discrete_derivative<S: Float>(x: &[S], dim: usize) -> Vec<S> {
+    assert_eq!(x.len() % dim, 0);
+    x[dim..].iter().zip(x.iter()).map(|(&next, &prev)| next - prev).collect()
+}
+
+/**Mean of all rows*/
+pub fn mean<S: Float>(x: &[S], dim: usize) -> Vec<S> {
+    assert_eq!(x.len() % dim, 0);
+    let batch = S::from(x.len() / dim).unwrap();
+    let mut mu = vec![S::zero(); dim];
+    for row in x.chunks_exact(dim) {
+        mu.iter_mut().zip(row.iter()).for_each(|(m, &r)| *m = *m + r);
+    }
+    mu.iter_mut().for_each(|m| *m = *m / batch);
+    mu
+}
+
+/**Covariance matrix `x^T x / (batch-1)` of centered (zero-mean) observations*/
+pub fn cov<S: Float>(x: &[S], dim: usize) -> Mat<S, 2> {
+    assert_eq!(x.len() % dim, 0);
+    let batch = x.len() / dim;
+    let norm = S::from(batch.max(2) - 1).unwrap();
+    let mut c = vec![S::zero(); dim * dim];
+    for row in x.chunks_exact(dim) {
+        for (i, &a) in row.iter().enumerate() {
+            c[i * dim..(i + 1) * dim].iter_mut().zip(row.iter()).for_each(|(c, &b)| *c = *c + a * b);
+        }
+    }
+    c.iter_mut().for_each(|c| *c = *c / norm);
+    Mat::new([dim as u32, dim as u32], c.into_boxed_slice())
+}
+
+/**All data is passed as row-major matrices of shape `[batch, dim]` flattened into slices*/
+#[derive(Clone, Debug)]
+pub struct DeMean<S> {
+    mu: Vec<S>,
+}
+
+impl<S: Float> DeMean<S> {
+    pub fn new(x: &[S], dim: usize) -> Self {
+        Self { mu: mean(x, dim) }
+    }
+    pub fn mu(&self) -> &[S] {
+        &self.mu
+    }
+    pub fn apply_(&self, x: &mut [S]) {
+        for row in x.chunks_exact_mut(self.mu.len()) {
+            row.iter_mut().zip(self.mu.iter()).for_each(|(r, &m)| *r = *r - m);
+        }
+    }
+}
+
+/**Running mean of all observations seen so far*/
+#[derive(Clone, Debug)]
+pub struct IncDeMean<S> {
+    mu: Vec<S>,
+    n: usize,
+}
+
+impl<S: Float> IncDeMean<S> {
+    pub fn new(dim: usize) -> Self {
+        Self { mu: vec![S::zero(); dim], n: 0 }
+    }
+    pub fn mu(&self) -> &[S] {
+        &self.mu
+    }
+    /**Updates the mean with all rows of `x` and then subtracts it from them*/
+    pub fn apply_(&mut self, x: &mut [S]) {
+        let dim = self.mu.len();
+        let batch = x.len() / dim;
+        let n = S::from(self.n).unwrap();
+        let total = S::from(self.n + batch).unwrap();
+        let sum = mean(x, dim);
+        let batch_s = S::from(batch).unwrap();
+        self.mu.iter_mut().zip(sum.iter()).for_each(|(m, &s)| *m = (n * *m + s * batch_s) / total);
+        self.n += batch;
+        for row in x.chunks_exact_mut(dim) {
+            row.iter_mut().zip(self.mu.iter()).for_each(|(r, &m)| *r = *r - m);
+        }
+    }
+}
+
+/**Exponential moving average of observations*/
+#[derive(Clone, Debug)]
+pub struct IncDeMeanMovAvg<S> {
+    mu: Vec<S>,
+    epsilon: S,
+}
+
+impl<S: Float> IncDeMeanMovAvg<S> {
+    pub fn new(dim: usize, epsilon: S) -> Self {
+        Self { mu: vec![S::zero(); dim], epsilon }
+    }
+    pub fn mu(&self) -> &[S] {
+        &self.mu
+    }
+    /**Updates the moving average with each row of `x` (in order) and then subtracts the final average from them*/
+    pub fn apply_(&mut self, x: &mut [S]) {
+        let e = self.epsilon;
+        for row in x.chunks_exact(self.mu.len()) {
+            self.mu.iter_mut().zip(row.iter()).for_each(|(m, &r)| *m = (S::one() - e) * *m + e * r);
+        }
+        for row in x.chunks_exact_mut(self.mu.len()) {
+            row.iter_mut().zip(self.mu.iter()).for_each(|(r, &m)| *r = *r - m);
+        }
+    }
+}
+
+/**Learning rate schedules of incremental algorithms*/
+#[derive(Clone, Debug)]
+pub enum IncEpsilon<S> {
+    /**`1/n` where `n` is the number of observations. Yields exact running mean.*/
+    ForMean { n: usize },
+    /**Constant learning rate*/
+    ForMovAvg(S),
+    /**CCIPCA amnesic average. Starts like `ForMean` and then gradually gives more weight to new observations.*/
+    Amnesic { n1: usize, n2: usize, m: usize, c: S, i: usize },
+}
+
+impl<S: Float> IncEpsilon<S> {
+    pub fn amnesic() -> Self {
+        Self::Amnesic { n1: 20, n2: 200, m: 2000, c: S::from(3).unwrap(), i: 0 }
+    }
+    /**Advances schedule by `batch` observations and returns current learning rate*/
+    pub fn next(&mut self, batch: usize) -> S {
+        match self {
+            Self::ForMean { n } => {
+                *n += batch;
+                S::one() / S::from(*n).unwrap()
+            }
+            &mut Self::ForMovAvg(e) => e,
+            Self::Amnesic { n1, n2, m, c, i } => {
+                *i += batch;
+                let l = if *i < *n1 {
+                    S::zero()
+                } else if *i < *n2 {
+                    *c * S::from(*i - *n1).unwrap() / S::from(*n2 - *n1).unwrap()
+                } else {
+                    *c + S::from(*i - *n2).unwrap() / S::from(*m).unwrap()
+                };
+                (S::one() + l) / S::from(*i).unwrap()
+            }
+        }
+    }
+    /**Sets the number of observations seen so far (no-op for constant learning rate)*/
+    pub fn set_count(&mut self, count: usize) {
+        match self {
+            Self::ForMean { n } => *n = count,
+            Self::ForMovAvg(_) => {}
+            Self::Amnesic { i, .. } => *i = count,
+        }
+    }
+}
+
+fn rand_unit_rows<S: Float>(k: usize, dim: usize, rng: &mut impl Rng) -> Vec<S> {
+    let mut v: Vec<S> = (0..k * dim).map(|_| S::from(rng.gen::<f64>()).unwrap()).collect();
+    v.chunks_exact_mut(dim).for_each(|r| { normalise_(r); });
+    v
+}
+
+/**Candid covariance-free incremental PCA (CCIPCA). Eigenvectors are stored as rows of a `[k, dim]` matrix.*/
+#[derive(Clone, Debug)]
+pub struct IncPca<S> {
+    dim: usize,
+    eig_vec: Vec<S>,
+    eig_val: Vec<S>,
+    epsilon: IncEpsilon<S>,
+}
+
+impl<S: Float> IncPca<S> {
+    /**`k` is the number of principal components to extract and `dim` is the length of input vectors*/
+    pub fn new(dim: usize, k: usize, epsilon: IncEpsilon<S>) -> Self {
+        Self::new_with_rng(dim, k, epsilon, &mut rand::thread_rng())
+    }
+    /**Same as `new` but the random initial estimate is drawn from `rng`, so that it's reproducible*/
+    pub fn new_with_rng(dim: usize, k: usize, epsilon: IncEpsilon<S>, rng: &mut impl Rng) -> Self {
+        let spread = S::from(0.1).unwrap();
+        let eig_vec = rand_unit_rows(k, dim, rng);
+        let eig_val = (0..k).map(|_| S::one() + spread * S::from(rng.gen::<f64>() * 2. - 1.).unwrap()).collect();
+        Self { dim, eig_vec, eig_val, epsilon }
+    }
+    pub fn k(&self) -> usize {
+        self.eig_val.len()
+    }
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+    /**Eigenvectors as rows of `[k, dim]` matrix*/
+    pub fn eig_vec(&self) -> &[S] {
+        &self.eig_vec
+    }
+    pub fn eig_val(&self) -> &[S] {
+        &self.eig_val
+    }
+    pub fn epsilon_mut(&mut self) -> &mut IncEpsilon<S> {
+        &mut self.epsilon
+    }
+    /**Overwrites current estimate (for example with the one obtained from batch PCA)*/
+    pub fn set(&mut self, eig_val: Vec<S>, eig_vec: Vec<S>) {
+        assert_eq!(eig_vec.len(), eig_val.len() * self.dim);
+        self.eig_val = eig_val;
+        self.eig_vec = eig_vec;
+    }
+    /**`x` is a single centered (zero-mean) observation*/
+    pub fn update(&mut self, x: &[S]) {
+        assert_eq!(x.len(), self.dim);
+        let e = self.epsilon.next(1);
+        let mut x = x.to_vec();
+        for (v, d) in self.eig_vec.chunks_exact_mut(self.dim).zip(self.eig_val.iter_mut()) {
+            // CCIPCA keeps the unnormalised estimate d·v, whose norm converges to the eigenvalue
+            let xv = dot(&x, v);
+            let mut new_v: Vec<S> = v.iter().zip(x.iter()).map(|(&v, &x)| (S::one() - e) * *d * v + e * xv * x).collect();
+            let new_d = normalise_(&mut new_v);
+            // degenerate observation (e.g. the very first one, which is always zero after centering)
+            if new_d > S::zero() && new_d.is_finite() {
+                v.copy_from_slice(&new_v);
+                *d = new_d;
+            }
+            let xv = dot(&x, v);
+            x.iter_mut().zip(v.iter()).for_each(|(x, &v)| *x = *x - xv * v);
+        }
+    }
+    /**Projects centered observation `x` onto principal components and scales them to unit variance.
+     Components whose variance is (numerically) zero are ignored.*/
+    pub fn whiten(&self, x: &[S], out: &mut [S]) {
+        assert_eq!(x.len(), self.dim);
+        assert_eq!(out.len(), self.k());
+        let max = self.eig_val.iter().fold(S::zero(), |a, &b| a.max(b));
+        for ((o, v), &d) in out.iter_mut().zip(self.eig_vec.chunks_exact(self.dim)).zip(self.eig_val.iter()) {
+            *o = if d > max * S::epsilon() { dot(x, v) / d.sqrt() } else { S::zero() };
+        }
+    }
+}
+
+/**Incremental minor component analysis. Finds eigenvectors of the smallest eigenvalues, sorted in ascending
+ order and stored as rows of `[k, dim]` matrix. Each vector `w_j` follows the update `w_j ← w_j - η·C_j·w_j`
+ (followed by normalisation), where `C_j = x·x^T + γ·Σ_{i<j} w_i·w_i^T`. The deflation term pushes previously
+ found components up the spectrum, hence `γ` must be greater than the largest eigenvalue of input covariance and
+ `η·(λ_max + γ)` must be less than 1.*/
+#[derive(Clone, Debug)]
+pub struct IncMca<S> {
+    dim: usize,
+    vec: Vec<S>,
+    gamma: S,
+    learning_rate: S,
+}
+
+impl<S: Float> IncMca<S> {
+    pub fn new(dim: usize, k: usize, gamma: S, learning_rate: S) -> Self {
+        Self::new_with_rng(dim, k, gamma, learning_rate, &mut rand::thread_rng())
+    }
+    /**Same as `new` but the random initial vectors are drawn from `rng`*/
+    pub fn new_with_rng(dim: usize, k: usize, gamma: S, learning_rate: S, rng: &mut impl Rng) -> Self {
+        Self { dim, vec: rand_unit_rows(k, dim, rng), gamma, learning_rate }
+    }
+    pub fn k(&self) -> usize {
+        self.vec.len() / self.dim
+    }
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+    pub fn vec(&self) -> &[S] {
+        &self.vec
+    }
+    pub fn set(&mut self, vec: Vec<S>) {
+        assert_eq!(vec.len(), self.k() * self.dim);
+        self.vec = vec;
+    }
+    pub fn learning_rate(&self) -> S {
+        self.learning_rate
+    }
+    pub fn set_learning_rate(&mut self, learning_rate: S) {
+        self.learning_rate = learning_rate
+    }
+    pub fn update(&mut self, x: &[S]) {
+        assert_eq!(x.len(), self.dim);
+        let dim = self.dim;
+        let eta = self.learning_rate;
+        for j in 0..self.k() {
+            let (prev, rest) = self.vec.split_at_mut(j * dim);
+            let w = &mut rest[..dim];
+            let xw = dot(x, w);
+            let mut cw: Vec<S> = x.iter().map(|&x| x * xw).collect();
+            for wi in prev.chunks_exact(dim) {
+                let wiw = self.gamma * dot(wi, w);
+                cw.iter_mut().zip(wi.iter()).for_each(|(c, &wi)| *c = *c + wiw * wi);
+            }
+            w.iter_mut().zip(cw.iter()).for_each(|(w, &c)| *w = *w - eta * c);
+            normalise_(w);
+        }
+    }
+    /**Projects `x` onto minor components*/
+    pub fn project(&self, x: &[S], out: &mut [S]) {
+        for (o, w) in out.iter_mut().zip(self.vec.chunks_exact(self.dim)) {
+            *o = dot(x, w);
+        }
+    }
+}
+
+/**Slow feature analysis (port of `py/sfa/sfa.py`) which can be trained on entire sequence at once (`fit`) or incrementally (`partial_fit`),
+ in which case it becomes IncSFA (Kompella et al. 2012). Both can be mixed, for example the batch solution
+ can be later refined online. Input is (optionally) expanded quadratically, de-meaned, whitened down to
+ `whitened_dim` features and then projected onto `output_dim` slowest directions of the whitened signal.*/
+#[derive(Clone, Debug)]
+pub struct Sfa<S> {
+    input_dim: usize,
+    quadratic: bool,
+    mu: Vec<S>,
+    mean_epsilon: IncEpsilon<S>,
+    pca: IncPca<S>,
+    mca: IncMca<S>,
+    prev: Option<Vec<S>>,
+}
+
+impl<S: Float> Sfa<S> {
+    /**`learning_rate` is used by incremental minor component analysis of the whitened signal derivatives*/
+    pub fn new(input_dim: usize, whitened_dim: usize, output_dim: usize, quadratic: bool, learning_rate: S) -> Self {
+        Self::new_with_rng(input_dim, whitened_dim, output_dim, quadratic, learning_rate, &mut rand::thread_rng())
+    }
+    /**Same as `new` but the random initial state of incremental PCA and MCA is drawn from `rng`*/
+    pub fn new_with_rng(input_dim: usize, whitened_dim: usize, output_dim: usize, quadratic: bool, learning_rate: S, rng: &mut impl Rng) -> Self {
+        assert!(output_dim <= whitened_dim, "Number of slow features {} can't exceed whitened dimensionality {}", output_dim, whitened_dim);
+        let dim = if quadratic { quadratic_expansion_dim(input_dim) } else { input_dim };
+        assert!(whitened_dim <= dim, "Whitened dimensionality {} can't exceed (expanded) input dimensionality {}", whitened_dim, dim);
+        // whitened signal has unit variance, so variance of its derivative is at most 4
+        let gamma = S::from(4.5).unwrap();
+        Self {
+            input_dim,
+            quadratic,
+            mu: vec![S::zero(); dim],
+            mean_epsilon: IncEpsilon::ForMean { n: 0 },
+            pca: IncPca::new_with_rng(dim, whitened_dim, IncEpsilon::amnesic(), rng),
+            mca: IncMca::new_with_rng(whitened_dim, output_dim, gamma, learning_rate, rng),
+            prev: None,
+        }
+    }
+    pub fn input_dim(&self) -> usize {
+        self.input_dim
+    }
+    /**Dimensionality after quadratic expansion (if enabled)*/
+    pub fn expanded_dim(&self) -> usize {
+        self.mu.len()
+    }
+    pub fn whitened_dim(&self) -> usize {
+        self.pca.k()
+    }
+    pub fn output_dim(&self) -> usize {
+        self.mca.k()
+    }
+    pub fn is_quadratic(&self) -> bool {
+        self.quadratic
+    }
+    pub fn mu(&self) -> &[S] {
+        &self.mu
+    }
+    pub fn pca(&self) -> &IncPca<S> {
+        &self.pca
+    }
+    pub fn pca_mut(&mut self) -> &mut IncPca<S> {
+        &mut self.pca
+    }
+    pub fn mca(&self) -> &IncMca<S> {
+        &self.mca
+    }
+    pub fn mca_mut(&mut self) -> &mut IncMca<S> {
+        &mut self.mca
+    }
+    fn expand(&self, x: &[S]) -> Vec<S> {
+        assert_eq!(x.len() % self.input_dim, 0, "Input length {} is not divisible by input dimensionality {}", x.len(), self.input_dim);
+        if self.quadratic { quadratic_expansion(x, self.input_dim) } else { x.to_vec() }
+    }
+    fn de_mean_and_whiten(&self, x: &mut [S]) -> Vec<S> {
+        DeMean { mu: self.mu.clone() }.apply_(x);
+        let dim = self.expanded_dim();
+        let k = self.whitened_dim();
+        let mut out = vec![S::zero(); x.len() / dim * k];
+        for (row, o) in x.chunks_exact(dim).zip(out.chunks_exact_mut(k)) {
+            self.pca.whiten(row, o);
+        }
+        out
+    }
+    /**Incrementally updates the model with consecutive observations `x` of shape `[batch, input_dim]`.
+     Derivative between the last observation of previous call and the first one of this call is taken into account.*/
+    pub fn partial_fit(&mut self, x: &[S]) {
+        let x = self.expand(x);
+        let dim = self.expanded_dim();
+        let mut whitened = vec![S::zero(); self.whitened_dim()];
+        let mut centered = vec![S::zero(); dim];
+        for row in x.chunks_exact(dim) {
+            let e = self.mean_epsilon.next(1);
+            self.mu.iter_mut().zip(row.iter()).for_each(|(m, &r)| *m = (S::one() - e) * *m + e * r);
+            centered.iter_mut().zip(row.iter().zip(self.mu.iter())).for_each(|(c, (&r, &m))| *c = r - m);
+            self.pca.update(&centered);
+            self.pca.whiten(&centered, &mut whitened);
+            if let Some(prev) = &mut self.prev {
+                prev.iter_mut().zip(whitened.iter()).for_each(|(p, &w)| *p = w - *p);
+                self.mca.update(prev);
+                prev.copy_from_slice(&whitened);
+            } else {
+                self.prev = Some(whitened.clone());
+            }
+        }
+    }
+    /**Projects observations `x` of shape `[batch, input_dim]` onto slow features. Output has shape `[batch, output_dim]`,
+     with the slowest feature first.*/
+    pub fn transform(&self, x: &[S]) -> Vec<S> {
+        let mut x = self.expand(x);
+        let whitened = self.de_mean_and_whiten(&mut x);
+        let k = self.whitened_dim();
+        let out_dim = self.output_dim();
+        let mut out = vec![S::zero(); whitened.len() / k * out_dim];
+        for (row, o) in whitened.chunks_exact(k).zip(out.chunks_exact_mut(out_dim)) {
+            self.mca.project(row, o);
+        }
+        out
+    }
+}
+
+impl<S: Float + Syevd + Syevr> Sfa<S> {
+    /**Batch SFA on a sequence of observations `x` of shape `[batch, input_dim]`. It overwrites the current state,
+     which can afterwards be refined with `partial_fit`.*/
+    pub fn fit(&mut self, x: &[S]) -> Result<(), LapackError> {
+        let mut x = self.expand(x);
+        let dim = self.expanded_dim();
+        let batch = x.len() / dim;
+        if batch < 2 {
+            return Err(LapackError::ShapeMismatch(format!("SFA needs at least 2 observations but got {}", batch)));
+        }
+        self.mu = mean(&x, dim);
+        self.mean_epsilon.set_count(batch);
+        DeMean { mu: self.mu.clone() }.apply_(&mut x);
+        let (eig_val, eig_vec) = cov(&x, dim).eigh_top_k(self.whitened_dim())?;
+        // eigenvectors are columns, but IncPca stores them as rows
+        self.pca.set(eig_val.as_slice().to_vec(), eig_vec.transpose().as_slice().to_vec());
+        self.pca.epsilon_mut().set_count(batch);
+        let k = self.whitened_dim();
+        let mut whitened = vec![S::zero(); batch * k];
+        for (row, o) in x.chunks_exact(dim).zip(whitened.chunks_exact_mut(k)) {
+            self.pca.whiten(row, o);
+        }
+        let derivative = discrete_derivative(&whitened, k);
+        let (_, slow) = cov(&derivative, k).eigh()?;
+        // eigh sorts eigenvalues in descending order, while we need the smallest ones first
+        let slow = slow.transpose();
+        let slow = slow.as_slice();
+        let out_dim = self.output_dim();
+        let vec = (0..out_dim).flat_map(|j| slow[(k - 1 - j) * k..(k - j) * k].iter().cloned()).collect();
+        self.mca.set(vec);
+        self.prev = Some(whitened[(batch - 1) * k..].to_vec());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test1() {
+        let x = [1., 2., 3., 4.];
+        let e = quadratic_expansion(&x, 2);
+        assert_eq!(e, vec![1., 2., 2., 4., 1., 2., 9., 12., 12., 16., 3., 4.]);
+        assert_eq!(discrete_derivative(&x, 2), vec![2., 2.]);
+    }
+
+    #[test]
+    fn test2() {
+        let x: Vec<f64> = (0..30).map(|_| rand::random()).collect();
+        let mut inc = IncDeMean::new(3);
+        let mut y = x.clone();
+        for row in y.chunks_exact_mut(3) {
+            inc.apply_(row);
+        }
+        let batch = DeMean::new(&x, 3);
+        assert!(inc.mu().iter().zip(batch.mu().iter()).all(|(a, b)| (a - b).abs() < 1e-9));
+        let mut eps = IncEpsilon::ForMean { n: 0 };
+        let mut mov = IncDeMeanMovAvg::new(3, 0.);
+        for row in x.chunks_exact(3) {
+            mov.epsilon = eps.next(1);
+            mov.apply_(&mut row.to_vec());
+        }
+        assert!(mov.mu().iter().zip(batch.mu().iter()).all(|(a, b)| (a - b).abs() < 1e-9));
+    }
+
+    #[test]
+    fn test3() {
+        // principal direction of strongly correlated data is [1,1]/sqrt(2)
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let mut pca = IncPca::new_with_rng(2, 1, IncEpsilon::amnesic(), &mut rng);
+        for _ in 0..5000 {
+            let a = rng.gen::<f64>() * 2. - 1.;
+            let b = (rng.gen::<f64>() * 2. - 1.) * 0.1;
+            pca.update(&[a + b, a - b]);
+        }
+        let v = pca.eig_vec();
+        assert!((v[0].abs() - 0.5f64.sqrt()).abs() < 0.05, "{:?}", v);
+        assert!((v[1].abs() - 0.5f64.sqrt()).abs() < 0.05, "{:?}", v);
+        assert!(v[0] * v[1] > 0.);
+    }
+
+    #[test]
+    fn test4() {
+        // linear mixture of slow and fast signal. Incremental SFA should unmix the slow one.
+        let signal = |t: usize| {
+            let t = t as f64;
+            let slow = (t * 0.01).sin();
+            let fast = (t * 1.3).sin();
+            [slow + 0.5 * fast, 0.3 * slow + fast]
+        };
+        use rand::SeedableRng;
+        let mut sfa = Sfa::new_with_rng(2, 2, 1, false, 0.005, &mut rand::rngs::StdRng::seed_from_u64(4));
+        let x: Vec<f64> = (0..20000).flat_map(signal).collect();
+        sfa.partial_fit(&x);
+        let test: Vec<f64> = (20000..21000).flat_map(signal).collect();
+        let y = sfa.transform(&test);
+        let slow: Vec<f64> = (20000..21000).map(|t| (t as f64 * 0.01).sin()).collect();
+        let corr = dot(&y, &slow) / (dot(&y, &y) * dot(&slow, &slow)).sqrt();
+        assert!(corr.abs() > 0.95, "{}", corr);
+    }
+
+    #[test]
+    fn test5() {
+        // independent channels with variances 9, 4 and 1. Online refinement must not drift away from batch eigenvalues.
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let lambda = [9., 4., 1.];
+        let mut sample = |n: usize| -> Vec<f64> {
+            (0..n).flat_map(|_| lambda.map(|l: f64| (3. * l).sqrt() * (rng.gen::<f64>() * 2. - 1.))).collect()
+        };
+        let mut sfa = Sfa::new_with_rng(3, 3, 1, false, 0.005, &mut rand::rngs::StdRng::seed_from_u64(8));
+        sfa.fit(&sample(5000)).unwrap();
+        for _ in 0..10 {
+            sfa.partial_fit(&sample(1000));
+            for (d, l) in sfa.pca().eig_val().iter().zip(lambda) {
+                assert!((d - l).abs() < 0.15 * l, "{:?}", sfa.pca().eig_val());
+            }
+        }
+    }
+}
@@ -1,4 +1,3 @@
-use std::cmp::Ordering::{Greater, Less};
 use std::iter::Step;
 use std::ops::{Add, Mul};
 use itertools::Itertools;
@@ -7,6 +6,8 @@ use crate::conv_shape::{channels, height, width};
 use crate::shape::Shape;
 use crate::VectorFieldOne;
 use crate::xyzw::z3;
+use crate::parallel::for_each_chunk_par;
+use crate::top_k::descending_key;
 
 pub const NULL:u8 = 2;
 
@@ -18,10 +19,10 @@ pub fn top_v_repeated_conv_(y_shape:&[usize;3], v:&[bool], s:&[f32], y:&mut [u8]
     let c = channels(y_shape).clone();
     assert_eq!(y.len(),m);
     assert_eq!(s.len(),m);
-    assert_eq!(v.len(),m*c);
+    assert_eq!(v.len(),c*c);
     for j0 in 0..y_shape[0]{
         for j1 in 0..y_shape[1]{
-            let y_from_j = (j0*c+j1)*c;
+            let y_from_j = (j0*y_shape[1]+j1)*c;
             let y_to_j = y_from_j+c;
             top_v_slice_(&v,&s[y_from_j..y_to_j],&mut y[y_from_j..y_to_j])
         }
@@ -37,10 +38,10 @@ pub fn top_v_conv_(y_shape:&[usize;3], v:&[bool], s:&[f32], y:&mut [u8]){
     let c = channels(y_shape).clone();
     assert_eq!(y.len(),m);
     assert_eq!(s.len(),m);
-    assert_eq!(v.len(),c*c);
+    assert_eq!(v.len(),m*c);
     for j0 in 0..y_shape[0]{
         for j1 in 0..y_shape[1]{
-            let y_from_j = (j0*c+j1)*c;
+            let y_from_j = (j0*y_shape[1]+j1)*c;
             let y_to_j = y_from_j+c;
             let v_from_j = y_from_j*c;
             let v_to_j = v_from_j+c*c;
@@ -68,18 +69,28 @@ pub fn top_v(v:impl Fn(usize,usize)->bool,s:&[f32])->Vec<bool>{
     unsafe{std::mem::transmute(y)}
 }
 pub fn top_v_(v:impl Fn(usize,usize)->bool,s:&[f32], y:&mut [u8]){
-    while let Some(k) = y.iter().cloned().enumerate().filter(|&(_,o)|o==NULL).map(|(k,_)|k).max_by(|&k,&j|if s[k] < s[j]{Less}else{Greater}){
-        debug_assert_eq!(y[k],NULL);
-        y[k] = 1;
-        for j in 0..s.len(){
-            if y[j] == NULL{
-                if v(k,j) && s[k] > s[j]{
-                    y[j] = 0;
-                }
-            }
+    soft_wta_(s, y, |k,j|v(k,j) && s[k] > s[j])
+}
+
+/**Decides all the neurons whose y is NULL. They are visited in descending order of s (ties are broken in favour
+ of lower index) and each one becomes active unless some previously activated neuron k inhibits it. This gives
+ the same result as repeatedly activating the strongest undecided neuron and letting it inhibit all the others,
+ but needs only a single sort and compares each neuron against the winners only. s must not contain NaN.*/
+fn soft_wta_(s:&[f32], y:&mut [u8], inhibits:impl Fn(usize,usize)->bool){
+    assert_eq!(s.len(), y.len());
+    assert!(s.len() <= u32::MAX as usize);
+    let mut order:Vec<u64> = y.iter().enumerate().filter(|&(_,&o)|o==NULL).map(|(j,_)|descending_key(s[j], j)).collect();
+    order.sort_unstable();
+    let mut winners = Vec::new();
+    for key in order{
+        let j = (key & u32::MAX as u64) as usize;
+        if winners.iter().any(|&k|inhibits(k,j)){
+            y[j] = 0;
+        }else{
+            y[j] = 1;
+            winners.push(j);
         }
     }
-    debug_assert!(!y.contains(&NULL));
 }
 
 /**shape of s is [height, width, channels], shape of u is [channels, channels],
@@ -93,7 +104,7 @@ pub fn top_u_repeated_conv_(y_shape:&[usize;3], u:&[f32], s:&[f32], y:&mut [u8])
     assert_eq!(u.len(),c*c);
     for j0 in 0..y_shape[0]{
         for j1 in 0..y_shape[1]{
-            let y_from_j = (j0*c+j1)*c;
+            let y_from_j = (j0*y_shape[1]+j1)*c;
             let y_to_j = y_from_j+c;
             top_u_slice_(u,&s[y_from_j..y_to_j],&mut y[y_from_j..y_to_j])
         }
@@ -111,7 +122,7 @@ pub fn top_u_conv_(y_shape:&[usize;3], u:&[f32], s:&[f32], y:&mut [u8]){
     assert_eq!(u.len(),m*c);
     for j0 in 0..y_shape[0]{
         for j1 in 0..y_shape[1]{
-            let y_from_j = (j0*c+j1)*c;
+            let y_from_j = (j0*y_shape[1]+j1)*c;
             let y_to_j = y_from_j+c;
             let u_from_j = y_from_j*c;
             let u_to_j = u_from_j+c*c;
@@ -134,18 +145,7 @@ pub fn top_u_slice_(u:&[f32], s:&[f32],y:&mut [u8]){
 }
 
 pub fn top_u_(u:impl Fn(usize,usize)->f32,s:&[f32],y:&mut [u8]){
-    while let Some(k) = y.iter().cloned().enumerate().filter(|&(_,o)|o==NULL).map(|(k,_)|k).max_by(|&k,&j|if s[k] < s[j]{Less}else{Greater}){
-        debug_assert_eq!(y[k],NULL);
-        y[k] = 1;
-        for j in 0..s.len(){
-            if y[j] == NULL{
-                if s[j] + u(k,j) < s[k]{
-                    y[j] = 0;
-                }
-            }
-        }
-    }
-    debug_assert!(!y.contains(&NULL));
+    soft_wta_(s, y, |k,j|s[j] + u(k,j) < s[k])
 }
 pub fn top_u(u:impl Fn(usize,usize)->f32,s:&[f32])->Vec<bool>{
     let mut y:Vec<u8> = vec![NULL;s.len()];
@@ -166,7 +166,7 @@ pub fn multiplicative_top_u_repeated_conv_(y_shape:&[usize;3], u:&[f32], s:&[f32
     assert_eq!(u.len(),c*c);
     for j0 in 0..y_shape[0]{
         for j1 in 0..y_shape[1]{
-            let y_from_j = (j0*c+j1)*c;
+            let y_from_j = (j0*y_shape[1]+j1)*c;
             let y_to_j = y_from_j+c;
             multiplicative_top_u_slice_(u,&s[y_from_j..y_to_j],&mut y[y_from_j..y_to_j])
         }
@@ -185,7 +185,7 @@ pub fn multiplicative_top_u_conv_(y_shape:&[usize;3], u:&[f32], s:&[f32],y:&mut
     assert_eq!(u.len(),m*c);
     for j0 in 0..y_shape[0]{
         for j1 in 0..y_shape[1]{
-            let y_from_j = (j0*c+j1)*c;
+            let y_from_j = (j0*y_shape[1]+j1)*c;
             let y_to_j = y_from_j+c;
             let u_from_j = y_from_j*c;
             let u_to_j = u_from_j+c*c;
@@ -207,18 +207,7 @@ pub fn multiplicative_top_u_slice_(u:&[f32], s:&[f32],y:&mut [u8]){
 }
 
 pub fn multiplicative_top_u_(u:impl Fn(usize,usize)->f32,s:&[f32],y:&mut [u8]){
-    while let Some(k) = y.iter().cloned().enumerate().filter(|&(_,o)|o==NULL).map(|(k,_)|k).max_by(|&k,&j|if s[k] < s[j]{Less}else{Greater}){
-        debug_assert_eq!(y[k],NULL);
-        y[k] = 1;
-        for j in 0..s.len(){
-            if y[j] == NULL{
-                if s[k] * s[j] > u(k,j) {
-                    y[j] = 0;
-                }
-            }
-        }
-    }
-    debug_assert!(!y.contains(&NULL));
+    soft_wta_(s, y, |k,j|s[k] * s[j] > u(k,j))
 }
 pub fn multiplicative_top_u(u:impl Fn(usize,usize)->f32,s:&[f32])->Vec<bool>{
     let mut y:Vec<u8> = vec![NULL;s.len()];
@@ -226,12 +215,117 @@ pub fn multiplicative_top_u(u:impl Fn(usize,usize)->f32,s:&[f32])->Vec<bool>{
     unsafe{std::mem::transmute(y)}
 }
 
+/**Runs column_wta over every minicolumn of s in parallel. Shape of s and y is [batch, height, width, channels],
+ where y_shape is [height, width, channels] and the batch dimension may be omitted.
+ column_wta receives index of the minicolumn within [height, width] grid (the same for all batch elements).*/
+fn conv_par_(y_shape:&[usize;3], s:&[f32], y:&mut [u8], column_wta:impl Fn(usize,&[f32],&mut [u8]) + Sync){
+    let m = y_shape.product();
+    let c = channels(y_shape).clone();
+    let columns = height(y_shape) * width(y_shape);
+    assert_eq!(y.len(),s.len());
+    assert_eq!(y.len()%m,0,"Length {} is not a multiple of {:?}",y.len(),y_shape);
+    for_each_chunk_par(y, c, |offset, y| for (i, y) in y.chunks_mut(c).enumerate() {
+        let from = offset + i * c;
+        column_wta((from / c) % columns, &s[from..from + c], y)
+    })
+}
+
+/**Same as top_v_repeated_conv_ but minicolumns are processed in parallel. s and y may have an additional
+ leading batch dimension, that is, shape [batch, height, width, channels].*/
+pub fn top_v_repeated_conv_par_(y_shape:&[usize;3], v:&[bool], s:&[f32], y:&mut [u8]){
+    let c = channels(y_shape).clone();
+    assert_eq!(v.len(),c*c);
+    conv_par_(y_shape, s, y, |_, s, y|top_v_slice_(v,s,y))
+}
+
+/**Same as top_v_conv_ but minicolumns are processed in parallel. s and y may have an additional
+ leading batch dimension, that is, shape [batch, height, width, channels]. All batch elements share the same v.*/
+pub fn top_v_conv_par_(y_shape:&[usize;3], v:&[bool], s:&[f32], y:&mut [u8]){
+    let c = channels(y_shape).clone();
+    assert_eq!(v.len(),y_shape.product()*c);
+    conv_par_(y_shape, s, y, |col, s, y|top_v_slice_(&v[col*c*c..(col+1)*c*c],s,y))
+}
+
+/**Same as top_u_repeated_conv_ but minicolumns are processed in parallel. s and y may have an additional
+ leading batch dimension, that is, shape [batch, height, width, channels].*/
+pub fn top_u_repeated_conv_par_(y_shape:&[usize;3], u:&[f32], s:&[f32], y:&mut [u8]){
+    let c = channels(y_shape).clone();
+    assert_eq!(u.len(),c*c);
+    conv_par_(y_shape, s, y, |_, s, y|top_u_slice_(u,s,y))
+}
+
+/**Same as top_u_conv_ but minicolumns are processed in parallel. s and y may have an additional
+ leading batch dimension, that is, shape [batch, height, width, channels]. All batch elements share the same u.*/
+pub fn top_u_conv_par_(y_shape:&[usize;3], u:&[f32], s:&[f32], y:&mut [u8]){
+    let c = channels(y_shape).clone();
+    assert_eq!(u.len(),y_shape.product()*c);
+    conv_par_(y_shape, s, y, |col, s, y|top_u_slice_(&u[col*c*c..(col+1)*c*c],s,y))
+}
+
+/**Same as multiplicative_top_u_repeated_conv_ but minicolumns are processed in parallel. s and y may have an additional
+ leading batch dimension, that is, shape [batch, height, width, channels].*/
+pub fn multiplicative_top_u_repeated_conv_par_(y_shape:&[usize;3], u:&[f32], s:&[f32], y:&mut [u8]){
+    let c = channels(y_shape).clone();
+    assert_eq!(u.len(),c*c);
+    conv_par_(y_shape, s, y, |_, s, y|multiplicative_top_u_slice_(u,s,y))
+}
+
+/**Same as multiplicative_top_u_conv_ but minicolumns are processed in parallel. s and y may have an additional
+ leading batch dimension, that is, shape [batch, height, width, channels]. All batch elements share the same u.*/
+pub fn multiplicative_top_u_conv_par_(y_shape:&[usize;3], u:&[f32], s:&[f32], y:&mut [u8]){
+    let c = channels(y_shape).clone();
+    assert_eq!(u.len(),y_shape.product()*c);
+    conv_par_(y_shape, s, y, |col, s, y|multiplicative_top_u_slice_(&u[col*c*c..(col+1)*c*c],s,y))
+}
+
 #[cfg(test)]
 mod tests {
+    use std::cmp::Ordering::{Greater, Less};
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
     use crate::init_rand::InitRandWithCapacity;
     use crate::VectorFieldPartialOrd;
     use super::*;
 
+    /**The original quadratic algorithm, kept as reference for the faster one*/
+    fn naive_soft_wta_(s:&[f32], y:&mut [u8], inhibits:impl Fn(usize,usize)->bool){
+        while let Some(k) = y.iter().cloned().enumerate().filter(|&(_,o)|o==NULL).map(|(k,_)|k).max_by(|&k,&j|if s[k] < s[j]{Less}else{Greater}){
+            y[k] = 1;
+            for j in 0..s.len(){
+                if y[j] == NULL && inhibits(k,j){
+                    y[j] = 0;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_equivalence(){
+        let mut rng = StdRng::seed_from_u64(634);
+        for _ in 0..200{
+            let l = rng.gen_range(1..40);
+            // coarse values produce many ties
+            let levels = rng.gen_range(2..20) as f32;
+            let s:Vec<f32> = (0..l).map(|_|if rng.gen_bool(0.1){-0.0}else{(rng.gen::<f32>()*levels).floor()/levels-0.5}).collect();
+            let u:Vec<f32> = (0..l*l).map(|_|rng.gen::<f32>()-0.3).collect();
+            let v:Vec<bool> = (0..l*l).map(|_|rng.gen_bool(0.3)).collect();
+            // some neurons may already be decided
+            let y0:Vec<u8> = (0..l).map(|_|if rng.gen_bool(0.8){NULL}else{rng.gen_range(0..2)}).collect();
+            let (mut y1, mut y2) = (y0.clone(), y0.clone());
+            top_v_slice_(&v,&s,&mut y1);
+            naive_soft_wta_(&s,&mut y2,|k,j|v[k*l+j] && s[k] > s[j]);
+            assert_eq!(y1, y2);
+            let (mut y1, mut y2) = (y0.clone(), y0.clone());
+            top_u_slice_(&u,&s,&mut y1);
+            naive_soft_wta_(&s,&mut y2,|k,j|s[j] + u[k*l+j] < s[k]);
+            assert_eq!(y1, y2);
+            let (mut y1, mut y2) = (y0.clone(), y0.clone());
+            multiplicative_top_u_slice_(&u,&s,&mut y1);
+            naive_soft_wta_(&s,&mut y2,|k,j|s[k] * s[j] > u[k*l+j]);
+            assert_eq!(y1, y2);
+        }
+    }
+
 
     #[test]
     fn test_real(){
@@ -281,4 +375,75 @@ mod tests {
         }
 
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_conv(){
+        let shape = [3, 4, 5];
+        let c = shape[2];
+        let m = shape.product();
+        let s = Vec::<f32>::rand(m);
+        let u = Vec::<f32>::rand(m*c);
+        let v = Vec::<bool>::rand(m*c);
+        let mut y_u = vec![NULL;m];
+        let mut y_u_repeated = vec![NULL;m];
+        let mut y_v = vec![NULL;m];
+        let mut y_v_repeated = vec![NULL;m];
+        top_u_conv_(&shape,&u,&s,&mut y_u);
+        top_u_repeated_conv_(&shape,&u[..c*c],&s,&mut y_u_repeated);
+        top_v_conv_(&shape,&v,&s,&mut y_v);
+        top_v_repeated_conv_(&shape,&v[..c*c],&s,&mut y_v_repeated);
+        for col in 0..m/c{
+            let r = col*c..(col+1)*c;
+            let mut y = vec![NULL;c];
+            top_u_slice_(&u[col*c*c..(col+1)*c*c],&s[r.clone()],&mut y);
+            assert_eq!(y, &y_u[r.clone()]);
+            let mut y = vec![NULL;c];
+            top_u_slice_(&u[..c*c],&s[r.clone()],&mut y);
+            assert_eq!(y, &y_u_repeated[r.clone()]);
+            let mut y = vec![NULL;c];
+            top_v_slice_(&v[col*c*c..(col+1)*c*c],&s[r.clone()],&mut y);
+            assert_eq!(y, &y_v[r.clone()]);
+            let mut y = vec![NULL;c];
+            top_v_slice_(&v[..c*c],&s[r.clone()],&mut y);
+            assert_eq!(y, &y_v_repeated[r.clone()]);
+        }
+    }
+
+    #[test]
+    fn test_conv_par(){
+        let shape = [5, 7, 6];
+        let c = shape[2];
+        let m = shape.product();
+        let batch = 3;
+        let s = Vec::<f32>::rand(m*batch);
+        let u = Vec::<f32>::rand(m*c);
+        let v = Vec::<bool>::rand(m*c);
+        for b in 0..batch{
+            let s_b = &s[b*m..(b+1)*m];
+            let par = |f:fn(&[usize;3],&[f32],&[f32],&mut [u8]), u:&[f32]|{
+                let mut y = vec![NULL;m*batch];
+                f(&shape,u,&s,&mut y);
+                y[b*m..(b+1)*m].to_vec()
+            };
+            let seq = |f:fn(&[usize;3],&[f32],&[f32],&mut [u8]), u:&[f32]|{
+                let mut y = vec![NULL;m];
+                f(&shape,u,s_b,&mut y);
+                y
+            };
+            assert_eq!(par(top_u_conv_par_,&u), seq(top_u_conv_,&u));
+            assert_eq!(par(top_u_repeated_conv_par_,&u[..c*c]), seq(top_u_repeated_conv_,&u[..c*c]));
+            assert_eq!(par(multiplicative_top_u_conv_par_,&u), seq(multiplicative_top_u_conv_,&u));
+            assert_eq!(par(multiplicative_top_u_repeated_conv_par_,&u[..c*c]), seq(multiplicative_top_u_repeated_conv_,&u[..c*c]));
+            let mut y_par = vec![NULL;m*batch];
+            let mut y_seq = vec![NULL;m];
+            top_v_conv_par_(&shape,&v,&s,&mut y_par);
+            top_v_conv_(&shape,&v,s_b,&mut y_seq);
+            assert_eq!(&y_par[b*m..(b+1)*m], y_seq.as_slice());
+            let mut y_par = vec![NULL;m*batch];
+            let mut y_seq = vec![NULL;m];
+            top_v_repeated_conv_par_(&shape,&v[..c*c],&s,&mut y_par);
+            top_v_repeated_conv_(&shape,&v[..c*c],s_b,&mut y_seq);
+            assert_eq!(&y_par[b*m..(b+1)*m], y_seq.as_slice());
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use std::fmt::Debug;
+use std::iter::Step;
+use num_traits::{AsPrimitive, PrimInt};
+use crate::conv_shape::ConvShape;
+use crate::ecc_layer::normalize_column;
+use crate::from_usize::FromUsize;
+use crate::init_rand::InitRandWithCapacity;
+use crate::top_k::argmax;
+
+/**Layer driven by ordered input spikes (port of `py/convergence_tests/stdp.py`). Input is a sparse binary vector,
+whose indices are sorted by the time at which they spiked (exactly the lists produced by `dense_to_sparse` or any
+ordering of them). Every minicolumn accumulates weights of incoming spikes, one at a time, and fires its strongest neuron
+as soon as it crosses the threshold. Minicolumns that never cross it fire their strongest neuron after all
+inputs have arrived. Weights are shared between minicolumns (`ConvShape::minicolumn_w_shape`), so a dense layer
+is simply `ConvShape::new_linear`, in which case W has shape `[n, m]`.
+Two things deliberately differ from the Python reference. Accumulation starts from the bias `r` rather than from zeros
+(otherwise decreasing `r` of every winner, which the reference also does, would have no effect on anything).
+Learning only strengthens the inputs that spiked before the output did, as in proper STDP, whereas the reference
+strengthens all of `x`.*/
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stdp<Idx: Debug + PrimInt> {
+    shape: ConvShape<Idx>,
+    /**weights of shape `[kernel_height, kernel_width, in_channels, out_channels]`*/
+    w: Vec<f32>,
+    /**bias of shape `[out_channels]`*/
+    r: Vec<f32>,
+    threshold: f32,
+    w_step: f32,
+    r_step: f32,
+    norm: usize,
+}
+
+impl<Idx: Debug + PrimInt + AsPrimitive<usize> + FromUsize + Step> Stdp<Idx> {
+    /**Random weights W normalised with respect to the given norm (1 or 2)*/
+    pub fn new(shape: ConvShape<Idx>, norm: usize, threshold: f32, w_step: f32, r_step: f32) -> Self {
+        let c = shape.out_channels().as_();
+        let mut slf = Self {
+            w: Vec::rand(shape.kernel_column_volume().as_() * c),
+            r: vec![0.; c],
+            shape,
+            threshold,
+            w_step,
+            r_step,
+            norm,
+        };
+        slf.normalize_w();
+        slf
+    }
+    pub fn shape(&self) -> &ConvShape<Idx> {
+        &self.shape
+    }
+    pub fn w(&self) -> &[f32] {
+        &self.w
+    }
+    pub fn w_mut(&mut self) -> &mut [f32] {
+        &mut self.w
+    }
+    pub fn r(&self) -> &[f32] {
+        &self.r
+    }
+    pub fn r_mut(&mut self) -> &mut [f32] {
+        &mut self.r
+    }
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold
+    }
+    pub fn w_step(&self) -> f32 {
+        self.w_step
+    }
+    pub fn set_w_step(&mut self, w_step: f32) {
+        self.w_step = w_step
+    }
+    pub fn r_step(&self) -> f32 {
+        self.r_step
+    }
+    pub fn set_r_step(&mut self, r_step: f32) {
+        self.r_step = r_step
+    }
+    pub fn norm(&self) -> usize {
+        self.norm
+    }
+    /**Normalises incoming weights of every output channel*/
+    pub fn normalize_w(&mut self) {
+        let c = self.r.len();
+        for j in 0..c {
+            normalize_column(&mut self.w, j, c, self.norm)
+        }
+    }
+    /**Returns the output spikes (one per minicolumn, in the order in which they fired) and, for every output spike,
+    the number of input spikes that arrived before it (including the one that triggered it).*/
+    pub fn infer_with_times(&self, x: &[Idx]) -> (Vec<Idx>, Vec<usize>) {
+        let c = self.r.len();
+        let area = self.shape.out_area().as_();
+        let mut s: Vec<f32> = self.r.iter().cloned().cycle().take(area * c).collect();
+        let mut fired = vec![false; area];
+        let mut y = Vec::with_capacity(area);
+        let mut times = Vec::with_capacity(area);
+        let mut touched = Vec::new();
+        for (t, &input_idx) in x.iter().enumerate() {
+            self.shape.sparse_dot_repeated(&[input_idx], |output_idx, w_index| {
+                let output_idx = output_idx.as_();
+                let column = output_idx / c;
+                if !fired[column] {
+                    if output_idx % c == 0 {
+                        touched.push(column);
+                    }
+                    s[output_idx] += self.w[w_index.as_()];
+                }
+            });
+            for column in touched.drain(..) {
+                let offset = column * c;
+                let k = argmax(&s[offset..offset + c]);
+                if s[offset + k] > self.threshold {
+                    fired[column] = true;
+                    y.push(Idx::from_usize(offset + k));
+                    times.push(t + 1);
+                }
+            }
+        }
+        for column in (0..area).filter(|&column| !fired[column]) {
+            let offset = column * c;
+            y.push(Idx::from_usize(offset + argmax(&s[offset..offset + c])));
+            times.push(x.len());
+        }
+        (y, times)
+    }
+    /**Returns the output spikes, one per minicolumn, in the order in which they fired*/
+    pub fn infer(&self, x: &[Idx]) -> Vec<Idx> {
+        self.infer_with_times(x).0
+    }
+    /**Every output spike `y[i]` strengthens weights of the first `times[i]` input spikes (those that arrived before it)
+    by `w_step` divided by their count, then incoming weights of its channel are renormalised and its bias is decreased by `r_step`.*/
+    pub fn learn(&mut self, x: &[Idx], y: &[Idx], times: &[usize]) {
+        assert_eq!(y.len(), times.len());
+        let c = self.r.len();
+        for (&k, &t) in y.iter().zip(times) {
+            let j = k.as_() % c;
+            self.shape.sparse_unbiased_increment_repeated(&mut self.w, self.w_step, &x[..t], &[k]);
+            normalize_column(&mut self.w, j, c, self.norm);
+            self.r[j] -= self.r_step;
+        }
+    }
+    /**Inference followed by learning (if enabled). Returns the output spikes.*/
+    pub fn run(&mut self, x: &[Idx], learn: bool) -> Vec<Idx> {
+        let (y, times) = self.infer_with_times(x);
+        if learn {
+            self.learn(x, &y, &times);
+        }
+        y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rand_set;
+    use super::*;
+
+    #[test]
+    fn test1() {
+        // dense layer behaves like the python loop
+        let (n, m) = (49u32, 20u32);
+        let mut stdp = Stdp::<u32>::new(ConvShape::new_linear(n, m), 2, 0.5, 0.01, 0.01);
+        for _ in 0..50 {
+            let x = rand_set(10, 0..n);
+            let mut s = stdp.r().to_vec();
+            let mut t = x.len();
+            for (i, &idx) in x.iter().enumerate() {
+                for j in 0..m as usize {
+                    s[j] += stdp.w()[idx as usize * m as usize + j];
+                }
+                if s[argmax(&s)] > stdp.threshold() {
+                    t = i + 1;
+                    break;
+                }
+            }
+            let k = argmax(&s) as u32;
+            let (y, times) = stdp.infer_with_times(&x);
+            assert_eq!(y, vec![k]);
+            assert_eq!(times, vec![t]);
+            stdp.run(&x, true);
+            let l2: f32 = stdp.w().iter().skip(k as usize).step_by(m as usize).map(|w| w * w).sum();
+            assert!((l2 - 1.).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    fn test2() {
+        let shape = ConvShape::new_in([8, 8, 2], 5, [3, 3], [1, 1]);
+        let mut stdp = Stdp::<u32>::new(shape.clone(), 1, 0.3, 0.05, 0.001);
+        let c = shape.out_channels();
+        for _ in 0..10 {
+            let x = rand_set(30, 0..shape.in_volume());
+            let (y, times) = stdp.infer_with_times(&x);
+            assert_eq!(y.len(), shape.out_area() as usize);
+            let mut columns: Vec<u32> = y.iter().map(|&k| k / c).collect();
+            columns.sort();
+            assert!(columns.iter().cloned().eq(0..shape.out_area()));
+            assert!(times.windows(2).all(|t| t[0] <= t[1]));
+            stdp.learn(&x, &y, &times);
+            for j in 0..c as usize {
+                let l1: f32 = stdp.w().iter().skip(j).step_by(c as usize).sum();
+                assert!((l1 - 1.).abs() < 0.0001);
+            }
+        }
+    }
+
+    #[test]
+    fn test3() {
+        // divergences from the python reference: bias is the starting point and late inputs are not strengthened
+        let (n, m) = (16u32, 4u32);
+        let mut stdp = Stdp::<u32>::new(ConvShape::new_linear(n, m), 1, 0.5, 0.1, 0.01);
+        stdp.r_mut()[2] = 1.;
+        let x = vec![5, 9, 1, 12];
+        let (y, times) = stdp.infer_with_times(&x);
+        assert_eq!(y, vec![2]);
+        assert_eq!(times, vec![1]);
+        let w_old = stdp.w().to_vec();
+        stdp.learn(&x, &y, &times);
+        let ratio = |i: usize| stdp.w()[i * m as usize + 2] / w_old[i * m as usize + 2];
+        let untouched = ratio(0);
+        for i in 1..n as usize {
+            if i == 5 {
+                assert!(ratio(i) > untouched + 0.0001);
+            } else {
+                assert!((ratio(i) - untouched).abs() < 0.0001, "{} {}", ratio(i), untouched);
+            }
+        }
+        assert!((stdp.r()[2] - 0.99).abs() < 0.0001);
+    }
+}
@@ -1,5 +1,11 @@
 use std::cmp::Ordering;
 use std::cmp::Ordering::{Greater, Less};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rand::seq::SliceRandom;
+use crate::conv_shape::channels;
+use crate::from_usize::FromUsize;
+use crate::VectorFieldOne;
 
 pub fn top_small_k_indices<V: Copy + PartialOrd>(mut k: usize, n: usize, f: impl Fn(usize) -> V) -> Vec<(usize, V)> {
     debug_assert!(k <= n);
@@ -41,6 +47,79 @@ pub fn top_large_k_indices<T>(mut k: usize, values: &[T], candidates_per_value:
     }
 }
 
+/**Integer key, such that sorting keys in ascending order sorts s in descending order and then j in ascending order*/
+pub(crate) fn descending_key(s: f32, j: usize) -> u64 {
+    let bits = (s + 0.0).to_bits(); // turns -0.0 into 0.0, because they compare equal
+    let ascending = if bits >> 31 == 1 { !bits } else { bits | 1 << 31 };
+    ((!ascending as u64) << 32) | j as u64
+}
+
+/**Appends offset+j to output for the k largest s[j] (in ascending order of j). Ties are broken in favour of lower j
+if rng is None, otherwise every order of tied elements is equally likely. keys and scratch are reusable buffers.*/
+fn k_wta_(s: &[f32], k: usize, offset: usize, rng: Option<&mut StdRng>, keys: &mut Vec<u64>, scratch: &mut Vec<u64>, output: &mut Vec<usize>) {
+    let n = s.len();
+    if k >= n {
+        output.extend(offset..offset + n);
+        return;
+    }
+    if k == 0 {
+        return;
+    }
+    keys.clear();
+    keys.extend(0..n as u64);
+    if let Some(rng) = rng {
+        keys.shuffle(rng); // random rank of every element takes place of its index
+    }
+    keys.iter_mut().zip(s).for_each(|(key, &s)| *key = descending_key(s, *key as usize));
+    scratch.clear();
+    scratch.extend_from_slice(keys);
+    // keys are distinct, so exactly k of them are less or equal to the k-th smallest
+    let kth = *scratch.select_nth_unstable(k - 1).1;
+    output.extend(keys.iter().enumerate().filter(|(_, &key)| key <= kth).map(|(j, _)| offset + j));
+}
+
+/**Sparse vector (sorted indices) of the k largest elements of s. Ties are broken in favour of lower indices
+if seed is None, otherwise they are broken randomly, in a way that is reproducible for the same seed.*/
+pub fn k_wta<Idx: FromUsize>(s: &[f32], k: usize, seed: Option<u64>) -> Vec<Idx> {
+    let mut rng = seed.map(StdRng::seed_from_u64);
+    let mut output = Vec::with_capacity(k.min(s.len()));
+    k_wta_(s, k, 0, rng.as_mut(), &mut Vec::new(), &mut Vec::new(), &mut output);
+    output.into_iter().map(Idx::from_usize).collect()
+}
+
+/**shape of s is [height, width, channels]. Returns sparse vector (sorted indices) with the k largest elements of
+every minicolumn (of every column at position (y,x) all the channels compete), which can be directly fed
+to `ConvShape::sparse_dot`. Ties are broken just like in `k_wta`.*/
+pub fn k_wta_conv<Idx: FromUsize>(s_shape: &[usize; 3], s: &[f32], k: usize, seed: Option<u64>) -> Vec<Idx> {
+    assert_eq!(s.len(), s_shape.product(), "s has wrong shape");
+    let c = *channels(s_shape);
+    let mut rng = seed.map(StdRng::seed_from_u64);
+    let mut output = Vec::with_capacity(k.min(c) * (s.len() / c.max(1)));
+    let (mut keys, mut scratch) = (Vec::with_capacity(c), Vec::with_capacity(c));
+    if c > 0 {
+        for (col, s) in s.chunks(c).enumerate() {
+            k_wta_(s, k, col * c, rng.as_mut(), &mut keys, &mut scratch, &mut output);
+        }
+    }
+    output.into_iter().map(Idx::from_usize).collect()
+}
+
+/**Index of the first largest element*/
+pub fn argmax<T: PartialOrd + Copy>(v: &[T]) -> usize {
+    let mut best = 0;
+    for i in 1..v.len() {
+        if v[i] > v[best] {
+            best = i;
+        }
+    }
+    best
+}
+
+pub fn argsort<T>(data: &[T], compare:impl Fn(&T,&T)->Ordering) -> Vec<usize> {
+    let mut indices = (0..data.len()).collect::<Vec<_>>();
+    indices.sort_by(|&i,&j| compare(&data[i],&data[j]));
+    indices
+}
 
 #[cfg(test)]
 mod tests {
@@ -96,9 +175,49 @@ mod tests {
             assert_eq!(top_idx, arr.len() - 1 - arr.iter().rev().position_max().unwrap());
         }
     }
+
+    #[test]
+    fn test11() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..50 {
+            let n = rng.gen_range(1usize..40);
+            let k = rng.gen_range(0..n + 2);
+            // few distinct values, so that there are plenty of ties
+            let s: Vec<f32> = (0..n).map(|_| rng.gen_range(0..4) as f32).collect();
+            let mut expected = argsort(&s, |a, b| b.total_cmp(a)); // stable, so lower index wins ties
+            expected.truncate(k);
+            expected.sort();
+            assert_eq!(k_wta::<usize>(&s, k, None), expected);
+            let seeded = k_wta::<usize>(&s, k, Some(7));
+            assert_eq!(seeded, k_wta::<usize>(&s, k, Some(7)));
+            assert_eq!(seeded.len(), k.min(n));
+            assert!(seeded.windows(2).all(|w| w[0] < w[1]));
+            // the selected values are the same, only tied indices may differ
+            let values = |v: &[usize]| v.iter().map(|&i| s[i]).sorted_by(f32::total_cmp).collect::<Vec<_>>();
+            assert_eq!(values(&seeded), values(&expected));
+        }
+        let ties = vec![1f32; 16];
+        let picks: Vec<Vec<u32>> = (0..8).map(|seed| k_wta(&ties, 1, Some(seed))).collect();
+        assert!(picks.iter().any(|p| p != &picks[0]), "Random tie-breaking always picked {:?}", picks[0]);
+    }
+
+    #[test]
+    fn test12() {
+        let mut rng = rand::thread_rng();
+        let shape = [3, 4, 6];
+        let s: Vec<f32> = (0..shape.product()).map(|_| rng.gen_range(0..3) as f32).collect();
+        for k in 0..8 {
+            for seed in [None, Some(3)] {
+                let y = k_wta_conv::<u32>(&shape, &s, k, seed);
+                assert!(y.windows(2).all(|w| w[0] < w[1]));
+                for col in 0..12 {
+                    let winners: Vec<u32> = y.iter().cloned().filter(|&i| i as usize / 6 == col).map(|i| i - col as u32 * 6).collect();
+                    assert_eq!(winners.len(), k.min(6));
+                    if seed.is_none() {
+                        assert_eq!(winners, k_wta::<u32>(&s[col * 6..col * 6 + 6], k, None));
+                    }
+                }
+            }
+        }
+    }
 }
-pub fn argsort<T>(data: &[T], compare:impl Fn(&T,&T)->Ordering) -> Vec<usize> {
-    let mut indices = (0..data.len()).collect::<Vec<_>>();
-    indices.sort_by(|&i,&j| compare(&data[i],&data[j]));
-    indices
-}
\ No newline at end of file
@@ -12,6 +12,29 @@ pub fn foreach2d<T: Copy>(range: &Range<[T; 2]>, mut f: impl FnMut([T; 2])) wher
     }
 }
 
+/**Visits all positions within the range in row-major order (the last axis changes fastest)*/
+pub fn foreach<T: Copy + PartialOrd + Add<Output=T> + One, const DIM: usize>(range: &Range<[T; DIM]>, mut f: impl FnMut([T; DIM])) {
+    if (0..DIM).any(|i| range.start[i] >= range.end[i]) {
+        return;
+    }
+    let mut pos = range.start;
+    loop {
+        f(pos);
+        let mut i = DIM;
+        loop {
+            if i == 0 {
+                return;
+            }
+            i -= 1;
+            pos[i] = pos[i] + T::one();
+            if pos[i] < range.end[i] {
+                break;
+            }
+            pos[i] = range.start[i];
+        }
+    }
+}
+
 pub fn contains<T: Copy + PartialOrd, const DIM: usize>(range: &Range<[T; DIM]>, element: &[T; DIM]) -> bool {
     range.start.all_le(element) && element.all_lt(&range.end)
 }